@@ -45,6 +45,8 @@ use near_primitives::views::{
     FinalExecutionOutcomeWithReceiptView, FinalExecutionStatus, LightClientBlockView,
     SignedTransactionView,
 };
+#[cfg(feature = "sandbox")]
+use near_store::snapshot::StoreDump;
 use near_store::{ColState, ColStateHeaders, ColStateParts, ShardTries, StoreUpdate};
 
 use near_primitives::state_record::StateRecord;
@@ -216,6 +218,9 @@ pub struct Chain {
     pub block_economics_config: BlockEconomicsConfig,
     pub doomslug_threshold_mode: DoomslugThresholdMode,
     pending_states_to_patch: Option<Vec<StateRecord>>,
+    /// Shift of the produced block timestamps relative to the current time, also accepted in the
+    /// timestamps of the received blocks. Only sandbox nodes shift it, see `sandbox_shift_time`.
+    pub sandbox_time_offset: Duration,
 }
 
 impl Chain {
@@ -225,7 +230,8 @@ impl Chain {
         doomslug_threshold_mode: DoomslugThresholdMode,
     ) -> Result<Chain, Error> {
         let (store, state_roots) = runtime_adapter.genesis_state();
-        let store = ChainStore::new(store, chain_genesis.height);
+        let store = ChainStore::new(store, chain_genesis.height)
+            .with_cold_store(runtime_adapter.get_cold_store());
         let genesis_chunks = genesis_chunks(
             state_roots.clone(),
             runtime_adapter.num_shards(&EpochId::default())?,
@@ -253,6 +259,7 @@ impl Chain {
             block_economics_config: BlockEconomicsConfig::from(chain_genesis),
             doomslug_threshold_mode,
             pending_states_to_patch: None,
+            sandbox_time_offset: Duration::zero(),
         })
     }
 
@@ -263,7 +270,8 @@ impl Chain {
     ) -> Result<Chain, Error> {
         // Get runtime initial state and create genesis block out of it.
         let (store, state_roots) = runtime_adapter.genesis_state();
-        let mut store = ChainStore::new(store, chain_genesis.height);
+        let mut store = ChainStore::new(store, chain_genesis.height)
+            .with_cold_store(runtime_adapter.get_cold_store());
         let genesis_chunks = genesis_chunks(
             state_roots.clone(),
             runtime_adapter.num_shards(&EpochId::default())?,
@@ -369,6 +377,7 @@ impl Chain {
             block_economics_config: BlockEconomicsConfig::from(chain_genesis),
             doomslug_threshold_mode,
             pending_states_to_patch: None,
+            sandbox_time_offset: Duration::zero(),
         })
     }
 
@@ -2223,6 +2232,7 @@ impl Chain {
             &self.genesis,
             self.transaction_validity_period,
             self.pending_states_to_patch.take(),
+            self.sandbox_time_offset,
         )
     }
 
@@ -2242,6 +2252,7 @@ impl Chain {
             &self.genesis,
             self.transaction_validity_period,
             self.pending_states_to_patch.take(),
+            self.sandbox_time_offset,
         )
     }
 
@@ -2687,6 +2698,24 @@ impl Chain {
     pub fn patch_state_in_progress(&self) -> bool {
         self.pending_states_to_patch.is_some()
     }
+
+    /// Reverts the store to `dump` and drops the blocks and caches of the reverted chain.
+    pub fn sandbox_restore(&mut self, dump: &StoreDump) -> Result<(), Error> {
+        dump.restore(&self.store.owned_store())?;
+        self.runtime_adapter.sandbox_reset_caches();
+        self.sandbox_reset_caches();
+        Ok(())
+    }
+
+    /// Drops the blocks and caches of this chain, after the store was reverted to a snapshot by
+    /// this or another chain over the same store.
+    pub fn sandbox_reset_caches(&mut self) {
+        self.store = ChainStore::new(self.store.owned_store(), self.store.get_genesis_height())
+            .with_cold_store(self.runtime_adapter.get_cold_store());
+        self.orphans = OrphanBlockPool::new();
+        self.blocks_with_missing_chunks = MissingChunksPool::new();
+        self.pending_states_to_patch = None;
+    }
 }
 
 /// Chain update helper, contains information that is needed to process block
@@ -2705,6 +2734,7 @@ pub struct ChainUpdate<'a> {
     #[allow(unused)]
     transaction_validity_period: BlockHeightDelta,
     states_to_patch: Option<Vec<StateRecord>>,
+    sandbox_time_offset: Duration,
 }
 
 pub struct SameHeightResult {
@@ -2745,6 +2775,7 @@ impl<'a> ChainUpdate<'a> {
         genesis: &'a Block,
         transaction_validity_period: BlockHeightDelta,
         states_to_patch: Option<Vec<StateRecord>>,
+        sandbox_time_offset: Duration,
     ) -> Self {
         let chain_store_update: ChainStoreUpdate<'_> = store.store_update();
         <ChainUpdate<'a>>::new_impl(
@@ -2757,6 +2788,7 @@ impl<'a> ChainUpdate<'a> {
             genesis,
             transaction_validity_period,
             states_to_patch,
+            sandbox_time_offset,
             chain_store_update,
         )
     }
@@ -2773,6 +2805,7 @@ impl<'a> ChainUpdate<'a> {
         genesis: &'a Block,
         transaction_validity_period: BlockHeightDelta,
         states_to_patch: Option<Vec<StateRecord>>,
+        sandbox_time_offset: Duration,
     ) -> Self {
         let chain_store_update = saved_store_update.restore(store);
         <ChainUpdate<'a>>::new_impl(
@@ -2785,6 +2818,7 @@ impl<'a> ChainUpdate<'a> {
             genesis,
             transaction_validity_period,
             states_to_patch,
+            sandbox_time_offset,
             chain_store_update,
         )
     }
@@ -2799,6 +2833,7 @@ impl<'a> ChainUpdate<'a> {
         genesis: &'a Block,
         transaction_validity_period: BlockHeightDelta,
         states_to_patch: Option<Vec<StateRecord>>,
+        sandbox_time_offset: Duration,
         chain_store_update: ChainStoreUpdate<'a>,
     ) -> Self {
         ChainUpdate {
@@ -2812,6 +2847,7 @@ impl<'a> ChainUpdate<'a> {
             genesis,
             transaction_validity_period,
             states_to_patch,
+            sandbox_time_offset,
         }
     }
 
@@ -3936,8 +3972,11 @@ impl<'a> ChainUpdate<'a> {
     where
         F: FnMut(ChallengeBody) -> (),
     {
-        // Refuse blocks from the too distant future.
-        if header.timestamp() > Utc::now() + Duration::seconds(ACCEPTABLE_TIME_DIFFERENCE) {
+        // Refuse blocks from the too distant future. Sandbox nodes shift block timestamps ahead
+        // by `sandbox_time_offset`, see `sandbox_shift_time`.
+        if header.timestamp()
+            > Utc::now() + self.sandbox_time_offset + Duration::seconds(ACCEPTABLE_TIME_DIFFERENCE)
+        {
             return Err(ErrorKind::InvalidBlockFutureTime(header.timestamp()).into());
         }
 
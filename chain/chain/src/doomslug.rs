@@ -8,6 +8,7 @@ use near_primitives::block::{Approval, ApprovalInner};
 use near_primitives::hash::CryptoHash;
 use near_primitives::types::{AccountId, ApprovalStake, Balance, BlockHeight, BlockHeightDelta};
 use near_primitives::validator_signer::ValidatorSigner;
+use tracing::warn;
 
 /// Have that many iterations in the timer instead of `loop` to prevent potential bugs from blocking
 /// the node
@@ -408,10 +409,16 @@ impl Doomslug {
         ret
     }
 
+    /// Returns `None` if there is no signer, or if it refused to sign the approval.
     pub fn create_approval(&self, target_height: BlockHeight) -> Option<Approval> {
-        self.signer.as_ref().map(|signer| {
-            Approval::new(self.tip.block_hash, self.tip.height, target_height, &**signer)
-        })
+        let signer = self.signer.as_ref()?;
+        match Approval::new(self.tip.block_hash, self.tip.height, target_height, &**signer) {
+            Ok(approval) => Some(approval),
+            Err(err) => {
+                warn!(target: "doomslug", "Not approving height {}: {}", target_height, err);
+                None
+            }
+        }
     }
 
     /// Determines whether a block has enough approvals to be produced.
@@ -792,7 +799,7 @@ mod tests {
         assert_eq!(
             ds.on_approval_message_internal(
                 now,
-                &Approval::new(hash(&[1]), 1, 2, &signers[0]),
+                &Approval::new(hash(&[1]), 1, 2, &signers[0]).unwrap(),
                 &stakes,
             ),
             DoomslugBlockProductionReadiness::NotReady,
@@ -802,7 +809,7 @@ mod tests {
         assert_eq!(
             ds.on_approval_message_internal(
                 now,
-                &Approval::new(hash(&[1]), 1, 4, &signers[2]),
+                &Approval::new(hash(&[1]), 1, 4, &signers[2]).unwrap(),
                 &stakes,
             ),
             DoomslugBlockProductionReadiness::NotReady,
@@ -812,7 +819,7 @@ mod tests {
         assert_eq!(
             ds.on_approval_message_internal(
                 now,
-                &Approval::new(hash(&[1]), 1, 4, &signers[3]),
+                &Approval::new(hash(&[1]), 1, 4, &signers[3]).unwrap(),
                 &stakes,
             ),
             DoomslugBlockProductionReadiness::NotReady,
@@ -822,7 +829,7 @@ mod tests {
         assert_eq!(
             ds.on_approval_message_internal(
                 now + Duration::from_millis(100),
-                &Approval::new(hash(&[1]), 1, 4, &signers[3]),
+                &Approval::new(hash(&[1]), 1, 4, &signers[3]).unwrap(),
                 &stakes,
             ),
             DoomslugBlockProductionReadiness::NotReady,
@@ -832,7 +839,7 @@ mod tests {
         assert_eq!(
             ds.on_approval_message_internal(
                 now,
-                &Approval::new(hash(&[1]), 1, 4, &signers[1]),
+                &Approval::new(hash(&[1]), 1, 4, &signers[1]).unwrap(),
                 &stakes,
             ),
             DoomslugBlockProductionReadiness::ReadySince(now),
@@ -842,7 +849,7 @@ mod tests {
         assert_eq!(
             ds.on_approval_message_internal(
                 now,
-                &Approval::new(hash(&[1]), 1, 4, &signers[0]),
+                &Approval::new(hash(&[1]), 1, 4, &signers[0]).unwrap(),
                 &stakes,
             ),
             DoomslugBlockProductionReadiness::ReadySince(now),
@@ -852,7 +859,7 @@ mod tests {
         assert_eq!(
             ds.on_approval_message_internal(
                 now,
-                &Approval::new(hash(&[1]), 1, 2, &signers[3]),
+                &Approval::new(hash(&[1]), 1, 2, &signers[3]).unwrap(),
                 &stakes,
             ),
             DoomslugBlockProductionReadiness::NotReady,
@@ -864,7 +871,7 @@ mod tests {
         assert_eq!(
             ds.on_approval_message_internal(
                 now,
-                &Approval::new(hash(&[1]), 1, 2, &signers[2]),
+                &Approval::new(hash(&[1]), 1, 2, &signers[2]).unwrap(),
                 &stakes,
             ),
             DoomslugBlockProductionReadiness::ReadySince(now),
@@ -874,7 +881,7 @@ mod tests {
         assert_eq!(
             ds.on_approval_message_internal(
                 now,
-                &Approval::new(hash(&[2]), 2, 4, &signers[1]),
+                &Approval::new(hash(&[2]), 2, 4, &signers[1]).unwrap(),
                 &stakes,
             ),
             DoomslugBlockProductionReadiness::NotReady,
@@ -906,13 +913,13 @@ mod tests {
             .collect::<Vec<_>>();
         let mut tracker = DoomslugApprovalsTrackersAtHeight::new();
 
-        let a1_1 = Approval::new(hash(&[1]), 1, 4, &signers[0]);
-        let a1_2 = Approval::new(hash(&[1]), 1, 4, &signers[1]);
-        let a1_3 = Approval::new(hash(&[1]), 1, 4, &signers[2]);
+        let a1_1 = Approval::new(hash(&[1]), 1, 4, &signers[0]).unwrap();
+        let a1_2 = Approval::new(hash(&[1]), 1, 4, &signers[1]).unwrap();
+        let a1_3 = Approval::new(hash(&[1]), 1, 4, &signers[2]).unwrap();
 
-        let a2_1 = Approval::new(hash(&[3]), 3, 4, &signers[0]);
-        let a2_2 = Approval::new(hash(&[3]), 3, 4, &signers[1]);
-        let a2_3 = Approval::new(hash(&[3]), 3, 4, &signers[2]);
+        let a2_1 = Approval::new(hash(&[3]), 3, 4, &signers[0]).unwrap();
+        let a2_2 = Approval::new(hash(&[3]), 3, 4, &signers[1]).unwrap();
+        let a2_3 = Approval::new(hash(&[3]), 3, 4, &signers[2]).unwrap();
 
         // Process first approval, and then process it again and make sure it works
         tracker.process_approval(Instant::now(), &a1_1, &stakes, DoomslugThresholdMode::TwoThirds);
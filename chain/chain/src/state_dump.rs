@@ -0,0 +1,93 @@
+//! Dumps of the state sync headers and parts on disk, so that nodes in air-gapped or
+//! bandwidth-limited environments can state sync without downloading the state from peers.
+//!
+//! The dump of a shard at a sync hash is the directory `<sync_hash>/shard<shard_id>` with the
+//! borsh serialized state sync `header` and the state parts `part<part_id>`. Loading a dump goes
+//! through the same validation as the headers and the parts received from peers.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use tracing::info;
+
+use near_chain_primitives::error::{Error, LogTransientStorageError};
+use near_primitives::hash::CryptoHash;
+use near_primitives::syncing::{get_num_state_parts, ShardStateSyncResponseHeader};
+use near_primitives::types::ShardId;
+
+use crate::Chain;
+
+/// Directory with the dump of the shard state at `sync_hash`.
+pub fn shard_dump_dir(dir: &Path, sync_hash: &CryptoHash, shard_id: ShardId) -> PathBuf {
+    dir.join(sync_hash.to_string()).join(format!("shard{}", shard_id))
+}
+
+fn header_path(dir: &Path, sync_hash: &CryptoHash, shard_id: ShardId) -> PathBuf {
+    shard_dump_dir(dir, sync_hash, shard_id).join("header")
+}
+
+fn part_path(dir: &Path, sync_hash: &CryptoHash, shard_id: ShardId, part_id: u64) -> PathBuf {
+    shard_dump_dir(dir, sync_hash, shard_id).join(format!("part{}", part_id))
+}
+
+/// Returns whether `dir` has the state sync header of the shard at `sync_hash`.
+pub fn has_state_dump(dir: &Path, sync_hash: &CryptoHash, shard_id: ShardId) -> bool {
+    header_path(dir, sync_hash, shard_id).is_file()
+}
+
+impl Chain {
+    /// Dumps the state sync header and all the state parts of the shard into `dir`. `sync_hash`
+    /// must be the hash of the first block of an epoch. Returns the number of parts.
+    pub fn dump_state_parts(
+        &mut self,
+        dir: &Path,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+    ) -> Result<u64, Error> {
+        let header = self.get_state_response_header(shard_id, sync_hash)?;
+        let state_root = header.chunk_prev_state_root();
+        let num_parts = get_num_state_parts(header.state_root_node().memory_usage);
+        let prev_hash = self.get_block_header(&sync_hash)?.prev_hash().clone();
+
+        fs::create_dir_all(shard_dump_dir(dir, &sync_hash, shard_id))?;
+        fs::write(header_path(dir, &sync_hash, shard_id), header.try_to_vec()?)?;
+        for part_id in 0..num_parts {
+            // Parts are not saved to the store as in `get_state_response_part`, as the whole
+            // state would end up there.
+            let part = self
+                .runtime_adapter
+                .obtain_state_part(shard_id, &prev_hash, &state_root, part_id, num_parts)
+                .log_storage_error("obtain_state_part fail")?;
+            fs::write(part_path(dir, &sync_hash, shard_id, part_id), part)?;
+            info!(target: "sync", "Dumped state part {} of {} for shard {}", part_id + 1, num_parts, shard_id);
+        }
+        Ok(num_parts)
+    }
+
+    /// Validates and saves the state sync header of the shard from the dump in `dir`.
+    pub fn set_state_header_from_dump(
+        &mut self,
+        dir: &Path,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+    ) -> Result<(), Error> {
+        let data = fs::read(header_path(dir, &sync_hash, shard_id))?;
+        let header = ShardStateSyncResponseHeader::try_from_slice(&data)?;
+        self.set_state_header(shard_id, sync_hash, header)
+    }
+
+    /// Validates and saves the state part of the shard from the dump in `dir`. The state sync
+    /// header must be already set.
+    pub fn set_state_part_from_dump(
+        &mut self,
+        dir: &Path,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+        part_id: u64,
+        num_parts: u64,
+    ) -> Result<(), Error> {
+        let data = fs::read(part_path(dir, &sync_hash, shard_id, part_id))?;
+        self.set_state_part(shard_id, sync_hash, part_id, num_parts, &data)
+    }
+}
@@ -36,18 +36,18 @@ use near_primitives::types::{
 use near_primitives::utils::{get_block_shard_id, index_to_bytes, to_timestamp};
 use near_primitives::views::LightClientBlockView;
 use near_store::{
-    read_with_cache, ColBlock, ColBlockExtra, ColBlockHeader, ColBlockHeight, ColBlockInfo,
-    ColBlockMerkleTree, ColBlockMisc, ColBlockOrdinal, ColBlockPerHeight, ColBlockRefCount,
-    ColBlocksToCatchup, ColChallengedBlocks, ColChunkExtra, ColChunkHashesByHeight,
-    ColChunkPerHeightShard, ColChunks, ColEpochLightClientBlocks, ColGCCount,
-    ColHeaderHashesByHeight, ColIncomingReceipts, ColInvalidChunks, ColLastBlockWithNewChunk,
-    ColNextBlockHashes, ColNextBlockWithNewChunk, ColOutcomeIds, ColOutgoingReceipts,
-    ColPartialChunks, ColProcessedBlockHeights, ColReceiptIdToShardId, ColReceipts, ColState,
-    ColStateChanges, ColStateDlInfos, ColStateHeaders, ColStateParts, ColTransactionResult,
-    ColTransactions, ColTrieChanges, DBCol, KeyForStateChanges, ShardTries, Store, StoreUpdate,
-    TrieChanges, WrappedTrieChanges, CHUNK_TAIL_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY,
-    HEADER_HEAD_KEY, HEAD_KEY, LARGEST_TARGET_HEIGHT_KEY, LATEST_KNOWN_KEY, SHOULD_COL_GC,
-    TAIL_KEY,
+    read_with_cache, read_with_cache_or_cold, ColBlock, ColBlockExtra, ColBlockHeader,
+    ColBlockHeight, ColBlockInfo, ColBlockMerkleTree, ColBlockMisc, ColBlockOrdinal,
+    ColBlockPerHeight, ColBlockRefCount, ColBlocksToCatchup, ColChallengedBlocks, ColChunkExtra,
+    ColChunkHashesByHeight, ColChunkPerHeightShard, ColChunks, ColEpochLightClientBlocks,
+    ColGCCount, ColHeaderHashesByHeight, ColIncomingReceipts, ColInvalidChunks,
+    ColLastBlockWithNewChunk, ColNextBlockHashes, ColNextBlockWithNewChunk, ColOutcomeIds,
+    ColOutgoingReceipts, ColPartialChunks, ColProcessedBlockHeights, ColReceiptIdToShardId,
+    ColReceipts, ColState, ColStateChanges, ColStateDlInfos, ColStateHeaders, ColStateParts,
+    ColTransactionResult, ColTransactions, ColTrieChanges, DBCol, KeyForStateChanges, ShardTries,
+    Store, StoreUpdate, TrieChanges, WrappedTrieChanges, CHUNK_TAIL_KEY, EPOCH_SYNC_HASH_KEY,
+    FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY, LARGEST_TARGET_HEIGHT_KEY,
+    LATEST_KNOWN_KEY, SHOULD_COL_GC, TAIL_KEY,
 };
 
 use crate::types::{Block, BlockHeader, LatestKnown};
@@ -306,6 +306,9 @@ pub trait ChainStoreAccess {
 /// All chain-related database operations.
 pub struct ChainStore {
     store: Arc<Store>,
+    /// Store of an archival node that garbage collected data is moved to. Reads of blocks, chunks
+    /// and outcomes fall back to it.
+    cold_store: Option<Arc<Store>>,
     /// Genesis block height.
     genesis_height: BlockHeight,
     /// Latest known.
@@ -379,6 +382,7 @@ impl ChainStore {
     pub fn new(store: Arc<Store>, genesis_height: BlockHeight) -> ChainStore {
         ChainStore {
             store,
+            cold_store: None,
             genesis_height,
             latest_known: None,
             head: None,
@@ -411,10 +415,24 @@ impl ChainStore {
         }
     }
 
+    pub fn with_cold_store(mut self, cold_store: Option<Arc<Store>>) -> Self {
+        self.cold_store = cold_store;
+        self
+    }
+
     pub fn owned_store(&self) -> Arc<Store> {
         self.store.clone()
     }
 
+    pub fn cold_store(&self) -> Option<&Store> {
+        self.cold_store.as_deref()
+    }
+
+    /// The first block of the epoch the node was epoch synced to, if it was epoch synced.
+    pub fn epoch_sync_hash(&self) -> Result<Option<CryptoHash>, Error> {
+        self.store.get_ser(ColBlockMisc, EPOCH_SYNC_HASH_KEY).map_err(|e| e.into())
+    }
+
     pub fn store_update(&mut self) -> ChainStoreUpdate<'_> {
         ChainStoreUpdate::new(self)
     }
@@ -570,7 +588,14 @@ impl ChainStore {
         &self,
         id: &CryptoHash,
     ) -> Result<Vec<ExecutionOutcomeWithIdAndProof>, Error> {
-        Ok(self.store.get_ser(ColTransactionResult, id.as_ref())?.unwrap_or_else(|| vec![]))
+        let mut outcomes: Vec<ExecutionOutcomeWithIdAndProof> =
+            self.store.get_ser(ColTransactionResult, id.as_ref())?.unwrap_or_else(|| vec![]);
+        if let Some(cold_store) = &self.cold_store {
+            let cold_outcomes: Vec<ExecutionOutcomeWithIdAndProof> =
+                cold_store.get_ser(ColTransactionResult, id.as_ref())?.unwrap_or_else(|| vec![]);
+            outcomes.extend(cold_outcomes);
+        }
+        Ok(outcomes)
     }
 
     /// Returns a vector of Outcome ids for given block and shard id
@@ -579,10 +604,14 @@ impl ChainStore {
         block_hash: &CryptoHash,
         shard_id: ShardId,
     ) -> Result<Vec<CryptoHash>, Error> {
-        Ok(self
-            .store
-            .get_ser(ColOutcomeIds, &get_block_shard_id(block_hash, shard_id))?
-            .unwrap_or_default())
+        let key = get_block_shard_id(block_hash, shard_id);
+        if let Some(outcome_ids) = self.store.get_ser(ColOutcomeIds, &key)? {
+            return Ok(outcome_ids);
+        }
+        match &self.cold_store {
+            Some(cold_store) => Ok(cold_store.get_ser(ColOutcomeIds, &key)?.unwrap_or_default()),
+            None => Ok(vec![]),
+        }
     }
 
     /// Returns a hashmap of epoch id -> set of all blocks got for current (height, epoch_id)
@@ -651,6 +680,17 @@ impl ChainStore {
         store_update.commit().map_err(|err| err.into())
     }
 
+    /// Returns the store which keeps state changes of the given block, which is the cold store if
+    /// the block was already moved there by garbage collection.
+    fn state_changes_store(&self, block_hash: &CryptoHash) -> Result<&Store, Error> {
+        match &self.cold_store {
+            Some(cold_store) if !self.store.exists(ColBlock, block_hash.as_ref())? => {
+                Ok(&**cold_store)
+            }
+            _ => Ok(&*self.store),
+        }
+    }
+
     /// Retrieve the kinds of state changes occurred in a given block.
     ///
     /// We store different types of data, so we prefer to only expose minimal information about the
@@ -672,9 +712,10 @@ impl ChainStore {
         // 2. Extract the original Trie key out of the keys returned by RocksDB
         // 3. Try extracting `account_id` from the key using KeyFor* implementations
 
+        let store = self.state_changes_store(block_hash)?;
         let storage_key = KeyForStateChanges::get_prefix(&block_hash);
 
-        let mut block_changes = storage_key.find_iter(&self.store);
+        let mut block_changes = storage_key.find_iter(store);
 
         Ok(StateChangesKinds::from_changes(&mut block_changes)?)
     }
@@ -683,9 +724,10 @@ impl ChainStore {
         &self,
         block_hash: &CryptoHash,
     ) -> Result<StateChanges, Error> {
+        let store = self.state_changes_store(block_hash)?;
         let storage_key = KeyForStateChanges::get_prefix(&block_hash);
 
-        let mut block_changes = storage_key.find_iter(&self.store);
+        let mut block_changes = storage_key.find_iter(store);
 
         Ok(StateChanges::from_changes(&mut block_changes)?)
     }
@@ -722,13 +764,14 @@ impl ChainStore {
         //         left working with a key that was used in the trie.
         //    2.2. Parse the trie key with a relevant KeyFor* implementation to ensure consistency
 
+        let store = self.state_changes_store(block_hash)?;
         Ok(match state_changes_request {
             StateChangesRequest::AccountChanges { account_ids } => {
                 let mut changes = StateChanges::new();
                 for account_id in account_ids {
                     let data_key = TrieKey::Account { account_id: account_id.clone() }.to_vec();
                     let storage_key = KeyForStateChanges::new(&block_hash, data_key.as_ref());
-                    let changes_per_key = storage_key.find_exact_iter(&self.store);
+                    let changes_per_key = storage_key.find_exact_iter(store);
                     changes.extend(StateChanges::from_account_changes(changes_per_key)?);
                 }
                 changes
@@ -742,7 +785,7 @@ impl ChainStore {
                     }
                     .to_vec();
                     let storage_key = KeyForStateChanges::new(&block_hash, data_key.as_ref());
-                    let changes_per_key = storage_key.find_exact_iter(&self.store);
+                    let changes_per_key = storage_key.find_exact_iter(store);
                     changes.extend(StateChanges::from_access_key_changes(changes_per_key)?);
                 }
                 changes
@@ -752,7 +795,7 @@ impl ChainStore {
                 for account_id in account_ids {
                     let data_key = trie_key_parsers::get_raw_prefix_for_access_keys(account_id);
                     let storage_key = KeyForStateChanges::new(&block_hash, data_key.as_ref());
-                    let changes_per_key_prefix = storage_key.find_iter(&self.store);
+                    let changes_per_key_prefix = storage_key.find_iter(store);
                     changes.extend(StateChanges::from_access_key_changes(changes_per_key_prefix)?);
                 }
                 changes
@@ -763,7 +806,7 @@ impl ChainStore {
                     let data_key =
                         TrieKey::ContractCode { account_id: account_id.clone() }.to_vec();
                     let storage_key = KeyForStateChanges::new(&block_hash, data_key.as_ref());
-                    let changes_per_key = storage_key.find_exact_iter(&self.store);
+                    let changes_per_key = storage_key.find_exact_iter(store);
                     changes.extend(StateChanges::from_contract_code_changes(changes_per_key)?);
                 }
                 changes
@@ -776,7 +819,7 @@ impl ChainStore {
                         key_prefix.as_ref(),
                     );
                     let storage_key = KeyForStateChanges::new(&block_hash, data_key.as_ref());
-                    let changes_per_key_prefix = storage_key.find_iter(&self.store);
+                    let changes_per_key_prefix = storage_key.find_iter(store);
                     changes.extend(StateChanges::from_data_changes(changes_per_key_prefix)?);
                 }
                 changes
@@ -852,14 +895,26 @@ impl ChainStoreAccess for ChainStore {
     /// Get full block.
     fn get_block(&mut self, h: &CryptoHash) -> Result<&Block, Error> {
         option_to_not_found(
-            read_with_cache(&*self.store, ColBlock, &mut self.blocks, h.as_ref()),
+            read_with_cache_or_cold(
+                &*self.store,
+                self.cold_store.as_deref(),
+                ColBlock,
+                &mut self.blocks,
+                h.as_ref(),
+            ),
             &format!("BLOCK: {}", h),
         )
     }
 
     /// Get full chunk.
     fn get_chunk(&mut self, chunk_hash: &ChunkHash) -> Result<&ShardChunk, Error> {
-        match read_with_cache(&*self.store, ColChunks, &mut self.chunks, chunk_hash.as_ref()) {
+        match read_with_cache_or_cold(
+            &*self.store,
+            self.cold_store.as_deref(),
+            ColChunks,
+            &mut self.chunks,
+            chunk_hash.as_ref(),
+        ) {
             Ok(Some(shard_chunk)) => Ok(shard_chunk),
             _ => Err(ErrorKind::ChunkMissing(chunk_hash.clone()).into()),
         }
@@ -880,7 +935,13 @@ impl ChainStoreAccess for ChainStore {
 
     /// Does this full block exist?
     fn block_exists(&self, h: &CryptoHash) -> Result<bool, Error> {
-        self.store.exists(ColBlock, h.as_ref()).map_err(|e| e.into())
+        if self.store.exists(ColBlock, h.as_ref())? {
+            return Ok(true);
+        }
+        match &self.cold_store {
+            Some(cold_store) => cold_store.exists(ColBlock, h.as_ref()).map_err(|e| e.into()),
+            None => Ok(false),
+        }
     }
 
     /// Get previous header.
@@ -891,8 +952,9 @@ impl ChainStoreAccess for ChainStore {
     /// Information from applying block.
     fn get_block_extra(&mut self, block_hash: &CryptoHash) -> Result<&BlockExtra, Error> {
         option_to_not_found(
-            read_with_cache(
+            read_with_cache_or_cold(
                 &*self.store,
+                self.cold_store.as_deref(),
                 ColBlockExtra,
                 &mut self.block_extras,
                 block_hash.as_ref(),
@@ -908,8 +970,9 @@ impl ChainStoreAccess for ChainStore {
         shard_uid: &ShardUId,
     ) -> Result<&ChunkExtra, Error> {
         option_to_not_found(
-            read_with_cache(
+            read_with_cache_or_cold(
                 &*self.store,
+                self.cold_store.as_deref(),
                 ColChunkExtra,
                 &mut self.chunk_extras,
                 &get_block_shard_uid(block_hash, shard_uid),
@@ -946,8 +1009,9 @@ impl ChainStoreAccess for ChainStore {
 
     fn get_next_block_hash(&mut self, hash: &CryptoHash) -> Result<&CryptoHash, Error> {
         option_to_not_found(
-            read_with_cache(
+            read_with_cache_or_cold(
                 &*self.store,
+                self.cold_store.as_deref(),
                 ColNextBlockHashes,
                 &mut self.next_block_hashes,
                 hash.as_ref(),
@@ -1007,8 +1071,9 @@ impl ChainStoreAccess for ChainStore {
         shard_id: ShardId,
     ) -> Result<&Vec<Receipt>, Error> {
         option_to_not_found(
-            read_with_cache(
+            read_with_cache_or_cold(
                 &*self.store,
+                self.cold_store.as_deref(),
                 ColOutgoingReceipts,
                 &mut self.outgoing_receipts,
                 &get_block_shard_id(prev_block_hash, shard_id),
@@ -1023,8 +1088,9 @@ impl ChainStoreAccess for ChainStore {
         shard_id: ShardId,
     ) -> Result<&Vec<ReceiptProof>, Error> {
         option_to_not_found(
-            read_with_cache(
+            read_with_cache_or_cold(
                 &*self.store,
+                self.cold_store.as_deref(),
                 ColIncomingReceipts,
                 &mut self.incoming_receipts,
                 &get_block_shard_id(block_hash, shard_id),
@@ -1059,8 +1125,9 @@ impl ChainStoreAccess for ChainStore {
 
     fn get_shard_id_for_receipt_id(&mut self, receipt_id: &CryptoHash) -> Result<&ShardId, Error> {
         option_to_not_found(
-            read_with_cache(
+            read_with_cache_or_cold(
                 &*self.store,
+                self.cold_store.as_deref(),
                 ColReceiptIdToShardId,
                 &mut self.receipt_id_to_shard_id,
                 receipt_id.as_ref(),
@@ -1100,13 +1167,25 @@ impl ChainStoreAccess for ChainStore {
         &mut self,
         tx_hash: &CryptoHash,
     ) -> Result<Option<&SignedTransaction>, Error> {
-        read_with_cache(&*self.store, ColTransactions, &mut self.transactions, tx_hash.as_ref())
-            .map_err(|e| e.into())
+        read_with_cache_or_cold(
+            &*self.store,
+            self.cold_store.as_deref(),
+            ColTransactions,
+            &mut self.transactions,
+            tx_hash.as_ref(),
+        )
+        .map_err(|e| e.into())
     }
 
     fn get_receipt(&mut self, receipt_id: &CryptoHash) -> Result<Option<&Receipt>, Error> {
-        read_with_cache(&*self.store, ColReceipts, &mut self.receipts, receipt_id.as_ref())
-            .map_err(|e| e.into())
+        read_with_cache_or_cold(
+            &*self.store,
+            self.cold_store.as_deref(),
+            ColReceipts,
+            &mut self.receipts,
+            receipt_id.as_ref(),
+        )
+        .map_err(|e| e.into())
     }
 
     fn get_genesis_height(&self) -> BlockHeight {
@@ -1191,6 +1270,11 @@ struct ChainStoreCacheUpdate {
 pub struct ChainStoreUpdateImpl<T> {
     chain_store: T,
     store_updates: Vec<StoreUpdate>,
+    /// Garbage collected data to be moved to the cold store, committed before `store_updates`.
+    cold_store_update: Option<StoreUpdate>,
+    /// Whether the data garbage collected by this update is already in the cold store, which
+    /// happens if the node stopped after committing the cold store but before the main one.
+    in_cold_store: bool,
     /// Blocks added during this update. Takes ownership (unclear how to not do it because of failure exists).
     chain_store_cache_update: ChainStoreCacheUpdate,
     head: Option<Tip>,
@@ -1223,6 +1307,8 @@ impl<'a> ChainStoreUpdate<'a> {
         ChainStoreUpdate {
             chain_store,
             store_updates: vec![],
+            cold_store_update: None,
+            in_cold_store: false,
             chain_store_cache_update: ChainStoreCacheUpdate::default(),
             head: None,
             tail: None,
@@ -2075,14 +2161,19 @@ impl<'a> ChainStoreUpdate<'a> {
                 let chunk = self.get_chunk(&chunk_hash)?.clone();
                 debug_assert_eq!(chunk.cloned_header().height_created(), height);
                 for transaction in chunk.transactions() {
-                    self.gc_col(ColTransactions, &transaction.get_hash().into());
+                    let key = transaction.get_hash().into();
+                    self.copy_to_cold_store(ColTransactions, &key)?;
+                    self.gc_col(ColTransactions, &key);
                 }
                 for receipt in chunk.receipts() {
-                    self.gc_col(ColReceipts, &receipt.get_hash().into());
+                    let key = receipt.get_hash().into();
+                    self.copy_to_cold_store(ColReceipts, &key)?;
+                    self.gc_col(ColReceipts, &key);
                 }
 
                 // 2. Delete chunk_hash-indexed data
                 let chunk_header_hash = chunk_hash.clone().into();
+                self.copy_to_cold_store(ColChunks, &chunk_header_hash)?;
                 self.gc_col(ColChunks, &chunk_header_hash);
                 self.gc_col(ColPartialChunks, &chunk_header_hash);
                 self.gc_col(ColInvalidChunks, &chunk_header_hash);
@@ -2112,6 +2203,19 @@ impl<'a> ChainStoreUpdate<'a> {
     ) -> Result<(), Error> {
         let mut store_update = self.store().store_update();
         let header = self.get_block_header(&block_hash).expect("block header must exist").clone();
+        // Archival nodes with a cold store move the data of the canonical chain there instead of
+        // deleting it.
+        let move_to_cold =
+            matches!(gc_mode, GCMode::Canonical(_)) && self.chain_store.cold_store.is_some();
+        if move_to_cold {
+            // The cold store is committed before the main one, so all the data moved there by an
+            // earlier attempt to clear this block, including the previous block itself, is
+            // already there if the node stopped in between. It must not be copied again, as that
+            // would increment the refcounts twice.
+            let prev_hash = *header.prev_hash();
+            let cold_store = self.chain_store.cold_store.as_ref().expect("cold store must exist");
+            self.in_cold_store = cold_store.exists(ColBlock, prev_hash.as_ref())?;
+        }
 
         // 1. Apply revert insertions or deletions from ColTrieChanges for Trie
         match gc_mode.clone() {
@@ -2144,9 +2248,17 @@ impl<'a> ChainStoreUpdate<'a> {
                     // TODO: pass in the actual shard version that this block uses
                     // https://github.com/near/nearcore/issues/4710
                     let shard_uid = ShardUId { version: 0, shard_id: shard_id as u32 };
-                    self.store()
-                        .get_ser(ColTrieChanges, &get_block_shard_uid(&block_hash, &shard_uid))?
-                        .map(|trie_changes: TrieChanges| {
+                    let trie_changes: Option<TrieChanges> = self
+                        .store()
+                        .get_ser(ColTrieChanges, &get_block_shard_uid(&block_hash, &shard_uid))?;
+                    if move_to_cold && !self.in_cold_store {
+                        // Archival nodes keep the state history in the cold store.
+                        if let Some(trie_changes) = &trie_changes {
+                            trie_changes.deletions_into(shard_uid, self.cold_store_update());
+                        }
+                    }
+                    trie_changes
+                        .map(|trie_changes| {
                             tries
                                 .apply_deletions(&trie_changes, shard_uid, &mut store_update)
                                 .map(|_| {
@@ -2180,6 +2292,10 @@ impl<'a> ChainStoreUpdate<'a> {
             .clone();
         let height = block.header().height();
 
+        if move_to_cold {
+            self.copy_block_data_to_cold_store(&block)?;
+        }
+
         // 2. Delete shard_id-indexed data (Receipts, State Headers and Parts, etc.)
         for shard_id in 0..block.header().chunk_mask().len() as ShardId {
             let block_shard_id = get_block_shard_id(&block_hash, shard_id);
@@ -2233,6 +2349,8 @@ impl<'a> ChainStoreUpdate<'a> {
         self.gc_outcomes(&block)?;
         match gc_mode {
             GCMode::StateSync { clear_block_info: false } => {}
+            // Block infos are needed to answer queries about old epochs
+            GCMode::Canonical(_) if move_to_cold => {}
             _ => self.gc_col(ColBlockInfo, &block_hash_vec),
         }
         self.gc_col(ColStateDlInfos, &block_hash_vec);
@@ -2265,6 +2383,109 @@ impl<'a> ChainStoreUpdate<'a> {
         Ok(())
     }
 
+    /// Copies the data of `block` which is garbage collected by `clear_block_data` to the cold
+    /// store.
+    fn copy_block_data_to_cold_store(&mut self, block: &Block) -> Result<(), Error> {
+        if self.in_cold_store {
+            return Ok(());
+        }
+        let block_hash = block.hash();
+        for shard_id in 0..block.header().chunk_mask().len() as ShardId {
+            let block_shard_id = get_block_shard_id(block_hash, shard_id);
+            self.copy_to_cold_store(ColIncomingReceipts, &block_shard_id)?;
+            self.copy_to_cold_store(ColOutgoingReceipts, &block_shard_id)?;
+            if let Ok(receipts) = self.get_outgoing_receipts(block_hash, shard_id) {
+                let receipt_ids: Vec<Vec<u8>> =
+                    receipts.iter().map(|receipt| receipt.receipt_id.as_ref().into()).collect();
+                for receipt_id in receipt_ids {
+                    self.copy_to_cold_store(ColReceiptIdToShardId, &receipt_id)?;
+                }
+            }
+        }
+        // Chunk extras are keyed by the shard uid, which depends on the shard layout of the block.
+        let chunk_extras: Vec<Vec<u8>> = self
+            .chain_store
+            .store()
+            .iter_prefix(ColChunkExtra, block_hash.as_ref())
+            .map(|key| key.0.into())
+            .collect();
+        for key in chunk_extras {
+            self.copy_to_cold_store(ColChunkExtra, &key)?;
+        }
+
+        let block_hash_vec: Vec<u8> = block_hash.as_ref().into();
+        self.copy_to_cold_store(ColBlock, &block_hash_vec)?;
+        self.copy_to_cold_store(ColBlockExtra, &block_hash_vec)?;
+        self.copy_to_cold_store(ColNextBlockHashes, &block_hash_vec)?;
+        let storage_key = KeyForStateChanges::get_prefix(block_hash);
+        let stored_state_changes: Vec<Vec<u8>> = self
+            .chain_store
+            .store()
+            .iter_prefix(ColStateChanges, storage_key.as_ref())
+            .map(|key| key.0.into())
+            .collect();
+        for key in stored_state_changes {
+            self.copy_to_cold_store(ColStateChanges, &key)?;
+        }
+
+        let cold_store = self.chain_store.cold_store.clone().expect("cold store must exist");
+        for chunk_header in
+            block.chunks().iter().filter(|h| h.height_included() == block.header().height())
+        {
+            let shard_id = chunk_header.shard_id();
+            self.copy_to_cold_store(ColOutcomeIds, &get_block_shard_id(block_hash, shard_id))?;
+            let outcome_ids =
+                self.chain_store.get_outcomes_by_block_hash_and_shard_id(block_hash, shard_id)?;
+            for outcome_id in outcome_ids {
+                // Outcomes are stored per id, so the ones of this block are merged with the ones
+                // already moved to the cold store, replacing any copied there before.
+                let outcomes_with_id: Vec<ExecutionOutcomeWithIdAndProof> = self
+                    .store()
+                    .get_ser(ColTransactionResult, outcome_id.as_ref())?
+                    .unwrap_or_default();
+                let mut cold_outcomes_with_id: Vec<ExecutionOutcomeWithIdAndProof> = cold_store
+                    .get_ser(ColTransactionResult, outcome_id.as_ref())?
+                    .unwrap_or_default();
+                cold_outcomes_with_id.retain(|outcome| &outcome.block_hash != block_hash);
+                cold_outcomes_with_id.extend(
+                    outcomes_with_id
+                        .into_iter()
+                        .filter(|outcome| &outcome.block_hash == block_hash),
+                );
+                self.cold_store_update().set_ser(
+                    ColTransactionResult,
+                    outcome_id.as_ref(),
+                    &cold_outcomes_with_id,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies the value under `key` in `col` to the cold store, if there is one.
+    fn copy_to_cold_store(&mut self, col: DBCol, key: &[u8]) -> Result<(), Error> {
+        if self.chain_store.cold_store.is_none() || self.in_cold_store {
+            return Ok(());
+        }
+        if let Some(value) = self.store().get(col, key)? {
+            let cold_store_update = self.cold_store_update();
+            if col.is_rc() {
+                cold_store_update.update_refcount(col, key, &value, 1);
+            } else {
+                cold_store_update.set(col, key, &value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Update of the cold store with all the data moved there by this update.
+    fn cold_store_update(&mut self) -> &mut StoreUpdate {
+        let cold_store = &self.chain_store.cold_store;
+        self.cold_store_update.get_or_insert_with(|| {
+            cold_store.as_ref().expect("cold store must exist").store_update()
+        })
+    }
+
     pub fn inc_gc_col_state(&mut self) {
         self.inc_gc(ColState);
     }
@@ -2362,7 +2583,10 @@ impl<'a> ChainStoreUpdate<'a> {
             let outcome_ids =
                 self.chain_store.get_outcomes_by_block_hash_and_shard_id(block_hash, shard_id)?;
             for outcome_id in outcome_ids {
-                let mut outcomes_with_id = self.chain_store.get_outcomes_by_id(&outcome_id)?;
+                let mut outcomes_with_id: Vec<ExecutionOutcomeWithIdAndProof> = self
+                    .store()
+                    .get_ser(ColTransactionResult, outcome_id.as_ref())?
+                    .unwrap_or_default();
                 outcomes_with_id.retain(|outcome| &outcome.block_hash != block_hash);
                 if outcomes_with_id.is_empty() {
                     self.gc_col(ColTransactionResult, &outcome_id.as_ref().into());
@@ -2858,6 +3082,10 @@ impl<'a> ChainStoreUpdate<'a> {
     }
 
     pub fn commit(mut self) -> Result<(), Error> {
+        // Data must reach the cold store before it is deleted from the main one.
+        if let Some(cold_store_update) = self.cold_store_update.take() {
+            cold_store_update.commit()?;
+        }
         let store_update = self.finalize()?;
         store_update.commit()?;
         let ChainStoreCacheUpdate {
@@ -2996,6 +3224,8 @@ impl Into<SavedStoreUpdate> for ChainStoreUpdate<'_> {
         SavedStoreUpdate {
             chain_store: (),
             store_updates: self.store_updates,
+            cold_store_update: self.cold_store_update,
+            in_cold_store: self.in_cold_store,
             chain_store_cache_update: self.chain_store_cache_update,
             head: self.head,
             tail: self.tail,
@@ -3026,6 +3256,8 @@ impl SavedStoreUpdate {
         ChainStoreUpdate {
             chain_store,
             store_updates: self.store_updates,
+            cold_store_update: self.cold_store_update,
+            in_cold_store: self.in_cold_store,
             chain_store_cache_update: self.chain_store_cache_update,
             head: self.head,
             tail: self.tail,
@@ -3400,6 +3632,106 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_clear_old_data_to_cold_store() {
+        let mut chain = get_chain_with_epoch_length(1);
+        let cold_store = create_test_store();
+        chain.mut_store().cold_store = Some(cold_store.clone());
+        let genesis = chain.get_block_by_height(0).unwrap().clone();
+        let signer = Arc::new(InMemoryValidatorSigner::from_seed(
+            "test1".parse().unwrap(),
+            KeyType::ED25519,
+            "test1",
+        ));
+        let mut prev_block = genesis.clone();
+        let mut blocks = vec![prev_block.clone()];
+        for i in 1..15 {
+            let block = Block::empty_with_height(&prev_block, i, &*signer.clone());
+            blocks.push(block.clone());
+            let mut store_update = chain.mut_store().store_update();
+            store_update.save_block(block.clone());
+            store_update.inc_block_refcount(block.header().prev_hash()).unwrap();
+            store_update.save_head(&Tip::from_header(block.header())).unwrap();
+            store_update.save_block_header(block.header().clone()).unwrap();
+            store_update
+                .chain_store_cache_update
+                .height_to_hashes
+                .insert(i, Some(*block.header().hash()));
+            store_update.save_next_block_hash(&prev_block.hash(), *block.hash());
+            store_update.commit().unwrap();
+
+            prev_block = block.clone();
+        }
+
+        let trie = chain.runtime_adapter.get_tries();
+        assert!(chain.clear_data(trie, 100).is_ok());
+
+        for i in 0..15 {
+            let block_hash = *blocks[i].hash();
+            let in_hot = chain.store().store.exists(DBCol::ColBlock, block_hash.as_ref()).unwrap();
+            let in_cold = cold_store.exists(DBCol::ColBlock, block_hash.as_ref()).unwrap();
+            assert_eq!(in_hot, i >= 8);
+            assert_eq!(in_cold, i < 8);
+            assert!(chain.get_block(&block_hash).is_ok());
+            assert!(chain.block_exists(&block_hash).unwrap());
+        }
+        assert_eq!(
+            chain.mut_store().get_next_block_hash(blocks[3].hash()).unwrap(),
+            blocks[4].hash()
+        );
+    }
+
+    /// Clearing a block again after the node stopped between committing the cold store and the
+    /// main one doesn't copy its data to the cold store twice.
+    #[test]
+    fn test_clear_block_data_already_in_cold_store() {
+        let mut chain = get_chain_with_epoch_length(1);
+        let cold_store = create_test_store();
+        chain.mut_store().cold_store = Some(cold_store.clone());
+        let genesis = chain.get_block_by_height(0).unwrap().clone();
+        let signer = Arc::new(InMemoryValidatorSigner::from_seed(
+            "test1".parse().unwrap(),
+            KeyType::ED25519,
+            "test1",
+        ));
+        let mut prev_block = genesis.clone();
+        let mut blocks = vec![prev_block.clone()];
+        for i in 1..5 {
+            let block = Block::empty_with_height(&prev_block, i, &*signer.clone());
+            blocks.push(block.clone());
+            let mut store_update = chain.mut_store().store_update();
+            store_update.save_block(block.clone());
+            store_update.inc_block_refcount(block.header().prev_hash()).unwrap();
+            store_update.save_head(&Tip::from_header(block.header())).unwrap();
+            store_update.save_block_header(block.header().clone()).unwrap();
+            store_update
+                .chain_store_cache_update
+                .height_to_hashes
+                .insert(i, Some(*block.header().hash()));
+            store_update.save_next_block_hash(&prev_block.hash(), *block.hash());
+            store_update.commit().unwrap();
+
+            prev_block = block.clone();
+        }
+
+        let tries = chain.runtime_adapter.get_tries();
+        let mut store_update = chain.mut_store().store_update();
+        store_update.clear_block_data(*blocks[2].hash(), GCMode::Canonical(tries.clone())).unwrap();
+        assert!(!store_update.in_cold_store);
+        store_update.cold_store_update.take().unwrap().commit().unwrap();
+        drop(store_update);
+        assert!(chain.store().store.exists(DBCol::ColBlock, blocks[1].hash().as_ref()).unwrap());
+        assert!(cold_store.exists(DBCol::ColBlock, blocks[1].hash().as_ref()).unwrap());
+
+        let mut store_update = chain.mut_store().store_update();
+        store_update.clear_block_data(*blocks[2].hash(), GCMode::Canonical(tries)).unwrap();
+        assert!(store_update.in_cold_store);
+        assert!(store_update.cold_store_update.is_none());
+        store_update.commit().unwrap();
+        assert!(!chain.store().store.exists(DBCol::ColBlock, blocks[1].hash().as_ref()).unwrap());
+        assert!(chain.get_block(blocks[1].hash()).is_ok());
+    }
+
     #[test]
     fn test_clear_old_data_fixed_height() {
         let mut chain = get_chain();
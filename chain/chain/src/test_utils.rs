@@ -39,7 +39,7 @@ use near_primitives::validator_signer::InMemoryValidatorSigner;
 use near_primitives::version::{ProtocolVersion, PROTOCOL_VERSION};
 use near_primitives::views::{
     AccessKeyInfoView, AccessKeyList, CallResult, ContractCodeView, EpochValidatorInfo,
-    QueryRequest, QueryResponse, QueryResponseKind, ViewStateResult,
+    QueryRequest, QueryResponse, QueryResponseKind, ViewStatePageResult, ViewStateResult,
 };
 use near_store::test_utils::create_test_store;
 use near_store::{
@@ -51,7 +51,7 @@ use crate::chain::{Chain, NUM_EPOCHS_TO_KEEP_STORE_DATA};
 use crate::store::ChainStoreAccess;
 use crate::types::{
     ApplySplitStateResult, ApplyTransactionResult, BlockHeaderInfo, ChainGenesis,
-    ValidatorInfoIdentifier,
+    SimulateTransactionResult, ValidatorInfoIdentifier,
 };
 #[cfg(feature = "protocol_feature_block_header_v3")]
 use crate::Doomslug;
@@ -852,6 +852,20 @@ impl RuntimeAdapter for KeyValueRuntime {
         unimplemented!();
     }
 
+    fn simulate_transaction(
+        &self,
+        _epoch_id: &EpochId,
+        _state_roots: &HashMap<ShardId, StateRoot>,
+        _block_height: BlockHeight,
+        _block_timestamp: u64,
+        _prev_block_hash: &CryptoHash,
+        _gas_price: Balance,
+        _random_seed: CryptoHash,
+        _transaction: &SignedTransaction,
+    ) -> Result<Result<SimulateTransactionResult, InvalidTxError>, Error> {
+        unimplemented!();
+    }
+
     fn query(
         &self,
         _shard_id: ShardUId,
@@ -911,6 +925,15 @@ impl RuntimeAdapter for KeyValueRuntime {
                 block_height,
                 block_hash: *block_hash,
             }),
+            QueryRequest::ViewStatePaginated { .. } => Ok(QueryResponse {
+                kind: QueryResponseKind::ViewStatePaginated(ViewStatePageResult {
+                    values: Default::default(),
+                    next_key: None,
+                    state_root: *state_root,
+                }),
+                block_height,
+                block_hash: *block_hash,
+            }),
             QueryRequest::CallFunction { .. } => Ok(QueryResponse {
                 kind: QueryResponseKind::CallResult(CallResult {
                     result: Default::default(),
@@ -1,5 +1,6 @@
 use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use borsh::{BorshDeserialize, BorshSerialize};
@@ -25,7 +26,8 @@ use near_primitives::transaction::{ExecutionOutcomeWithId, SignedTransaction};
 use near_primitives::types::validator_stake::{ValidatorStake, ValidatorStakeIter};
 use near_primitives::types::{
     AccountId, ApprovalStake, Balance, BlockHeight, BlockHeightDelta, EpochId, Gas, MerkleHash,
-    NumBlocks, ShardId, StateChangesForSplitStates, StateRoot, StateRootNode,
+    NumBlocks, RawStateChangesWithTrieKey, ShardId, StateChangesForSplitStates, StateRoot,
+    StateRootNode,
 };
 use near_primitives::version::{
     ProtocolVersion, MIN_GAS_PRICE_NEP_92, MIN_GAS_PRICE_NEP_92_FIX, MIN_PROTOCOL_VERSION_NEP_92,
@@ -107,6 +109,17 @@ pub struct ApplyTransactionResult {
     pub processed_delayed_receipts: Vec<Receipt>,
 }
 
+/// Result of `RuntimeAdapter::simulate_transaction`.
+pub struct SimulateTransactionResult {
+    /// Outcomes of the transaction and of the executed receipts, in the execution order.
+    pub outcomes: Vec<ExecutionOutcomeWithId>,
+    /// Executed receipts, in the execution order.
+    pub receipts: Vec<Receipt>,
+    /// Receipts to the shards without state, which were not executed.
+    pub unprocessed_receipts: Vec<Receipt>,
+    pub state_changes: Vec<RawStateChangesWithTrieKey>,
+}
+
 impl ApplyTransactionResult {
     /// Returns root and paths for all the outcomes in the result.
     pub fn compute_outcomes_proof(
@@ -263,6 +276,18 @@ pub trait RuntimeAdapter: Send + Sync {
 
     fn get_store(&self) -> Arc<Store>;
 
+    /// Store that garbage collection moves old data to instead of deleting it, only set on
+    /// archival nodes.
+    fn get_cold_store(&self) -> Option<Arc<Store>> {
+        None
+    }
+
+    /// Directory to take a snapshot of the store into at the start of every epoch and the number
+    /// of the most recent snapshots to keep, if snapshots are enabled.
+    fn get_store_snapshots_config(&self) -> Option<(PathBuf, usize)> {
+        None
+    }
+
     /// Returns trie. Since shard layout may change from epoch to epoch, `shard_id` itself is
     /// not enough to identify the trie. `prev_hash` is used to identify the epoch the given
     /// `shard_id` is at.
@@ -655,6 +680,23 @@ pub trait RuntimeAdapter: Send + Sync {
         request: &QueryRequest,
     ) -> Result<QueryResponse, near_chain_primitives::error::QueryError>;
 
+    /// Executes `transaction` and all the receipts it produces on top of `state_roots`, the
+    /// states of the shards after `prev_block_hash`, as if they were included in the next blocks.
+    /// `epoch_id` is the epoch of `prev_block_hash`, which gives the shards of `state_roots` and
+    /// the protocol version of the execution. Receipts to the shards missing from `state_roots`
+    /// are not executed. Nothing is persisted.
+    fn simulate_transaction(
+        &self,
+        epoch_id: &EpochId,
+        state_roots: &HashMap<ShardId, StateRoot>,
+        block_height: BlockHeight,
+        block_timestamp: u64,
+        prev_block_hash: &CryptoHash,
+        gas_price: Balance,
+        random_seed: CryptoHash,
+        transaction: &SignedTransaction,
+    ) -> Result<Result<SimulateTransactionResult, InvalidTxError>, Error>;
+
     fn get_validator_info(
         &self,
         epoch_id: ValidatorInfoIdentifier,
@@ -745,6 +787,18 @@ pub trait RuntimeAdapter: Send + Sync {
         prev_block_hash: &CryptoHash,
     ) -> Result<EpochId, Error>;
 
+    /// Drops the in-memory state derived from the store, after the sandbox reverted the store to
+    /// an earlier snapshot.
+    #[cfg(feature = "sandbox")]
+    fn sandbox_reset_caches(&self) {}
+
+    /// Number of times the caches were reset by `sandbox_reset_caches`, so that the other users
+    /// of the store, e.g. view clients, notice the store was reverted.
+    #[cfg(feature = "sandbox")]
+    fn sandbox_resets(&self) -> u64 {
+        0
+    }
+
     /// Build receipts hashes.
     // Due to borsh serialization constraints, we have to use `&Vec<Receipt>` instead of `&[Receipt]`
     // here.
@@ -834,7 +888,8 @@ mod tests {
             KeyType::ED25519,
             "other2",
         );
-        let approvals = vec![Some(Approval::new(*b1.hash(), 1, 2, &other_signer).signature)];
+        let approvals =
+            vec![Some(Approval::new(*b1.hash(), 1, 2, &other_signer).unwrap().signature)];
         let b2 = Block::empty_with_approvals(
             &b1,
             2,
@@ -19,7 +19,9 @@ use near_network::types::{
     PartialEncodedChunkResponseMsg,
 };
 use near_network::NetworkRequests;
-use near_pool::{PoolIteratorWrapper, TransactionPool};
+use near_pool::{
+    InsertTransactionResult, PoolIteratorWrapper, TransactionPool, TransactionPoolConfig,
+};
 use near_primitives::block::{BlockHeader, Tip};
 use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::merkle::{merklize, verify_path, MerklePath};
@@ -80,6 +82,13 @@ pub enum ProcessPartialEncodedChunkResult {
     NeedBlock,
 }
 
+impl ProcessPartialEncodedChunkResult {
+    /// Whether the chunk had parts or receipts which were not known yet and passed validation.
+    pub fn has_new_valid_parts(&self) -> bool {
+        matches!(self, Self::HaveAllPartsAndReceipts(_) | Self::NeedMorePartsOrReceipts)
+    }
+}
+
 #[derive(Clone, Debug)]
 struct ChunkRequestInfo {
     height: BlockHeight,
@@ -377,6 +386,7 @@ pub struct ShardsManager {
     me: Option<AccountId>,
 
     tx_pools: HashMap<ShardId, TransactionPool>,
+    tx_pool_config: TransactionPoolConfig,
 
     runtime_adapter: Arc<dyn RuntimeAdapter>,
     network_adapter: Arc<dyn NetworkAdapter>,
@@ -394,10 +404,12 @@ impl ShardsManager {
         me: Option<AccountId>,
         runtime_adapter: Arc<dyn RuntimeAdapter>,
         network_adapter: Arc<dyn NetworkAdapter>,
+        tx_pool_config: TransactionPoolConfig,
     ) -> Self {
         Self {
             me: me.clone(),
             tx_pools: HashMap::new(),
+            tx_pool_config,
             runtime_adapter: runtime_adapter.clone(),
             network_adapter,
             encoded_chunks: EncodedChunksCache::new(),
@@ -768,9 +780,16 @@ impl ShardsManager {
         self.encoded_chunks.get_chunk_headers_for_block(&prev_block_hash)
     }
 
-    /// Returns true if transaction is not in the pool before call
-    pub fn insert_transaction(&mut self, shard_id: ShardId, tx: SignedTransaction) -> bool {
-        self.tx_pools.entry(shard_id).or_insert_with(TransactionPool::new).insert_transaction(tx)
+    pub fn insert_transaction(
+        &mut self,
+        shard_id: ShardId,
+        tx: SignedTransaction,
+    ) -> InsertTransactionResult {
+        let tx_pool_config = self.tx_pool_config;
+        self.tx_pools
+            .entry(shard_id)
+            .or_insert_with(|| TransactionPool::new(tx_pool_config))
+            .insert_transaction(tx)
     }
 
     pub fn remove_transactions(
@@ -788,9 +807,10 @@ impl ShardsManager {
         shard_id: ShardId,
         transactions: &Vec<SignedTransaction>,
     ) {
+        let tx_pool_config = self.tx_pool_config;
         self.tx_pools
             .entry(shard_id)
-            .or_insert_with(TransactionPool::new)
+            .or_insert_with(|| TransactionPool::new(tx_pool_config))
             .reintroduce_transactions(transactions.clone());
     }
 
@@ -1738,6 +1758,7 @@ mod test {
             Some("test".parse().unwrap()),
             runtime_adapter,
             network_adapter.clone(),
+            TransactionPoolConfig::default(),
         );
         shards_manager.requested_partial_encoded_chunks.insert(
             ChunkHash(hash(&[1])),
@@ -1792,6 +1813,7 @@ mod test {
             Some("test".parse().unwrap()),
             runtime_adapter.clone(),
             network_adapter.clone(),
+            TransactionPoolConfig::default(),
         );
         let signer =
             InMemoryValidatorSigner::from_seed("test".parse().unwrap(), KeyType::ED25519, "test");
@@ -1958,6 +1980,7 @@ mod test {
             Some(fixture.mock_chunk_part_owner.clone()),
             fixture.mock_runtime.clone(),
             fixture.mock_network.clone(),
+            TransactionPoolConfig::default(),
         );
         let partial_encoded_chunk = fixture.make_partial_encoded_chunk(&fixture.mock_part_ords);
         let result = shards_manager
@@ -2004,6 +2027,29 @@ mod test {
         assert!(requests_count > 0);
     }
 
+    #[test]
+    fn test_known_parts_are_not_new() {
+        let mut fixture = ChunkForwardingTestFixture::default();
+        let mut shards_manager = ShardsManager::new(
+            Some(fixture.mock_chunk_part_owner.clone()),
+            fixture.mock_runtime.clone(),
+            fixture.mock_network.clone(),
+            TransactionPoolConfig::default(),
+        );
+        let partial_encoded_chunk = fixture.make_partial_encoded_chunk(&fixture.mock_part_ords);
+        for expected_new_valid_parts in vec![true, false] {
+            let result = shards_manager
+                .process_partial_encoded_chunk(
+                    MaybeValidated::NotValidated(&partial_encoded_chunk),
+                    &mut fixture.chain_store,
+                    &mut fixture.rs,
+                    PROTOCOL_VERSION,
+                )
+                .unwrap();
+            assert_eq!(result.has_new_valid_parts(), expected_new_valid_parts);
+        }
+    }
+
     #[test]
     fn test_receive_forward_before_header() {
         // When a node receives a chunk forward before the chunk header, it should store
@@ -2013,6 +2059,7 @@ mod test {
             Some(fixture.mock_shard_tracker.clone()),
             fixture.mock_runtime.clone(),
             fixture.mock_network.clone(),
+            TransactionPoolConfig::default(),
         );
         let (most_parts, other_parts) = {
             let mut most_parts = fixture.mock_chunk_parts.clone();
@@ -8,6 +8,7 @@ use near_chain::types::RuntimeAdapter;
 use near_chain::ChainStore;
 use near_crypto::KeyType;
 use near_network::test_utils::MockNetworkAdapter;
+use near_pool::TransactionPoolConfig;
 use near_primitives::block::BlockHeader;
 use near_primitives::hash::{self, CryptoHash};
 use near_primitives::merkle;
@@ -205,6 +206,7 @@ impl Default for ChunkForwardingTestFixture {
             Some(mock_chunk_producer.clone()),
             mock_runtime.clone(),
             mock_network.clone(),
+            TransactionPoolConfig::default(),
         );
         let receipts = Vec::new();
         let shard_layout = mock_runtime.get_shard_layout(&EpochId::default()).unwrap();
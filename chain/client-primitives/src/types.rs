@@ -5,6 +5,7 @@ use std::sync::Arc;
 use actix::Message;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
 
 use near_chain_configs::ProtocolConfigView;
 use near_network_primitives::types::{AccountOrPeerIdOrHash, KnownProducer, PeerInfo};
@@ -12,6 +13,7 @@ use near_primitives::errors::InvalidTxError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{MerklePath, PartialMerkleTree};
 use near_primitives::sharding::ChunkHash;
+use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::{
     AccountId, BlockHeight, BlockReference, EpochReference, MaybeBlockId, ShardId,
     TransactionOrReceiptId,
@@ -21,8 +23,8 @@ use near_primitives::views::validator_stake_view::ValidatorStakeView;
 use near_primitives::views::{
     BlockView, ChunkView, EpochValidatorInfo, ExecutionOutcomeWithIdView,
     FinalExecutionOutcomeViewEnum, GasPriceView, LightClientBlockLiteView, LightClientBlockView,
-    QueryRequest, QueryResponse, ReceiptView, StateChangesKindsView, StateChangesRequestView,
-    StateChangesView,
+    QueryRequest, QueryResponse, ReceiptView, SimulatedTransactionView, StateChangesKindsView,
+    StateChangesRequestView, StateChangesView,
 };
 pub use near_primitives::views::{StatusResponse, StatusSyncInfo};
 
@@ -425,6 +427,22 @@ impl Message for GetNetworkInfo {
     type Result = Result<NetworkInfoResponse, String>;
 }
 
+/// New head of the chain, sent to [`SubscribeChainHead`] subscribers.
+#[derive(Clone, Debug)]
+pub struct ChainHeadEvent {
+    pub block_hash: CryptoHash,
+    pub height: BlockHeight,
+    pub last_final_block: CryptoHash,
+}
+
+/// Subscribes to the changes of the chain head. Receiver lags if it doesn't keep up, see
+/// [`broadcast::Receiver::recv`].
+pub struct SubscribeChainHead {}
+
+impl Message for SubscribeChainHead {
+    type Result = broadcast::Receiver<ChainHeadEvent>;
+}
+
 pub struct GetGasPrice {
     pub block_id: MaybeBlockId,
 }
@@ -606,6 +624,15 @@ impl Message for GetStateChangesWithCauseInBlock {
     type Result = Result<StateChangesView, GetStateChangesError>;
 }
 
+/// Like `GetStateChangesWithCauseInBlock`, but grouped by the shard of the changed account.
+pub struct GetStateChangesWithCauseInBlockByShard {
+    pub block_hash: CryptoHash,
+}
+
+impl Message for GetStateChangesWithCauseInBlockByShard {
+    type Result = Result<HashMap<ShardId, StateChangesView>, GetStateChangesError>;
+}
+
 pub struct GetExecutionOutcome {
     pub id: TransactionOrReceiptId,
 }
@@ -753,6 +780,41 @@ impl Message for GetReceipt {
     type Result = Result<Option<ReceiptView>, GetReceiptError>;
 }
 
+/// Executes a transaction and the receipts it produces on top of the state after the head block,
+/// without submitting it.
+pub struct SimulateTransaction {
+    pub signed_transaction: SignedTransaction,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SimulateTransactionError {
+    #[error("IO Error: {0}")]
+    IOError(String),
+    #[error("Invalid transaction: {0}")]
+    InvalidTransaction(InvalidTxError),
+    #[error("Node doesn't track the shard of the transaction signer")]
+    DoesNotTrackShard,
+    // NOTE: Currently, the underlying errors are too broad, and while we tried to handle
+    // expected cases, we cannot statically guarantee that no other errors will be returned
+    // in the future.
+    // TODO #3851: Remove this variant once we can exhaustively match all the underlying errors
+    #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}")]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for SimulateTransactionError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        match error.kind() {
+            near_chain_primitives::ErrorKind::IOErr(s) => Self::IOError(s),
+            _ => Self::Unreachable(error.to_string()),
+        }
+    }
+}
+
+impl Message for SimulateTransaction {
+    type Result = Result<SimulatedTransactionView, SimulateTransactionError>;
+}
+
 pub struct GetProtocolConfig(pub BlockReference);
 
 impl Message for GetProtocolConfig {
@@ -27,6 +27,7 @@ use near_network::{
     FullPeerInfo, NetworkAdapter, NetworkClientResponses, NetworkRequests,
     EPOCH_SYNC_PEER_TIMEOUT_MS, EPOCH_SYNC_REQUEST_TIMEOUT_MS,
 };
+use near_pool::{InsertTransactionResult, TransactionPoolConfig};
 use near_primitives::block::{Approval, ApprovalInner, ApprovalMessage, Block, BlockHeader, Tip};
 use near_primitives::challenge::{Challenge, ChallengeBody};
 use near_primitives::hash::CryptoHash;
@@ -44,6 +45,9 @@ use near_primitives::types::{AccountId, ApprovalStake, BlockHeight, EpochId, Sha
 use near_primitives::unwrap_or_return;
 use near_primitives::utils::{to_timestamp, MaybeValidated};
 use near_primitives::validator_signer::ValidatorSigner;
+use near_store::snapshot::Snapshotter;
+#[cfg(feature = "sandbox")]
+use near_store::snapshot::StoreDump;
 
 use crate::metrics;
 use crate::sync::{BlockSync, EpochSync, HeaderSync, StateSync, StateSyncResult};
@@ -56,6 +60,22 @@ use near_network::types::PartialEncodedChunkForwardMsg;
 
 const NUM_REBROADCAST_BLOCKS: usize = 30;
 
+/// Limits of the sandbox snapshots kept in memory. The oldest snapshots are dropped first, the
+/// newest one is always kept.
+#[cfg(feature = "sandbox")]
+const MAX_SANDBOX_SNAPSHOTS: usize = 16;
+#[cfg(feature = "sandbox")]
+const MAX_SANDBOX_SNAPSHOTS_SIZE_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Result of processing a partial encoded chunk.
+pub struct ProcessedPartialEncodedChunk {
+    /// Blocks accepted once the chunk was complete.
+    pub accepted_blocks: Vec<AcceptedBlock>,
+    /// Whether the chunk had parts or receipts which were not known yet and passed validation.
+    /// Known parts and parts stored until their block arrives don't count.
+    pub new_valid_parts: bool,
+}
+
 pub struct Client {
     /// Adversarial controls
     #[cfg(feature = "test_features")]
@@ -63,6 +83,17 @@ pub struct Client {
     #[cfg(feature = "test_features")]
     pub adv_produce_blocks_only_valid: bool,
 
+    /// Sandbox controls
+    /// Number of blocks left to produce without waiting for doomslug timeouts.
+    #[cfg(feature = "sandbox")]
+    pub sandbox_fast_forward_blocks: near_primitives::types::BlockHeightDelta,
+    /// Snapshots taken by `sandbox_snapshot` by their ids, up to `MAX_SANDBOX_SNAPSHOTS`.
+    #[cfg(feature = "sandbox")]
+    sandbox_snapshots: std::collections::BTreeMap<u64, StoreDump>,
+
+    /// Takes store snapshots at the start of every epoch, if they are enabled.
+    store_snapshotter: Snapshotter,
+
     pub config: ClientConfig,
     pub sync_status: SyncStatus,
     pub chain: Chain,
@@ -117,13 +148,17 @@ impl Client {
             validator_signer.as_ref().map(|x| x.validator_id().clone()),
             runtime_adapter.clone(),
             network_adapter.clone(),
+            TransactionPoolConfig {
+                max_transactions: config.tx_pool_max_transactions,
+                max_size_bytes: config.tx_pool_max_size_bytes,
+                max_transactions_per_signer: config.tx_pool_max_transactions_per_signer,
+            },
         );
         let sync_status = SyncStatus::AwaitingPeers;
         let genesis_block = chain.genesis_block();
-        let epoch_sync = EpochSync::new(
+        let mut epoch_sync = EpochSync::new(
             network_adapter.clone(),
             genesis_block.header().epoch_id().clone(),
-            genesis_block.header().next_epoch_id().clone(),
             runtime_adapter
                 .get_epoch_block_producers_ordered(
                     &genesis_block.header().epoch_id(),
@@ -135,6 +170,10 @@ impl Client {
             Duration::from_millis(EPOCH_SYNC_REQUEST_TIMEOUT_MS),
             Duration::from_millis(EPOCH_SYNC_PEER_TIMEOUT_MS),
         );
+        if let Some(sync_hash) = chain.store().epoch_sync_hash()? {
+            epoch_sync.sync_hash = sync_hash;
+            epoch_sync.done = true;
+        }
         let header_sync = HeaderSync::new(
             network_adapter.clone(),
             config.header_sync_initial_timeout,
@@ -144,7 +183,11 @@ impl Client {
         );
         let block_sync =
             BlockSync::new(network_adapter.clone(), config.block_fetch_horizon, config.archive);
-        let state_sync = StateSync::new(network_adapter.clone(), config.state_sync_timeout);
+        let state_sync = StateSync::new(
+            network_adapter.clone(),
+            config.state_sync_timeout,
+            config.state_sync_parts_dir.clone(),
+        );
         let num_block_producer_seats = config.num_block_producer_seats as usize;
         let data_parts = runtime_adapter.num_data_parts();
         let parity_parts = runtime_adapter.num_total_parts() - data_parts;
@@ -164,6 +207,11 @@ impl Client {
             adv_produce_blocks: false,
             #[cfg(feature = "test_features")]
             adv_produce_blocks_only_valid: false,
+            #[cfg(feature = "sandbox")]
+            sandbox_fast_forward_blocks: 0,
+            #[cfg(feature = "sandbox")]
+            sandbox_snapshots: Default::default(),
+            store_snapshotter: Snapshotter::default(),
             config,
             sync_status,
             chain,
@@ -476,7 +524,12 @@ impl Client {
         // let challenges = self.challenges.drain().map(|(_, challenge)| challenge).collect();
         let protocol_version = self.runtime_adapter.get_epoch_protocol_version(&next_epoch_id)?;
 
-        let block = Block::produce(
+        #[cfg(feature = "sandbox")]
+        let timestamp_override = Some(Utc::now() + self.chain.sandbox_time_offset);
+        #[cfg(not(feature = "sandbox"))]
+        let timestamp_override = None;
+
+        let block = match Block::produce(
             protocol_version,
             &prev_header,
             next_height,
@@ -497,7 +550,14 @@ impl Client {
             &*validator_signer,
             next_bp_hash,
             block_merkle_root,
-        );
+            timestamp_override,
+        ) {
+            Ok(block) => block,
+            Err(err) => {
+                warn!(target: "client", "Not producing block at height {}: {}", next_height, err);
+                return Ok(None);
+            }
+        };
 
         // Update latest known even before returning block out, to prevent race conditions.
         self.chain.mut_store().save_latest_known(LatestKnown {
@@ -670,7 +730,13 @@ impl Client {
     pub fn send_challenges(&mut self, challenges: Arc<RwLock<Vec<ChallengeBody>>>) {
         if let Some(validator_signer) = self.validator_signer.as_ref() {
             for body in challenges.write().unwrap().drain(..) {
-                let challenge = Challenge::produce(body, &**validator_signer);
+                let challenge = match Challenge::produce(body, &**validator_signer) {
+                    Ok(challenge) => challenge,
+                    Err(err) => {
+                        warn!(target: "client", "Not sending challenge: {}", err);
+                        continue;
+                    }
+                };
                 self.challenges.insert(challenge.hash, challenge.clone());
                 self.network_adapter.do_send(NetworkRequests::Challenge(challenge));
             }
@@ -729,27 +795,25 @@ impl Client {
 
         // Send out challenge if the block was found to be invalid.
         if let Some(validator_signer) = self.validator_signer.as_ref() {
-            match &result {
+            let body = match &result {
                 Err(e) => match e.kind() {
                     near_chain::ErrorKind::InvalidChunkProofs(chunk_proofs) => {
-                        self.network_adapter.do_send(NetworkRequests::Challenge(
-                            Challenge::produce(
-                                ChallengeBody::ChunkProofs(*chunk_proofs),
-                                &**validator_signer,
-                            ),
-                        ));
+                        Some(ChallengeBody::ChunkProofs(*chunk_proofs))
                     }
                     near_chain::ErrorKind::InvalidChunkState(chunk_state) => {
-                        self.network_adapter.do_send(NetworkRequests::Challenge(
-                            Challenge::produce(
-                                ChallengeBody::ChunkState(*chunk_state),
-                                &**validator_signer,
-                            ),
-                        ));
+                        Some(ChallengeBody::ChunkState(*chunk_state))
                     }
-                    _ => {}
+                    _ => None,
                 },
-                _ => {}
+                _ => None,
+            };
+            if let Some(body) = body {
+                match Challenge::produce(body, &**validator_signer) {
+                    Ok(challenge) => {
+                        self.network_adapter.do_send(NetworkRequests::Challenge(challenge))
+                    }
+                    Err(err) => warn!(target: "client", "Not sending challenge: {}", err),
+                }
             }
         }
 
@@ -786,7 +850,7 @@ impl Client {
     pub fn process_partial_encoded_chunk_response(
         &mut self,
         response: PartialEncodedChunkResponseMsg,
-    ) -> Result<Vec<AcceptedBlock>, Error> {
+    ) -> Result<ProcessedPartialEncodedChunk, Error> {
         let header = self.shards_mgr.get_partial_encoded_chunk_header(&response.chunk_hash)?;
         let partial_chunk = PartialEncodedChunk::new(header, response.parts, response.receipts);
         // We already know the header signature is valid because we read it from the
@@ -797,7 +861,7 @@ impl Client {
     pub fn process_partial_encoded_chunk_forward(
         &mut self,
         forward: PartialEncodedChunkForwardMsg,
-    ) -> Result<Vec<AcceptedBlock>, Error> {
+    ) -> Result<ProcessedPartialEncodedChunk, Error> {
         let maybe_header = self
             .shards_mgr
             .validate_partial_encoded_chunk_forward(&forward)
@@ -840,13 +904,13 @@ impl Client {
     pub fn process_partial_encoded_chunk(
         &mut self,
         partial_encoded_chunk: MaybeValidated<PartialEncodedChunk>,
-    ) -> Result<Vec<AcceptedBlock>, Error> {
+    ) -> Result<ProcessedPartialEncodedChunk, Error> {
         fn missing_block_handler(
             client: &mut Client,
             pec: PartialEncodedChunkV2,
-        ) -> Result<Vec<AcceptedBlock>, Error> {
+        ) -> Result<ProcessedPartialEncodedChunk, Error> {
             client.shards_mgr.store_partial_encoded_chunk(client.chain.head_header()?, pec);
-            Ok(vec![])
+            Ok(ProcessedPartialEncodedChunk { accepted_blocks: vec![], new_valid_parts: false })
         }
         let block_hash = partial_encoded_chunk.prev_block();
         match self.runtime_adapter.get_epoch_id_from_prev_block(block_hash) {
@@ -868,11 +932,12 @@ impl Client {
                     protocol_version,
                 )?;
 
-                match process_result {
-                    ProcessPartialEncodedChunkResult::Known => Ok(vec![]),
+                let new_valid_parts = process_result.has_new_valid_parts();
+                let accepted_blocks = match process_result {
+                    ProcessPartialEncodedChunkResult::Known => vec![],
                     ProcessPartialEncodedChunkResult::HaveAllPartsAndReceipts(_) => {
                         self.chain.blocks_with_missing_chunks.accept_chunk(&chunk_hash);
-                        Ok(self.process_blocks_with_missing_chunks(protocol_version))
+                        self.process_blocks_with_missing_chunks(protocol_version)
                     }
                     ProcessPartialEncodedChunkResult::NeedMorePartsOrReceipts => {
                         let chunk_header = pec_v2.extract().header;
@@ -881,12 +946,13 @@ impl Client {
                             &self.chain.header_head()?,
                             protocol_version,
                         );
-                        Ok(vec![])
+                        vec![]
                     }
                     ProcessPartialEncodedChunkResult::NeedBlock => {
-                        missing_block_handler(self, pec_v2.extract())
+                        return missing_block_handler(self, pec_v2.extract());
                     }
-                }
+                };
+                Ok(ProcessedPartialEncodedChunk { accepted_blocks, new_valid_parts })
             }
 
             // If the epoch_id cannot be looked up then we have not processed
@@ -997,7 +1063,8 @@ impl Client {
                 self.chain.get_block_header(last_final_block).map_or(0, |header| header.height())
             };
             self.chain.blocks_with_missing_chunks.prune_blocks_below_height(last_finalized_height);
-            if !self.config.archive {
+            // Archival nodes only garbage collect if they have a cold store to move old data to.
+            if !self.config.archive || self.chain.store().cold_store().is_some() {
                 let timer = near_metrics::start_timer(&metrics::GC_TIME);
                 if let Err(err) = self
                     .chain
@@ -1016,6 +1083,15 @@ impl Client {
                 if next_epoch_protocol_version > PROTOCOL_VERSION {
                     panic!("The client protocol version is older than the protocol version of the network. Please update nearcore");
                 }
+                if let Some((snapshots_dir, keep)) =
+                    self.runtime_adapter.get_store_snapshots_config()
+                {
+                    self.store_snapshotter.start(
+                        self.chain.store().owned_store(),
+                        snapshots_dir,
+                        keep,
+                    );
+                }
             }
         }
 
@@ -1137,11 +1213,11 @@ impl Client {
         for (_shard_id, partial_encoded_chunk) in partial_encoded_chunks.drain() {
             let chunk =
                 MaybeValidated::NotValidated(PartialEncodedChunk::V2(partial_encoded_chunk));
-            if let Ok(accepted_blocks) = self.process_partial_encoded_chunk(chunk) {
+            if let Ok(processed) = self.process_partial_encoded_chunk(chunk) {
                 // Executing process_partial_encoded_chunk can unlock some blocks.
                 // Any block that is in the blocks_with_missing_chunks which doesn't have any chunks
                 // for which we track shards will be unblocked here.
-                for accepted_block in accepted_blocks {
+                for accepted_block in processed.accepted_blocks {
                     self.on_block_accepted(
                         accepted_block.hash,
                         accepted_block.status,
@@ -1519,7 +1595,15 @@ impl Client {
                     shard_id,
                     is_forwarded
                 );
-                self.shards_mgr.insert_transaction(shard_id, tx.clone());
+                if self.shards_mgr.insert_transaction(shard_id, tx.clone())
+                    == InsertTransactionResult::NoSpaceLeft
+                {
+                    debug!(target: "client", "Transaction pool of shard {} is full, dropping transaction {}", shard_id, tx.get_hash());
+                    // Other validators may still have space for the transaction.
+                    if active_validator {
+                        return Ok(NetworkClientResponses::TransactionPoolFull);
+                    }
+                }
 
                 // Active validator:
                 //   possibly forward to next epoch validators
@@ -1624,11 +1708,12 @@ impl Client {
                 }
             };
             let state_sync_timeout = self.config.state_sync_timeout;
+            let state_sync_parts_dir = self.config.state_sync_parts_dir.clone();
             let epoch_id = self.chain.get_block(&sync_hash)?.header().epoch_id().clone();
             let (state_sync, new_shard_sync, blocks_catch_up_state) =
                 self.catchup_state_syncs.entry(sync_hash).or_insert_with(|| {
                     (
-                        StateSync::new(network_adapter1, state_sync_timeout),
+                        StateSync::new(network_adapter1, state_sync_timeout, state_sync_parts_dir),
                         new_shard_sync,
                         BlocksCatchUpState::new(sync_hash.clone(), epoch_id),
                     )
@@ -1732,3 +1817,62 @@ impl Client {
         Ok(())
     }
 }
+
+/// Sandbox node specific operations
+#[cfg(feature = "sandbox")]
+impl Client {
+    /// Copies the chain and the state into memory. Returns the id to restore the snapshot by.
+    ///
+    /// Drops the oldest snapshots beyond `MAX_SANDBOX_SNAPSHOTS` or
+    /// `MAX_SANDBOX_SNAPSHOTS_SIZE_BYTES`.
+    pub fn sandbox_snapshot(&mut self) -> u64 {
+        let snapshot_id = self.sandbox_snapshots.keys().next_back().map_or(0, |id| id + 1);
+        self.sandbox_snapshots
+            .insert(snapshot_id, StoreDump::take(&self.chain.store().owned_store()));
+        let mut size_bytes: u64 = self.sandbox_snapshots.values().map(StoreDump::size_bytes).sum();
+        while self.sandbox_snapshots.len() > 1
+            && (self.sandbox_snapshots.len() > MAX_SANDBOX_SNAPSHOTS
+                || size_bytes > MAX_SANDBOX_SNAPSHOTS_SIZE_BYTES)
+        {
+            let oldest_id = *self.sandbox_snapshots.keys().next().expect("checked above");
+            let oldest = self.sandbox_snapshots.remove(&oldest_id).expect("checked above");
+            size_bytes -= oldest.size_bytes();
+            warn!(target: "client", "Dropping sandbox snapshot {} to stay within the snapshot limits", oldest_id);
+        }
+        snapshot_id
+    }
+
+    /// Reverts the node to the snapshot `snapshot_id`, dropping the blocks, chunks, transactions
+    /// and approvals received since. Returns false if there is no such snapshot.
+    pub fn sandbox_restore(&mut self, snapshot_id: u64) -> Result<bool, Error> {
+        let dump = match self.sandbox_snapshots.get(&snapshot_id) {
+            Some(dump) => dump,
+            None => return Ok(false),
+        };
+        self.chain.sandbox_restore(dump)?;
+        self.shards_mgr = ShardsManager::new(
+            self.validator_signer.as_ref().map(|x| x.validator_id().clone()),
+            self.runtime_adapter.clone(),
+            self.network_adapter.clone(),
+            TransactionPoolConfig {
+                max_transactions: self.config.tx_pool_max_transactions,
+                max_size_bytes: self.config.tx_pool_max_size_bytes,
+                max_transactions_per_signer: self.config.tx_pool_max_transactions_per_signer,
+            },
+        );
+        self.doomslug = Doomslug::new(
+            self.chain.store().largest_target_height()?,
+            self.config.min_block_production_delay,
+            self.config.max_block_production_delay,
+            self.config.max_block_production_delay / 10,
+            self.config.max_block_wait_delay,
+            self.validator_signer.clone(),
+            self.chain.doomslug_threshold_mode,
+        );
+        self.pending_approvals.cache_clear();
+        self.challenges.clear();
+        self.rebroadcasted_blocks.cache_clear();
+        self.sandbox_fast_forward_blocks = 0;
+        Ok(true)
+    }
+}
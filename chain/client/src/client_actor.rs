@@ -6,12 +6,13 @@ use std::thread;
 use std::time::{Duration, Instant};
 
 use actix::dev::ToEnvelope;
-use actix::{Actor, Addr, Arbiter, AsyncContext, Context, Handler, Message};
+use actix::{Actor, Addr, Arbiter, AsyncContext, Context, Handler, Message, MessageResult};
 use actix_rt::ArbiterHandle;
 use borsh::BorshSerialize;
 use chrono::Duration as OldDuration;
 use chrono::{DateTime, Utc};
 use log::{debug, error, info, trace, warn};
+use tokio::sync::broadcast;
 
 #[cfg(feature = "delay_detector")]
 use delay_detector::DelayDetector;
@@ -29,7 +30,7 @@ use near_chain_configs::GenesisConfig;
 use near_crypto::Signature;
 #[cfg(feature = "test_features")]
 use near_network::types::NetworkAdversarialMessage;
-use near_network::types::{NetworkInfo, ReasonForBan};
+use near_network::types::{NetworkInfo, PeerReputationEvent, ReasonForBan};
 #[cfg(feature = "sandbox")]
 use near_network::types::{NetworkSandboxMessage, SandboxResponse};
 use near_network::{
@@ -49,7 +50,7 @@ use near_primitives::views::ValidatorInfo;
 use near_store::ColBlock;
 use near_telemetry::TelemetryActor;
 
-use crate::client::Client;
+use crate::client::{Client, ProcessedPartialEncodedChunk};
 use crate::info::{InfoHelper, ValidatorInfoHelper};
 use crate::sync::{highest_height_peer, StateSync, StateSyncResult};
 #[cfg(feature = "test_features")]
@@ -61,8 +62,8 @@ use near_chain::chain::{
     BlockCatchUpResponse, StateSplitRequest, StateSplitResponse,
 };
 use near_client_primitives::types::{
-    Error, GetNetworkInfo, NetworkInfoResponse, ShardSyncDownload, ShardSyncStatus, Status,
-    StatusError, StatusSyncInfo, SyncStatus,
+    ChainHeadEvent, Error, GetNetworkInfo, NetworkInfoResponse, ShardSyncDownload, ShardSyncStatus,
+    Status, StatusError, StatusSyncInfo, SubscribeChainHead, SyncStatus,
 };
 use near_primitives::block_header::ApprovalType;
 use near_primitives::syncing::StatePartKey;
@@ -75,6 +76,8 @@ const BLOCK_HORIZON: u64 = 500;
 /// `max_block_production_time` times this multiplier is how long we wait before rebroadcasting
 /// the current `head`
 const HEAD_STALL_MULTIPLIER: u32 = 4;
+/// Number of chain head events buffered for every subscriber, see [`SubscribeChainHead`].
+const CHAIN_HEAD_EVENTS_CAPACITY: usize = 128;
 
 pub struct ClientActor {
     /// Adversarial controls
@@ -102,6 +105,7 @@ pub struct ClientActor {
     block_catch_up_scheduler: Box<dyn Fn(BlockCatchUpRequest)>,
     state_split_scheduler: Box<dyn Fn(StateSplitRequest)>,
     state_parts_client_arbiter: Arbiter,
+    chain_head_sender: broadcast::Sender<ChainHeadEvent>,
 }
 
 /// Blocks the program until given genesis time arrives.
@@ -195,6 +199,7 @@ impl ClientActor {
                 sync_jobs_actor_addr,
             ),
             state_parts_client_arbiter: state_parts_arbiter,
+            chain_head_sender: broadcast::channel(CHAIN_HEAD_EVENTS_CAPACITY).0,
         })
     }
 }
@@ -360,6 +365,47 @@ impl Handler<NetworkClientMessages> for ClientActor {
                             ),
                         )
                     }
+                    NetworkSandboxMessage::SandboxFastForward(delta_height) => {
+                        let idle = self.client.sandbox_fast_forward_blocks == 0;
+                        self.client.sandbox_fast_forward_blocks += delta_height;
+                        if idle {
+                            self.sandbox_fast_forward(ctx);
+                        }
+                        NetworkClientResponses::NoResponse
+                    }
+                    NetworkSandboxMessage::SandboxFastForwardStatus => {
+                        NetworkClientResponses::SandboxResult(
+                            SandboxResponse::SandboxFastForwardBlocksLeft(
+                                self.client.sandbox_fast_forward_blocks,
+                            ),
+                        )
+                    }
+                    NetworkSandboxMessage::SandboxShiftTime(delta_nanos) => {
+                        match self.sandbox_shift_time(delta_nanos) {
+                            Ok(()) => NetworkClientResponses::NoResponse,
+                            Err(err) => NetworkClientResponses::SandboxResult(
+                                SandboxResponse::SandboxShiftTimeFailed(err),
+                            ),
+                        }
+                    }
+                    NetworkSandboxMessage::SandboxSnapshot => {
+                        NetworkClientResponses::SandboxResult(
+                            SandboxResponse::SandboxSnapshotTaken(self.client.sandbox_snapshot()),
+                        )
+                    }
+                    NetworkSandboxMessage::SandboxRestore(snapshot_id) => {
+                        match self.client.sandbox_restore(snapshot_id) {
+                            Ok(restored) => NetworkClientResponses::SandboxResult(
+                                SandboxResponse::SandboxRestoreFinished(restored),
+                            ),
+                            Err(err) => {
+                                error!(target: "client", "Failed to restore sandbox snapshot {}: {}", snapshot_id, err);
+                                NetworkClientResponses::SandboxResult(
+                                    SandboxResponse::SandboxRestoreFailed(err.to_string()),
+                                )
+                            }
+                        }
+                    }
                 };
             }
             NetworkClientMessages::Transaction { transaction, is_forwarded, check_only } => {
@@ -538,13 +584,22 @@ impl Handler<NetworkClientMessages> for ClientActor {
 
                 NetworkClientResponses::NoResponse
             }
-            NetworkClientMessages::EpochSyncResponse(_peer_id, _response) => {
-                // TODO #3488
-                NetworkClientResponses::NoResponse
+            NetworkClientMessages::EpochSyncResponse(peer_id, response) => {
+                match self.client.epoch_sync.on_response(peer_id, response) {
+                    Ok(()) => NetworkClientResponses::NoResponse,
+                    Err(ban_reason) => NetworkClientResponses::Ban { ban_reason },
+                }
             }
-            NetworkClientMessages::EpochSyncFinalizationResponse(_peer_id, _response) => {
-                // TODO #3488
-                NetworkClientResponses::NoResponse
+            NetworkClientMessages::EpochSyncFinalizationResponse(peer_id, response) => {
+                match self.client.epoch_sync.on_finalization_response(
+                    peer_id,
+                    response,
+                    &mut self.client.chain,
+                    &self.client.runtime_adapter,
+                ) {
+                    Ok(()) => NetworkClientResponses::NoResponse,
+                    Err(ban_reason) => NetworkClientResponses::Ban { ban_reason },
+                }
             }
             NetworkClientMessages::PartialEncodedChunkRequest(part_request_msg, route_back) => {
                 let _ = self.client.shards_mgr.process_partial_encoded_chunk_request(
@@ -555,24 +610,23 @@ impl Handler<NetworkClientMessages> for ClientActor {
                 NetworkClientResponses::NoResponse
             }
             NetworkClientMessages::PartialEncodedChunkResponse(response) => {
-                if let Ok(accepted_blocks) =
-                    self.client.process_partial_encoded_chunk_response(response)
+                if let Ok(processed) = self.client.process_partial_encoded_chunk_response(response)
                 {
-                    self.process_accepted_blocks(accepted_blocks);
+                    return self.process_partial_encoded_chunk_result(processed);
                 }
                 NetworkClientResponses::NoResponse
             }
             NetworkClientMessages::PartialEncodedChunk(partial_encoded_chunk) => {
-                if let Ok(accepted_blocks) = self.client.process_partial_encoded_chunk(
+                if let Ok(processed) = self.client.process_partial_encoded_chunk(
                     MaybeValidated::NotValidated(partial_encoded_chunk),
                 ) {
-                    self.process_accepted_blocks(accepted_blocks);
+                    return self.process_partial_encoded_chunk_result(processed);
                 }
                 NetworkClientResponses::NoResponse
             }
             NetworkClientMessages::PartialEncodedChunkForward(forward) => {
                 match self.client.process_partial_encoded_chunk_forward(forward) {
-                    Ok(accepted_blocks) => self.process_accepted_blocks(accepted_blocks),
+                    Ok(processed) => return self.process_partial_encoded_chunk_result(processed),
                     // Unknown chunk is normal if we get parts before the header
                     Err(Error::Chunk(near_chunks::Error::UnknownChunk)) => (),
                     Err(err) => {
@@ -707,14 +761,20 @@ impl Handler<GetNetworkInfo> for ClientActor {
     }
 }
 
+impl Handler<SubscribeChainHead> for ClientActor {
+    type Result = MessageResult<SubscribeChainHead>;
+
+    fn handle(&mut self, _msg: SubscribeChainHead, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.chain_head_sender.subscribe())
+    }
+}
+
 impl ClientActor {
     fn sign_announce_account(&self, epoch_id: &EpochId) -> Result<Signature, ()> {
         if let Some(validator_signer) = self.client.validator_signer.as_ref() {
-            Ok(validator_signer.sign_account_announce(
-                &validator_signer.validator_id(),
-                &self.node_id,
-                epoch_id,
-            ))
+            validator_signer
+                .sign_account_announce(&validator_signer.validator_id(), &self.node_id, epoch_id)
+                .map_err(|err| warn!(target: "client", "Failed to sign account announce: {}", err))
         } else {
             Err(())
         }
@@ -756,8 +816,11 @@ impl ClientActor {
         // Check client is part of the futures validators
         if self.client.is_validator(&next_epoch_id, &prev_block_hash) {
             debug!(target: "client", "Sending announce account for {}", validator_signer.validator_id());
+            let signature = match self.sign_announce_account(&next_epoch_id) {
+                Ok(signature) => signature,
+                Err(()) => return,
+            };
             self.last_validator_announce_time = Some(now);
-            let signature = self.sign_announce_account(&next_epoch_id).unwrap();
 
             self.network_adapter.do_send(NetworkRequests::AnnounceAccount(AnnounceAccount {
                 account_id: validator_signer.validator_id().clone(),
@@ -790,6 +853,12 @@ impl ClientActor {
         let epoch_id =
             self.client.runtime_adapter.get_epoch_id_from_prev_block(&head.last_block_hash)?;
 
+        // Blocks are produced by `sandbox_fast_forward` until it's done.
+        #[cfg(feature = "sandbox")]
+        if self.client.sandbox_fast_forward_blocks > 0 {
+            return Ok(());
+        }
+
         for height in
             latest_known.height + 1..=self.client.doomslug.get_largest_height_crossing_threshold()
         {
@@ -819,6 +888,92 @@ impl ClientActor {
         Ok(())
     }
 
+    /// Produces the blocks requested by `sandbox_fast_forward`, endorsing each head ourselves
+    /// instead of waiting for the doomslug timer. Produces one block at a time and schedules the
+    /// next one, so that the actor keeps handling messages in between.
+    ///
+    /// Every fast forwarded block moves the block timestamps by the minimal block production
+    /// delay, so that contracts observe the time passing as if the blocks were produced on time.
+    #[cfg(feature = "sandbox")]
+    fn sandbox_fast_forward(&mut self, ctx: &mut Context<Self>) {
+        if self.client.sandbox_fast_forward_blocks == 0 {
+            return;
+        }
+        let wait = match self.sandbox_fast_forward_block() {
+            Ok(true) => Duration::from_millis(0),
+            // The block is not accepted yet, e.g. its chunks are missing. Retry a bit later.
+            Ok(false) => self.client.config.block_production_tracking_delay,
+            Err(err) => {
+                error!(target: "client", "Sandbox fast forward failed: {}", err);
+                self.client.config.block_production_tracking_delay
+            }
+        };
+        near_performance_metrics::actix::run_later(ctx, wait, move |act, ctx| {
+            act.sandbox_fast_forward(ctx);
+        });
+    }
+
+    /// Produces the next fast forwarded block. Returns whether the block was accepted.
+    #[cfg(feature = "sandbox")]
+    fn sandbox_fast_forward_block(&mut self) -> Result<bool, Error> {
+        let _ = self.client.check_and_update_doomslug_tip();
+        let head = self.client.chain.head()?;
+        let height = head.height + 1;
+        let epoch_id =
+            self.client.runtime_adapter.get_epoch_id_from_prev_block(&head.last_block_hash)?;
+        let next_block_producer_account =
+            self.client.runtime_adapter.get_block_producer(&epoch_id, height)?;
+        if self.client.validator_signer.as_ref().map(|bp| bp.validator_id())
+            != Some(&next_block_producer_account)
+        {
+            warn!(target: "client", "Sandbox fast forward stopped at #{}: next block producer is {}", head.height, next_block_producer_account);
+            self.client.sandbox_fast_forward_blocks = 0;
+            return Ok(false);
+        }
+
+        if let Some(approval) = self.client.doomslug.create_approval(height) {
+            self.client.collect_block_approval(&approval, ApprovalType::SelfApproval);
+        }
+        self.produce_block(height)?;
+        if self.client.chain.head()?.height != height {
+            return Ok(false);
+        }
+        self.client.sandbox_fast_forward_blocks -= 1;
+        let delay = OldDuration::from_std(self.client.config.min_block_production_delay)
+            .unwrap_or_else(|_| OldDuration::zero());
+        if let Err(err) = self.shift_sandbox_time_offset(delay) {
+            warn!(target: "client", "Sandbox fast forward doesn't move the time: {}", err);
+        }
+        Ok(true)
+    }
+
+    /// Moves the timestamps of the produced blocks forward by `delta_nanos`.
+    #[cfg(feature = "sandbox")]
+    fn sandbox_shift_time(&mut self, delta_nanos: u64) -> Result<(), String> {
+        let delta: i64 = std::convert::TryFrom::try_from(delta_nanos)
+            .map_err(|_| format!("Time shift of {} nanoseconds is out of range", delta_nanos))?;
+        self.shift_sandbox_time_offset(OldDuration::nanoseconds(delta))
+    }
+
+    /// Adds `delta` to the block timestamp offset, unless the timestamps would overflow.
+    #[cfg(feature = "sandbox")]
+    fn shift_sandbox_time_offset(&mut self, delta: OldDuration) -> Result<(), String> {
+        let offset = self
+            .client
+            .chain
+            .sandbox_time_offset
+            .checked_add(&delta)
+            .filter(|offset| {
+                // Block timestamps are nanoseconds since the epoch in an i64.
+                Utc::now()
+                    .checked_add_signed(*offset)
+                    .map_or(false, |time| time.timestamp().checked_mul(1_000_000_000).is_some())
+            })
+            .ok_or_else(|| format!("Time shift by {} overflows block timestamps", delta))?;
+        self.client.chain.sandbox_time_offset = offset;
+        Ok(())
+    }
+
     fn schedule_triggers(&mut self, ctx: &mut Context<Self>) {
         let wait = self.check_triggers(ctx);
 
@@ -980,9 +1135,24 @@ impl ClientActor {
         }
     }
 
+    /// Processes the blocks accepted after processing a partial encoded chunk. Only new parts that
+    /// passed validation earn reputation to the peer which sent them.
+    fn process_partial_encoded_chunk_result(
+        &mut self,
+        processed: ProcessedPartialEncodedChunk,
+    ) -> NetworkClientResponses {
+        self.process_accepted_blocks(processed.accepted_blocks);
+        if processed.new_valid_parts {
+            NetworkClientResponses::ValidChunkPart
+        } else {
+            NetworkClientResponses::NoResponse
+        }
+    }
+
     /// Process all blocks that were accepted by calling other relevant services.
     fn process_accepted_blocks(&mut self, accepted_blocks: Vec<AcceptedBlock>) {
         for accepted_block in accepted_blocks {
+            let is_new_head = accepted_block.status.is_new_head();
             self.client.on_block_accepted(
                 accepted_block.hash,
                 accepted_block.status,
@@ -992,9 +1162,18 @@ impl ClientActor {
             let gas_used = Block::compute_gas_used(block.chunks().iter(), block.header().height());
 
             let last_final_hash = *block.header().last_final_block();
+            let height = block.header().height();
 
             self.info_helper.block_processed(gas_used);
             self.check_send_announce_account(last_final_hash);
+            if is_new_head {
+                // Sending only fails when there are no subscribers.
+                let _ = self.chain_head_sender.send(ChainHeadEvent {
+                    block_hash: accepted_block.hash,
+                    height,
+                    last_final_block: last_final_hash,
+                });
+            }
         }
     }
 
@@ -1013,6 +1192,10 @@ impl ClientActor {
         } else {
             match self.client.chain.validate_block(&block) {
                 Ok(_) => {
+                    self.network_adapter.do_send(NetworkRequests::ReportPeer {
+                        peer_id: peer_id.clone(),
+                        event: PeerReputationEvent::ValidBlock,
+                    });
                     let head = self.client.chain.head()?;
                     // do not broadcast blocks that are too far back.
                     if (head.height < block.header().height()
@@ -1233,6 +1416,10 @@ impl ClientActor {
         let header_head = self.client.chain.header_head()?;
         let mut sync_hash = header_head.prev_block_hash;
         for _ in 0..self.client.config.state_fetch_horizon {
+            // After the epoch sync there are no headers before the epoch it synced to.
+            if sync_hash == self.client.epoch_sync.sync_hash {
+                break;
+            }
             sync_hash = *self.client.chain.get_block_header(&sync_hash)?.prev_hash();
         }
         let mut epoch_start_sync_hash =
@@ -1343,6 +1530,22 @@ impl ClientActor {
             }
             wait_period = self.client.config.sync_check_period;
         } else {
+            // Skip the epochs using the light client blocks before downloading the headers. The
+            // epoch data can only be checked against the headers since `BlockHeaderV3`.
+            if cfg!(feature = "protocol_feature_block_header_v3")
+                && self.client.config.epoch_sync_enabled
+                && !self.client.config.archive
+                && !unwrap_or_run_later!(self.client.epoch_sync.run(
+                    &mut self.client.sync_status,
+                    &self.client.chain,
+                    &self.network_info.highest_height_peers
+                ))
+            {
+                near_performance_metrics::actix::run_later(ctx, wait_period, move |act, ctx| {
+                    act.sync(ctx);
+                });
+                return;
+            }
             // Run each step of syncing separately.
             unwrap_or_run_later!(self.client.header_sync.run(
                 &mut self.client.sync_status,
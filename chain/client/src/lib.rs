@@ -2,14 +2,16 @@
 extern crate lazy_static;
 
 pub use near_client_primitives::types::{
-    Error, GetBlock, GetBlockProof, GetBlockProofResponse, GetBlockWithMerkleTree, GetChunk,
-    GetExecutionOutcome, GetExecutionOutcomeResponse, GetExecutionOutcomesForBlock, GetGasPrice,
-    GetNetworkInfo, GetNextLightClientBlock, GetProtocolConfig, GetReceipt, GetStateChanges,
-    GetStateChangesInBlock, GetStateChangesWithCauseInBlock, GetValidatorInfo, GetValidatorOrdered,
-    Query, QueryError, Status, StatusResponse, SyncStatus, TxStatus, TxStatusError,
+    ChainHeadEvent, Error, GetBlock, GetBlockProof, GetBlockProofResponse, GetBlockWithMerkleTree,
+    GetChunk, GetExecutionOutcome, GetExecutionOutcomeResponse, GetExecutionOutcomesForBlock,
+    GetGasPrice, GetNetworkInfo, GetNextLightClientBlock, GetProtocolConfig, GetReceipt,
+    GetStateChanges, GetStateChangesInBlock, GetStateChangesWithCauseInBlock,
+    GetStateChangesWithCauseInBlockByShard, GetValidatorInfo, GetValidatorOrdered, Query,
+    QueryError, SimulateTransaction, SimulateTransactionError, Status, StatusResponse,
+    SubscribeChainHead, SyncStatus, TxStatus, TxStatusError,
 };
 
-pub use crate::client::Client;
+pub use crate::client::{Client, ProcessedPartialEncodedChunk};
 pub use crate::client_actor::{start_client, ClientActor};
 #[cfg(feature = "test_features")]
 pub use crate::view_client::AdversarialControls;
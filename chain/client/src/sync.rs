@@ -1,6 +1,7 @@
 use near_chain::{near_chain_primitives, ChainStoreAccess, Error};
 use std::cmp::min;
 use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::{ops::Add, time::Duration as TimeDuration};
@@ -13,24 +14,32 @@ use rand::seq::{IteratorRandom, SliceRandom};
 use rand::{thread_rng, Rng};
 
 use near_chain::{Chain, RuntimeAdapter};
-use near_network::types::{AccountOrPeerIdOrHash, NetworkResponses, ReasonForBan};
+use near_network::types::{
+    AccountOrPeerIdOrHash, NetworkResponses, PeerReputationEvent, ReasonForBan,
+};
 use near_network::{FullPeerInfo, NetworkAdapter, NetworkRequests};
 use near_primitives::block::Tip;
 use near_primitives::hash::CryptoHash;
+use near_primitives::light_client::validate_light_client_block;
 use near_primitives::network::PeerId;
-use near_primitives::syncing::get_num_state_parts;
+use near_primitives::syncing::{
+    get_num_state_parts, EpochSyncFinalizationResponse, EpochSyncResponse,
+};
 use near_primitives::types::validator_stake::ValidatorStake;
 use near_primitives::types::{
     AccountId, BlockHeight, BlockHeightDelta, EpochId, ShardId, StateRoot,
 };
 use near_primitives::utils::to_timestamp;
+use near_primitives::views::validator_stake_view::ValidatorStakeView;
 
 use cached::{Cached, SizedCache};
 use near_chain::chain::{ApplyStatePartsRequest, StateSplitRequest};
+use near_chain::state_dump;
 use near_client_primitives::types::{
     DownloadStatus, ShardSyncDownload, ShardSyncStatus, SyncStatus,
 };
 use near_primitives::shard_layout::ShardUId;
+use near_store::{ColBlockMisc, EPOCH_SYNC_HASH_KEY};
 
 /// Maximum number of block headers send over the network.
 pub const MAX_BLOCK_HEADERS: u64 = 512;
@@ -45,6 +54,9 @@ pub const MAX_STATE_PART_REQUEST: u64 = 16;
 /// Number of state parts already requested stored as pending.
 /// This number should not exceed MAX_STATE_PART_REQUEST times (number of peers in the network).
 pub const MAX_PENDING_PART: u64 = MAX_STATE_PART_REQUEST * 10000;
+/// Maximum number of state parts to load from the state dump on each round, so that the client
+/// is not blocked for too long.
+const MAX_STATE_PARTS_FROM_DUMP: usize = 16;
 
 pub const NS_PER_SECOND: u128 = 1_000_000_000;
 
@@ -60,25 +72,25 @@ pub fn highest_height_peer(highest_height_peers: &Vec<FullPeerInfo>) -> Option<F
     }
 }
 
-/// Helper to keep track of the Epoch Sync
-// TODO #3488
-#[allow(dead_code)]
+/// Helper to keep track of the Epoch Sync.
+///
+/// A node which has nothing but the genesis walks the light client blocks of all the epochs,
+/// checking the approvals of every one with the block producers taken from the previous one, up
+/// to the current epoch. Then it initializes the epoch manager at the current epoch and saves
+/// the headers the header sync and the state sync start from, so that it doesn't need to
+/// download all the headers since genesis.
 pub struct EpochSync {
     network_adapter: Arc<dyn NetworkAdapter>,
     /// Datastructure to keep track of when the last request to each peer was made.
     /// Peers do not respond to Epoch Sync requests more frequently than once per a certain time
     /// interval, thus there's no point in requesting more frequently.
     peer_to_last_request_time: HashMap<PeerId, DateTime<Utc>>,
-    /// Tracks all the peers who have reported that we are already up to date
-    peers_reporting_up_to_date: HashSet<PeerId>,
-    /// The last epoch we are synced to
+    /// The epoch we need the light client block of, the last one we have the block producers of
     current_epoch_id: EpochId,
-    /// The next epoch id we need to sync
-    next_epoch_id: EpochId,
-    /// The block producers set to validate the light client block view for the next epoch
-    next_block_producers: Vec<ValidatorStake>,
-    /// The last epoch id that we have requested
-    requested_epoch_id: EpochId,
+    /// The block producers of `current_epoch_id`, to validate its light client block
+    current_block_producers: Vec<ValidatorStakeView>,
+    /// Number of epochs we have synced through
+    epoch_ord: u64,
     /// When and to whom was the last request made
     last_request_time: DateTime<Utc>,
     last_request_peer_id: Option<PeerId>,
@@ -88,17 +100,16 @@ pub struct EpochSync {
     /// How frequently to send request to the same peer
     peer_timeout: Duration,
 
-    /// True, if all peers agreed that we're at the last Epoch.
+    /// True, if a peer reported that we're at the last Epoch.
     /// Only finalization is needed.
     have_all_epochs: bool,
     /// Whether the Epoch Sync was performed to completion previously.
     /// Current state machine allows for only one Epoch Sync.
     pub done: bool,
-
+    /// The first block of the epoch we synced to, or default if the epoch sync was not needed.
+    /// There are no headers before the previous epoch.
     pub sync_hash: CryptoHash,
 
-    received_epoch: bool,
-
     is_just_started: bool,
 }
 
@@ -106,30 +117,279 @@ impl EpochSync {
     pub fn new(
         network_adapter: Arc<dyn NetworkAdapter>,
         genesis_epoch_id: EpochId,
-        genesis_next_epoch_id: EpochId,
-        first_epoch_block_producers: Vec<ValidatorStake>,
+        first_epoch_block_producers: Vec<ValidatorStakeView>,
         request_timeout: TimeDuration,
         peer_timeout: TimeDuration,
     ) -> Self {
         Self {
             network_adapter,
             peer_to_last_request_time: HashMap::new(),
-            peers_reporting_up_to_date: HashSet::new(),
-            current_epoch_id: genesis_epoch_id.clone(),
-            next_epoch_id: genesis_next_epoch_id.clone(),
-            next_block_producers: first_epoch_block_producers,
-            requested_epoch_id: genesis_epoch_id,
+            current_epoch_id: genesis_epoch_id,
+            current_block_producers: first_epoch_block_producers,
+            epoch_ord: 0,
             last_request_time: Utc::now(),
             last_request_peer_id: None,
             request_timeout: Duration::from_std(request_timeout).unwrap(),
             peer_timeout: Duration::from_std(peer_timeout).unwrap(),
-            received_epoch: false,
             have_all_epochs: false,
             done: false,
             sync_hash: CryptoHash::default(),
             is_just_started: true,
         }
     }
+
+    /// Requests the light client block of the next epoch, or the finalization data once we're at
+    /// the last epoch. Returns whether the epoch sync is done.
+    pub fn run(
+        &mut self,
+        sync_status: &mut SyncStatus,
+        chain: &Chain,
+        highest_height_peers: &[FullPeerInfo],
+    ) -> Result<bool, near_chain::Error> {
+        if self.is_just_started {
+            self.is_just_started = false;
+            // Only a node with nothing but the genesis can skip the epochs.
+            let header_head = chain.header_head()?;
+            if header_head.height != chain.genesis().height() {
+                self.done = true;
+            }
+        }
+        if self.done {
+            return Ok(true);
+        }
+        *sync_status = SyncStatus::EpochSync { epoch_ord: self.epoch_ord };
+
+        let now = Utc::now();
+        if self.last_request_peer_id.is_some() {
+            if now - self.last_request_time < self.request_timeout {
+                return Ok(false);
+            }
+            debug!(target: "sync", "Epoch sync: request to {:?} timed out", self.last_request_peer_id);
+            // The peers might have moved to the next epoch since they told us we are at the last one.
+            self.have_all_epochs = false;
+        }
+
+        let peer = highest_height_peers
+            .iter()
+            .filter(|peer| match self.peer_to_last_request_time.get(&peer.peer_info.id) {
+                Some(last_request_time) => now - *last_request_time >= self.peer_timeout,
+                None => true,
+            })
+            .choose(&mut thread_rng());
+        let peer_id = match peer {
+            Some(peer) => peer.peer_info.id.clone(),
+            None => return Ok(false),
+        };
+        self.request(peer_id);
+        Ok(false)
+    }
+
+    fn request(&mut self, peer_id: PeerId) {
+        let epoch_id = self.current_epoch_id.clone();
+        if self.have_all_epochs {
+            debug!(target: "sync", "Epoch sync: requesting finalization of epoch {:?} from {}", epoch_id, peer_id);
+            self.network_adapter.do_send(NetworkRequests::EpochSyncFinalizationRequest {
+                peer_id: peer_id.clone(),
+                epoch_id,
+            });
+        } else {
+            debug!(target: "sync", "Epoch sync: requesting epoch {:?} from {}", epoch_id, peer_id);
+            self.network_adapter
+                .do_send(NetworkRequests::EpochSyncRequest { peer_id: peer_id.clone(), epoch_id });
+        }
+        let now = Utc::now();
+        self.peer_to_last_request_time.insert(peer_id.clone(), now);
+        self.last_request_time = now;
+        self.last_request_peer_id = Some(peer_id);
+    }
+
+    /// Advances to the next epoch if the light client block is valid.
+    pub fn on_response(
+        &mut self,
+        peer_id: PeerId,
+        response: EpochSyncResponse,
+    ) -> Result<(), ReasonForBan> {
+        if self.done || self.have_all_epochs || self.last_request_peer_id.as_ref() != Some(&peer_id)
+        {
+            debug!(target: "sync", "Epoch sync: ignoring unexpected response from {}", peer_id);
+            return Ok(());
+        }
+        match response {
+            EpochSyncResponse::UpToDate => {
+                // Without two epochs to skip the head is still in the epoch before the header
+                // head, so the header sync and the block sync from genesis are needed anyway.
+                if self.epoch_ord < 2 {
+                    info!(target: "sync", "Epoch sync: not needed, the chain is at epoch {}", self.epoch_ord);
+                    self.done = true;
+                    return Ok(());
+                }
+                self.have_all_epochs = true;
+                self.request(peer_id);
+            }
+            EpochSyncResponse::Advance { light_client_block_view } => {
+                if light_client_block_view.inner_lite.epoch_id != self.current_epoch_id.0 {
+                    warn!(target: "sync", "Epoch sync: {} sent the light client block of epoch {} instead of {:?}",
+                        peer_id, light_client_block_view.inner_lite.epoch_id, self.current_epoch_id);
+                    return Err(ReasonForBan::EpochSyncInvalidResponse);
+                }
+                if let Err(err) = validate_light_client_block(
+                    &light_client_block_view,
+                    &self.current_block_producers,
+                    true,
+                ) {
+                    warn!(target: "sync", "Epoch sync: invalid light client block from {}: {}", peer_id, err);
+                    return Err(ReasonForBan::EpochSyncInvalidResponse);
+                }
+                self.current_epoch_id = EpochId(light_client_block_view.inner_lite.next_epoch_id);
+                self.current_block_producers = light_client_block_view.next_bps.unwrap_or_default();
+                self.epoch_ord += 1;
+                self.last_request_peer_id = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// Initializes the epoch manager at the current epoch and saves the headers to start the
+    /// header sync from if the finalization data is valid.
+    pub fn on_finalization_response(
+        &mut self,
+        peer_id: PeerId,
+        response: EpochSyncFinalizationResponse,
+        chain: &mut Chain,
+        runtime_adapter: &Arc<dyn RuntimeAdapter>,
+    ) -> Result<(), ReasonForBan> {
+        if self.done
+            || !self.have_all_epochs
+            || self.last_request_peer_id.as_ref() != Some(&peer_id)
+        {
+            debug!(target: "sync", "Epoch sync: ignoring unexpected finalization response from {}", peer_id);
+            return Ok(());
+        }
+        if let Err(err) = self.validate_finalization_response(&response) {
+            warn!(target: "sync", "Epoch sync: invalid finalization response from {}: {}", peer_id, err);
+            return Err(ReasonForBan::EpochSyncInvalidFinalizationResponse);
+        }
+        let sync_hash = *response.cur_epoch_header.hash();
+        if let Err(err) = Self::finalize(response, chain, runtime_adapter) {
+            error!(target: "sync", "Epoch sync: finalization failed: {}", err);
+            self.last_request_peer_id = None;
+            return Ok(());
+        }
+        info!(target: "sync", "Epoch sync: done, synced {} epochs up to {}", self.epoch_ord, sync_hash);
+        self.sync_hash = sync_hash;
+        self.done = true;
+        Ok(())
+    }
+
+    fn validate_finalization_response(
+        &self,
+        response: &EpochSyncFinalizationResponse,
+    ) -> Result<(), &'static str> {
+        let cur_epoch_header = &response.cur_epoch_header;
+        let header_sync_init_header = &response.header_sync_init_header;
+        if cur_epoch_header.epoch_id() != &self.current_epoch_id {
+            return Err("the first header is not in the current epoch");
+        }
+
+        // The first header of the epoch must be signed by its block producer, which is among the
+        // block producers from the light client block of the previous epoch.
+        if response.cur_epoch_info.block_producers_settlement().is_empty() {
+            return Err("the epoch has no block producers");
+        }
+        let block_producer = response.cur_epoch_info.get_validator(
+            response.cur_epoch_info.sample_block_producer(cur_epoch_header.height()),
+        );
+        let is_known_block_producer =
+            self.current_block_producers.iter().cloned().map(ValidatorStake::from).any(|bp| {
+                bp.account_id() == block_producer.account_id()
+                    && bp.public_key() == block_producer.public_key()
+            });
+        if !is_known_block_producer
+            || !cur_epoch_header.verify_block_producer(block_producer.public_key())
+        {
+            return Err("the first header is not signed by its block producer");
+        }
+        let epoch_sync_data_hash = cur_epoch_header
+            .epoch_sync_data_hash()
+            .ok_or("the first header doesn't commit to the epoch data")?;
+        if epoch_sync_data_hash != response.epoch_sync_data_hash() {
+            return Err("the epoch data doesn't match the first header");
+        }
+        if response.prev_epoch_last_block_info.hash() != header_sync_init_header.hash()
+            || response.prev_epoch_prev_last_block_info.hash()
+                != header_sync_init_header.prev_hash()
+        {
+            return Err("the block infos don't match the headers");
+        }
+
+        // The other headers are trusted by being ancestors of the first one.
+        if cur_epoch_header.prev_hash() != header_sync_init_header.hash() {
+            return Err("the last header of the previous epoch is not the parent of the first one");
+        }
+        let mut block_merkle_tree = response.header_sync_init_header_tree.clone();
+        if &block_merkle_tree.root() != header_sync_init_header.block_merkle_root() {
+            return Err("the block merkle tree doesn't match the previous header");
+        }
+        block_merkle_tree.insert(*header_sync_init_header.hash());
+        if &block_merkle_tree.root() != cur_epoch_header.block_merkle_root() {
+            return Err("the block merkle tree doesn't match the first header");
+        }
+        let mut next_hash = header_sync_init_header.prev_hash();
+        for header in response.prev_epoch_headers.iter().rev() {
+            if header.hash() != next_hash {
+                return Err("the headers of the previous epoch are not a chain");
+            }
+            next_hash = header.prev_hash();
+        }
+        Ok(())
+    }
+
+    fn finalize(
+        response: EpochSyncFinalizationResponse,
+        chain: &mut Chain,
+        runtime_adapter: &Arc<dyn RuntimeAdapter>,
+    ) -> Result<(), near_chain::Error> {
+        let EpochSyncFinalizationResponse {
+            cur_epoch_header,
+            prev_epoch_headers,
+            header_sync_init_header,
+            header_sync_init_header_tree,
+            prev_epoch_first_block_info,
+            prev_epoch_prev_last_block_info,
+            prev_epoch_last_block_info,
+            prev_epoch_info,
+            cur_epoch_info,
+            next_epoch_info,
+        } = response;
+        let prev_epoch_id = prev_epoch_last_block_info.epoch_id().clone();
+        runtime_adapter.epoch_sync_init_epoch_manager(
+            prev_epoch_first_block_info,
+            prev_epoch_prev_last_block_info,
+            prev_epoch_last_block_info,
+            &prev_epoch_id,
+            prev_epoch_info,
+            cur_epoch_header.epoch_id(),
+            cur_epoch_info,
+            cur_epoch_header.next_epoch_id(),
+            next_epoch_info,
+        )?;
+
+        // The header sync continues from the last block of the previous epoch, and the state sync
+        // only needs the headers of the previous epoch down to where the chunks were included.
+        let mut store_update = chain.store().owned_store().store_update();
+        store_update.set_ser(ColBlockMisc, EPOCH_SYNC_HASH_KEY, cur_epoch_header.hash())?;
+        let mut chain_store_update = chain.mut_store().store_update();
+        chain_store_update.merge(store_update);
+        for header in prev_epoch_headers {
+            chain_store_update.save_block_header_no_update_tree(header)?;
+        }
+        let header_head = Tip::from_header(&header_sync_init_header);
+        chain_store_update
+            .save_block_merkle_tree(*header_sync_init_header.hash(), header_sync_init_header_tree);
+        chain_store_update.save_block_header_no_update_tree(header_sync_init_header)?;
+        chain_store_update.force_save_header_head(&header_head)?;
+        chain_store_update.commit()
+    }
 }
 
 /// Helper to keep track of sync headers.
@@ -251,8 +511,15 @@ impl HeaderSync {
             );
 
             if stalling {
+                // Report the peer once per stall, it gets banned if the stall goes on.
                 if self.stalling_ts.is_none() {
                     self.stalling_ts = Some(now);
+                    if let Some(ref peer) = self.syncing_peer {
+                        self.network_adapter.do_send(NetworkRequests::ReportPeer {
+                            peer_id: peer.peer_info.id.clone(),
+                            event: PeerReputationEvent::HeaderSyncStall,
+                        });
+                    }
                 }
             } else {
                 self.stalling_ts = None;
@@ -613,10 +880,22 @@ pub struct StateSync {
 
     /// Maps shard_id to result of splitting state for resharding
     split_state_roots: HashMap<ShardId, Result<HashMap<ShardUId, StateRoot>, Error>>,
+
+    /// Directory with the state dumps to load the state from instead of requesting it from
+    /// peers, see `near_chain::state_dump`.
+    state_parts_dir: Option<PathBuf>,
+
+    /// Shards at the sync hash whose state dump failed the validation. The rest of their state is
+    /// requested from peers.
+    invalid_state_dumps: HashSet<(CryptoHash, ShardId)>,
 }
 
 impl StateSync {
-    pub fn new(network_adapter: Arc<dyn NetworkAdapter>, timeout: TimeDuration) -> Self {
+    pub fn new(
+        network_adapter: Arc<dyn NetworkAdapter>,
+        timeout: TimeDuration,
+        state_parts_dir: Option<PathBuf>,
+    ) -> Self {
         StateSync {
             network_adapter,
             state_sync_time: Default::default(),
@@ -626,6 +905,8 @@ impl StateSync {
             timeout: Duration::from_std(timeout).unwrap(),
             state_parts_apply_results: HashMap::new(),
             split_state_roots: HashMap::new(),
+            state_parts_dir,
+            invalid_state_dumps: HashSet::new(),
         }
     }
 
@@ -1051,6 +1332,25 @@ impl StateSync {
         shard_sync_download: ShardSyncDownload,
         highest_height_peers: &Vec<FullPeerInfo>,
     ) -> Result<ShardSyncDownload, near_chain::Error> {
+        if let Some(dir) = &self.state_parts_dir {
+            if !self.invalid_state_dumps.contains(&(sync_hash, shard_id))
+                && state_dump::has_state_dump(dir, &sync_hash, shard_id)
+            {
+                let (shard_sync_download, is_valid) = Self::request_shard_from_dump(
+                    dir,
+                    shard_id,
+                    chain,
+                    sync_hash,
+                    shard_sync_download,
+                );
+                if !is_valid {
+                    warn!(target: "sync", "State sync: invalid state dump of shard {} at {}, requesting the state from peers", shard_id, sync_hash);
+                    self.invalid_state_dumps.insert((sync_hash, shard_id));
+                }
+                return Ok(shard_sync_download);
+            }
+        }
+
         let possible_targets = self.possible_targets(
             me,
             shard_id,
@@ -1134,6 +1434,60 @@ impl StateSync {
         Ok(new_shard_sync_download)
     }
 
+    /// Loads the header or the parts that need to be requested from the state dump in `dir`.
+    /// Returns whether all of them passed the validation. The ones that didn't are marked with
+    /// an error, so that they are requested again.
+    fn request_shard_from_dump(
+        dir: &Path,
+        shard_id: ShardId,
+        chain: &mut Chain,
+        sync_hash: CryptoHash,
+        mut shard_sync_download: ShardSyncDownload,
+    ) -> (ShardSyncDownload, bool) {
+        let mut is_valid = true;
+        match shard_sync_download.status {
+            ShardSyncStatus::StateDownloadHeader => {
+                let download = &mut shard_sync_download.downloads[0];
+                download.run_me.store(false, Ordering::SeqCst);
+                download.state_requests_count += 1;
+                match chain.set_state_header_from_dump(dir, shard_id, sync_hash) {
+                    Ok(()) => download.done = true,
+                    Err(err) => {
+                        error!(target: "sync", "State sync set_state_header from dump error, shard = {}, hash = {}: {:?}", shard_id, sync_hash, err);
+                        download.error = true;
+                        is_valid = false;
+                    }
+                }
+            }
+            ShardSyncStatus::StateDownloadParts => {
+                let num_parts = shard_sync_download.downloads.len() as u64;
+                for (part_id, download) in shard_sync_download
+                    .downloads
+                    .iter_mut()
+                    .enumerate()
+                    .filter(|(_, download)| download.run_me.load(Ordering::SeqCst))
+                    .take(MAX_STATE_PARTS_FROM_DUMP)
+                {
+                    let part_id = part_id as u64;
+                    download.run_me.store(false, Ordering::SeqCst);
+                    download.state_requests_count += 1;
+                    match chain
+                        .set_state_part_from_dump(dir, shard_id, sync_hash, part_id, num_parts)
+                    {
+                        Ok(()) => download.done = true,
+                        Err(err) => {
+                            error!(target: "sync", "State sync set_state_part from dump error, shard = {}, part = {}, hash = {}: {:?}", shard_id, part_id, sync_hash, err);
+                            download.error = true;
+                            is_valid = false;
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        (shard_sync_download, is_valid)
+    }
+
     pub fn run(
         &mut self,
         me: &Option<AccountId>,
@@ -1255,6 +1609,7 @@ mod test {
     use std::sync::Arc;
     use std::thread;
 
+    use borsh::BorshSerialize;
     use near_chain::test_utils::{setup, setup_with_validators};
     use near_chain::{ChainGenesis, Provenance};
     use near_crypto::{KeyType, PublicKey};
@@ -1262,6 +1617,8 @@ mod test {
     use near_network::types::PeerChainInfoV2;
     use near_network::PeerInfo;
     use near_primitives::block::{Approval, Block, GenesisId};
+    use near_primitives::block_header::ApprovalInner;
+    use near_primitives::hash::hash;
     use near_primitives::network::PeerId;
 
     use super::*;
@@ -1269,8 +1626,9 @@ mod test {
     use near_network::routing::EdgeInfo;
     use near_primitives::merkle::PartialMerkleTree;
     use near_primitives::types::EpochId;
-    use near_primitives::validator_signer::InMemoryValidatorSigner;
+    use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
     use near_primitives::version::PROTOCOL_VERSION;
+    use near_primitives::views::{BlockHeaderInnerLiteView, LightClientBlockView};
     use num_rational::Ratio;
     use std::collections::HashSet;
 
@@ -1419,6 +1777,7 @@ mod test {
                             current_height,
                             &signer,
                         )
+                        .unwrap()
                         .signature
                     })
                 })
@@ -1453,7 +1812,9 @@ mod test {
                 &*signers[3],
                 last_block.header().next_bp_hash().clone(),
                 block_merkle_tree.root(),
-            );
+                None,
+            )
+            .unwrap();
             block_merkle_tree.insert(*block.hash());
 
             all_blocks.push(block);
@@ -1503,6 +1864,19 @@ mod test {
         } else {
             assert!(false);
         }
+        // The stall was reported once, before the peer was banned.
+        let requests = network_adapter.requests.read().unwrap();
+        assert!(matches!(
+            requests.front(),
+            Some(NetworkRequests::ReportPeer { event: PeerReputationEvent::HeaderSyncStall, .. })
+        ));
+        assert_eq!(
+            requests
+                .iter()
+                .filter(|request| matches!(request, NetworkRequests::ReportPeer { .. }))
+                .count(),
+            1
+        );
     }
 
     /// Helper function for block sync tests
@@ -1613,4 +1987,285 @@ mod test {
             blocks.iter().take(1).map(|b| *b.hash()).collect::<HashSet<_>>()
         );
     }
+
+    /// Checks that the epoch sync only accepts the responses of the peer it requested, and that
+    /// it is done without finalization if the chain has not moved past the first epochs.
+    #[test]
+    fn test_epoch_sync_up_to_date() {
+        let network_adapter = Arc::new(MockNetworkAdapter::default());
+        let (chain, _, _) = setup();
+        let mut epoch_sync = EpochSync::new(
+            network_adapter.clone(),
+            chain.genesis().epoch_id().clone(),
+            vec![],
+            TimeDuration::from_secs(10),
+            TimeDuration::from_secs(60),
+        );
+        let peers: Vec<_> = (0..2)
+            .map(|_| FullPeerInfo {
+                peer_info: PeerInfo::random(),
+                chain_info: Default::default(),
+                edge_info: Default::default(),
+            })
+            .collect();
+        let mut sync_status = SyncStatus::NoSync;
+        assert!(!epoch_sync.run(&mut sync_status, &chain, &peers).unwrap());
+        assert!(matches!(sync_status, SyncStatus::EpochSync { epoch_ord: 0 }));
+        let requested_peer_id = match network_adapter.pop().unwrap() {
+            NetworkRequests::EpochSyncRequest { peer_id, epoch_id } => {
+                assert_eq!(&epoch_id, chain.genesis().epoch_id());
+                peer_id
+            }
+            request => panic!("Unexpected request {:?}", request),
+        };
+        // No new request until the previous one times out.
+        assert!(!epoch_sync.run(&mut sync_status, &chain, &peers).unwrap());
+        assert!(network_adapter.pop().is_none());
+
+        let other_peer_id = peers
+            .iter()
+            .map(|peer| peer.peer_info.id.clone())
+            .find(|peer_id| peer_id != &requested_peer_id)
+            .unwrap();
+        assert_eq!(epoch_sync.on_response(other_peer_id, EpochSyncResponse::UpToDate), Ok(()));
+        assert!(!epoch_sync.done);
+        assert_eq!(epoch_sync.on_response(requested_peer_id, EpochSyncResponse::UpToDate), Ok(()));
+        assert!(epoch_sync.done);
+        assert!(epoch_sync.run(&mut sync_status, &chain, &peers).unwrap());
+        assert!(network_adapter.pop().is_none());
+    }
+
+    fn epoch_sync_signers(epoch: usize) -> Vec<InMemoryValidatorSigner> {
+        (0..3)
+            .map(|i| {
+                let account_id = format!("test{}_{}", epoch, i);
+                InMemoryValidatorSigner::from_seed(
+                    account_id.parse().unwrap(),
+                    KeyType::ED25519,
+                    &account_id,
+                )
+            })
+            .collect()
+    }
+
+    fn epoch_sync_block_producers(signers: &[InMemoryValidatorSigner]) -> Vec<ValidatorStakeView> {
+        signers
+            .iter()
+            .map(|signer| {
+                let account_id = signer.validator_id().clone();
+                #[cfg(feature = "protocol_feature_block_header_v3")]
+                let validator_stake = ValidatorStake::new_v1(account_id, signer.public_key(), 100);
+                #[cfg(not(feature = "protocol_feature_block_header_v3"))]
+                let validator_stake = ValidatorStake::new(account_id, signer.public_key(), 100);
+                validator_stake.into()
+            })
+            .collect()
+    }
+
+    /// Returns the light client block of the last final block of `epoch_id`, approved by
+    /// `signers` and announcing `next_bps` as the block producers of `next_epoch_id`.
+    fn epoch_sync_light_client_block(
+        epoch_id: &EpochId,
+        next_epoch_id: &EpochId,
+        signers: &[InMemoryValidatorSigner],
+        next_bps: Vec<ValidatorStakeView>,
+    ) -> LightClientBlockView {
+        let next_bp_stakes: Vec<ValidatorStake> =
+            next_bps.iter().cloned().map(Into::into).collect();
+        let mut block = LightClientBlockView {
+            prev_block_hash: hash(&[1]),
+            next_block_inner_hash: hash(&[2]),
+            inner_lite: BlockHeaderInnerLiteView {
+                height: 10,
+                epoch_id: epoch_id.0,
+                next_epoch_id: next_epoch_id.0,
+                prev_state_root: hash(&[3]),
+                outcome_root: hash(&[4]),
+                timestamp: 0,
+                timestamp_nanosec: 0,
+                next_bp_hash: hash(&next_bp_stakes.try_to_vec().unwrap()),
+                block_merkle_root: hash(&[5]),
+            },
+            inner_rest_hash: hash(&[6]),
+            next_bps: Some(next_bps),
+            approvals_after_next: vec![],
+        };
+        let approval = ApprovalInner::Endorsement(block.next_block_hash());
+        block.approvals_after_next = signers
+            .iter()
+            .map(|signer| Some(signer.sign_approval(&approval, 12).unwrap()))
+            .collect();
+        block
+    }
+
+    fn requested_epoch_sync_peer(
+        network_adapter: &MockNetworkAdapter,
+        epoch_id: &EpochId,
+    ) -> PeerId {
+        match network_adapter.pop().unwrap() {
+            NetworkRequests::EpochSyncRequest { peer_id, epoch_id: requested_epoch_id } => {
+                assert_eq!(&requested_epoch_id, epoch_id);
+                peer_id
+            }
+            request => panic!("Unexpected request {:?}", request),
+        }
+    }
+
+    /// Checks that the epoch sync only advances on the light client blocks of the requested epoch
+    /// approved by its block producers, and that it bans the peers sending any other block.
+    #[test]
+    fn test_epoch_sync_advance() {
+        let network_adapter = Arc::new(MockNetworkAdapter::default());
+        let (chain, _, _) = setup();
+        let genesis_epoch_id = chain.genesis().epoch_id().clone();
+        let next_epoch_id = EpochId(hash(&[7]));
+        let signers = epoch_sync_signers(0);
+        let next_signers = epoch_sync_signers(1);
+        let mut epoch_sync = EpochSync::new(
+            network_adapter.clone(),
+            genesis_epoch_id.clone(),
+            epoch_sync_block_producers(&signers),
+            TimeDuration::from_secs(0),
+            TimeDuration::from_secs(0),
+        );
+        let peers = create_peer_infos(1);
+        let mut sync_status = SyncStatus::NoSync;
+        let block = epoch_sync_light_client_block(
+            &genesis_epoch_id,
+            &next_epoch_id,
+            &signers,
+            epoch_sync_block_producers(&next_signers),
+        );
+
+        let mut wrong_epoch = block.clone();
+        wrong_epoch.inner_lite.epoch_id = next_epoch_id.0;
+        let mut not_enough_approvals = block.clone();
+        not_enough_approvals.approvals_after_next[1] = None;
+        not_enough_approvals.approvals_after_next[2] = None;
+        let mut truncated_approvals = block.clone();
+        truncated_approvals.approvals_after_next.truncate(2);
+        let signed_by_next_signers = epoch_sync_light_client_block(
+            &genesis_epoch_id,
+            &next_epoch_id,
+            &next_signers,
+            epoch_sync_block_producers(&next_signers),
+        );
+        for invalid_block in
+            vec![wrong_epoch, not_enough_approvals, truncated_approvals, signed_by_next_signers]
+        {
+            assert!(!epoch_sync.run(&mut sync_status, &chain, &peers).unwrap());
+            let peer_id = requested_epoch_sync_peer(&network_adapter, &genesis_epoch_id);
+            assert_eq!(
+                epoch_sync.on_response(
+                    peer_id,
+                    EpochSyncResponse::Advance { light_client_block_view: invalid_block }
+                ),
+                Err(ReasonForBan::EpochSyncInvalidResponse)
+            );
+            assert!(matches!(sync_status, SyncStatus::EpochSync { epoch_ord: 0 }));
+        }
+
+        assert!(!epoch_sync.run(&mut sync_status, &chain, &peers).unwrap());
+        let peer_id = requested_epoch_sync_peer(&network_adapter, &genesis_epoch_id);
+        assert_eq!(
+            epoch_sync.on_response(
+                peer_id,
+                EpochSyncResponse::Advance { light_client_block_view: block }
+            ),
+            Ok(())
+        );
+        // The next epoch is requested and validated with the block producers of the block.
+        assert!(!epoch_sync.run(&mut sync_status, &chain, &peers).unwrap());
+        assert!(matches!(sync_status, SyncStatus::EpochSync { epoch_ord: 1 }));
+        let peer_id = requested_epoch_sync_peer(&network_adapter, &next_epoch_id);
+        let block = epoch_sync_light_client_block(
+            &next_epoch_id,
+            &EpochId(hash(&[8])),
+            &next_signers,
+            vec![],
+        );
+        assert_eq!(
+            epoch_sync.on_response(
+                peer_id,
+                EpochSyncResponse::Advance { light_client_block_view: block }
+            ),
+            Ok(())
+        );
+        assert!(!epoch_sync.done);
+    }
+
+    /// Checks that the epoch sync requests the finalization once it reached the current epoch,
+    /// and that it bans the peer sending an invalid finalization response without saving anything.
+    #[test]
+    fn test_epoch_sync_invalid_finalization() {
+        let network_adapter = Arc::new(MockNetworkAdapter::default());
+        let (mut chain, runtime_adapter, _) = setup();
+        let runtime_adapter: Arc<dyn RuntimeAdapter> = runtime_adapter;
+        let genesis_epoch_id = chain.genesis().epoch_id().clone();
+        let epoch_ids: Vec<_> =
+            vec![genesis_epoch_id.clone(), EpochId(hash(&[7])), EpochId(hash(&[8]))];
+        let signers: Vec<_> = (0..3).map(epoch_sync_signers).collect();
+        let mut epoch_sync = EpochSync::new(
+            network_adapter.clone(),
+            genesis_epoch_id,
+            epoch_sync_block_producers(&signers[0]),
+            TimeDuration::from_secs(0),
+            TimeDuration::from_secs(0),
+        );
+        let peers = create_peer_infos(1);
+        let mut sync_status = SyncStatus::NoSync;
+        for epoch in 0..2 {
+            assert!(!epoch_sync.run(&mut sync_status, &chain, &peers).unwrap());
+            let peer_id = requested_epoch_sync_peer(&network_adapter, &epoch_ids[epoch]);
+            let block = epoch_sync_light_client_block(
+                &epoch_ids[epoch],
+                &epoch_ids[epoch + 1],
+                &signers[epoch],
+                epoch_sync_block_producers(&signers[epoch + 1]),
+            );
+            assert_eq!(
+                epoch_sync.on_response(
+                    peer_id,
+                    EpochSyncResponse::Advance { light_client_block_view: block }
+                ),
+                Ok(())
+            );
+        }
+
+        assert!(!epoch_sync.run(&mut sync_status, &chain, &peers).unwrap());
+        let peer_id = requested_epoch_sync_peer(&network_adapter, &epoch_ids[2]);
+        assert_eq!(epoch_sync.on_response(peer_id.clone(), EpochSyncResponse::UpToDate), Ok(()));
+        match network_adapter.pop().unwrap() {
+            NetworkRequests::EpochSyncFinalizationRequest {
+                peer_id: requested_peer_id,
+                epoch_id,
+            } => {
+                assert_eq!(requested_peer_id, peer_id);
+                assert_eq!(epoch_id, epoch_ids[2]);
+            }
+            request => panic!("Unexpected request {:?}", request),
+        }
+
+        // The genesis is not the first block of the current epoch.
+        let genesis_header = chain.genesis().clone();
+        let response = EpochSyncFinalizationResponse {
+            cur_epoch_header: genesis_header.clone(),
+            prev_epoch_headers: vec![],
+            header_sync_init_header: genesis_header,
+            header_sync_init_header_tree: PartialMerkleTree::default(),
+            prev_epoch_first_block_info: Default::default(),
+            prev_epoch_prev_last_block_info: Default::default(),
+            prev_epoch_last_block_info: Default::default(),
+            prev_epoch_info: Default::default(),
+            cur_epoch_info: Default::default(),
+            next_epoch_info: Default::default(),
+        };
+        assert_eq!(
+            epoch_sync.on_finalization_response(peer_id, response, &mut chain, &runtime_adapter),
+            Err(ReasonForBan::EpochSyncInvalidFinalizationResponse)
+        );
+        assert!(!epoch_sync.done);
+        assert_eq!(epoch_sync.sync_hash, CryptoHash::default());
+        assert_eq!(chain.store().epoch_sync_hash().unwrap(), None);
+    }
 }
@@ -10,6 +10,7 @@ use std::time::{Duration, Instant};
 
 use actix::{Actor, Addr, Handler, SyncArbiter, SyncContext};
 use cached::{Cached, SizedCache};
+use chrono::Utc;
 use log::{debug, error, info, trace, warn};
 
 use near_chain::types::ValidatorInfoIdentifier;
@@ -23,8 +24,8 @@ use near_client_primitives::types::{
     GetBlockWithMerkleTree, GetChunkError, GetExecutionOutcome, GetExecutionOutcomeError,
     GetExecutionOutcomesForBlock, GetGasPrice, GetGasPriceError, GetNextLightClientBlockError,
     GetProtocolConfig, GetProtocolConfigError, GetReceipt, GetReceiptError, GetStateChangesError,
-    GetStateChangesWithCauseInBlock, GetValidatorInfoError, Query, QueryError, TxStatus,
-    TxStatusError,
+    GetStateChangesWithCauseInBlock, GetStateChangesWithCauseInBlockByShard, GetValidatorInfoError,
+    Query, QueryError, SimulateTransaction, SimulateTransactionError, TxStatus, TxStatusError,
 };
 #[cfg(feature = "test_features")]
 use near_network::types::NetworkAdversarialMessage;
@@ -41,19 +42,20 @@ use near_primitives::merkle::{merklize, PartialMerkleTree};
 use near_primitives::network::AnnounceAccount;
 use near_primitives::sharding::ShardChunk;
 use near_primitives::syncing::{
-    ShardStateSyncResponse, ShardStateSyncResponseHeader, ShardStateSyncResponseV1,
-    ShardStateSyncResponseV2,
+    EpochSyncFinalizationResponse, EpochSyncResponse, ShardStateSyncResponse,
+    ShardStateSyncResponseHeader, ShardStateSyncResponseV1, ShardStateSyncResponseV2,
 };
 use near_primitives::types::{
     AccountId, BlockHeight, BlockId, BlockReference, EpochId, EpochReference, Finality,
-    MaybeBlockId, ShardId, TransactionOrReceiptId,
+    MaybeBlockId, ShardId, StateChanges, StateChangesExt, TransactionOrReceiptId,
 };
+use near_primitives::utils::to_timestamp;
 use near_primitives::views::validator_stake_view::ValidatorStakeView;
 use near_primitives::views::{
-    BlockView, ChunkView, EpochValidatorInfo, ExecutionOutcomeWithIdView,
+    BlockView, ChunkView, EpochValidatorInfo, ExecutionOutcomeWithIdView, ExecutionStatusView,
     FinalExecutionOutcomeView, FinalExecutionOutcomeViewEnum, FinalExecutionStatus, GasPriceView,
-    LightClientBlockView, QueryRequest, QueryResponse, ReceiptView, StateChangesKindsView,
-    StateChangesView,
+    LightClientBlockView, QueryRequest, QueryResponse, ReceiptView, SimulatedTransactionView,
+    StateChangeWithCauseView, StateChangesKindsView, StateChangesView,
 };
 
 use crate::{
@@ -103,6 +105,9 @@ pub struct ViewClientActor {
     pub config: ClientConfig,
     request_manager: Arc<RwLock<ViewClientRequestManager>>,
     state_request_cache: Arc<Mutex<VecDeque<Instant>>>,
+    /// Value of `RuntimeAdapter::sandbox_resets` the chain caches were last dropped at.
+    #[cfg(feature = "sandbox")]
+    sandbox_resets: u64,
 }
 
 impl ViewClientRequestManager {
@@ -141,6 +146,8 @@ impl ViewClientActor {
             adv,
             validator_account_id,
             chain,
+            #[cfg(feature = "sandbox")]
+            sandbox_resets: runtime_adapter.sandbox_resets(),
             runtime_adapter,
             network_adapter,
             config,
@@ -149,6 +156,21 @@ impl ViewClientActor {
         })
     }
 
+    /// Drops the chain caches and the cached responses if the client reverted the store to a
+    /// sandbox snapshot since the last request.
+    fn check_sandbox_restore(&mut self) {
+        #[cfg(feature = "sandbox")]
+        {
+            let sandbox_resets = self.runtime_adapter.sandbox_resets();
+            if sandbox_resets != self.sandbox_resets {
+                self.sandbox_resets = sandbox_resets;
+                self.chain.sandbox_reset_caches();
+                *self.request_manager.write().expect(POISONED_LOCK_ERR) =
+                    ViewClientRequestManager::new();
+            }
+        }
+    }
+
     fn maybe_block_id_to_block_hash(
         &mut self,
         block_id: MaybeBlockId,
@@ -243,6 +265,7 @@ impl ViewClientActor {
         let account_id = match &msg.request {
             QueryRequest::ViewAccount { account_id, .. } => account_id,
             QueryRequest::ViewState { account_id, .. } => account_id,
+            QueryRequest::ViewStatePaginated { account_id, .. } => account_id,
             QueryRequest::ViewAccessKey { account_id, .. } => account_id,
             QueryRequest::ViewAccessKeyList { account_id, .. } => account_id,
             QueryRequest::CallFunction { account_id, .. } => account_id,
@@ -480,6 +503,110 @@ impl ViewClientActor {
         Ok(headers)
     }
 
+    /// Returns the light client block of the last final block of the epoch, which has the block
+    /// producers of the next epoch, or `UpToDate` if the epoch is the current one.
+    fn get_epoch_sync_response(
+        &mut self,
+        epoch_id: &EpochId,
+    ) -> Result<Option<EpochSyncResponse>, near_chain::Error> {
+        match self.chain.mut_store().get_epoch_light_client_block(&epoch_id.0) {
+            Ok(light_client_block_view) => Ok(Some(EpochSyncResponse::Advance {
+                light_client_block_view: light_client_block_view.clone(),
+            })),
+            Err(e) => match e.kind() {
+                ErrorKind::DBNotFoundErr(_) if &self.chain.head()?.epoch_id == epoch_id => {
+                    Ok(Some(EpochSyncResponse::UpToDate))
+                }
+                ErrorKind::DBNotFoundErr(_) => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Returns the headers and the epoch manager data needed to start the header sync and the
+    /// state sync from the first block of the current epoch.
+    fn get_epoch_sync_finalization_response(
+        &mut self,
+        epoch_id: &EpochId,
+    ) -> Result<EpochSyncFinalizationResponse, near_chain::Error> {
+        let head = self.chain.head()?;
+        if &head.epoch_id != epoch_id {
+            return Err(ErrorKind::Other(format!(
+                "Epoch {:?} is not the current epoch {:?}",
+                epoch_id, head.epoch_id
+            ))
+            .into());
+        }
+        let epoch_start_height =
+            self.runtime_adapter.get_epoch_start_height(&head.last_block_hash)?;
+        let cur_epoch_header = self
+            .chain
+            .get_header_on_chain_by_height(&head.last_block_hash, epoch_start_height)?
+            .clone();
+        let header_sync_init_header =
+            self.chain.get_block_header(cur_epoch_header.prev_hash())?.clone();
+        let header_sync_init_header_tree =
+            self.chain.mut_store().get_block_merkle_tree(header_sync_init_header.hash())?.clone();
+
+        // The state sync to the first block of the epoch checks the chunks of the last block of
+        // the previous epoch, and the previous chunks of the same shards, together with the
+        // headers of the blocks they are included in.
+        let sync_prev_block = self.chain.get_block(header_sync_init_header.hash())?.clone();
+        let mut min_height_included = header_sync_init_header.height();
+        for (shard_id, chunk) in sync_prev_block.chunks().iter().enumerate() {
+            let block_header = self
+                .chain
+                .get_header_on_chain_by_height(
+                    header_sync_init_header.hash(),
+                    chunk.height_included(),
+                )?
+                .clone();
+            if block_header.prev_hash() == &CryptoHash::default() {
+                min_height_included = min_height_included.min(block_header.height());
+                continue;
+            }
+            let prev_block = self.chain.get_block(block_header.prev_hash())?;
+            min_height_included =
+                min_height_included.min(prev_block.chunks()[shard_id].height_included());
+        }
+        let mut prev_epoch_headers = vec![];
+        let mut prev_hash = *header_sync_init_header.prev_hash();
+        while prev_hash != CryptoHash::default() {
+            let header = self.chain.get_block_header(&prev_hash)?.clone();
+            if header.height() < min_height_included {
+                break;
+            }
+            prev_hash = *header.prev_hash();
+            prev_epoch_headers.push(header);
+        }
+        prev_epoch_headers.reverse();
+
+        let (
+            prev_epoch_first_block_info,
+            prev_epoch_prev_last_block_info,
+            prev_epoch_last_block_info,
+            prev_epoch_info,
+            cur_epoch_info,
+            next_epoch_info,
+        ) = self.runtime_adapter.get_epoch_sync_data(
+            header_sync_init_header.hash(),
+            cur_epoch_header.epoch_id(),
+            cur_epoch_header.next_epoch_id(),
+        )?;
+        Ok(EpochSyncFinalizationResponse {
+            cur_epoch_header,
+            prev_epoch_headers,
+            header_sync_init_header,
+            header_sync_init_header_tree,
+            prev_epoch_first_block_info,
+            prev_epoch_prev_last_block_info,
+            prev_epoch_last_block_info,
+            prev_epoch_info,
+            cur_epoch_info,
+            next_epoch_info,
+        })
+    }
+
     fn check_signature_account_announce(
         &self,
         announce_account: &AnnounceAccount,
@@ -535,6 +662,7 @@ impl Handler<Query> for ViewClientActor {
 
     #[perf]
     fn handle(&mut self, msg: Query, _: &mut Self::Context) -> Self::Result {
+        self.check_sandbox_restore();
         self.handle_query(msg)
     }
 }
@@ -545,6 +673,7 @@ impl Handler<GetBlock> for ViewClientActor {
 
     #[perf]
     fn handle(&mut self, msg: GetBlock, _: &mut Self::Context) -> Self::Result {
+        self.check_sandbox_restore();
         let block = match msg.0 {
             BlockReference::Finality(finality) => {
                 let block_hash = self.get_block_hash_by_finality(&finality)?;
@@ -580,6 +709,7 @@ impl Handler<GetBlockWithMerkleTree> for ViewClientActor {
 
     #[perf]
     fn handle(&mut self, msg: GetBlockWithMerkleTree, ctx: &mut Self::Context) -> Self::Result {
+        self.check_sandbox_restore();
         let block_view = self.handle(GetBlock(msg.0), ctx)?;
         self.chain
             .mut_store()
@@ -594,6 +724,7 @@ impl Handler<GetChunk> for ViewClientActor {
 
     #[perf]
     fn handle(&mut self, msg: GetChunk, _: &mut Self::Context) -> Self::Result {
+        self.check_sandbox_restore();
         let get_chunk_from_block = |block: Block,
                                     shard_id: ShardId,
                                     chain: &mut Chain|
@@ -644,6 +775,7 @@ impl Handler<TxStatus> for ViewClientActor {
 
     #[perf]
     fn handle(&mut self, msg: TxStatus, _: &mut Self::Context) -> Self::Result {
+        self.check_sandbox_restore();
         self.get_tx_status(msg.tx_hash, msg.signer_account_id, msg.fetch_receipt)
     }
 }
@@ -653,6 +785,7 @@ impl Handler<GetValidatorInfo> for ViewClientActor {
 
     #[perf]
     fn handle(&mut self, msg: GetValidatorInfo, _: &mut Self::Context) -> Self::Result {
+        self.check_sandbox_restore();
         let epoch_identifier = match msg.epoch_reference {
             EpochReference::EpochId(id) => {
                 // By `EpochId` we can get only cached epochs.
@@ -697,6 +830,7 @@ impl Handler<GetValidatorOrdered> for ViewClientActor {
 
     #[perf]
     fn handle(&mut self, msg: GetValidatorOrdered, _: &mut Self::Context) -> Self::Result {
+        self.check_sandbox_restore();
         Ok(self
             .maybe_block_id_to_block_hash(msg.block_id)
             .and_then(|block_hash| self.chain.get_block_header(&block_hash).map(|h| h.clone()))
@@ -715,6 +849,7 @@ impl Handler<GetStateChangesInBlock> for ViewClientActor {
 
     #[perf]
     fn handle(&mut self, msg: GetStateChangesInBlock, _: &mut Self::Context) -> Self::Result {
+        self.check_sandbox_restore();
         Ok(self
             .chain
             .store()
@@ -731,6 +866,7 @@ impl Handler<GetStateChanges> for ViewClientActor {
 
     #[perf]
     fn handle(&mut self, msg: GetStateChanges, _: &mut Self::Context) -> Self::Result {
+        self.check_sandbox_restore();
         Ok(self
             .chain
             .store()
@@ -751,6 +887,7 @@ impl Handler<GetStateChangesWithCauseInBlock> for ViewClientActor {
         msg: GetStateChangesWithCauseInBlock,
         _: &mut Self::Context,
     ) -> Self::Result {
+        self.check_sandbox_restore();
         Ok(self
             .chain
             .store()
@@ -761,6 +898,31 @@ impl Handler<GetStateChangesWithCauseInBlock> for ViewClientActor {
     }
 }
 
+impl Handler<GetStateChangesWithCauseInBlockByShard> for ViewClientActor {
+    type Result = Result<HashMap<ShardId, StateChangesView>, GetStateChangesError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: GetStateChangesWithCauseInBlockByShard,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        self.check_sandbox_restore();
+        let epoch_id = self.chain.get_block_header(&msg.block_hash)?.epoch_id().clone();
+        let mut state_changes_by_shard: HashMap<ShardId, StateChangesView> = HashMap::new();
+        for state_change in
+            self.chain.store().get_state_changes_with_cause_in_block(&msg.block_hash)?
+        {
+            let state_change: StateChangeWithCauseView = state_change.into();
+            let shard_id = self
+                .runtime_adapter
+                .account_id_to_shard_id(state_change.value.affected_account_id(), &epoch_id)?;
+            state_changes_by_shard.entry(shard_id).or_default().push(state_change);
+        }
+        Ok(state_changes_by_shard)
+    }
+}
+
 /// Returns the next light client block, given the hash of the last block known to the light client.
 /// There are three cases:
 ///  1. The last block known to the light client is in the same epoch as the tip:
@@ -774,6 +936,7 @@ impl Handler<GetNextLightClientBlock> for ViewClientActor {
 
     #[perf]
     fn handle(&mut self, msg: GetNextLightClientBlock, _: &mut Self::Context) -> Self::Result {
+        self.check_sandbox_restore();
         let last_block_header = self.chain.get_block_header(&msg.last_block_hash)?;
         let last_epoch_id = last_block_header.epoch_id().clone();
         let last_next_epoch_id = last_block_header.next_epoch_id().clone();
@@ -813,6 +976,7 @@ impl Handler<GetExecutionOutcome> for ViewClientActor {
 
     #[perf]
     fn handle(&mut self, msg: GetExecutionOutcome, _: &mut Self::Context) -> Self::Result {
+        self.check_sandbox_restore();
         let (id, account_id) = match msg.id {
             TransactionOrReceiptId::Transaction { transaction_hash, sender_id } => {
                 (transaction_hash, sender_id)
@@ -895,6 +1059,7 @@ impl Handler<GetExecutionOutcomesForBlock> for ViewClientActor {
 
     #[perf]
     fn handle(&mut self, msg: GetExecutionOutcomesForBlock, _: &mut Self::Context) -> Self::Result {
+        self.check_sandbox_restore();
         Ok(self
             .chain
             .get_block_execution_outcomes(&msg.block_hash)
@@ -910,6 +1075,7 @@ impl Handler<GetReceipt> for ViewClientActor {
 
     #[perf]
     fn handle(&mut self, msg: GetReceipt, _: &mut Self::Context) -> Self::Result {
+        self.check_sandbox_restore();
         Ok(self
             .chain
             .mut_store()
@@ -918,11 +1084,96 @@ impl Handler<GetReceipt> for ViewClientActor {
     }
 }
 
+/// Executes the transaction and the receipts it produces on top of the state at the chain head.
+/// Nothing is persisted or sent to the network.
+impl Handler<SimulateTransaction> for ViewClientActor {
+    type Result = Result<SimulatedTransactionView, SimulateTransactionError>;
+
+    #[perf]
+    fn handle(&mut self, msg: SimulateTransaction, _: &mut Self::Context) -> Self::Result {
+        self.check_sandbox_restore();
+        let transaction = msg.signed_transaction;
+        let head = self.chain.head()?;
+        let header = self.chain.get_block_header(&head.last_block_hash)?.clone();
+
+        let mut state_roots = HashMap::new();
+        for shard_id in 0..self.runtime_adapter.num_shards(&head.epoch_id)? {
+            let shard_uid = self.runtime_adapter.shard_id_to_uid(shard_id, &head.epoch_id)?;
+            if let Ok(chunk_extra) = self.chain.get_chunk_extra(&head.last_block_hash, &shard_uid) {
+                state_roots.insert(shard_id, *chunk_extra.state_root());
+            }
+        }
+        let signer_shard_id = self
+            .runtime_adapter
+            .account_id_to_shard_id(&transaction.transaction.signer_id, &head.epoch_id)?;
+        if !state_roots.contains_key(&signer_shard_id) {
+            return Err(SimulateTransactionError::DoesNotTrackShard);
+        }
+
+        let result = self
+            .runtime_adapter
+            .simulate_transaction(
+                &head.epoch_id,
+                &state_roots,
+                head.height + 1,
+                to_timestamp(Utc::now()),
+                &head.last_block_hash,
+                header.gas_price(),
+                *header.random_value(),
+                &transaction,
+            )?
+            .map_err(SimulateTransactionError::InvalidTransaction)?;
+
+        let mut receipts_outcome: Vec<ExecutionOutcomeWithIdView> = result
+            .outcomes
+            .into_iter()
+            .map(|outcome_with_id| ExecutionOutcomeWithIdView {
+                proof: vec![],
+                block_hash: CryptoHash::default(),
+                id: outcome_with_id.id,
+                outcome: outcome_with_id.outcome.into(),
+            })
+            .collect();
+        // Follow the chain of the returned receipts, as `Chain::get_final_transaction_result`
+        // does. It is cut short if it leads to a receipt that was not executed.
+        let mut looking_for_id = transaction.get_hash();
+        let status = loop {
+            let outcome = receipts_outcome.iter().find(|outcome| outcome.id == looking_for_id);
+            match outcome.map(|outcome| &outcome.outcome.status) {
+                Some(ExecutionStatusView::SuccessReceiptId(id)) => looking_for_id = *id,
+                Some(ExecutionStatusView::SuccessValue(value)) => {
+                    break FinalExecutionStatus::SuccessValue(value.clone())
+                }
+                Some(ExecutionStatusView::Failure(err)) => {
+                    break FinalExecutionStatus::Failure(err.clone())
+                }
+                Some(ExecutionStatusView::Unknown) | None => break FinalExecutionStatus::Started,
+            }
+        };
+        let transaction_outcome = receipts_outcome.remove(0);
+
+        let state_changes = StateChanges::from_changes(result.state_changes.into_iter().map(Ok))
+            .map_err(|err| SimulateTransactionError::IOError(err.to_string()))?;
+        Ok(SimulatedTransactionView {
+            final_outcome: FinalExecutionOutcomeView {
+                status,
+                transaction: transaction.into(),
+                transaction_outcome,
+                receipts_outcome,
+            },
+            receipts: result.receipts.into_iter().map(Into::into).collect(),
+            unprocessed_receipts: result.unprocessed_receipts.into_iter().map(Into::into).collect(),
+            state_changes: state_changes.into_iter().map(Into::into).collect(),
+        })
+    }
+}
+
 impl Handler<GetBlockProof> for ViewClientActor {
     type Result = Result<GetBlockProofResponse, GetBlockProofError>;
 
     #[perf]
     fn handle(&mut self, msg: GetBlockProof, _: &mut Self::Context) -> Self::Result {
+        self.check_sandbox_restore();
         self.chain.check_block_final_and_canonical(&msg.block_hash)?;
         self.chain.check_block_final_and_canonical(&msg.head_block_hash)?;
         let block_header_lite = self.chain.get_block_header(&msg.block_hash)?.clone().into();
@@ -936,6 +1187,7 @@ impl Handler<GetProtocolConfig> for ViewClientActor {
 
     #[perf]
     fn handle(&mut self, msg: GetProtocolConfig, _: &mut Self::Context) -> Self::Result {
+        self.check_sandbox_restore();
         let block_header = match msg.0 {
             BlockReference::Finality(finality) => {
                 let block_hash = self.get_block_hash_by_finality(&finality)?;
@@ -970,6 +1222,7 @@ impl Handler<NetworkViewClientMessages> for ViewClientActor {
 
     #[perf_with_debug]
     fn handle(&mut self, msg: NetworkViewClientMessages, _ctx: &mut Self::Context) -> Self::Result {
+        self.check_sandbox_restore();
         match msg {
             #[cfg(feature = "test_features")]
             NetworkViewClientMessages::Adversarial(adversarial_msg) => {
@@ -1097,14 +1350,16 @@ impl Handler<NetworkViewClientMessages> for ViewClientActor {
                 Ok(head) => {
                     match self.runtime_adapter.num_shards(&head.epoch_id) {
                         Ok(num_shards) => {
-                            // convert config tracked shards
-                            // runtime will track all shards if config tracked shards is not empty
-                            // https://github.com/near/nearcore/issues/4930
-                            let tracked_shards = if self.config.tracked_shards.is_empty() {
-                                vec![]
-                            } else {
-                                (0..num_shards).collect()
-                            };
+                            let tracked_shards = (0..num_shards)
+                                .filter(|&shard_id| {
+                                    self.runtime_adapter.cares_about_shard(
+                                        None,
+                                        &head.last_block_hash,
+                                        shard_id,
+                                        true,
+                                    )
+                                })
+                                .collect();
                             NetworkViewClientResponses::ChainInfo {
                                 genesis_id: GenesisId {
                                     chain_id: self.config.chain_id.clone(),
@@ -1281,13 +1536,26 @@ impl Handler<NetworkViewClientMessages> for ViewClientActor {
 
                 NetworkViewClientResponses::AnnounceAccount(filtered_announce_accounts)
             }
-            NetworkViewClientMessages::EpochSyncRequest { epoch_id: _epoch_id } => {
-                // TODO #3488
-                NetworkViewClientResponses::NoResponse
+            NetworkViewClientMessages::EpochSyncRequest { epoch_id } => {
+                match self.get_epoch_sync_response(&epoch_id) {
+                    Ok(Some(response)) => NetworkViewClientResponses::EpochSyncResponse(response),
+                    Ok(None) => NetworkViewClientResponses::NoResponse,
+                    Err(err) => {
+                        error!(target: "sync", "Cannot build epoch sync response for epoch {:?}: {}", epoch_id, err);
+                        NetworkViewClientResponses::NoResponse
+                    }
+                }
             }
-            NetworkViewClientMessages::EpochSyncFinalizationRequest { epoch_id: _epoch_id } => {
-                // TODO #3488
-                NetworkViewClientResponses::NoResponse
+            NetworkViewClientMessages::EpochSyncFinalizationRequest { epoch_id } => {
+                match self.get_epoch_sync_finalization_response(&epoch_id) {
+                    Ok(response) => {
+                        NetworkViewClientResponses::EpochSyncFinalizationResponse(response)
+                    }
+                    Err(err) => {
+                        debug!(target: "sync", "Cannot build epoch sync finalization response for epoch {:?}: {}", epoch_id, err);
+                        NetworkViewClientResponses::NoResponse
+                    }
+                }
             }
         }
     }
@@ -1298,6 +1566,7 @@ impl Handler<GetGasPrice> for ViewClientActor {
 
     #[perf]
     fn handle(&mut self, msg: GetGasPrice, _ctx: &mut Self::Context) -> Self::Result {
+        self.check_sandbox_restore();
         let header = self
             .maybe_block_id_to_block_hash(msg.block_id)
             .and_then(|block_hash| self.chain.get_block_header(&block_hash));
@@ -103,22 +103,25 @@ fn store_partial_encoded_chunk_sanity() {
     let signer =
         InMemoryValidatorSigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
     let mut partial_encoded_chunk = PartialEncodedChunkV2 {
-        header: ShardChunkHeader::V2(ShardChunkHeaderV2::new(
-            CryptoHash::default(),
-            CryptoHash::default(),
-            CryptoHash::default(),
-            CryptoHash::default(),
-            1,
-            1,
-            0,
-            0,
-            0,
-            0,
-            CryptoHash::default(),
-            CryptoHash::default(),
-            vec![],
-            &signer,
-        )),
+        header: ShardChunkHeader::V2(
+            ShardChunkHeaderV2::new(
+                CryptoHash::default(),
+                CryptoHash::default(),
+                CryptoHash::default(),
+                CryptoHash::default(),
+                1,
+                1,
+                0,
+                0,
+                0,
+                0,
+                CryptoHash::default(),
+                CryptoHash::default(),
+                vec![],
+                &signer,
+            )
+            .unwrap(),
+        ),
         parts: vec![],
         receipts: vec![],
     };
@@ -147,22 +150,25 @@ fn store_partial_encoded_chunk_sanity() {
 
     // Check adding
     let mut partial_encoded_chunk2 = partial_encoded_chunk.clone();
-    let h = ShardChunkHeader::V2(ShardChunkHeaderV2::new(
-        CryptoHash::default(),
-        CryptoHash::default(),
-        CryptoHash::default(),
-        CryptoHash::default(),
-        1,
-        1,
-        173465755,
-        0,
-        0,
-        0,
-        CryptoHash::default(),
-        CryptoHash::default(),
-        vec![],
-        &signer,
-    ));
+    let h = ShardChunkHeader::V2(
+        ShardChunkHeaderV2::new(
+            CryptoHash::default(),
+            CryptoHash::default(),
+            CryptoHash::default(),
+            CryptoHash::default(),
+            1,
+            1,
+            173465755,
+            0,
+            0,
+            0,
+            CryptoHash::default(),
+            CryptoHash::default(),
+            vec![],
+            &signer,
+        )
+        .unwrap(),
+    );
     partial_encoded_chunk2.header = h;
     assert_eq!(env.clients[0].shards_mgr.get_stored_partial_encoded_chunks(1).len(), 1);
     env.clients[0]
@@ -181,22 +187,25 @@ fn store_partial_encoded_chunk_sanity() {
     // Check horizon
     env.produce_block(0, 3);
     let mut partial_encoded_chunk3 = partial_encoded_chunk.clone();
-    let mut h = ShardChunkHeader::V2(ShardChunkHeaderV2::new(
-        CryptoHash::default(),
-        CryptoHash::default(),
-        CryptoHash::default(),
-        CryptoHash::default(),
-        1,
-        2,
-        1,
-        0,
-        0,
-        0,
-        CryptoHash::default(),
-        CryptoHash::default(),
-        vec![],
-        &signer,
-    ));
+    let mut h = ShardChunkHeader::V2(
+        ShardChunkHeaderV2::new(
+            CryptoHash::default(),
+            CryptoHash::default(),
+            CryptoHash::default(),
+            CryptoHash::default(),
+            1,
+            2,
+            1,
+            0,
+            0,
+            0,
+            CryptoHash::default(),
+            CryptoHash::default(),
+            vec![],
+            &signer,
+        )
+        .unwrap(),
+    );
     partial_encoded_chunk3.header = h.clone();
     env.clients[0]
         .shards_mgr
@@ -159,10 +159,7 @@ impl EpochManager {
         self.save_epoch_info(&mut store_update, &prev_epoch_id, prev_epoch_info)?;
         self.save_epoch_info(&mut store_update, &epoch_id, epoch_info)?;
         self.save_epoch_info(&mut store_update, &next_epoch_id, next_epoch_info)?;
-        // TODO #3488
-        // put unreachable! here to avoid warnings
-        unreachable!();
-        // Ok(store_update)
+        Ok(store_update)
     }
 
     /// # Parameters
@@ -445,6 +442,18 @@ impl EpochManager {
         Ok(())
     }
 
+    /// Drops everything cached from the store, e.g. after the store was reverted to an earlier
+    /// state.
+    pub fn reset_caches(&mut self) {
+        self.epochs_info.cache_clear();
+        self.blocks_info.cache_clear();
+        self.epoch_id_to_start.cache_clear();
+        self.epoch_validators_ordered.cache_clear();
+        self.epoch_validators_ordered_unique.cache_clear();
+        self.epoch_info_aggregator = None;
+        self.largest_final_height = 0;
+    }
+
     pub fn record_block_info(
         &mut self,
         mut block_info: BlockInfo,
@@ -1259,6 +1268,12 @@ impl EpochManager {
         Ok(EpochId(*first_block_info.prev_hash()))
     }
 
+    /// Returns the shard layouts of all the protocol versions, to look up the parent layouts of
+    /// a shard layout by version.
+    pub fn get_all_shard_layouts(&self) -> Vec<&ShardLayout> {
+        self.config.shard_layouts()
+    }
+
     pub fn get_shard_layout(&mut self, epoch_id: &EpochId) -> Result<&ShardLayout, EpochError> {
         let protocol_version = self.get_epoch_info(epoch_id)?.protocol_version();
         let shard_layout = &self.config.for_protocol_version(protocol_version).shard_layout;
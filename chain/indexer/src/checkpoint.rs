@@ -0,0 +1,137 @@
+//! Height of the last block delivered by the indexer, stored next to the node's database.
+//!
+//! A backfill keeps its own checkpoint for its range of blocks, so that it neither moves the
+//! checkpoint of the live indexer nor resumes from the checkpoint of another range.
+
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use rocksdb::{WriteOptions, DB};
+
+use near_primitives::types::BlockHeight;
+
+use crate::SyncModeEnum;
+
+const LAST_SYNCED_BLOCK_HEIGHT_KEY: &[u8] = b"last_synced_block_height";
+
+pub(crate) struct Checkpoint {
+    db: DB,
+    key: Vec<u8>,
+}
+
+impl Checkpoint {
+    /// Opens the checkpoint used in `sync_mode`.
+    pub(crate) fn open(home_dir: &Path, sync_mode: &SyncModeEnum) -> Result<Self, rocksdb::Error> {
+        let mut path = nearcore::get_store_path(home_dir);
+        path.push("indexer");
+        let key = match sync_mode {
+            SyncModeEnum::Backfill { start_height, end_height, .. } => {
+                format!("backfill_{}_{}", start_height, end_height).into_bytes()
+            }
+            _ => LAST_SYNCED_BLOCK_HEIGHT_KEY.to_vec(),
+        };
+        Ok(Self { db: DB::open_default(path)?, key })
+    }
+
+    /// Returns the height of the last delivered block, if any.
+    pub(crate) fn last_synced_block_height(&self) -> io::Result<Option<BlockHeight>> {
+        let value = match self.db.get(&self.key).map_err(db_error)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        std::str::from_utf8(&value)
+            .ok()
+            .and_then(|value| value.parse::<BlockHeight>().ok())
+            .map(Some)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Invalid last synced block height {:?}",
+                        String::from_utf8_lossy(&value)
+                    ),
+                )
+            })
+    }
+
+    /// Records that the block at `block_height` has been delivered. Resolves once the write
+    /// reached the disk, so that the block is not delivered again after a crash. The write runs
+    /// on the blocking thread pool.
+    pub(crate) async fn commit(self: Arc<Self>, block_height: BlockHeight) -> io::Result<()> {
+        tokio::task::spawn_blocking(move || self.commit_sync(block_height))
+            .await
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
+    }
+
+    fn commit_sync(&self, block_height: BlockHeight) -> io::Result<()> {
+        let mut write_options = WriteOptions::default();
+        write_options.set_sync(true);
+        self.db.put_opt(&self.key, block_height.to_string(), &write_options).map_err(db_error)
+    }
+}
+
+fn db_error(err: rocksdb::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Returns a home directory with an empty store directory.
+    pub(crate) fn home_dir() -> tempfile::TempDir {
+        let home_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(nearcore::get_store_path(home_dir.path())).unwrap();
+        home_dir
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_persists() {
+        let home_dir = home_dir();
+        {
+            let checkpoint = Arc::new(
+                Checkpoint::open(home_dir.path(), &SyncModeEnum::FromInterruption).unwrap(),
+            );
+            assert_eq!(checkpoint.last_synced_block_height().unwrap(), None);
+            checkpoint.clone().commit(10).await.unwrap();
+            checkpoint.clone().commit(11).await.unwrap();
+            assert_eq!(checkpoint.last_synced_block_height().unwrap(), Some(11));
+        }
+        let checkpoint = Checkpoint::open(home_dir.path(), &SyncModeEnum::LatestSynced).unwrap();
+        assert_eq!(checkpoint.last_synced_block_height().unwrap(), Some(11));
+    }
+
+    #[tokio::test]
+    async fn test_backfill_checkpoint() {
+        let home_dir = home_dir();
+        let backfill = |start_height, end_height| SyncModeEnum::Backfill {
+            start_height,
+            end_height,
+            workers: 4,
+        };
+        let checkpoint = Arc::new(Checkpoint::open(home_dir.path(), &backfill(10, 20)).unwrap());
+        checkpoint.clone().commit(15).await.unwrap();
+        drop(checkpoint);
+
+        let checkpoint = Checkpoint::open(home_dir.path(), &backfill(10, 20)).unwrap();
+        assert_eq!(checkpoint.last_synced_block_height().unwrap(), Some(15));
+        drop(checkpoint);
+        let checkpoint = Checkpoint::open(home_dir.path(), &backfill(10, 30)).unwrap();
+        assert_eq!(checkpoint.last_synced_block_height().unwrap(), None);
+        drop(checkpoint);
+        let checkpoint =
+            Checkpoint::open(home_dir.path(), &SyncModeEnum::FromInterruption).unwrap();
+        assert_eq!(checkpoint.last_synced_block_height().unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid_checkpoint() {
+        let home_dir = home_dir();
+        let checkpoint =
+            Checkpoint::open(home_dir.path(), &SyncModeEnum::FromInterruption).unwrap();
+        checkpoint.db.put(LAST_SYNCED_BLOCK_HEIGHT_KEY, b"ten").unwrap();
+        let err = checkpoint.last_synced_block_height().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
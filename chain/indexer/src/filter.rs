@@ -0,0 +1,112 @@
+use std::collections::HashSet;
+
+use near_primitives::types::{AccountId, ShardId};
+use near_primitives::views;
+
+/// Selects the parts of the blocks the indexer streams, see `IndexerConfig::filter`. The default
+/// filter selects everything.
+///
+/// The filter is applied while the `StreamerMessage` is built, so data of shards and accounts
+/// that aren't selected is not fetched from the node in the first place.
+#[derive(Debug, Clone, Default)]
+pub struct IndexerFilter {
+    /// Accounts to stream, `None` to stream all of them. A pattern like `*.pool.near` selects all
+    /// the subaccounts of `pool.near`, but not `pool.near` itself.
+    ///
+    /// Transactions are selected by signer or receiver, receipts by predecessor or receiver,
+    /// execution outcomes of receipts by executor and state changes by the changed account.
+    pub accounts: Option<Vec<String>>,
+    /// Shards to stream, `None` to stream all the tracked shards. State changes are selected by
+    /// the shard of the changed account.
+    pub shard_ids: Option<HashSet<ShardId>>,
+    /// Kinds of actions to stream, `None` to stream all of them. Only transactions and receipts
+    /// with at least one of these actions are selected, so data receipts are skipped.
+    pub action_kinds: Option<HashSet<ActionKind>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ActionKind {
+    CreateAccount,
+    DeployContract,
+    FunctionCall,
+    Transfer,
+    Stake,
+    AddKey,
+    DeleteKey,
+    DeleteAccount,
+}
+
+impl From<&views::ActionView> for ActionKind {
+    fn from(action: &views::ActionView) -> Self {
+        match action {
+            views::ActionView::CreateAccount => ActionKind::CreateAccount,
+            views::ActionView::DeployContract { .. } => ActionKind::DeployContract,
+            views::ActionView::FunctionCall { .. } => ActionKind::FunctionCall,
+            views::ActionView::Transfer { .. } => ActionKind::Transfer,
+            views::ActionView::Stake { .. } => ActionKind::Stake,
+            views::ActionView::AddKey { .. } => ActionKind::AddKey,
+            views::ActionView::DeleteKey { .. } => ActionKind::DeleteKey,
+            views::ActionView::DeleteAccount { .. } => ActionKind::DeleteAccount,
+        }
+    }
+}
+
+impl IndexerFilter {
+    pub(crate) fn matches_shard(&self, shard_id: ShardId) -> bool {
+        self.shard_ids.as_ref().map_or(true, |shard_ids| shard_ids.contains(&shard_id))
+    }
+
+    pub(crate) fn matches_account(&self, account_id: &AccountId) -> bool {
+        let account_id: &str = account_id.as_ref();
+        self.accounts.as_ref().map_or(true, |patterns| {
+            patterns.iter().any(|pattern| match pattern.strip_prefix('*') {
+                Some(suffix) => account_id.ends_with(suffix),
+                None => account_id == pattern,
+            })
+        })
+    }
+
+    fn matches_actions(&self, actions: &[views::ActionView]) -> bool {
+        self.action_kinds.as_ref().map_or(true, |action_kinds| {
+            actions.iter().any(|action| action_kinds.contains(&ActionKind::from(action)))
+        })
+    }
+
+    pub(crate) fn matches_transaction(&self, transaction: &views::SignedTransactionView) -> bool {
+        (self.matches_account(&transaction.signer_id)
+            || self.matches_account(&transaction.receiver_id))
+            && self.matches_actions(&transaction.actions)
+    }
+
+    pub(crate) fn matches_receipt(&self, receipt: &views::ReceiptView) -> bool {
+        let matches_actions = match &receipt.receipt {
+            views::ReceiptEnumView::Action { actions, .. } => self.matches_actions(actions),
+            views::ReceiptEnumView::Data { .. } => self.action_kinds.is_none(),
+        };
+        (self.matches_account(&receipt.predecessor_id)
+            || self.matches_account(&receipt.receiver_id))
+            && matches_actions
+    }
+
+    pub(crate) fn matches_state_change(&self, change: &views::StateChangeWithCauseView) -> bool {
+        self.matches_account(change.value.affected_account_id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IndexerFilter;
+
+    #[test]
+    fn test_matches_account() {
+        let filter = IndexerFilter {
+            accounts: Some(vec!["alice.near".to_string(), "*.pool.near".to_string()]),
+            ..Default::default()
+        };
+        assert!(filter.matches_account(&"alice.near".parse().unwrap()));
+        assert!(filter.matches_account(&"staked.pool.near".parse().unwrap()));
+        assert!(!filter.matches_account(&"pool.near".parse().unwrap()));
+        assert!(!filter.matches_account(&"bob.near".parse().unwrap()));
+        assert!(IndexerFilter::default().matches_account(&"bob.near".parse().unwrap()));
+    }
+}
@@ -7,18 +7,27 @@
 //! See the [example] for further details.
 //!
 //! [example]: https://github.com/nearprotocol/nearcore/tree/master/tools/indexer/example
+use std::sync::Arc;
+
 use tokio::sync::mpsc;
 
 pub use near_primitives;
 use near_primitives::types::Gas;
 pub use nearcore::{get_default_home, init_configs, NearConfig};
 
+pub use self::filter::{ActionKind, IndexerFilter};
+pub use self::sinks::{JsonLinesSink, Sink, SinkError};
+#[cfg(feature = "sqlite_sink")]
+pub use self::sinks::{SqliteSink, SQL_SCHEMA};
 pub use self::streamer::{
     IndexerChunkView, IndexerExecutionOutcomeWithOptionalReceipt,
     IndexerExecutionOutcomeWithReceipt, IndexerShard, IndexerTransactionWithOutcome,
     StreamerMessage,
 };
 
+mod checkpoint;
+mod filter;
+mod sinks;
 mod streamer;
 
 pub const INDEXER: &str = "indexer";
@@ -62,6 +71,13 @@ pub enum SyncModeEnum {
     FromInterruption,
     /// Specific block height to start syncing from
     BlockHeight(u64),
+    /// Streams the blocks from `start_height` to `end_height` inclusive and stops. The blocks are
+    /// fetched by `workers` concurrent workers, but are still streamed in height order. The node
+    /// must be archival to have the old blocks.
+    ///
+    /// The backfill of a range has its own checkpoint: restarting it resumes from the block it was
+    /// interrupted at, and the checkpoint of `FromInterruption` is left untouched.
+    Backfill { start_height: u64, end_height: u64, workers: usize },
 }
 
 /// Enum to define whether await for node to be fully synced or stream while syncing (useful for indexing from genesis)
@@ -82,6 +98,8 @@ pub struct IndexerConfig {
     pub sync_mode: SyncModeEnum,
     /// Whether await for node to be synced or not
     pub await_for_node_synced: AwaitForNodeSyncedEnum,
+    /// Parts of the blocks to stream
+    pub filter: IndexerFilter,
 }
 
 /// This is the core component, which handles `nearcore` and internal `streamer`.
@@ -90,6 +108,7 @@ pub struct Indexer {
     near_config: nearcore::NearConfig,
     view_client: actix::Addr<near_client::ViewClientActor>,
     client: actix::Addr<near_client::ClientActor>,
+    checkpoint: Arc<checkpoint::Checkpoint>,
 }
 
 impl Indexer {
@@ -105,24 +124,74 @@ impl Indexer {
             nearcore::config::load_config_without_genesis_records(&indexer_config.home_dir);
 
         assert!(
-            !&near_config.client_config.tracked_shards.is_empty(),
+            !near_config.client_config.tracked_shards.is_empty()
+                || !near_config.client_config.tracked_shard_uids.is_empty(),
             "Indexer should track at least one shard. \n\
             Tip: You may want to update {} with `\"tracked_shards\": [0]`
             ",
             indexer_config.home_dir.join("config.json").display()
         );
+        if let SyncModeEnum::Backfill { .. } = indexer_config.sync_mode {
+            assert!(
+                near_config.client_config.archive,
+                "Indexer should run on an archival node to backfill. \n\
+                Tip: You may want to update {} with `\"archive\": true`
+                ",
+                indexer_config.home_dir.join("config.json").display()
+            );
+        }
+        let checkpoint = Arc::new(
+            checkpoint::Checkpoint::open(&indexer_config.home_dir, &indexer_config.sync_mode)
+                .expect("Failed to open the indexer database"),
+        );
         let nearcore::NearNode { client, view_client, .. } =
             nearcore::start_with_config(&indexer_config.home_dir, near_config.clone());
-        Self { view_client, client, near_config, indexer_config }
+        Self { view_client, client, near_config, indexer_config, checkpoint }
     }
 
     /// Boots up `near_indexer::streamer`, so it monitors the new blocks with chunks, transactions, receipts, and execution outcomes inside. The returned stream handler should be drained and handled on the user side.
+    ///
+    /// A block is checkpointed as soon as it is sent to the stream, so blocks the user hasn't handled yet are lost on crash. Use [`Indexer::start_sink`] to avoid that.
     pub fn streamer(&self) -> mpsc::Receiver<streamer::StreamerMessage> {
+        let mut messages = self.start_streamer();
+        let (sender, receiver) = mpsc::channel(16);
+        let checkpoint = self.checkpoint.clone();
+        actix::spawn(async move {
+            while let Some(message) = messages.recv().await {
+                let block_height = message.block.header.height;
+                if sender.send(message).await.is_err() {
+                    tracing::info!(
+                        target: INDEXER,
+                        "Unable to send StreamerMessage to listener, listener doesn't listen. terminating..."
+                    );
+                    break;
+                }
+                if let Err(err) = checkpoint.clone().commit(block_height).await {
+                    tracing::error!(
+                        target: INDEXER,
+                        "Failed to checkpoint block #{}: {}",
+                        block_height,
+                        err
+                    );
+                }
+            }
+        });
+        receiver
+    }
+
+    /// Boots up `near_indexer::streamer` and delivers the blocks to `sink`. A block is checkpointed only after the sink acknowledged it, so with `SyncModeEnum::FromInterruption` no block is lost on crash.
+    pub fn start_sink<S: Sink + 'static>(&self, sink: S) {
+        let messages = self.start_streamer();
+        actix::spawn(sinks::run(messages, sink, self.checkpoint.clone()));
+    }
+
+    fn start_streamer(&self) -> mpsc::Receiver<streamer::StreamerMessage> {
         let (sender, receiver) = mpsc::channel(16);
         actix::spawn(streamer::start(
             self.view_client.clone(),
             self.client.clone(),
             self.indexer_config.clone(),
+            self.checkpoint.clone(),
             sender,
         ));
         receiver
@@ -0,0 +1,110 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use tracing::warn;
+
+use super::{Sink, SinkError};
+use crate::{StreamerMessage, INDEXER};
+
+/// Appends every `StreamerMessage` as a line of JSON to a file.
+///
+/// Writes are synced to the disk before the block is acknowledged. They run on the blocking thread
+/// pool.
+pub struct JsonLinesSink {
+    file: File,
+}
+
+impl JsonLinesSink {
+    /// Opens the file at `path` for appending, creating it if needed. A line left incomplete by a
+    /// crash is removed.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new().create(true).read(true).append(true).open(path)?;
+        truncate_incomplete_line(&mut file)?;
+        Ok(Self { file })
+    }
+
+    async fn append(&mut self, message: &StreamerMessage) -> Result<(), SinkError> {
+        let mut line = serde_json::to_vec(message)?;
+        line.push(b'\n');
+        let mut file = self.file.try_clone()?;
+        tokio::task::spawn_blocking(move || append_line(&mut file, &line))
+            .await
+            .map_err(|err| SinkError::Other(format!("Failed to append the line: {}", err)))??;
+        Ok(())
+    }
+}
+
+impl Sink for JsonLinesSink {
+    fn deliver<'a>(
+        &'a mut self,
+        message: &'a StreamerMessage,
+    ) -> LocalBoxFuture<'a, Result<(), SinkError>> {
+        self.append(message).boxed_local()
+    }
+}
+
+/// Appends `line` to `file` and syncs it.
+fn append_line(file: &mut File, line: &[u8]) -> io::Result<()> {
+    let len = file.metadata()?.len();
+    let result = file.write_all(line).and_then(|()| file.sync_data());
+    if result.is_err() {
+        // Don't leave a partial line behind, the message is appended again on retry.
+        let _ = file.set_len(len);
+    }
+    result
+}
+
+/// Truncates `file` after its last newline.
+fn truncate_incomplete_line(file: &mut File) -> io::Result<()> {
+    let len = file.metadata()?.len();
+    let mut complete_len = 0;
+    let mut end = len;
+    let mut buf = vec![0; 64 * 1024];
+    while end > 0 {
+        let start = end.saturating_sub(buf.len() as u64);
+        let chunk = &mut buf[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(chunk)?;
+        if let Some(pos) = chunk.iter().rposition(|&byte| byte == b'\n') {
+            complete_len = start + pos as u64 + 1;
+            break;
+        }
+        end = start;
+    }
+    if complete_len < len {
+        warn!(target: INDEXER, "Removing {} bytes of an incomplete line", len - complete_len);
+        file.set_len(complete_len)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::sinks::tests::test_message;
+
+    #[tokio::test]
+    async fn test_json_lines_sink() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blocks.jsonl");
+        fs::write(&path, "{\"block\": 1}\n{\"blo").unwrap();
+
+        let mut sink = JsonLinesSink::open(&path).unwrap();
+        sink.deliver(&test_message(2)).await.unwrap();
+        sink.deliver(&test_message(3)).await.unwrap();
+        drop(sink);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> =
+            contents.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["block"], 1);
+        assert_eq!(lines[1]["block"]["header"]["height"], 2);
+        assert_eq!(lines[2]["block"]["header"]["height"], 3);
+    }
+}
@@ -0,0 +1,183 @@
+//! Sinks store `StreamerMessage`s on behalf of the indexer.
+//!
+//! The indexer checkpoints a block only after its sink acknowledged the block by successfully
+//! delivering it, so no block is lost when the indexer crashes. Since the crash may happen between
+//! the delivery and the checkpoint, the last block may be delivered again after a restart, so
+//! sinks must tolerate repeated blocks.
+
+use std::cmp::min;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::LocalBoxFuture;
+use tokio::sync::mpsc;
+use tokio::time;
+use tracing::{error, warn};
+
+use crate::checkpoint::Checkpoint;
+use crate::{StreamerMessage, INDEXER};
+
+pub use self::json_lines::JsonLinesSink;
+#[cfg(feature = "sqlite_sink")]
+pub use self::sql::{SqliteSink, SQL_SCHEMA};
+
+mod json_lines;
+#[cfg(feature = "sqlite_sink")]
+mod sql;
+
+const MIN_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Destination of the indexed blocks, see `Indexer::start_sink`.
+pub trait Sink {
+    /// Stores `message` durably. Resolving to `Ok` acknowledges the block, which is then never
+    /// delivered again, except for the last acknowledged block after a crash. On errors the
+    /// delivery is retried until it succeeds.
+    fn deliver<'a>(
+        &'a mut self,
+        message: &'a StreamerMessage,
+    ) -> LocalBoxFuture<'a, Result<(), SinkError>>;
+}
+
+/// Error occurs in case a sink fails to store a block
+#[derive(Debug)]
+pub enum SinkError {
+    IO(io::Error),
+    Other(String),
+}
+
+impl fmt::Display for SinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SinkError::IO(err) => write!(f, "IO error: {}", err),
+            SinkError::Other(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for SinkError {}
+
+impl From<io::Error> for SinkError {
+    fn from(err: io::Error) -> Self {
+        SinkError::IO(err)
+    }
+}
+
+impl From<serde_json::Error> for SinkError {
+    fn from(err: serde_json::Error) -> Self {
+        SinkError::Other(format!("Failed to serialize StreamerMessage: {}", err))
+    }
+}
+
+/// Delivers every message to `sink` and checkpoints it once the sink acknowledged it.
+pub(crate) async fn run<S: Sink>(
+    mut messages: mpsc::Receiver<StreamerMessage>,
+    mut sink: S,
+    checkpoint: Arc<Checkpoint>,
+) {
+    while let Some(message) = messages.recv().await {
+        let block_height = message.block.header.height;
+        let mut retry_interval = MIN_RETRY_INTERVAL;
+        while let Err(err) = sink.deliver(&message).await {
+            warn!(
+                target: INDEXER,
+                "Failed to deliver block #{} to the sink, retrying in {:?}: {}",
+                block_height,
+                retry_interval,
+                err
+            );
+            time::sleep(retry_interval).await;
+            retry_interval = min(retry_interval * 2, MAX_RETRY_INTERVAL);
+        }
+        // The block has been delivered, so failing to checkpoint it only delivers it again after
+        // a restart.
+        if let Err(err) = checkpoint.clone().commit(block_height).await {
+            error!(target: INDEXER, "Failed to checkpoint block #{}: {}", block_height, err);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use futures::FutureExt;
+
+    use near_primitives::block::Block;
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::types::BlockHeight;
+    use near_primitives::utils::from_timestamp;
+    use near_primitives::version::PROTOCOL_VERSION;
+    use near_primitives::views::BlockView;
+
+    use super::*;
+    use crate::checkpoint::tests::home_dir;
+    use crate::SyncModeEnum;
+
+    /// Returns a message with an empty block at `height`.
+    pub(crate) fn test_message(height: BlockHeight) -> StreamerMessage {
+        let block = Block::genesis(
+            PROTOCOL_VERSION,
+            vec![],
+            from_timestamp(0),
+            height,
+            1_000,
+            1_000,
+            CryptoHash::default(),
+        );
+        StreamerMessage {
+            block: BlockView::from_author_block("test".parse().unwrap(), block),
+            shards: vec![],
+            state_changes: vec![],
+        }
+    }
+
+    /// Fails the first delivery of every block and records the checkpoint seen by the others.
+    struct FlakySink {
+        checkpoint: Arc<Checkpoint>,
+        failed: bool,
+        delivered: Rc<RefCell<Vec<(BlockHeight, Option<BlockHeight>)>>>,
+    }
+
+    impl Sink for FlakySink {
+        fn deliver<'a>(
+            &'a mut self,
+            message: &'a StreamerMessage,
+        ) -> LocalBoxFuture<'a, Result<(), SinkError>> {
+            let result = if std::mem::replace(&mut self.failed, !self.failed) {
+                let last_synced_block_height = self.checkpoint.last_synced_block_height().unwrap();
+                self.delivered
+                    .borrow_mut()
+                    .push((message.block.header.height, last_synced_block_height));
+                Ok(())
+            } else {
+                Err(SinkError::Other("Not this time".to_string()))
+            };
+            futures::future::ready(result).boxed_local()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_after_delivery() {
+        let home_dir = home_dir();
+        let checkpoint =
+            Arc::new(Checkpoint::open(home_dir.path(), &SyncModeEnum::FromInterruption).unwrap());
+        let delivered = Rc::new(RefCell::new(vec![]));
+        let sink = FlakySink {
+            checkpoint: checkpoint.clone(),
+            failed: false,
+            delivered: delivered.clone(),
+        };
+        let (sender, receiver) = mpsc::channel(4);
+        sender.send(test_message(5)).await.unwrap();
+        sender.send(test_message(6)).await.unwrap();
+        drop(sender);
+
+        run(receiver, sink, checkpoint.clone()).await;
+        assert_eq!(*delivered.borrow(), [(5, None), (6, Some(5))]);
+        assert_eq!(checkpoint.last_synced_block_height().unwrap(), Some(6));
+    }
+}
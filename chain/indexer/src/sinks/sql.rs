@@ -0,0 +1,221 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection};
+
+use near_primitives::views::ExecutionOutcomeWithIdView;
+
+use super::{Sink, SinkError};
+use crate::StreamerMessage;
+
+/// Tables filled by [`SqliteSink`]. The statements are compatible with both SQLite and PostgreSQL,
+/// so the same schema can be used to load the data into PostgreSQL.
+///
+/// Hashes and account ids are stored as text, structured data such as actions, statuses and state
+/// changes as JSON text.
+pub const SQL_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS blocks (
+    height BIGINT PRIMARY KEY,
+    hash TEXT NOT NULL UNIQUE,
+    prev_hash TEXT NOT NULL,
+    timestamp_nanosec BIGINT NOT NULL,
+    author TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS chunks (
+    hash TEXT PRIMARY KEY,
+    block_height BIGINT NOT NULL,
+    shard_id BIGINT NOT NULL,
+    author TEXT NOT NULL,
+    gas_used BIGINT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS transactions (
+    hash TEXT PRIMARY KEY,
+    block_height BIGINT NOT NULL,
+    shard_id BIGINT NOT NULL,
+    signer_id TEXT NOT NULL,
+    receiver_id TEXT NOT NULL,
+    actions TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS receipts (
+    receipt_id TEXT PRIMARY KEY,
+    block_height BIGINT NOT NULL,
+    shard_id BIGINT NOT NULL,
+    predecessor_id TEXT NOT NULL,
+    receiver_id TEXT NOT NULL,
+    receipt TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS execution_outcomes (
+    id TEXT PRIMARY KEY,
+    block_height BIGINT NOT NULL,
+    shard_id BIGINT NOT NULL,
+    executor_id TEXT NOT NULL,
+    gas_burnt BIGINT NOT NULL,
+    status TEXT NOT NULL,
+    logs TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS state_changes (
+    block_height BIGINT NOT NULL,
+    index_in_block BIGINT NOT NULL,
+    change TEXT NOT NULL,
+    PRIMARY KEY (block_height, index_in_block)
+);
+";
+
+// Blocks may be delivered again after a crash, so rows that already exist are skipped.
+const INSERT_BLOCK: &str = "INSERT INTO blocks VALUES ($1, $2, $3, $4, $5) ON CONFLICT DO NOTHING";
+const INSERT_CHUNK: &str = "INSERT INTO chunks VALUES ($1, $2, $3, $4, $5) ON CONFLICT DO NOTHING";
+const INSERT_TRANSACTION: &str =
+    "INSERT INTO transactions VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT DO NOTHING";
+const INSERT_RECEIPT: &str =
+    "INSERT INTO receipts VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT DO NOTHING";
+const INSERT_EXECUTION_OUTCOME: &str =
+    "INSERT INTO execution_outcomes VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT DO NOTHING";
+const INSERT_STATE_CHANGE: &str =
+    "INSERT INTO state_changes VALUES ($1, $2, $3) ON CONFLICT DO NOTHING";
+
+/// Stores every `StreamerMessage` in an SQLite database with [`SQL_SCHEMA`], a block per database
+/// transaction. The database is written on the blocking thread pool.
+pub struct SqliteSink {
+    connection: Arc<Mutex<Connection>>,
+}
+
+/// An insert statement with its parameters.
+type Row = (&'static str, Vec<Value>);
+
+impl SqliteSink {
+    /// Opens the database at `path`, creating it and the tables if needed.
+    pub fn open(path: &Path) -> Result<Self, SinkError> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SQL_SCHEMA)?;
+        Ok(Self { connection: Arc::new(Mutex::new(connection)) })
+    }
+
+    async fn store(&mut self, message: &StreamerMessage) -> Result<(), SinkError> {
+        let rows = rows(message)?;
+        let connection = self.connection.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection.lock().unwrap_or_else(|err| err.into_inner());
+            insert(&mut connection, &rows)
+        })
+        .await
+        .map_err(|err| SinkError::Other(format!("Failed to store the block: {}", err)))?
+    }
+}
+
+/// Returns the rows to insert for `message`.
+fn rows(message: &StreamerMessage) -> Result<Vec<Row>, SinkError> {
+    let mut rows: Vec<Row> = vec![];
+    let header = &message.block.header;
+    let height = header.height as i64;
+    rows.push((
+        INSERT_BLOCK,
+        vec![
+            height.into(),
+            header.hash.to_string().into(),
+            header.prev_hash.to_string().into(),
+            (header.timestamp_nanosec as i64).into(),
+            message.block.author.to_string().into(),
+        ],
+    ));
+    for shard in &message.shards {
+        let shard_id = shard.shard_id as i64;
+        if let Some(chunk) = &shard.chunk {
+            rows.push((
+                INSERT_CHUNK,
+                vec![
+                    chunk.header.chunk_hash.to_string().into(),
+                    height.into(),
+                    shard_id.into(),
+                    chunk.author.to_string().into(),
+                    (chunk.header.gas_used as i64).into(),
+                ],
+            ));
+            for transaction in &chunk.transactions {
+                let outcome = &transaction.outcome.execution_outcome;
+                let transaction = &transaction.transaction;
+                rows.push((
+                    INSERT_TRANSACTION,
+                    vec![
+                        transaction.hash.to_string().into(),
+                        height.into(),
+                        shard_id.into(),
+                        transaction.signer_id.to_string().into(),
+                        transaction.receiver_id.to_string().into(),
+                        serde_json::to_string(&transaction.actions)?.into(),
+                    ],
+                ));
+                rows.push(execution_outcome_row(outcome, height, shard_id)?);
+            }
+            for receipt in &chunk.receipts {
+                rows.push((
+                    INSERT_RECEIPT,
+                    vec![
+                        receipt.receipt_id.to_string().into(),
+                        height.into(),
+                        shard_id.into(),
+                        receipt.predecessor_id.to_string().into(),
+                        receipt.receiver_id.to_string().into(),
+                        serde_json::to_string(&receipt.receipt)?.into(),
+                    ],
+                ));
+            }
+        }
+        for outcome in &shard.receipt_execution_outcomes {
+            rows.push(execution_outcome_row(&outcome.execution_outcome, height, shard_id)?);
+        }
+    }
+    for (index, change) in message.state_changes.iter().enumerate() {
+        rows.push((
+            INSERT_STATE_CHANGE,
+            vec![height.into(), (index as i64).into(), serde_json::to_string(change)?.into()],
+        ));
+    }
+    Ok(rows)
+}
+
+fn execution_outcome_row(
+    outcome: &ExecutionOutcomeWithIdView,
+    height: i64,
+    shard_id: i64,
+) -> Result<Row, SinkError> {
+    Ok((
+        INSERT_EXECUTION_OUTCOME,
+        vec![
+            outcome.id.to_string().into(),
+            height.into(),
+            shard_id.into(),
+            outcome.outcome.executor_id.to_string().into(),
+            (outcome.outcome.gas_burnt as i64).into(),
+            serde_json::to_string(&outcome.outcome.status)?.into(),
+            serde_json::to_string(&outcome.outcome.logs)?.into(),
+        ],
+    ))
+}
+
+/// Inserts `rows` in a single database transaction.
+fn insert(connection: &mut Connection, rows: &[Row]) -> Result<(), SinkError> {
+    let db_transaction = connection.transaction()?;
+    for (statement, params) in rows {
+        db_transaction.execute(statement, params_from_iter(params))?;
+    }
+    db_transaction.commit()?;
+    Ok(())
+}
+
+impl Sink for SqliteSink {
+    fn deliver<'a>(
+        &'a mut self,
+        message: &'a StreamerMessage,
+    ) -> LocalBoxFuture<'a, Result<(), SinkError>> {
+        self.store(message).boxed_local()
+    }
+}
+
+impl From<rusqlite::Error> for SinkError {
+    fn from(err: rusqlite::Error) -> Self {
+        SinkError::Other(format!("SQLite error: {}", err))
+    }
+}
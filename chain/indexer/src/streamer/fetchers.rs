@@ -12,6 +12,7 @@ pub use near_primitives::{types, views};
 use super::errors::FailedToFetchData;
 use super::types::IndexerExecutionOutcomeWithOptionalReceipt;
 use super::INDEXER;
+use crate::IndexerFilter;
 
 pub(crate) async fn fetch_status(
     client: &Addr<near_client::ClientActor>,
@@ -67,6 +68,17 @@ pub(crate) async fn fetch_state_changes(
         .map_err(|err| FailedToFetchData::String(err.to_string()))
 }
 
+/// Fetches the state changes of the block, grouped by the shard of the changed account.
+pub(crate) async fn fetch_state_changes_by_shard(
+    client: &Addr<near_client::ViewClientActor>,
+    block_hash: CryptoHash,
+) -> Result<HashMap<types::ShardId, views::StateChangesView>, FailedToFetchData> {
+    client
+        .send(near_client::GetStateChangesWithCauseInBlockByShard { block_hash })
+        .await?
+        .map_err(|err| FailedToFetchData::String(err.to_string()))
+}
+
 /// Fetches single chunk (as `near_primitives::views::ChunkView`) by provided `near_client::GetChunk` enum
 async fn fetch_single_chunk(
     client: &Addr<near_client::ViewClientActor>,
@@ -77,9 +89,12 @@ async fn fetch_single_chunk(
 
 /// Fetch all ExecutionOutcomeWithId for current block
 /// Returns a HashMap where the key is shard id IndexerExecutionOutcomeWithOptionalReceipt
+/// Outcomes of shards not selected by `filter` are skipped, and receipts are fetched only for
+/// outcomes of selected executors.
 pub(crate) async fn fetch_outcomes(
     client: &Addr<near_client::ViewClientActor>,
     block_hash: CryptoHash,
+    filter: &IndexerFilter,
 ) -> Result<
     HashMap<near_primitives::types::ShardId, Vec<IndexerExecutionOutcomeWithOptionalReceipt>>,
     FailedToFetchData,
@@ -94,8 +109,18 @@ pub(crate) async fn fetch_outcomes(
         Vec<IndexerExecutionOutcomeWithOptionalReceipt>,
     > = HashMap::new();
     for (shard_id, shard_outcomes) in outcomes {
+        if !filter.matches_shard(shard_id) {
+            continue;
+        }
         let mut outcomes_with_receipts: Vec<IndexerExecutionOutcomeWithOptionalReceipt> = vec![];
         for outcome in shard_outcomes {
+            if !filter.matches_account(&outcome.outcome.executor_id) {
+                outcomes_with_receipts.push(IndexerExecutionOutcomeWithOptionalReceipt {
+                    execution_outcome: outcome,
+                    receipt: None,
+                });
+                continue;
+            }
             let receipt = match fetch_receipt_by_id(&client, outcome.id).await {
                 Ok(res) => res,
                 Err(e) => {
@@ -1,21 +1,24 @@
+use std::sync::Arc;
 use std::time::Duration;
 
 use actix::Addr;
 use async_recursion::async_recursion;
-use rocksdb::DB;
+use futures::{stream, StreamExt};
 use tokio::sync::mpsc;
 use tokio::time;
-use tracing::{debug, info};
+use tracing::{debug, error, info};
 
 use near_primitives::hash::CryptoHash;
+use near_primitives::types::BlockHeight;
 pub use near_primitives::views;
 
-use crate::{AwaitForNodeSyncedEnum, IndexerConfig};
+use crate::checkpoint::Checkpoint;
+use crate::{AwaitForNodeSyncedEnum, IndexerConfig, IndexerFilter, SyncModeEnum};
 
 use self::errors::FailedToFetchData;
 use self::fetchers::{
     fetch_block_by_hash, fetch_block_by_height, fetch_chunks, fetch_latest_block, fetch_outcomes,
-    fetch_state_changes, fetch_status,
+    fetch_state_changes, fetch_state_changes_by_shard, fetch_status,
 };
 pub use self::types::{
     IndexerChunkView, IndexerExecutionOutcomeWithOptionalReceipt,
@@ -42,29 +45,38 @@ const PROBLEMATIC_BLOKS: [&'static str; 2] = [
 /// This function supposed to return the entire `StreamerMessage`.
 /// It fetches the block and all related parts (chunks, outcomes, state changes etc.)
 /// and returns everything together in one struct
+/// Only the parts selected by `filter` are fetched and included.
 #[async_recursion]
 async fn build_streamer_message(
     client: &Addr<near_client::ViewClientActor>,
     block: views::BlockView,
+    filter: &IndexerFilter,
 ) -> Result<StreamerMessage, FailedToFetchData> {
     let chunks_to_fetch = block
         .chunks
         .iter()
         .filter_map(|c| {
-            if c.height_included == block.header.height {
+            if c.height_included == block.header.height && filter.matches_shard(c.shard_id) {
                 Some(c.chunk_hash)
             } else {
                 None
             }
         })
         .collect::<Vec<_>>();
+    // Outcomes and state changes appear only in blocks with new chunks, so there is nothing to
+    // fetch if none of the selected shards has a new chunk.
+    let skip_block_data = chunks_to_fetch.is_empty() && filter.shard_ids.is_some();
     let chunks = fetch_chunks(&client, chunks_to_fetch).await?;
 
     let protocol_config_view = fetch_protocol_config(&client, block.header.hash).await?;
     let num_shards = protocol_config_view.num_block_producer_seats_per_shard.len()
         as near_primitives::types::NumShards;
 
-    let mut shards_outcomes = fetch_outcomes(&client, block.header.hash).await?;
+    let mut shards_outcomes = if skip_block_data {
+        Default::default()
+    } else {
+        fetch_outcomes(&client, block.header.hash, filter).await?
+    };
     let mut indexer_shards: Vec<IndexerShard> = vec![];
 
     for shard_id in 0..num_shards {
@@ -85,6 +97,9 @@ async fn build_streamer_message(
 
         // Take execution outcomes for receipts from the vec and keep only the ones for transactions
         let mut receipt_outcomes = outcomes.split_off(transactions.len());
+        receipt_outcomes.retain(|outcome| {
+            filter.matches_account(&outcome.execution_outcome.outcome.executor_id)
+        });
 
         let indexer_transactions = transactions
             .into_iter()
@@ -108,6 +123,10 @@ async fn build_streamer_message(
             &block,
         )
         .await?;
+        let indexer_transactions = indexer_transactions
+            .into_iter()
+            .filter(|tx| filter.matches_transaction(&tx.transaction))
+            .collect::<Vec<IndexerTransactionWithOutcome>>();
 
         // Add local receipts to corresponding outcomes
         for receipt in &chunk_local_receipts {
@@ -156,8 +175,12 @@ async fn build_streamer_message(
                     prev_block_tried += 1;
                 }
             };
-            receipt_execution_outcomes
-                .push(IndexerExecutionOutcomeWithReceipt { execution_outcome, receipt: receipt });
+            if filter.matches_receipt(&receipt) {
+                receipt_execution_outcomes.push(IndexerExecutionOutcomeWithReceipt {
+                    execution_outcome,
+                    receipt: receipt,
+                });
+            }
         }
 
         // Blocks #47317863 and #47317864
@@ -188,6 +211,7 @@ async fn build_streamer_message(
         }
 
         chunk_receipts.extend(chunk_non_local_receipts);
+        chunk_receipts.retain(|receipt| filter.matches_receipt(receipt));
 
         indexer_shards[shard_id].receipt_execution_outcomes = receipt_execution_outcomes;
         // Put the chunk into corresponding indexer shard
@@ -204,14 +228,38 @@ async fn build_streamer_message(
     // That might happen before the fix https://github.com/near/nearcore/pull/4228
     for (shard_id, outcomes) in shards_outcomes {
         indexer_shards[shard_id as usize].receipt_execution_outcomes.extend(
-            outcomes.into_iter().map(|outcome| IndexerExecutionOutcomeWithReceipt {
-                execution_outcome: outcome.execution_outcome,
-                receipt: outcome.receipt.expect("`receipt` must be present at this moment"),
-            }),
+            outcomes
+                .into_iter()
+                .filter(|outcome| {
+                    filter.matches_account(&outcome.execution_outcome.outcome.executor_id)
+                })
+                .map(|outcome| IndexerExecutionOutcomeWithReceipt {
+                    execution_outcome: outcome.execution_outcome,
+                    receipt: outcome.receipt.expect("`receipt` must be present at this moment"),
+                })
+                .filter(|outcome| filter.matches_receipt(&outcome.receipt)),
         )
     }
-
-    let state_changes = fetch_state_changes(&client, block.header.hash).await?;
+    indexer_shards.retain(|shard| filter.matches_shard(shard.shard_id));
+
+    let state_changes = if skip_block_data {
+        vec![]
+    } else {
+        let mut state_changes = if filter.shard_ids.is_some() {
+            // Changes of the selected shards, in shard order.
+            let mut state_changes_by_shard =
+                fetch_state_changes_by_shard(&client, block.header.hash).await?;
+            (0..num_shards)
+                .filter(|&shard_id| filter.matches_shard(shard_id))
+                .filter_map(|shard_id| state_changes_by_shard.remove(&shard_id))
+                .flatten()
+                .collect()
+        } else {
+            fetch_state_changes(&client, block.header.hash).await?
+        };
+        state_changes.retain(|change| filter.matches_state_change(change));
+        state_changes
+    };
 
     Ok(StreamerMessage { block, shards: indexer_shards, state_changes })
 }
@@ -237,7 +285,8 @@ async fn find_local_receipt_by_id_in_block(
     let chunks = fetch_chunks(&client, chunks_to_fetch).await?;
     let protocol_config_view = fetch_protocol_config(&client, block.header.hash).await?;
 
-    let mut shards_outcomes = fetch_outcomes(&client, block.header.hash).await?;
+    let mut shards_outcomes =
+        fetch_outcomes(&client, block.header.hash, &IndexerFilter::default()).await?;
 
     for chunk in chunks {
         let views::ChunkView { header, transactions, .. } = chunk;
@@ -272,23 +321,57 @@ async fn find_local_receipt_by_id_in_block(
     Ok(None)
 }
 
+/// Fetches the block of the given height and builds the `StreamerMessage` for it.
+/// Returns `None` if there is no block of this height.
+async fn fetch_streamer_message(
+    client: &Addr<near_client::ViewClientActor>,
+    block_height: BlockHeight,
+    filter: &IndexerFilter,
+) -> Option<Result<StreamerMessage, FailedToFetchData>> {
+    let block = fetch_block_by_height(client, block_height).await.ok()?;
+    Some(build_streamer_message(client, block, filter).await)
+}
+
+/// Returns the height of the first block to stream in `sync_mode`, given the height of the last
+/// block in the checkpoint of `sync_mode`. The checkpointed block itself is streamed again, since
+/// it may not have been handled before an interruption.
+fn start_block_height(
+    sync_mode: &SyncModeEnum,
+    checkpointed_height: Option<BlockHeight>,
+    latest_block_height: BlockHeight,
+) -> BlockHeight {
+    match *sync_mode {
+        SyncModeEnum::FromInterruption => checkpointed_height.unwrap_or(latest_block_height),
+        SyncModeEnum::LatestSynced => latest_block_height,
+        SyncModeEnum::BlockHeight(height) => height,
+        SyncModeEnum::Backfill { start_height, .. } => {
+            checkpointed_height.map_or(start_height, |height| height.max(start_height))
+        }
+    }
+}
+
 /// Function that starts Streamer's busy loop. Every half a seconds it fetches the status
 /// compares to already fetched block height and in case it differs fetches new block of given height.
 ///
 /// We have to pass `client: Addr<near_client::ClientActor>` and `view_client: Addr<near_client::ViewClientActor>`.
+/// The `checkpoint` is only read to resume an interrupted `SyncModeEnum::FromInterruption` or
+/// `SyncModeEnum::Backfill`, it is up to the receiver of the messages to update it.
+///
+/// In `SyncModeEnum::Backfill` the blocks are built concurrently and the streamer stops once the
+/// last block of the range is sent.
 pub(crate) async fn start(
     view_client: Addr<near_client::ViewClientActor>,
     client: Addr<near_client::ClientActor>,
     indexer_config: IndexerConfig,
+    checkpoint: Arc<Checkpoint>,
     blocks_sink: mpsc::Sender<StreamerMessage>,
 ) {
     info!(target: INDEXER, "Starting Streamer...");
-    let mut indexer_db_path = nearcore::get_store_path(&indexer_config.home_dir);
-    indexer_db_path.push("indexer");
-
-    // TODO: implement proper error handling
-    let db = DB::open_default(indexer_db_path).unwrap();
-    let mut last_synced_block_height: Option<near_primitives::types::BlockHeight> = None;
+    let mut last_synced_block_height: Option<BlockHeight> = None;
+    let (end_block_height, workers) = match indexer_config.sync_mode {
+        SyncModeEnum::Backfill { end_height, workers, .. } => (Some(end_height), workers.max(1)),
+        _ => (None, 1),
+    };
 
     'main: loop {
         time::sleep(INTERVAL).await;
@@ -311,22 +394,27 @@ pub(crate) async fn start(
         };
 
         let latest_block_height = block.header.height;
-        let start_syncing_block_height = if let Some(last_synced_block_height) =
-            last_synced_block_height
-        {
-            last_synced_block_height + 1
-        } else {
-            match indexer_config.sync_mode {
-                crate::SyncModeEnum::FromInterruption => {
-                    match db.get(b"last_synced_block_height").unwrap() {
-                        Some(value) => String::from_utf8(value).unwrap().parse::<u64>().unwrap(),
-                        None => latest_block_height,
+        let start_syncing_block_height =
+            if let Some(last_synced_block_height) = last_synced_block_height {
+                last_synced_block_height + 1
+            } else {
+                match checkpoint.last_synced_block_height() {
+                    Ok(checkpointed_height) => start_block_height(
+                        &indexer_config.sync_mode,
+                        checkpointed_height,
+                        latest_block_height,
+                    ),
+                    Err(err) => {
+                        error!(
+                            target: INDEXER,
+                            "Failed to read the last synced block height, terminating: {}", err
+                        );
+                        break 'main;
                     }
                 }
-                crate::SyncModeEnum::LatestSynced => latest_block_height,
-                crate::SyncModeEnum::BlockHeight(height) => height,
-            }
-        };
+            };
+        let latest_block_height = end_block_height
+            .map_or(latest_block_height, |end_height| end_height.min(latest_block_height));
 
         debug!(
             target: INDEXER,
@@ -334,10 +422,18 @@ pub(crate) async fn start(
             start_syncing_block_height,
             latest_block_height
         );
-        for block_height in start_syncing_block_height..=latest_block_height {
-            if let Ok(block) = fetch_block_by_height(&view_client, block_height).await {
-                let response = build_streamer_message(&view_client, block).await;
-
+        // `buffered` polls up to `workers` futures at once, but yields their results in order.
+        let mut responses = stream::iter(start_syncing_block_height..=latest_block_height)
+            .map(|block_height| {
+                let view_client = &view_client;
+                let filter = &indexer_config.filter;
+                async move {
+                    (block_height, fetch_streamer_message(view_client, block_height, filter).await)
+                }
+            })
+            .buffered(workers);
+        while let Some((block_height, response)) = responses.next().await {
+            if let Some(response) = response {
                 match response {
                     Ok(streamer_message) => {
                         debug!(target: INDEXER, "{:#?}", &streamer_message);
@@ -358,8 +454,185 @@ pub(crate) async fn start(
                     }
                 }
             }
-            db.put(b"last_synced_block_height", &block_height.to_string()).unwrap();
             last_synced_block_height = Some(block_height);
         }
+        if end_block_height == Some(latest_block_height) {
+            info!(target: INDEXER, "Backfill up to block #{} is done", latest_block_height);
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use actix::System;
+
+    use near_actix_test_utils::run_actix;
+    use near_chain_configs::Genesis;
+    use near_crypto::{InMemorySigner, KeyType};
+    use near_network::test_utils::open_port;
+    use near_network::NetworkClientMessages;
+    use near_primitives::shard_layout::{account_id_to_shard_id, ShardLayout};
+    use near_primitives::transaction::SignedTransaction;
+    use near_primitives::types::{AccountId, ShardId};
+    use nearcore::config::GenesisExt;
+
+    use super::*;
+
+    fn shard_ids(shard_id: ShardId) -> Option<HashSet<ShardId>> {
+        Some(vec![shard_id].into_iter().collect())
+    }
+
+    /// Sends money between accounts of different shards and checks the messages built with
+    /// shard and account filters against the unfiltered ones, until the money is received.
+    async fn check_filters(
+        view_client: Addr<near_client::ViewClientActor>,
+        client: Addr<near_client::ClientActor>,
+        shard_layout: ShardLayout,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+    ) {
+        let mut block = fetch_latest_block(&view_client).await;
+        while !matches!(&block, Ok(block) if block.header.height >= 2) {
+            time::sleep(INTERVAL).await;
+            block = fetch_latest_block(&view_client).await;
+        }
+        let block = block.unwrap();
+        let signer =
+            InMemorySigner::from_seed(sender_id.clone(), KeyType::ED25519, sender_id.as_ref());
+        let transaction = SignedTransaction::send_money(
+            1,
+            sender_id.clone(),
+            receiver_id.clone(),
+            &signer,
+            1,
+            block.header.hash,
+        );
+        let tx_hash = transaction.get_hash();
+        client
+            .send(NetworkClientMessages::Transaction {
+                transaction,
+                is_forwarded: false,
+                check_only: false,
+            })
+            .await
+            .unwrap();
+
+        let sender_shard_id = account_id_to_shard_id(&sender_id, &shard_layout);
+        let mut tx_seen = false;
+        let mut height = block.header.height + 1;
+        loop {
+            let block = match fetch_block_by_height(&view_client, height).await {
+                Ok(block) => block,
+                Err(_) => {
+                    time::sleep(INTERVAL).await;
+                    continue;
+                }
+            };
+            height += 1;
+            let message =
+                build_streamer_message(&view_client, block.clone(), &IndexerFilter::default())
+                    .await
+                    .unwrap();
+
+            for shard_id in 0..shard_layout.num_shards() {
+                let filter = IndexerFilter { shard_ids: shard_ids(shard_id), ..Default::default() };
+                let filtered =
+                    build_streamer_message(&view_client, block.clone(), &filter).await.unwrap();
+                assert!(filtered.shards.iter().all(|shard| shard.shard_id == shard_id));
+                let expected: Vec<_> = message
+                    .state_changes
+                    .iter()
+                    .filter(|change| {
+                        account_id_to_shard_id(change.value.affected_account_id(), &shard_layout)
+                            == shard_id
+                    })
+                    .map(|change| serde_json::to_value(change).unwrap())
+                    .collect();
+                let actual: Vec<_> = filtered
+                    .state_changes
+                    .iter()
+                    .map(|change| serde_json::to_value(change).unwrap())
+                    .collect();
+                assert_eq!(actual, expected);
+                let has_tx = filtered.shards.iter().any(|shard| {
+                    shard.chunk.iter().any(|chunk| {
+                        chunk.transactions.iter().any(|tx| tx.transaction.hash == tx_hash)
+                    })
+                });
+                assert!(!has_tx || shard_id == sender_shard_id);
+                tx_seen |= has_tx;
+            }
+
+            let filter = IndexerFilter {
+                accounts: Some(vec![receiver_id.to_string()]),
+                ..Default::default()
+            };
+            let filtered = build_streamer_message(&view_client, block, &filter).await.unwrap();
+            assert!(filtered
+                .state_changes
+                .iter()
+                .all(|change| change.value.affected_account_id() == &receiver_id));
+            for shard in &filtered.shards {
+                for tx in shard.chunk.iter().flat_map(|chunk| chunk.transactions.iter()) {
+                    assert_eq!(tx.transaction.receiver_id, receiver_id);
+                }
+            }
+
+            let received = filtered.state_changes.iter().any(|change| {
+                matches!(change.cause, views::StateChangeCauseView::ReceiptProcessing { .. })
+            });
+            if received {
+                assert!(tx_seen);
+                return;
+            }
+        }
+    }
+
+    #[test]
+    fn test_start_block_height() {
+        let backfill = SyncModeEnum::Backfill { start_height: 10, end_height: 20, workers: 4 };
+        assert_eq!(start_block_height(&backfill, None, 100), 10);
+        assert_eq!(start_block_height(&backfill, Some(15), 100), 15);
+        assert_eq!(start_block_height(&SyncModeEnum::FromInterruption, None, 100), 100);
+        assert_eq!(start_block_height(&SyncModeEnum::FromInterruption, Some(15), 100), 15);
+        assert_eq!(start_block_height(&SyncModeEnum::LatestSynced, Some(15), 100), 100);
+        assert_eq!(start_block_height(&SyncModeEnum::BlockHeight(5), Some(15), 100), 5);
+    }
+
+    #[test]
+    fn test_filters() {
+        let accounts: Vec<AccountId> =
+            (0..10).map(|i| format!("test{}", i).parse().unwrap()).collect();
+        let genesis = Genesis::test_sharded(accounts.clone(), 1, vec![1, 1]);
+        let shard_layout = genesis.config.shard_layout.clone();
+        let sender_id = accounts[0].clone();
+        let sender_shard_id = account_id_to_shard_id(&sender_id, &shard_layout);
+        let receiver_id = accounts
+            .iter()
+            .find(|account_id| account_id_to_shard_id(account_id, &shard_layout) != sender_shard_id)
+            .expect("Test accounts should be in both shards")
+            .clone();
+        let mut near_config = nearcore::load_test_config("test0", open_port(), genesis);
+        near_config.client_config.min_num_peers = 0;
+        near_config.client_config.tracked_shards = vec![0, 1];
+        let dir = tempfile::Builder::new().prefix("indexer_filters").tempdir().unwrap();
+        let home_dir = dir.path().to_path_buf();
+
+        run_actix(async move {
+            let nearcore::NearNode { client, view_client, .. } =
+                nearcore::start_with_config(&home_dir, near_config);
+            actix::spawn(async move {
+                time::timeout(
+                    Duration::from_secs(60),
+                    check_filters(view_client, client, shard_layout, sender_id, receiver_id),
+                )
+                .await
+                .expect("Timed out waiting for the transfer");
+                System::current().stop();
+            });
+        });
     }
 }
@@ -38,6 +38,7 @@ pub enum RpcErrorKind {
 pub enum RpcRequestValidationErrorKind {
     MethodNotFound { method_name: String },
     ParseError { error_message: String },
+    InvalidRequest { error_message: String },
 }
 
 /// A general Server Error
@@ -94,6 +95,27 @@ impl RpcError {
         }
     }
 
+    /// Create an Invalid Request error, for messages that are valid JSON but can't be served.
+    pub fn invalid_request(e: String) -> Self {
+        RpcError {
+            code: -32_600,
+            message: "Invalid Request".to_owned(),
+            data: Some(Value::String(e.clone())),
+            error_struct: Some(RpcErrorKind::RequestValidationError(
+                RpcRequestValidationErrorKind::InvalidRequest { error_message: e },
+            )),
+        }
+    }
+
+    /// Create an error for a request rejected because the node is already serving as many
+    /// requests of this kind as it is configured to.
+    pub fn too_many_requests(kind: String) -> Self {
+        RpcError::new_internal_error(
+            Some(Value::String(kind.clone())),
+            format!("Too many concurrent `{}` requests. Try again later.", kind),
+        )
+    }
+
     pub fn serialization_error(e: String) -> Self {
         RpcError::new_internal_error(Some(Value::String(e.clone())), e)
     }
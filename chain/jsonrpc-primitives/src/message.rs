@@ -147,6 +147,18 @@ pub struct Notification {
     pub params: Option<Value>,
 }
 
+impl Notification {
+    /// Turns the notification into a request with an auto-generated ID, to serve it like one.
+    pub fn into_request(self) -> Request {
+        Request {
+            jsonrpc: Version,
+            method: self.method,
+            params: self.params,
+            id: Value::String(Uuid::new_v4().to_hyphenated().to_string()),
+        }
+    }
+}
+
 /// One message of the JSON RPC protocol.
 ///
 /// One message, directly mapped from the structures of the protocol. See the
@@ -80,6 +80,7 @@ pub enum QueryResponseKind {
     CallResult(near_primitives::views::CallResult),
     AccessKey(near_primitives::views::AccessKeyView),
     AccessKeyList(near_primitives::views::AccessKeyList),
+    ViewStatePaginated(near_primitives::views::ViewStatePageResult),
 }
 
 impl RpcQueryRequest {
@@ -245,6 +246,9 @@ impl From<near_primitives::views::QueryResponseKind> for QueryResponseKind {
             near_primitives::views::QueryResponseKind::AccessKeyList(access_key_list) => {
                 Self::AccessKeyList(access_key_list)
             }
+            near_primitives::views::QueryResponseKind::ViewStatePaginated(
+                view_state_page_result,
+            ) => Self::ViewStatePaginated(view_state_page_result),
         }
     }
 }
@@ -1,4 +1,5 @@
 use near_primitives::state_record::StateRecord;
+use near_primitives::types::BlockHeightDelta;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -43,3 +44,168 @@ impl From<RpcSandboxPatchStateError> for crate::errors::RpcError {
         Self::new_internal_or_handler_error(Some(error_data.clone()), error_data)
     }
 }
+
+#[derive(Deserialize, Serialize)]
+pub struct RpcSandboxFastForwardRequest {
+    pub delta_height: BlockHeightDelta,
+}
+
+impl RpcSandboxFastForwardRequest {
+    pub fn parse(value: Option<Value>) -> Result<Self, crate::errors::RpcParseError> {
+        Ok(crate::utils::parse_params::<RpcSandboxFastForwardRequest>(value)?)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RpcSandboxFastForwardResponse {}
+
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcSandboxFastForwardError {
+    #[error("No block was produced in time, {blocks_left} blocks are left to produce")]
+    TimeoutError { blocks_left: BlockHeightDelta },
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<actix::MailboxError> for RpcSandboxFastForwardError {
+    fn from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl From<RpcSandboxFastForwardError> for crate::errors::RpcError {
+    fn from(error: RpcSandboxFastForwardError) -> Self {
+        let error_data = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcSandboxFastForwardError: {:?}", err),
+                )
+            }
+        };
+        Self::new_internal_or_handler_error(Some(error_data.clone()), error_data)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RpcSandboxShiftTimeRequest {
+    pub delta_nanos: u64,
+}
+
+impl RpcSandboxShiftTimeRequest {
+    pub fn parse(value: Option<Value>) -> Result<Self, crate::errors::RpcParseError> {
+        Ok(crate::utils::parse_params::<RpcSandboxShiftTimeRequest>(value)?)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RpcSandboxShiftTimeResponse {}
+
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcSandboxShiftTimeError {
+    #[error("Invalid time shift: {error_message}")]
+    InvalidTimeShift { error_message: String },
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<actix::MailboxError> for RpcSandboxShiftTimeError {
+    fn from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl From<RpcSandboxShiftTimeError> for crate::errors::RpcError {
+    fn from(error: RpcSandboxShiftTimeError) -> Self {
+        let error_data = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcSandboxShiftTimeError: {:?}", err),
+                )
+            }
+        };
+        Self::new_internal_or_handler_error(Some(error_data.clone()), error_data)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RpcSandboxSnapshotResponse {
+    pub snapshot_id: u64,
+}
+
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcSandboxSnapshotError {
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<actix::MailboxError> for RpcSandboxSnapshotError {
+    fn from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl From<RpcSandboxSnapshotError> for crate::errors::RpcError {
+    fn from(error: RpcSandboxSnapshotError) -> Self {
+        let error_data = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcSandboxSnapshotError: {:?}", err),
+                )
+            }
+        };
+        Self::new_internal_or_handler_error(Some(error_data.clone()), error_data)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RpcSandboxRestoreRequest {
+    pub snapshot_id: u64,
+}
+
+impl RpcSandboxRestoreRequest {
+    pub fn parse(value: Option<Value>) -> Result<Self, crate::errors::RpcParseError> {
+        Ok(crate::utils::parse_params::<RpcSandboxRestoreRequest>(value)?)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RpcSandboxRestoreResponse {}
+
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcSandboxRestoreError {
+    #[error("Snapshot {snapshot_id} doesn't exist")]
+    UnknownSnapshot { snapshot_id: u64 },
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<actix::MailboxError> for RpcSandboxRestoreError {
+    fn from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl From<RpcSandboxRestoreError> for crate::errors::RpcError {
+    fn from(error: RpcSandboxRestoreError) -> Self {
+        let error_data = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcSandboxRestoreError: {:?}", err),
+                )
+            }
+        };
+        Self::new_internal_or_handler_error(Some(error_data.clone()), error_data)
+    }
+}
@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Events a WebSocket connection can subscribe to with the `subscribe` method.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "subscription", rename_all = "snake_case")]
+pub enum RpcSubscriptionKind {
+    /// Every new final block.
+    FinalBlocks,
+    /// Chunks of the shard included in new final blocks.
+    Chunks { shard_id: near_primitives::types::ShardId },
+    /// Final execution outcome of the transaction. The subscription is removed once the
+    /// notification is sent.
+    TxStatus {
+        tx_hash: near_primitives::hash::CryptoHash,
+        sender_account_id: near_primitives::types::AccountId,
+    },
+    /// Non-empty state changes matching the request in new final blocks.
+    StateChanges { changes: near_primitives::views::StateChangesRequestView },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcSubscribeRequest {
+    #[serde(flatten)]
+    pub kind: RpcSubscriptionKind,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcSubscribeResponse {
+    pub subscription_id: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcUnsubscribeRequest {
+    pub subscription_id: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcUnsubscribeResponse {
+    /// Whether the subscription existed.
+    pub removed: bool,
+}
+
+/// Params of the `subscription` notification sent for every event.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcSubscriptionNotification {
+    pub subscription_id: u64,
+    pub result: Value,
+}
+
+impl RpcSubscribeRequest {
+    pub fn parse(value: Option<Value>) -> Result<Self, crate::errors::RpcParseError> {
+        Ok(crate::utils::parse_params::<Self>(value)?)
+    }
+}
+
+impl RpcUnsubscribeRequest {
+    pub fn parse(value: Option<Value>) -> Result<Self, crate::errors::RpcParseError> {
+        Ok(crate::utils::parse_params::<Self>(value)?)
+    }
+}
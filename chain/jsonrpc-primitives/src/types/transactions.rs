@@ -40,6 +40,8 @@ pub enum RpcTransactionError {
     InternalError { debug_info: String },
     #[error("Timeout")]
     TimeoutError,
+    #[error("The transaction pool is full. Try again later")]
+    TransactionPoolFull,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -53,6 +55,12 @@ pub struct RpcBroadcastTxSyncResponse {
     pub transaction_hash: near_primitives::hash::CryptoHash,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcSimulateTransactionResponse {
+    #[serde(flatten)]
+    pub simulated_transaction: near_primitives::views::SimulatedTransactionView,
+}
+
 impl RpcBroadcastTransactionRequest {
     pub fn parse(value: Option<Value>) -> Result<Self, crate::errors::RpcParseError> {
         let signed_transaction = crate::utils::parse_signed_transaction(value)?;
@@ -97,6 +105,32 @@ impl From<near_client_primitives::types::TxStatusError> for RpcTransactionError
     }
 }
 
+impl From<near_client_primitives::types::SimulateTransactionError> for RpcTransactionError {
+    fn from(error: near_client_primitives::types::SimulateTransactionError) -> Self {
+        match error {
+            near_client_primitives::types::SimulateTransactionError::IOError(debug_info) => {
+                Self::InternalError { debug_info }
+            }
+            near_client_primitives::types::SimulateTransactionError::InvalidTransaction(
+                context,
+            ) => Self::InvalidTransaction { context },
+            near_client_primitives::types::SimulateTransactionError::DoesNotTrackShard => {
+                Self::DoesNotTrackShard
+            }
+            near_client_primitives::types::SimulateTransactionError::Unreachable(
+                ref error_message,
+            ) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", &error_message);
+                near_metrics::inc_counter_vec(
+                    &crate::metrics::RPC_UNREACHABLE_ERROR_COUNT,
+                    &["RpcTransactionError"],
+                );
+                Self::InternalError { debug_info: error.to_string() }
+            }
+        }
+    }
+}
+
 impl From<near_primitives::views::FinalExecutionOutcomeViewEnum> for RpcTransactionResponse {
     fn from(
         final_execution_outcome: near_primitives::views::FinalExecutionOutcomeViewEnum,
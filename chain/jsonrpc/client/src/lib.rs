@@ -194,6 +194,8 @@ jsonrpc_client!(pub struct JsonRpcClient {
     pub fn EXPERIMENTAL_broadcast_tx_sync(&self, tx: String) -> RpcRequest<serde_json::Value>;
     #[allow(non_snake_case)]
     pub fn EXPERIMENTAL_tx_status(&self, tx: String) -> RpcRequest<serde_json::Value>;
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_simulate_tx(&self, tx: String) -> RpcRequest<serde_json::Value>;
     pub fn health(&self) -> RpcRequest<()>;
     pub fn tx(&self, hash: String, account_id: AccountId) -> RpcRequest<FinalExecutionOutcomeView>;
     pub fn chunk(&self, id: ChunkId) -> RpcRequest<ChunkView>;
@@ -227,6 +229,23 @@ impl JsonRpcClient {
         call_method(&self.client, &self.server_addr, "block", request)
     }
 
+    pub fn next_light_client_block(
+        &self,
+        request: near_jsonrpc_primitives::types::light_client::RpcLightClientNextBlockRequest,
+    ) -> RpcRequest<near_jsonrpc_primitives::types::light_client::RpcLightClientNextBlockResponse>
+    {
+        call_method(&self.client, &self.server_addr, "next_light_client_block", request)
+    }
+
+    pub fn light_client_proof(
+        &self,
+        request: near_jsonrpc_primitives::types::light_client::RpcLightClientExecutionProofRequest,
+    ) -> RpcRequest<
+        near_jsonrpc_primitives::types::light_client::RpcLightClientExecutionProofResponse,
+    > {
+        call_method(&self.client, &self.server_addr, "light_client_proof", request)
+    }
+
     #[allow(non_snake_case)]
     pub fn EXPERIMENTAL_changes(
         &self,
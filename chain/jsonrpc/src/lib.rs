@@ -1,4 +1,6 @@
-use std::time::Duration;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use actix::Addr;
 use actix_cors::Cors;
@@ -8,6 +10,7 @@ use futures::FutureExt;
 use prometheus;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use tokio::sync::{Semaphore, SemaphorePermit};
 use tokio::time::{sleep, timeout};
 use tracing::info;
 
@@ -15,8 +18,8 @@ use near_chain_configs::GenesisConfig;
 use near_client::{
     ClientActor, GetBlock, GetBlockProof, GetChunk, GetExecutionOutcome, GetGasPrice,
     GetNetworkInfo, GetNextLightClientBlock, GetProtocolConfig, GetReceipt, GetStateChanges,
-    GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered, Query, Status, TxStatus,
-    TxStatusError, ViewClientActor,
+    GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered, Query, SimulateTransaction,
+    Status, TxStatus, TxStatusError, ViewClientActor,
 };
 #[cfg(feature = "test_features")]
 use near_jsonrpc_adversarial_primitives::SetAdvOptionsRequest;
@@ -60,6 +63,7 @@ use near_primitives::types::AccountId;
 use near_primitives::views::FinalExecutionOutcomeViewEnum;
 
 mod metrics;
+mod subscriptions;
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub struct RpcPollingConfig {
@@ -77,14 +81,134 @@ impl Default for RpcPollingConfig {
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
 pub struct RpcLimitsConfig {
     /// Maximum byte size of the json payload.
     pub json_payload_max_size: usize,
+    /// Maximum number of requests in a batch.
+    pub max_batch_size: usize,
+    /// Maximum number of requests served at the same time, by method. Requests over the limit are
+    /// rejected right away. `query` requests can also be limited by request type, with keys like
+    /// `query.call_function`. A batch takes at most one slot of each limit and serves its
+    /// requests under that limit one at a time.
+    pub max_concurrent_requests: HashMap<String, usize>,
+    /// Maximum number of requests per second, by method, with the same keys as
+    /// `max_concurrent_requests`. Bursts of up to a second worth of requests are allowed.
+    /// Requests over the limit are rejected right away.
+    pub max_requests_per_second: HashMap<String, u32>,
 }
 
 impl Default for RpcLimitsConfig {
     fn default() -> Self {
-        Self { json_payload_max_size: 10 * 1024 * 1024 }
+        Self {
+            json_payload_max_size: 10 * 1024 * 1024,
+            max_batch_size: 100,
+            max_concurrent_requests: vec![("query.call_function".to_owned(), 32)]
+                .into_iter()
+                .collect(),
+            max_requests_per_second: HashMap::new(),
+        }
+    }
+}
+
+/// Returns the keys of the limits that apply to `request`: its method and, for `query`, its
+/// request type.
+fn limit_keys(request: &Request) -> Vec<String> {
+    let mut keys = vec![request.method.clone()];
+    if request.method == "query" {
+        let request_type = request
+            .params
+            .as_ref()
+            .and_then(|params| params.get("request_type"))
+            .and_then(Value::as_str);
+        if let Some(request_type) = request_type {
+            keys.push(format!("query.{}", request_type));
+        }
+    }
+    keys
+}
+
+/// A token bucket refilled at `rate` tokens per second, holding at most `rate` tokens.
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u32) -> Self {
+        Self { rate: rate as f64, tokens: rate as f64, last_refill: Instant::now() }
+    }
+
+    fn try_take(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.last_refill = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+/// The concurrency slots held by a batch, by limit key, with the semaphore its requests under that
+/// limit take turns on. A slot that couldn't be taken holds the error to reply with instead.
+type BatchSlots<'a> = HashMap<String, Result<(Vec<SemaphorePermit<'a>>, Semaphore), RpcError>>;
+
+/// Limits of concurrent requests and of request rates shared by all HTTP workers.
+struct RequestLimits {
+    concurrency: HashMap<String, Semaphore>,
+    rate: HashMap<String, Mutex<TokenBucket>>,
+}
+
+impl RequestLimits {
+    fn new(config: &RpcLimitsConfig) -> Self {
+        Self {
+            concurrency: config
+                .max_concurrent_requests
+                .iter()
+                .map(|(key, limit)| (key.clone(), Semaphore::new(*limit)))
+                .collect(),
+            rate: config
+                .max_requests_per_second
+                .iter()
+                .map(|(key, rate)| (key.clone(), Mutex::new(TokenBucket::new(*rate))))
+                .collect(),
+        }
+    }
+
+    /// Counts a request against the rate limits of `keys`. Fails if any of them is exceeded.
+    fn check_rate(&self, keys: &[String]) -> Result<(), RpcError> {
+        for key in keys {
+            if let Some(bucket) = self.rate.get(key) {
+                if !bucket.lock().unwrap_or_else(|err| err.into_inner()).try_take() {
+                    near_metrics::inc_counter_vec(&metrics::RPC_REJECTED_REQUESTS, &[key.as_str()]);
+                    return Err(RpcError::too_many_requests(key.clone()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns whether `key` has a concurrency limit.
+    fn is_concurrency_limited(&self, key: &str) -> bool {
+        self.concurrency.contains_key(key)
+    }
+
+    /// Takes a slot from every concurrency limit of `keys`, which is held until the permits are
+    /// dropped. Fails if any of these limits is reached.
+    fn try_acquire(&self, keys: &[String]) -> Result<Vec<SemaphorePermit<'_>>, RpcError> {
+        keys.iter()
+            .filter_map(|key| self.concurrency.get(key).map(|semaphore| (key, semaphore)))
+            .map(|(key, semaphore)| {
+                semaphore.try_acquire().map_err(|_| {
+                    near_metrics::inc_counter_vec(&metrics::RPC_REJECTED_REQUESTS, &[key.as_str()]);
+                    RpcError::too_many_requests(key.clone())
+                })
+            })
+            .collect()
     }
 }
 
@@ -145,6 +269,7 @@ impl near_jsonrpc_primitives::types::transactions::RpcTransactionError {
         match responses {
             NetworkClientResponses::InvalidTx(context) => Self::InvalidTransaction { context },
             NetworkClientResponses::NoResponse => Self::TimeoutError,
+            NetworkClientResponses::TransactionPoolFull => Self::TransactionPoolFull,
             NetworkClientResponses::DoesNotTrackShard | NetworkClientResponses::RequestRouted => {
                 Self::DoesNotTrackShard
             }
@@ -226,6 +351,8 @@ struct JsonRpcHandler {
     view_client_addr: Addr<ViewClientActor>,
     polling_config: RpcPollingConfig,
     genesis_config: GenesisConfig,
+    max_batch_size: usize,
+    request_limits: Arc<RequestLimits>,
     #[cfg(feature = "test_features")]
     peer_manager_addr: Addr<PeerManagerActor>,
     #[cfg(feature = "test_features")]
@@ -233,20 +360,113 @@ struct JsonRpcHandler {
 }
 
 impl JsonRpcHandler {
-    pub async fn process(&self, message: Message) -> Result<Message, HttpError> {
+    /// Processes a message and returns the reply, if any: a batch made only of notifications
+    /// gets none.
+    pub async fn process(&self, message: Message) -> Result<Option<Message>, HttpError> {
         let id = message.id();
         match message {
             Message::Request(request) => {
-                Ok(Message::response(id, self.process_request(request).await))
+                Ok(Some(Message::response(id, self.process_request(request).await)))
             }
-            _ => Ok(Message::error(RpcError::parse_error(
+            Message::Batch(messages) => Ok(self.process_batch(messages).await),
+            _ => Ok(Some(Message::error(RpcError::parse_error(
                 "JSON RPC Request format was expected".to_owned(),
-            ))),
+            )))),
+        }
+    }
+
+    /// Processes the requests and notifications of a batch concurrently and replies to each
+    /// request, in order.
+    ///
+    /// The batch takes a single slot of each concurrency limit that applies to its entries, and
+    /// serves the entries under the same limit one at a time, so that a batch never competes with
+    /// itself for slots.
+    async fn process_batch(&self, messages: Vec<Message>) -> Option<Message> {
+        if messages.is_empty() {
+            return Some(Message::error(RpcError::invalid_request("Batch is empty".to_owned())));
         }
+        if messages.len() > self.max_batch_size {
+            return Some(Message::error(RpcError::invalid_request(format!(
+                "Batch has {} requests, but at most {} are allowed",
+                messages.len(),
+                self.max_batch_size
+            ))));
+        }
+        let entries: Vec<_> = messages
+            .into_iter()
+            .map(|message| match message {
+                Message::Request(request) => Ok((Some(request.id.clone()), request)),
+                Message::Notification(notification) => Ok((None, notification.into_request())),
+                _ => Err(RpcError::invalid_request(
+                    "Batch entries must be requests or notifications".to_owned(),
+                )),
+            })
+            .collect();
+        let keys: HashSet<String> = entries
+            .iter()
+            .filter_map(|entry| entry.as_ref().ok())
+            .flat_map(|(_, request)| limit_keys(request))
+            .filter(|key| self.request_limits.is_concurrency_limited(key))
+            .collect();
+        let batch_slots: BatchSlots<'_> = keys
+            .into_iter()
+            .map(|key| {
+                let slot = self
+                    .request_limits
+                    .try_acquire(std::slice::from_ref(&key))
+                    .map(|permits| (permits, Semaphore::new(1)));
+                (key, slot)
+            })
+            .collect();
+        let batch_slots = &batch_slots;
+        let responses = entries.into_iter().map(|entry| async move {
+            match entry {
+                Ok((id, request)) => {
+                    let result = self.process_batch_request(request, batch_slots).await;
+                    id.map(|id| Message::response(id, result))
+                }
+                Err(err) => Some(Message::error(err)),
+            }
+        });
+        let responses: Vec<Message> =
+            futures::future::join_all(responses).await.into_iter().flatten().collect();
+        if responses.is_empty() {
+            None
+        } else {
+            Some(Message::Batch(responses))
+        }
+    }
+
+    /// Processes a request of a batch under the concurrency slots held by the batch.
+    async fn process_batch_request(
+        &self,
+        request: Request,
+        batch_slots: &BatchSlots<'_>,
+    ) -> Result<Value, RpcError> {
+        near_metrics::inc_counter_vec(&metrics::HTTP_RPC_REQUEST_COUNT, &[request.method.as_ref()]);
+        let keys = limit_keys(&request);
+        self.request_limits.check_rate(&keys)?;
+        let mut _permits = Vec::new();
+        for key in &keys {
+            if let Some(slot) = batch_slots.get(key) {
+                let (_, semaphore) = slot.as_ref().map_err(Clone::clone)?;
+                _permits.push(semaphore.acquire().await.map_err(|_| {
+                    RpcError::new_internal_error(None, "Batch limits were closed".to_owned())
+                })?);
+            }
+        }
+        self.serve_request(request).await
     }
 
     async fn process_request(&self, request: Request) -> Result<Value, RpcError> {
         near_metrics::inc_counter_vec(&metrics::HTTP_RPC_REQUEST_COUNT, &[request.method.as_ref()]);
+        let keys = limit_keys(&request);
+        self.request_limits.check_rate(&keys)?;
+        let _permits = self.request_limits.try_acquire(&keys)?;
+        self.serve_request(request).await
+    }
+
+    async fn serve_request(&self, request: Request) -> Result<Value, RpcError> {
         let _rpc_processing_time = near_metrics::start_timer_vec(
             &metrics::RPC_PROCESSING_TIME,
             &[request.method.as_ref()],
@@ -522,6 +742,15 @@ impl JsonRpcHandler {
                 serde_json::to_value(receipt)
                     .map_err(|err| RpcError::serialization_error(err.to_string()))
             }
+            "EXPERIMENTAL_simulate_tx" => {
+                let rpc_transaction_request =
+                    near_jsonrpc_primitives::types::transactions::RpcBroadcastTransactionRequest::parse(
+                        request.params,
+                    )?;
+                let simulate_tx_response = self.simulate_tx(rpc_transaction_request).await?;
+                serde_json::to_value(simulate_tx_response)
+                    .map_err(|err| RpcError::serialization_error(err.to_string()))
+            }
             "EXPERIMENTAL_tx_status" => {
                 let rpc_transaction_status_common_request = near_jsonrpc_primitives::types::transactions::RpcTransactionStatusCommonRequest::parse(request.params)?;
                 let rpc_transaction_response =
@@ -549,6 +778,45 @@ impl JsonRpcHandler {
                 serde_json::to_value(sandbox_patch_state_response)
                     .map_err(|err| RpcError::serialization_error(err.to_string()))
             }
+            #[cfg(feature = "sandbox")]
+            "sandbox_fast_forward" => {
+                let sandbox_fast_forward_request =
+                    near_jsonrpc_primitives::types::sandbox::RpcSandboxFastForwardRequest::parse(
+                        request.params,
+                    )?;
+                let sandbox_fast_forward_response =
+                    self.sandbox_fast_forward(sandbox_fast_forward_request).await?;
+                serde_json::to_value(sandbox_fast_forward_response)
+                    .map_err(|err| RpcError::serialization_error(err.to_string()))
+            }
+            #[cfg(feature = "sandbox")]
+            "sandbox_shift_time" => {
+                let sandbox_shift_time_request =
+                    near_jsonrpc_primitives::types::sandbox::RpcSandboxShiftTimeRequest::parse(
+                        request.params,
+                    )?;
+                let sandbox_shift_time_response =
+                    self.sandbox_shift_time(sandbox_shift_time_request).await?;
+                serde_json::to_value(sandbox_shift_time_response)
+                    .map_err(|err| RpcError::serialization_error(err.to_string()))
+            }
+            #[cfg(feature = "sandbox")]
+            "sandbox_snapshot" => {
+                let sandbox_snapshot_response = self.sandbox_snapshot().await?;
+                serde_json::to_value(sandbox_snapshot_response)
+                    .map_err(|err| RpcError::serialization_error(err.to_string()))
+            }
+            #[cfg(feature = "sandbox")]
+            "sandbox_restore" => {
+                let sandbox_restore_request =
+                    near_jsonrpc_primitives::types::sandbox::RpcSandboxRestoreRequest::parse(
+                        request.params,
+                    )?;
+                let sandbox_restore_response =
+                    self.sandbox_restore(sandbox_restore_request).await?;
+                serde_json::to_value(sandbox_restore_response)
+                    .map_err(|err| RpcError::serialization_error(err.to_string()))
+            }
             _ => Err(RpcError::method_not_found(request.method.clone())),
         };
 
@@ -800,6 +1068,22 @@ impl JsonRpcHandler {
         }
     }
 
+    async fn simulate_tx(
+        &self,
+        request_data: near_jsonrpc_primitives::types::transactions::RpcBroadcastTransactionRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::transactions::RpcSimulateTransactionResponse,
+        near_jsonrpc_primitives::types::transactions::RpcTransactionError,
+    > {
+        let simulated_transaction = self
+            .view_client_addr
+            .send(SimulateTransaction { signed_transaction: request_data.signed_transaction })
+            .await??;
+        Ok(near_jsonrpc_primitives::types::transactions::RpcSimulateTransactionResponse {
+            simulated_transaction,
+        })
+    }
+
     async fn send_tx_commit(
         &self,
         request_data: near_jsonrpc_primitives::types::transactions::RpcBroadcastTransactionRequest,
@@ -1127,6 +1411,150 @@ impl JsonRpcHandler {
 
         Ok(near_jsonrpc_primitives::types::sandbox::RpcSandboxPatchStateResponse {})
     }
+
+    async fn sandbox_fast_forward(
+        &self,
+        fast_forward_request: near_jsonrpc_primitives::types::sandbox::RpcSandboxFastForwardRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxFastForwardResponse,
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxFastForwardError,
+    > {
+        self.client_addr
+            .send(NetworkClientMessages::Sandbox(NetworkSandboxMessage::SandboxFastForward(
+                fast_forward_request.delta_height,
+            )))
+            .await?;
+
+        // Producing a lot of blocks takes a while, so the polling timeout applies to every block
+        // rather than to the whole fast forward.
+        let mut blocks_left = fast_forward_request.delta_height;
+        let mut last_progress = Instant::now();
+        loop {
+            let fast_forward_status = self
+                .client_addr
+                .send(NetworkClientMessages::Sandbox(
+                    NetworkSandboxMessage::SandboxFastForwardStatus {},
+                ))
+                .await?;
+            match fast_forward_status {
+                NetworkClientResponses::SandboxResult(
+                    SandboxResponse::SandboxFastForwardBlocksLeft(0),
+                ) => break,
+                NetworkClientResponses::SandboxResult(
+                    SandboxResponse::SandboxFastForwardBlocksLeft(left),
+                ) => {
+                    if left < blocks_left {
+                        blocks_left = left;
+                        last_progress = Instant::now();
+                    }
+                }
+                response => {
+                    return Err(near_jsonrpc_primitives::types::sandbox::RpcSandboxFastForwardError::InternalError {
+                        error_message: format!("Unexpected response: {:?}", response),
+                    })
+                }
+            }
+            if last_progress.elapsed() > self.polling_config.polling_timeout {
+                return Err(
+                    near_jsonrpc_primitives::types::sandbox::RpcSandboxFastForwardError::TimeoutError {
+                        blocks_left,
+                    },
+                );
+            }
+            let _ = sleep(self.polling_config.polling_interval).await;
+        }
+
+        Ok(near_jsonrpc_primitives::types::sandbox::RpcSandboxFastForwardResponse {})
+    }
+
+    async fn sandbox_shift_time(
+        &self,
+        shift_time_request: near_jsonrpc_primitives::types::sandbox::RpcSandboxShiftTimeRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxShiftTimeResponse,
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxShiftTimeError,
+    > {
+        match self
+            .client_addr
+            .send(NetworkClientMessages::Sandbox(NetworkSandboxMessage::SandboxShiftTime(
+                shift_time_request.delta_nanos,
+            )))
+            .await?
+        {
+            NetworkClientResponses::SandboxResult(SandboxResponse::SandboxShiftTimeFailed(
+                error_message,
+            )) => Err(
+                near_jsonrpc_primitives::types::sandbox::RpcSandboxShiftTimeError::InvalidTimeShift {
+                    error_message,
+                },
+            ),
+            _ => Ok(near_jsonrpc_primitives::types::sandbox::RpcSandboxShiftTimeResponse {}),
+        }
+    }
+
+    async fn sandbox_snapshot(
+        &self,
+    ) -> Result<
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxSnapshotResponse,
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxSnapshotError,
+    > {
+        match self
+            .client_addr
+            .send(NetworkClientMessages::Sandbox(NetworkSandboxMessage::SandboxSnapshot))
+            .await?
+        {
+            NetworkClientResponses::SandboxResult(SandboxResponse::SandboxSnapshotTaken(
+                snapshot_id,
+            )) => Ok(near_jsonrpc_primitives::types::sandbox::RpcSandboxSnapshotResponse {
+                snapshot_id,
+            }),
+            response => Err(
+                near_jsonrpc_primitives::types::sandbox::RpcSandboxSnapshotError::InternalError {
+                    error_message: format!("Unexpected response: {:?}", response),
+                },
+            ),
+        }
+    }
+
+    async fn sandbox_restore(
+        &self,
+        restore_request: near_jsonrpc_primitives::types::sandbox::RpcSandboxRestoreRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxRestoreResponse,
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxRestoreError,
+    > {
+        let snapshot_id = restore_request.snapshot_id;
+        match self
+            .client_addr
+            .send(NetworkClientMessages::Sandbox(NetworkSandboxMessage::SandboxRestore(
+                snapshot_id,
+            )))
+            .await?
+        {
+            NetworkClientResponses::SandboxResult(SandboxResponse::SandboxRestoreFinished(
+                true,
+            )) => Ok(near_jsonrpc_primitives::types::sandbox::RpcSandboxRestoreResponse {}),
+            NetworkClientResponses::SandboxResult(SandboxResponse::SandboxRestoreFinished(
+                false,
+            )) => Err(
+                near_jsonrpc_primitives::types::sandbox::RpcSandboxRestoreError::UnknownSnapshot {
+                    snapshot_id,
+                },
+            ),
+            NetworkClientResponses::SandboxResult(SandboxResponse::SandboxRestoreFailed(
+                error_message,
+            )) => Err(
+                near_jsonrpc_primitives::types::sandbox::RpcSandboxRestoreError::InternalError {
+                    error_message,
+                },
+            ),
+            response => Err(
+                near_jsonrpc_primitives::types::sandbox::RpcSandboxRestoreError::InternalError {
+                    error_message: format!("Unexpected response: {:?}", response),
+                },
+            ),
+        }
+    }
 }
 
 #[cfg(feature = "test_features")]
@@ -1246,8 +1674,10 @@ fn rpc_handler(
     handler: web::Data<JsonRpcHandler>,
 ) -> impl Future<Output = Result<HttpResponse, HttpError>> {
     let response = async move {
-        let message = handler.process(message.0).await?;
-        Ok(HttpResponse::Ok().json(&message))
+        match handler.process(message.0).await? {
+            Some(message) => Ok(HttpResponse::Ok().json(&message)),
+            None => Ok(HttpResponse::NoContent().finish()),
+        }
     };
     response.boxed()
 }
@@ -1318,7 +1748,8 @@ fn get_cors(cors_allowed_origins: &[String]) -> Cors {
 
 /// Starts HTTP server(s) listening for RPC requests.
 ///
-/// Starts an HTTP server which handles JSON RPC calls as well as states
+/// Starts an HTTP server which handles JSON RPC calls (over HTTP POST on `/` and
+/// over WebSocket on `/ws` with subscriptions to chain events) as well as states
 /// endpoints such as `/status`, `/health`, `/metrics` etc.  Depending on
 /// configuration may also start another HTTP server just for providing
 /// Prometheus metrics (i.e. covering the `/metrics` path).
@@ -1339,6 +1770,7 @@ pub fn start_http(
         config;
     let prometheus_addr = prometheus_addr.filter(|it| it != &addr);
     let cors_allowed_origins_clone = cors_allowed_origins.clone();
+    let request_limits = Arc::new(RequestLimits::new(&limits_config));
     info!(target:"network", "Starting http server at {}", addr);
     let mut servers = Vec::new();
     let server = HttpServer::new(move || {
@@ -1349,6 +1781,8 @@ pub fn start_http(
                 view_client_addr: view_client_addr.clone(),
                 polling_config,
                 genesis_config: genesis_config.clone(),
+                max_batch_size: limits_config.max_batch_size,
+                request_limits: request_limits.clone(),
                 #[cfg(feature = "test_features")]
                 peer_manager_addr: peer_manager_addr.clone(),
                 #[cfg(feature = "test_features")]
@@ -1357,6 +1791,7 @@ pub fn start_http(
             .app_data(web::JsonConfig::default().limit(limits_config.json_payload_max_size))
             .wrap(middleware::Logger::default())
             .service(web::resource("/").route(web::post().to(rpc_handler)))
+            .service(web::resource("/ws").route(web::get().to(subscriptions::ws_handler)))
             .service(
                 web::resource("/status")
                     .route(web::get().to(status_handler))
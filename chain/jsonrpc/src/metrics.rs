@@ -36,4 +36,10 @@ lazy_static! {
             "Total count of errors by method and message",
             &["method", "err_code"]
         );
+    pub static ref RPC_REJECTED_REQUESTS: near_metrics::Result<IntCounterVec> =
+        near_metrics::try_create_int_counter_vec(
+            "near_rpc_rejected_requests_total",
+            "Total count of rpc requests rejected by concurrency limits, by limit",
+            &["limit"]
+        );
 }
@@ -0,0 +1,356 @@
+//! WebSocket endpoint of the JSON RPC server.
+//!
+//! A WebSocket connection accepts every regular JSON RPC method plus `subscribe` and
+//! `unsubscribe`. Subscribers are notified with `subscription` notifications as the client
+//! updates the chain head, so that they don't need to poll `block` or `tx`.
+
+use std::collections::HashMap;
+
+use actix_codec::{Decoder, Encoder};
+use actix_http::ws;
+use actix_web::{web, Error as HttpError, HttpRequest, HttpResponse};
+use bytes::BytesMut;
+use futures::channel::mpsc;
+use futures::StreamExt;
+use serde_json::Value;
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+use near_client::{
+    ChainHeadEvent, GetBlock, GetChunk, GetStateChanges, SubscribeChainHead, TxStatus,
+};
+use near_jsonrpc_primitives::errors::RpcError;
+use near_jsonrpc_primitives::message::{self, Message, Request};
+use near_jsonrpc_primitives::types::subscriptions::{
+    RpcSubscribeRequest, RpcSubscribeResponse, RpcSubscriptionKind, RpcSubscriptionNotification,
+    RpcUnsubscribeRequest, RpcUnsubscribeResponse,
+};
+use near_primitives::hash::CryptoHash;
+use near_primitives::sharding::ChunkHash;
+use near_primitives::types::{BlockHeight, BlockId, BlockReference};
+use near_primitives::views::{BlockView, FinalExecutionOutcomeViewEnum, FinalExecutionStatus};
+
+use crate::JsonRpcHandler;
+
+/// Maximum number of final blocks to notify about at once when the final block jumps ahead, e.g.
+/// after the node catches up.
+const MAX_FINAL_BLOCKS_PER_HEAD: usize = 100;
+/// Maximum number of messages waiting to be sent to the peer. A peer which doesn't read them fast
+/// enough is disconnected, so that it can't make the node buffer an unbounded amount of data.
+const MAX_QUEUED_MESSAGES: usize = 1024;
+/// Maximum number of active subscriptions of a connection.
+const MAX_SUBSCRIPTIONS_PER_CONNECTION: usize = 64;
+
+pub(crate) async fn ws_handler(
+    request: HttpRequest,
+    payload: web::Payload,
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    let mut response = ws::handshake(request.head())?;
+    let chain_head_events = handler
+        .client_addr
+        .send(SubscribeChainHead {})
+        .await
+        .map_err(actix_web::error::ErrorServiceUnavailable)?;
+
+    let (sender, receiver) = mpsc::channel(MAX_QUEUED_MESSAGES);
+    let mut codec = ws::Codec::new();
+    let body = receiver.map(move |message| {
+        let mut buf = BytesMut::new();
+        codec.encode(message, &mut buf).map(|()| buf.freeze()).map_err(HttpError::from)
+    });
+    let session = Session {
+        handler,
+        sender,
+        overflowed: false,
+        subscriptions: HashMap::new(),
+        next_subscription_id: 0,
+        last_final_block: None,
+    };
+    actix::spawn(session.run(payload, chain_head_events));
+    Ok(HttpResponse::from(response.streaming(body)))
+}
+
+struct Session {
+    handler: web::Data<JsonRpcHandler>,
+    sender: mpsc::Sender<ws::Message>,
+    /// Set once the outgoing queue is full, the connection is closed then.
+    overflowed: bool,
+    subscriptions: HashMap<u64, RpcSubscriptionKind>,
+    next_subscription_id: u64,
+    /// Height and hash of the last final block the subscribers were notified about.
+    last_final_block: Option<(BlockHeight, CryptoHash)>,
+}
+
+impl Session {
+    async fn run(
+        mut self,
+        mut payload: web::Payload,
+        mut chain_head_events: broadcast::Receiver<ChainHeadEvent>,
+    ) {
+        let mut codec = ws::Codec::new();
+        let mut buf = BytesMut::new();
+        loop {
+            tokio::select! {
+                chunk = payload.next() => match chunk {
+                    Some(Ok(chunk)) => {
+                        buf.extend_from_slice(&chunk);
+                        loop {
+                            match codec.decode(&mut buf) {
+                                Ok(Some(frame)) => {
+                                    if !self.on_frame(frame).await {
+                                        return;
+                                    }
+                                }
+                                Ok(None) => break,
+                                Err(err) => {
+                                    debug!(target: "jsonrpc", "Closing WebSocket connection: {}", err);
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                    _ => return,
+                },
+                event = chain_head_events.recv() => match event {
+                    Ok(event) => self.on_chain_head(event).await,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(target: "jsonrpc", "WebSocket subscriber skipped {} chain head updates", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+            }
+            if self.overflowed {
+                debug!(target: "jsonrpc", "Closing WebSocket connection: too many queued messages");
+                return;
+            }
+        }
+    }
+
+    /// Handles a frame received from the peer. Returns whether the connection stays open.
+    async fn on_frame(&mut self, frame: ws::Frame) -> bool {
+        match frame {
+            ws::Frame::Text(bytes) | ws::Frame::Binary(bytes) => {
+                let response = match message::from_slice(&bytes) {
+                    Ok(Message::Request(request)) => {
+                        let id = request.id.clone();
+                        Message::response(id, self.process_request(request).await)
+                    }
+                    Ok(_) => Message::error(RpcError::parse_error(
+                        "JSON RPC Request format was expected".to_owned(),
+                    )),
+                    Err(broken) => broken.reply(),
+                };
+                let text: String = response.into();
+                self.send(ws::Message::Text(text.into()));
+                true
+            }
+            ws::Frame::Ping(bytes) => {
+                self.send(ws::Message::Pong(bytes));
+                true
+            }
+            ws::Frame::Pong(_) => true,
+            ws::Frame::Close(reason) => {
+                self.send(ws::Message::Close(reason));
+                false
+            }
+            ws::Frame::Continuation(_) => {
+                self.send(ws::Message::Close(Some(ws::CloseCode::Unsupported.into())));
+                false
+            }
+        }
+    }
+
+    async fn process_request(&mut self, request: Request) -> Result<Value, RpcError> {
+        match request.method.as_ref() {
+            "subscribe" => {
+                let RpcSubscribeRequest { kind } = RpcSubscribeRequest::parse(request.params)?;
+                if self.subscriptions.len() >= MAX_SUBSCRIPTIONS_PER_CONNECTION {
+                    return Err(RpcError::invalid_request(format!(
+                        "A connection can have at most {} subscriptions",
+                        MAX_SUBSCRIPTIONS_PER_CONNECTION
+                    )));
+                }
+                let subscription_id = self.next_subscription_id;
+                self.next_subscription_id += 1;
+                self.subscriptions.insert(subscription_id, kind);
+                serde_json::to_value(RpcSubscribeResponse { subscription_id })
+                    .map_err(|err| RpcError::serialization_error(err.to_string()))
+            }
+            "unsubscribe" => {
+                let RpcUnsubscribeRequest { subscription_id } =
+                    RpcUnsubscribeRequest::parse(request.params)?;
+                let removed = self.subscriptions.remove(&subscription_id).is_some();
+                serde_json::to_value(RpcUnsubscribeResponse { removed })
+                    .map_err(|err| RpcError::serialization_error(err.to_string()))
+            }
+            _ => self.handler.process_request(request).await,
+        }
+    }
+
+    async fn on_chain_head(&mut self, event: ChainHeadEvent) {
+        let tx_subscriptions: Vec<_> = self
+            .subscriptions
+            .iter()
+            .filter_map(|(id, kind)| match kind {
+                RpcSubscriptionKind::TxStatus { tx_hash, sender_account_id } => {
+                    Some((*id, *tx_hash, sender_account_id.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        for (subscription_id, tx_hash, signer_account_id) in tx_subscriptions {
+            let outcome = self
+                .handler
+                .view_client_addr
+                .send(TxStatus { tx_hash, signer_account_id, fetch_receipt: false })
+                .await;
+            if let Ok(Ok(Some(FinalExecutionOutcomeViewEnum::FinalExecutionOutcome(outcome)))) =
+                outcome
+            {
+                match outcome.status {
+                    FinalExecutionStatus::NotStarted | FinalExecutionStatus::Started => {}
+                    FinalExecutionStatus::Failure(_) | FinalExecutionStatus::SuccessValue(_) => {
+                        self.notify(subscription_id, &outcome);
+                        self.subscriptions.remove(&subscription_id);
+                    }
+                }
+            }
+        }
+
+        let needs_final_blocks = self.subscriptions.values().any(|kind| match kind {
+            RpcSubscriptionKind::TxStatus { .. } => false,
+            _ => true,
+        });
+        if !needs_final_blocks {
+            self.last_final_block = None;
+            return;
+        }
+        if self.last_final_block.map(|(_, hash)| hash) == Some(event.last_final_block) {
+            return;
+        }
+        for block in self.new_final_blocks(event.last_final_block).await {
+            self.last_final_block = Some((block.header.height, block.header.hash));
+            self.on_final_block(&block).await;
+        }
+    }
+
+    /// Returns final blocks up to `last_final_block` that the subscribers were not notified
+    /// about yet, from the oldest to the newest.
+    async fn new_final_blocks(&self, last_final_block: CryptoHash) -> Vec<BlockView> {
+        let last_notified_height = self.last_final_block.map(|(height, _)| height);
+        let mut blocks = vec![];
+        let mut block_hash = last_final_block;
+        while blocks.len() < MAX_FINAL_BLOCKS_PER_HEAD {
+            let block = match self.get_block(block_hash).await {
+                Some(block) => block,
+                None => break,
+            };
+            let prev_height = block.header.prev_height;
+            block_hash = block.header.prev_hash;
+            blocks.push(block);
+            match (last_notified_height, prev_height) {
+                (Some(notified), Some(prev_height)) if prev_height > notified => {}
+                _ => break,
+            }
+        }
+        blocks.reverse();
+        blocks
+    }
+
+    async fn on_final_block(&mut self, block: &BlockView) {
+        let subscriptions: Vec<_> =
+            self.subscriptions.iter().map(|(id, kind)| (*id, kind.clone())).collect();
+        for (subscription_id, kind) in subscriptions {
+            match kind {
+                RpcSubscriptionKind::FinalBlocks => self.notify(subscription_id, block),
+                RpcSubscriptionKind::Chunks { shard_id } => {
+                    let new_chunks = block.chunks.iter().filter(|chunk| {
+                        chunk.shard_id == shard_id && chunk.height_included == block.header.height
+                    });
+                    for chunk in new_chunks {
+                        let chunk = self
+                            .handler
+                            .view_client_addr
+                            .send(GetChunk::ChunkHash(ChunkHash(chunk.chunk_hash)))
+                            .await;
+                        match chunk {
+                            Ok(Ok(chunk)) => self.notify(subscription_id, &chunk),
+                            Ok(Err(err)) => {
+                                debug!(target: "jsonrpc", "Failed to get chunk for subscription: {}", err)
+                            }
+                            Err(err) => {
+                                debug!(target: "jsonrpc", "Failed to get chunk for subscription: {}", err)
+                            }
+                        }
+                    }
+                }
+                RpcSubscriptionKind::StateChanges { changes } => {
+                    let block_hash = block.header.hash;
+                    let changes = self
+                        .handler
+                        .view_client_addr
+                        .send(GetStateChanges { block_hash, state_changes_request: changes })
+                        .await;
+                    match changes {
+                        Ok(Ok(changes)) if !changes.is_empty() => self.notify(
+                            subscription_id,
+                            &near_jsonrpc_primitives::types::changes::RpcStateChangesInBlockResponse {
+                                block_hash,
+                                changes,
+                            },
+                        ),
+                        Ok(Ok(_)) => {}
+                        Ok(Err(err)) => {
+                            debug!(target: "jsonrpc", "Failed to get state changes for subscription: {}", err)
+                        }
+                        Err(err) => {
+                            debug!(target: "jsonrpc", "Failed to get state changes for subscription: {}", err)
+                        }
+                    }
+                }
+                RpcSubscriptionKind::TxStatus { .. } => {}
+            }
+        }
+    }
+
+    async fn get_block(&self, block_hash: CryptoHash) -> Option<BlockView> {
+        let block_reference = BlockReference::BlockId(BlockId::Hash(block_hash));
+        match self.handler.view_client_addr.send(GetBlock(block_reference)).await {
+            Ok(Ok(block)) => Some(block),
+            Ok(Err(err)) => {
+                debug!(target: "jsonrpc", "Failed to get block {} for subscription: {}", block_hash, err);
+                None
+            }
+            Err(err) => {
+                debug!(target: "jsonrpc", "Failed to get block {} for subscription: {}", block_hash, err);
+                None
+            }
+        }
+    }
+
+    fn notify<T: serde::Serialize>(&mut self, subscription_id: u64, result: &T) {
+        let result = match serde_json::to_value(result) {
+            Ok(result) => result,
+            Err(err) => {
+                warn!(target: "jsonrpc", "Failed to serialize subscription notification: {}", err);
+                return;
+            }
+        };
+        let params = serde_json::to_value(RpcSubscriptionNotification { subscription_id, result })
+            .expect("notification params are always serializable");
+        let notification = Message::notification("subscription".to_owned(), Some(params));
+        let text: String = notification.into();
+        self.send(ws::Message::Text(text.into()));
+    }
+
+    fn send(&mut self, message: ws::Message) {
+        if let Err(err) = self.sender.try_send(message) {
+            // The connection is either closed already, or has to be closed since the peer
+            // doesn't keep up with the messages.
+            if err.is_full() {
+                self.overflowed = true;
+            }
+        }
+    }
+}
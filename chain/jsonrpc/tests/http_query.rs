@@ -1,8 +1,10 @@
 use actix::System;
 use futures::{future, FutureExt};
+use serde_json::{json, Value};
 
 use near_actix_test_utils::run_actix;
 use near_jsonrpc::client::new_http_client;
+use near_jsonrpc::RpcLimitsConfig;
 use near_logger_utils::init_test_logger;
 
 pub mod test_utils;
@@ -26,3 +28,102 @@ fn test_status() {
         }));
     });
 }
+
+/// Sends a batch of requests and a batch over the size limit.
+#[test]
+fn test_batch() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::NonValidator);
+
+        actix::spawn(async move {
+            let client = awc::Client::new();
+            let url = format!("http://{}", addr);
+            let batch = json!([
+                {"jsonrpc": "2.0", "id": 1, "method": "status", "params": []},
+                {"jsonrpc": "2.0", "id": 2, "method": "block", "params": {"finality": "final"}},
+                {"jsonrpc": "2.0", "id": 3, "method": "no_such_method", "params": []},
+            ]);
+            let response: Value =
+                client.post(&url).send_json(&batch).await.unwrap().json().await.unwrap();
+            let responses = response.as_array().unwrap();
+            assert_eq!(responses.len(), 3);
+            assert_eq!(responses[0]["id"], 1);
+            assert_eq!(responses[0]["result"]["chain_id"], "unittest");
+            assert_eq!(responses[1]["id"], 2);
+            assert!(responses[1]["result"]["header"]["hash"].is_string());
+            assert_eq!(responses[2]["id"], 3);
+            assert_eq!(responses[2]["error"]["code"], -32_601);
+
+            let request = json!({"jsonrpc": "2.0", "id": 1, "method": "status", "params": []});
+            let batch = Value::Array(vec![request; 101]);
+            let response: Value =
+                client.post(&url).send_json(&batch).await.unwrap().json().await.unwrap();
+            assert_eq!(response["error"]["code"], -32_600);
+            System::current().stop();
+        });
+    });
+}
+
+/// Checks that a batch doesn't exceed concurrency limits by itself, that rate limits apply to each
+/// of its requests, that notifications are not answered and that invalid entries get their own
+/// errors.
+#[test]
+fn test_batch_limits_and_notifications() {
+    init_test_logger();
+
+    run_actix(async {
+        let limits_config = RpcLimitsConfig {
+            max_concurrent_requests: vec![("status".to_owned(), 1)].into_iter().collect(),
+            max_requests_per_second: vec![("block".to_owned(), 3)].into_iter().collect(),
+            ..Default::default()
+        };
+        let (_view_client_addr, addr) =
+            test_utils::start_all_with_limits(test_utils::NodeType::NonValidator, limits_config);
+
+        actix::spawn(async move {
+            let client = awc::Client::new();
+            let url = format!("http://{}", addr);
+
+            let status = json!({"jsonrpc": "2.0", "id": 1, "method": "status", "params": []});
+            let batch = Value::Array(vec![status; 5]);
+            let response: Value =
+                client.post(&url).send_json(&batch).await.unwrap().json().await.unwrap();
+            let responses = response.as_array().unwrap();
+            assert_eq!(responses.len(), 5);
+            assert!(responses.iter().all(|response| response["result"].is_object()));
+
+            let block = json!({
+                "jsonrpc": "2.0", "id": 1, "method": "block", "params": {"finality": "final"}
+            });
+            let batch = Value::Array(vec![block; 5]);
+            let response: Value =
+                client.post(&url).send_json(&batch).await.unwrap().json().await.unwrap();
+            let responses = response.as_array().unwrap();
+            assert_eq!(responses.len(), 5);
+            assert!(responses[..3].iter().all(|response| response["result"].is_object()));
+            assert!(responses[3..].iter().all(|response| response["error"].is_object()));
+
+            let batch = json!([
+                {"jsonrpc": "2.0", "method": "status", "params": []},
+                {"jsonrpc": "2.0", "id": 2, "method": "status", "params": []},
+                {"foo": "bar"},
+            ]);
+            let response: Value =
+                client.post(&url).send_json(&batch).await.unwrap().json().await.unwrap();
+            let responses = response.as_array().unwrap();
+            assert_eq!(responses.len(), 2);
+            assert_eq!(responses[0]["id"], 2);
+            assert!(responses[0]["result"].is_object());
+            assert_eq!(responses[1]["id"], Value::Null);
+            assert_eq!(responses[1]["error"]["code"], -32_600);
+
+            let batch = json!([{"jsonrpc": "2.0", "method": "status", "params": []}]);
+            let mut response = client.post(&url).send_json(&batch).await.unwrap();
+            assert_eq!(response.status(), awc::http::StatusCode::NO_CONTENT);
+            assert!(response.body().await.unwrap().is_empty());
+            System::current().stop();
+        });
+    });
+}
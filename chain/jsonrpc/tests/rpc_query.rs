@@ -316,6 +316,34 @@ fn test_query_state() {
     });
 }
 
+/// Connect to json rpc and query a page of state.
+#[test]
+fn test_query_state_paginated() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let query_response = client
+            .query(near_jsonrpc_primitives::types::query::RpcQueryRequest {
+                block_reference: BlockReference::latest(),
+                request: QueryRequest::ViewStatePaginated {
+                    account_id: "test".parse().unwrap(),
+                    prefix: vec![].into(),
+                    start_key: None,
+                    limit: 10,
+                    include_proof: true,
+                },
+            })
+            .await
+            .unwrap();
+        assert_eq!(query_response.block_height, 0);
+        let page = if let QueryResponseKind::ViewStatePaginated(page) = query_response.kind {
+            page
+        } else {
+            panic!("queried state, but received something else: {:?}", query_response.kind);
+        };
+        assert_eq!(page.values.len(), 0);
+        assert_eq!(page.next_key, None);
+    });
+}
+
 /// Connect to json rpc and call function
 #[test]
 fn test_query_call_function() {
@@ -7,7 +7,7 @@ use serde_json::json;
 use near_chain_configs::GenesisConfig;
 use near_client::test_utils::setup_no_network_with_validity_period_and_no_epoch_sync;
 use near_client::ViewClientActor;
-use near_jsonrpc::{start_http, RpcConfig};
+use near_jsonrpc::{start_http, RpcConfig, RpcLimitsConfig};
 use near_jsonrpc_primitives::message::{from_slice, Message};
 use near_network::test_utils::open_port;
 #[cfg(feature = "test_features")]
@@ -28,10 +28,31 @@ pub fn start_all(node_type: NodeType) -> (Addr<ViewClientActor>, String) {
     start_all_with_validity_period_and_no_epoch_sync(node_type, 100, false)
 }
 
+pub fn start_all_with_limits(
+    node_type: NodeType,
+    limits_config: RpcLimitsConfig,
+) -> (Addr<ViewClientActor>, String) {
+    start_all_with_config(node_type, 100, false, limits_config)
+}
+
 pub fn start_all_with_validity_period_and_no_epoch_sync(
     node_type: NodeType,
     transaction_validity_period: NumBlocks,
     enable_doomslug: bool,
+) -> (Addr<ViewClientActor>, String) {
+    start_all_with_config(
+        node_type,
+        transaction_validity_period,
+        enable_doomslug,
+        RpcLimitsConfig::default(),
+    )
+}
+
+fn start_all_with_config(
+    node_type: NodeType,
+    transaction_validity_period: NumBlocks,
+    enable_doomslug: bool,
+    limits_config: RpcLimitsConfig,
 ) -> (Addr<ViewClientActor>, String) {
     let (client_addr, view_client_addr) = setup_no_network_with_validity_period_and_no_epoch_sync(
         vec!["test1".parse().unwrap(), "test2".parse().unwrap()],
@@ -61,7 +82,7 @@ pub fn start_all_with_validity_period_and_no_epoch_sync(
     .start();
 
     start_http(
-        RpcConfig::new(&addr),
+        RpcConfig { limits_config, ..RpcConfig::new(&addr) },
         TEST_GENESIS_CONFIG.clone(),
         client_addr.clone(),
         view_client_addr.clone(),
@@ -0,0 +1,116 @@
+use actix::System;
+use futures::{SinkExt, Stream, StreamExt};
+use serde_json::{json, Value};
+
+use near_actix_test_utils::run_actix;
+use near_logger_utils::init_test_logger;
+
+pub mod test_utils;
+
+/// Returns the next text message received over the WebSocket as json.
+async fn recv_json<S>(connection: &mut S) -> Value
+where
+    S: Stream<Item = Result<awc::ws::Frame, awc::error::WsProtocolError>> + Unpin,
+{
+    loop {
+        if let awc::ws::Frame::Text(bytes) = connection.next().await.unwrap().unwrap() {
+            return serde_json::from_slice(&bytes).unwrap();
+        }
+    }
+}
+
+/// Calls a regular method and subscribes to final blocks over WebSocket.
+#[test]
+fn test_subscribe_final_blocks() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::Validator);
+
+        actix::spawn(async move {
+            let (_response, mut connection) =
+                awc::Client::new().ws(format!("ws://{}/ws", addr)).connect().await.unwrap();
+
+            let request = json!({"jsonrpc": "2.0", "id": 1, "method": "status", "params": []});
+            connection.send(awc::ws::Message::Text(request.to_string().into())).await.unwrap();
+            let response = recv_json(&mut connection).await;
+            assert_eq!(response["id"], 1);
+            assert_eq!(response["result"]["chain_id"], "unittest");
+
+            let request = json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "subscribe",
+                "params": {"subscription": "final_blocks"},
+            });
+            connection.send(awc::ws::Message::Text(request.to_string().into())).await.unwrap();
+            let response = recv_json(&mut connection).await;
+            assert_eq!(response["id"], 2);
+            let subscription_id = response["result"]["subscription_id"].clone();
+            assert!(subscription_id.is_u64());
+
+            let mut last_height = None;
+            for _ in 0..2 {
+                let notification = recv_json(&mut connection).await;
+                assert_eq!(notification["method"], "subscription");
+                assert_eq!(notification["params"]["subscription_id"], subscription_id);
+                let height = notification["params"]["result"]["header"]["height"].as_u64();
+                assert!(height > last_height);
+                last_height = height;
+            }
+
+            let request = json!({
+                "jsonrpc": "2.0",
+                "id": 3,
+                "method": "unsubscribe",
+                "params": {"subscription_id": subscription_id},
+            });
+            connection.send(awc::ws::Message::Text(request.to_string().into())).await.unwrap();
+            loop {
+                let response = recv_json(&mut connection).await;
+                if response["id"] == 3 {
+                    assert_eq!(response["result"]["removed"], true);
+                    break;
+                }
+            }
+            System::current().stop();
+        });
+    });
+}
+
+/// A connection can't have more than 64 subscriptions.
+#[test]
+fn test_subscriptions_limit() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::Validator);
+
+        actix::spawn(async move {
+            let (_response, mut connection) =
+                awc::Client::new().ws(format!("ws://{}/ws", addr)).connect().await.unwrap();
+
+            for id in 0..=64 {
+                let request = json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "method": "subscribe",
+                    "params": {"subscription": "chunks", "shard_id": 0},
+                });
+                connection.send(awc::ws::Message::Text(request.to_string().into())).await.unwrap();
+                let response = loop {
+                    let response = recv_json(&mut connection).await;
+                    if response["id"] == id {
+                        break response;
+                    }
+                };
+                if id < 64 {
+                    assert!(response["result"]["subscription_id"].is_u64());
+                } else {
+                    assert!(response["error"].is_object());
+                }
+            }
+            System::current().stop();
+        });
+    });
+}
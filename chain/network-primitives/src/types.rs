@@ -32,6 +32,8 @@ use near_primitives::syncing::{
     ShardStateSyncResponseV1,
 };
 use near_primitives::transaction::{ExecutionOutcomeWithIdAndProof, SignedTransaction};
+#[cfg(feature = "sandbox")]
+use near_primitives::types::BlockHeightDelta;
 use near_primitives::types::{AccountId, BlockHeight, BlockReference, EpochId, ShardId};
 use near_primitives::utils::{from_timestamp, to_timestamp};
 use near_primitives::views::{FinalExecutionOutcomeView, QueryRequest, QueryResponse};
@@ -650,6 +652,42 @@ impl KnownPeerStatus {
     }
 }
 
+/// Bounds of the peer reputation, so that a long history doesn't outweigh what a peer does now.
+pub const MIN_PEER_REPUTATION: i32 = -1000;
+pub const MAX_PEER_REPUTATION: i32 = 1000;
+
+/// Behaviour of a peer that changes its reputation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerReputationEvent {
+    /// Peer delivered a block that passed validation.
+    ValidBlock,
+    /// Peer delivered a chunk part that passed validation.
+    ValidChunkPart,
+    /// Peer accepted our connection quickly.
+    LowLatency,
+    /// Peer didn't respond in time.
+    Timeout,
+    /// Peer sent data that failed validation, which is also what every ban is for.
+    InvalidData,
+    /// Header sync from the peer stopped making progress.
+    HeaderSyncStall,
+}
+
+impl PeerReputationEvent {
+    /// Change of the reputation. Misbehaviour weighs a lot more than useful behaviour, since
+    /// useful messages are frequent and cheap to send.
+    pub fn score(self) -> i32 {
+        match self {
+            PeerReputationEvent::ValidBlock => 2,
+            PeerReputationEvent::ValidChunkPart => 1,
+            PeerReputationEvent::LowLatency => 5,
+            PeerReputationEvent::Timeout => -20,
+            PeerReputationEvent::InvalidData => -200,
+            PeerReputationEvent::HeaderSyncStall => -50,
+        }
+    }
+}
+
 /// Information node stores about known peers.
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct KnownPeerState {
@@ -657,6 +695,9 @@ pub struct KnownPeerState {
     pub status: KnownPeerStatus,
     pub first_seen: u64,
     pub last_seen: u64,
+    /// Score accumulated from the peer behaviour, between `MIN_PEER_REPUTATION` and
+    /// `MAX_PEER_REPUTATION`. New peers start at 0.
+    pub reputation: i32,
 }
 
 impl KnownPeerState {
@@ -666,9 +707,15 @@ impl KnownPeerState {
             status: KnownPeerStatus::Unknown,
             first_seen: to_timestamp(Utc::now()),
             last_seen: to_timestamp(Utc::now()),
+            reputation: 0,
         }
     }
 
+    pub fn update_reputation(&mut self, event: PeerReputationEvent) {
+        self.reputation =
+            (self.reputation + event.score()).clamp(MIN_PEER_REPUTATION, MAX_PEER_REPUTATION);
+    }
+
     pub fn first_seen(&self) -> DateTime<Utc> {
         from_timestamp(self.first_seen)
     }
@@ -851,6 +898,15 @@ pub enum NetworkAdversarialMessage {
 pub enum NetworkSandboxMessage {
     SandboxPatchState(Vec<StateRecord>),
     SandboxPatchStateStatus,
+    /// Produce the given number of blocks as fast as possible.
+    SandboxFastForward(BlockHeightDelta),
+    SandboxFastForwardStatus,
+    /// Move the timestamps of the produced blocks forward by the given number of nanoseconds.
+    SandboxShiftTime(u64),
+    /// Copy the chain and the state into memory.
+    SandboxSnapshot,
+    /// Revert the node to the snapshot with the given id.
+    SandboxRestore(u64),
 }
 
 #[derive(AsStaticStr)]
@@ -1,22 +1,38 @@
-use std::io::{Error, ErrorKind};
+use std::io::{Error, ErrorKind, Read};
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use bytes::{Buf, BufMut, BytesMut};
-use bytesize::{GIB, MIB};
+use bytesize::{GIB, KIB, MIB};
 use tokio_util::codec::{Decoder, Encoder};
 use tracing::error;
 
 use near_performance_metrics::framed_write::EncoderCallBack;
 #[cfg(feature = "performance_stats")]
 use near_performance_metrics::stats_enabled::get_thread_stats_logger;
+use near_primitives::checked_feature;
+use near_primitives::version::ProtocolVersion;
 use near_rust_allocator_proxy::allocator::get_tid;
 
 use crate::metrics;
-use crate::types::{PeerMessage, ReasonForBan};
+use crate::types::{PeerMessage, ReasonForBan, RoutedMessageBody};
 
 const NETWORK_MESSAGE_MAX_SIZE: u32 = 512 * MIB as u32;
 const MAX_CAPACITY: u64 = GIB;
 
+/// First byte of a compressed message. Borsh starts a `PeerMessage` with the index of its
+/// variant, so no uncompressed message starts with it.
+const COMPRESSED_MESSAGE_MARKER: u8 = u8::MAX;
+/// zstd compression level, low levels are fast and compress blocks and chunks almost as well.
+const COMPRESSION_LEVEL: i32 = 3;
+const BLOCK_COMPRESSION_THRESHOLD: usize = 2 * KIB as usize;
+const CHUNK_COMPRESSION_THRESHOLD: usize = 4 * KIB as usize;
+const STATE_RESPONSE_COMPRESSION_THRESHOLD: usize = KIB as usize;
+/// Limits of the decompressed size of messages, well below `NETWORK_MESSAGE_MAX_SIZE`, so that a
+/// small compressed message can't make us allocate a large buffer.
+const MAX_DECOMPRESSED_BLOCK_SIZE: u64 = 16 * MIB;
+const MAX_DECOMPRESSED_EPOCH_SYNC_SIZE: u64 = 16 * MIB;
+const MAX_DECOMPRESSED_ROUTED_SIZE: u64 = 32 * MIB;
+
 pub struct Codec {
     max_length: u32,
 }
@@ -110,6 +126,134 @@ pub fn bytes_to_peer_message(bytes: &[u8]) -> Result<PeerMessage, std::io::Error
     PeerMessage::try_from_slice(bytes)
 }
 
+/// Size from which a message of the given type is worth compressing. Messages of other types
+/// are small or hardly compressible, and are never compressed.
+fn compression_threshold(msg: &PeerMessage) -> Option<usize> {
+    match msg {
+        PeerMessage::Block(_) | PeerMessage::BlockHeaders(_) => Some(BLOCK_COMPRESSION_THRESHOLD),
+        PeerMessage::EpochSyncResponse(_) | PeerMessage::EpochSyncFinalizationResponse(_) => {
+            Some(STATE_RESPONSE_COMPRESSION_THRESHOLD)
+        }
+        PeerMessage::Routed(routed_message) => match routed_message.body {
+            RoutedMessageBody::StateResponse(_) | RoutedMessageBody::VersionedStateResponse(_) => {
+                Some(STATE_RESPONSE_COMPRESSION_THRESHOLD)
+            }
+            RoutedMessageBody::PartialEncodedChunk(_)
+            | RoutedMessageBody::VersionedPartialEncodedChunk(_)
+            | RoutedMessageBody::PartialEncodedChunkResponse(_)
+            | RoutedMessageBody::PartialEncodedChunkForward(_) => Some(CHUNK_COMPRESSION_THRESHOLD),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Limit of the decompressed size of a message starting with `variant`, the borsh index of its
+/// `PeerMessage` variant. Only messages of the types `compression_threshold` compresses have one.
+fn max_decompressed_size(variant: u8) -> Option<u64> {
+    match variant {
+        // PeerMessage::BlockHeaders and PeerMessage::Block
+        9 | 11 => Some(MAX_DECOMPRESSED_BLOCK_SIZE),
+        // PeerMessage::Routed
+        13 => Some(MAX_DECOMPRESSED_ROUTED_SIZE),
+        // PeerMessage::EpochSyncResponse and PeerMessage::EpochSyncFinalizationResponse
+        18 | 20 => Some(MAX_DECOMPRESSED_EPOCH_SYNC_SIZE),
+        _ => None,
+    }
+}
+
+/// Whether messages to a peer on `protocol_version` may be compressed.
+pub fn supports_compression(protocol_version: ProtocolVersion) -> bool {
+    checked_feature!(
+        "protocol_feature_peer_message_compression",
+        PeerMessageCompression,
+        protocol_version
+    )
+}
+
+/// Serializes `msg`, compressing it if `compress` is set and it's worth it.
+pub fn encode_peer_message(msg: &PeerMessage, compress: bool) -> Result<Vec<u8>, Error> {
+    let bytes = peer_message_to_bytes(msg)?;
+    Ok(if compress { compress_peer_message(msg, bytes) } else { bytes })
+}
+
+/// Compresses serialized `msg` if it's large enough for its type and compression makes it
+/// smaller. Only peers supporting `PeerMessageCompression` can read compressed messages.
+pub fn compress_peer_message(msg: &PeerMessage, bytes: Vec<u8>) -> Vec<u8> {
+    try_compress_peer_message(msg, &bytes).unwrap_or(bytes)
+}
+
+/// Like `compress_peer_message`, but returns `None` if the message is not worth compressing.
+pub fn try_compress_peer_message(msg: &PeerMessage, bytes: &[u8]) -> Option<Vec<u8>> {
+    match compression_threshold(msg) {
+        Some(threshold) if bytes.len() >= threshold => {}
+        _ => return None,
+    }
+    let mut compressed = vec![COMPRESSED_MESSAGE_MARKER];
+    if let Err(err) = zstd::stream::copy_encode(bytes, &mut compressed, COMPRESSION_LEVEL) {
+        error!(target: "network", "Failed to compress {} message: {}", msg.msg_variant(), err);
+        return None;
+    }
+    if compressed.len() >= bytes.len() {
+        return None;
+    }
+    record_compressed_message("sent", msg, bytes.len(), compressed.len());
+    Some(compressed)
+}
+
+pub fn is_compressed(bytes: &[u8]) -> bool {
+    bytes.first() == Some(&COMPRESSED_MESSAGE_MARKER)
+}
+
+/// Decompresses a message compressed by `compress_peer_message`. Uncompressed messages are
+/// returned as they are. Compressed messages are only valid once compression is negotiated with
+/// the peer, and only for the types of messages we compress, up to a size limit for each type.
+pub fn decompress_peer_message(
+    bytes: Vec<u8>,
+    compression_negotiated: bool,
+) -> Result<Vec<u8>, Error> {
+    if !is_compressed(&bytes) {
+        return Ok(bytes);
+    }
+    if !compression_negotiated {
+        return Err(Error::new(ErrorKind::InvalidData, "Compression was not negotiated"));
+    }
+    let mut decoder = zstd::stream::read::Decoder::new(&bytes[1..])?;
+    let mut decompressed = vec![0u8];
+    decoder.read_exact(&mut decompressed)?;
+    let max_size = max_decompressed_size(decompressed[0]).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Message of variant {} can't be compressed", decompressed[0]),
+        )
+    })?;
+    // Limit the output, so that a tiny message can't make us allocate a large buffer.
+    decoder.take(max_size).read_to_end(&mut decompressed)?;
+    if decompressed.len() as u64 > max_size {
+        return Err(Error::new(ErrorKind::InvalidData, "Decompressed message is too long"));
+    }
+    Ok(decompressed)
+}
+
+pub fn record_compressed_message(
+    direction: &str,
+    msg: &PeerMessage,
+    uncompressed_len: usize,
+    compressed_len: usize,
+) {
+    let labels = [direction, msg.msg_variant()];
+    near_metrics::inc_counter_vec_by(
+        &metrics::PEER_MESSAGE_UNCOMPRESSED_BYTES,
+        &labels,
+        uncompressed_len as u64,
+    );
+    near_metrics::inc_counter_vec_by(
+        &metrics::PEER_MESSAGE_COMPRESSED_BYTES,
+        &labels,
+        compressed_len as u64,
+    );
+}
+
 fn peer_id_type_field_len(enum_var: u8) -> Option<usize> {
     // 1 byte for enum variant, then some number depending on the
     // public key type
@@ -167,6 +311,7 @@ mod test {
     use near_primitives::block::{Approval, ApprovalInner};
     use near_primitives::hash::{self, CryptoHash};
     use near_primitives::network::{AnnounceAccount, PeerId};
+    use near_primitives::sharding::{ChunkHash, PartialEncodedChunkPart};
     use near_primitives::transaction::{SignedTransaction, Transaction};
     use near_primitives::{
         types::EpochId,
@@ -174,8 +319,9 @@ mod test {
     };
 
     use crate::types::{
-        Handshake, HandshakeFailureReason, HandshakeV2, PeerChainInfo, PeerChainInfoV2,
-        PeerIdOrHash, PeerInfo, RoutedMessage, RoutedMessageBody, SyncData,
+        Handshake, HandshakeFailureReason, HandshakeV2, PartialEncodedChunkResponseMsg,
+        PeerChainInfo, PeerChainInfoV2, PeerIdOrHash, PeerInfo, RoutedMessage, RoutedMessageBody,
+        SyncData,
     };
 
     use super::*;
@@ -404,6 +550,108 @@ mod test {
         assert_eq!(account_id, dec_account_id);
     }
 
+    fn create_chunk_response(part_size: usize) -> PeerMessage {
+        let sk = SecretKey::from_seed(KeyType::ED25519, "test");
+        let signature = sk.sign(CryptoHash::default().as_ref());
+        PeerMessage::Routed(RoutedMessage {
+            target: PeerIdOrHash::Hash(CryptoHash::default()),
+            author: sk.public_key().into(),
+            signature,
+            ttl: 100,
+            body: RoutedMessageBody::PartialEncodedChunkResponse(PartialEncodedChunkResponseMsg {
+                chunk_hash: ChunkHash(CryptoHash::default()),
+                parts: vec![PartialEncodedChunkPart {
+                    part_ord: 0,
+                    part: vec![7; part_size].into_boxed_slice(),
+                    merkle_proof: vec![],
+                }],
+                receipts: vec![],
+            }),
+        })
+    }
+
+    #[test]
+    fn test_compress_peer_message() {
+        let msg = create_chunk_response(64 * KIB as usize);
+        let bytes = peer_message_to_bytes(&msg).unwrap();
+        let compressed = compress_peer_message(&msg, bytes.clone());
+        assert!(is_compressed(&compressed));
+        assert!(compressed.len() < bytes.len());
+
+        let mut codec = Codec::new();
+        let mut buffer = BytesMut::new();
+        codec.encode(compressed, &mut buffer).unwrap();
+        let decoded = codec.decode(&mut buffer).unwrap().unwrap().unwrap();
+        let decompressed = decompress_peer_message(decoded, true).unwrap();
+        assert_eq!(decompressed, bytes);
+        assert_eq!(bytes_to_peer_message(&decompressed).unwrap(), msg);
+    }
+
+    #[test]
+    fn test_compression_threshold() {
+        // Small messages and messages of types that aren't compressed are sent as they are.
+        for msg in vec![create_chunk_response(16), PeerMessage::PeersResponse(vec![])] {
+            let bytes = peer_message_to_bytes(&msg).unwrap();
+            assert_eq!(compress_peer_message(&msg, bytes.clone()), bytes);
+            assert_eq!(decompress_peer_message(bytes.clone(), false).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn test_decompress_invalid() {
+        assert!(decompress_peer_message(vec![COMPRESSED_MESSAGE_MARKER, 1, 2, 3], true).is_err());
+    }
+
+    #[test]
+    fn test_compression_negotiation() {
+        let msg = create_chunk_response(64 * KIB as usize);
+        let bytes = peer_message_to_bytes(&msg).unwrap();
+
+        // A peer on an older protocol version gets uncompressed messages.
+        assert!(!supports_compression(OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION));
+        assert_eq!(
+            encode_peer_message(
+                &msg,
+                supports_compression(OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION)
+            )
+            .unwrap(),
+            bytes
+        );
+        assert_eq!(
+            supports_compression(PROTOCOL_VERSION),
+            cfg!(feature = "protocol_feature_peer_message_compression")
+        );
+
+        // A compressed message is rejected until compression is negotiated.
+        let compressed = encode_peer_message(&msg, true).unwrap();
+        assert!(is_compressed(&compressed));
+        assert!(decompress_peer_message(compressed.clone(), false).is_err());
+        assert_eq!(decompress_peer_message(compressed, true).unwrap(), bytes);
+    }
+
+    /// Compresses `bytes` the way `compress_peer_message` does, whatever message they hold.
+    fn compress(bytes: &[u8]) -> Vec<u8> {
+        let mut compressed = vec![COMPRESSED_MESSAGE_MARKER];
+        zstd::stream::copy_encode(bytes, &mut compressed, COMPRESSION_LEVEL).unwrap();
+        compressed
+    }
+
+    #[test]
+    fn test_decompress_limits() {
+        // Messages of types that are never compressed are rejected.
+        let bytes = peer_message_to_bytes(&PeerMessage::PeersResponse(vec![])).unwrap();
+        assert!(decompress_peer_message(compress(&bytes), true).is_err());
+
+        // A small message decompressing to a large block is rejected.
+        let mut bytes = vec![0u8; MAX_DECOMPRESSED_BLOCK_SIZE as usize + 1];
+        bytes[0] = 11;
+        let compressed = compress(&bytes);
+        assert!(compressed.len() < 64 * KIB as usize);
+        assert!(decompress_peer_message(compressed, true).is_err());
+        bytes.pop();
+        assert_eq!(decompress_peer_message(compress(&bytes), true).unwrap(), bytes);
+    }
+
     #[test]
     fn test_abusive() {
         let mut codec = Codec::new();
@@ -5,7 +5,8 @@ use strum::VariantNames;
 
 use near_metrics::{
     inc_counter_by_opt, inc_counter_opt, try_create_histogram, try_create_int_counter,
-    try_create_int_gauge, Histogram, IntCounter, IntGauge,
+    try_create_int_counter_vec, try_create_int_gauge, Histogram, IntCounter, IntCounterVec,
+    IntGauge,
 };
 
 use crate::types::{PeerMessage, RoutedMessageBody};
@@ -76,6 +77,18 @@ lazy_static! {
             "near_dropped_messages_count",
             "Total count of messages which were dropped, because write buffer was full"
         );
+    pub static ref PEER_MESSAGE_UNCOMPRESSED_BYTES: near_metrics::Result<IntCounterVec> =
+        try_create_int_counter_vec(
+            "near_peer_message_uncompressed_bytes",
+            "Size of compressed peer messages before compression, by direction and message type",
+            &["direction", "type"]
+        );
+    pub static ref PEER_MESSAGE_COMPRESSED_BYTES: near_metrics::Result<IntCounterVec> =
+        try_create_int_counter_vec(
+            "near_peer_message_compressed_bytes",
+            "Size of compressed peer messages on the wire, by direction and message type",
+            &["direction", "type"]
+        );
 }
 
 #[derive(Clone)]
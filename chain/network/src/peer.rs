@@ -31,17 +31,17 @@ use near_primitives::version::{
 };
 use near_rust_allocator_proxy::allocator::get_tid;
 
-use crate::codec::{self, bytes_to_peer_message, peer_message_to_bytes, Codec};
+use crate::codec::{self, bytes_to_peer_message, Codec};
 use crate::rate_counter::RateCounter;
 use crate::routing::{Edge, EdgeInfo};
 use crate::types::{
-    Ban, Consolidate, ConsolidateResponse, Handshake, HandshakeFailureReason, HandshakeV2,
-    NetworkClientMessages, NetworkClientResponses, NetworkRequests, NetworkViewClientMessages,
-    NetworkViewClientResponses, PeerChainInfo, PeerChainInfoV2, PeerInfo, PeerManagerRequest,
-    PeerMessage, PeerRequest, PeerResponse, PeerStatsResult, PeerStatus, PeerType, PeersRequest,
-    PeersResponse, QueryPeerStats, ReasonForBan, RoutedMessage, RoutedMessageBody,
-    RoutedMessageFrom, SendMessage, StateResponseInfo, Unregister,
-    UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE,
+    Ban, BroadcastMessage, Consolidate, ConsolidateResponse, Handshake, HandshakeFailureReason,
+    HandshakeV2, NetworkClientMessages, NetworkClientResponses, NetworkRequests,
+    NetworkViewClientMessages, NetworkViewClientResponses, PeerChainInfo, PeerChainInfoV2,
+    PeerInfo, PeerManagerRequest, PeerMessage, PeerReputationEvent, PeerRequest, PeerResponse,
+    PeerStatsResult, PeerStatus, PeerType, PeersRequest, PeersResponse, QueryPeerStats,
+    ReasonForBan, RoutedMessage, RoutedMessageBody, RoutedMessageFrom, SendMessage,
+    StateResponseInfo, Unregister, UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE,
 };
 use crate::PeerManagerActor;
 use crate::{
@@ -165,6 +165,8 @@ pub struct Peer {
     pub peer_status: PeerStatus,
     /// Protocol version to communicate with this peer.
     pub protocol_version: ProtocolVersion,
+    /// Whether large messages to this peer are compressed. Known once we get its handshake.
+    compress_messages: bool,
     /// Framed wrapper to send messages through the TCP connection.
     framed: FramedWrite<Vec<u8>, WriteHalf, Codec, Codec>,
     /// Handshake timeout.
@@ -218,6 +220,7 @@ impl Peer {
             peer_type,
             peer_status: PeerStatus::Connecting,
             protocol_version: PROTOCOL_VERSION,
+            compress_messages: false,
             framed,
             handshake_timeout,
             peer_manager_addr,
@@ -245,32 +248,42 @@ impl Peer {
             || self.tracker.sent_bytes.count_per_min() > MAX_PEER_MSG_PER_MIN
     }
 
-    fn send_message(&mut self, msg: &PeerMessage) {
+    /// Whether `msg` should be sent to this peer, records block requests in tracker.
+    fn should_send(&mut self, msg: &PeerMessage) -> bool {
         // Skip sending block and headers if we received it or header from this peer.
         // Record block requests in tracker.
         match msg {
-            PeerMessage::Block(b) if self.tracker.has_received(b.hash()) => return,
+            PeerMessage::Block(b) if self.tracker.has_received(b.hash()) => return false,
             PeerMessage::BlockRequest(h) => self.tracker.push_request(*h),
             _ => (),
         };
+        true
+    }
 
-        match peer_message_to_bytes(msg) {
-            Ok(bytes) => {
-                self.tracker.increment_sent(bytes.len() as u64);
-                let bytes_len = bytes.len();
-                if !self.framed.write(bytes) {
-                    error!(
-                        "{} Failed to send message {} of size {}",
-                        get_tid(),
-                        strum::AsStaticRef::as_static(msg),
-                        bytes_len,
-                    )
-                }
-            }
+    fn send_message(&mut self, msg: &PeerMessage) {
+        if !self.should_send(msg) {
+            return;
+        }
+        match codec::encode_peer_message(msg, self.compress_messages) {
+            Ok(bytes) => self.send_bytes(msg, bytes),
             Err(err) => error!(target: "network", "Error converting message to bytes: {}", err),
         };
     }
 
+    /// Sends `msg` already serialized, and compressed if compression is negotiated, as `bytes`.
+    fn send_bytes(&mut self, msg: &PeerMessage, bytes: Vec<u8>) {
+        self.tracker.increment_sent(bytes.len() as u64);
+        let bytes_len = bytes.len();
+        if !self.framed.write(bytes) {
+            error!(
+                "{} Failed to send message {} of size {}",
+                get_tid(),
+                strum::AsStaticRef::as_static(msg),
+                bytes_len,
+            )
+        }
+    }
+
     fn fetch_client_chain_info(&mut self, ctx: &mut Context<Peer>) {
         ctx.wait(
             self.view_client_addr
@@ -401,7 +414,14 @@ impl Peer {
                 NetworkViewClientMessages::BlockHeadersRequest(hashes)
             }
             PeerMessage::EpochSyncRequest(epoch_id) => {
-                self.last_time_received_epoch_sync_request = Instant::now();
+                let now = Instant::now();
+                if now.duration_since(self.last_time_received_epoch_sync_request)
+                    < Duration::from_millis(EPOCH_SYNC_PEER_TIMEOUT_MS)
+                {
+                    debug!(target: "network", "Peer {} sends epoch sync requests too often", self.peer_info);
+                    return;
+                }
+                self.last_time_received_epoch_sync_request = now;
                 NetworkViewClientMessages::EpochSyncRequest { epoch_id }
             }
             PeerMessage::EpochSyncFinalizationRequest(epoch_id) => {
@@ -589,6 +609,14 @@ impl Peer {
                     Ok(NetworkClientResponses::Ban { ban_reason }) => {
                         act.ban_peer(ctx, ban_reason);
                     }
+                    Ok(NetworkClientResponses::ValidChunkPart) => {
+                        if let Some(peer_id) = act.peer_id() {
+                            act.peer_manager_addr.do_send(PeerRequest::ReputationEvent(
+                                peer_id,
+                                PeerReputationEvent::ValidChunkPart,
+                            ));
+                        }
+                    }
                     Err(err) => {
                         error!(
                             target: "network",
@@ -634,6 +662,12 @@ impl Actor for Peer {
         near_performance_metrics::actix::run_later(ctx, self.handshake_timeout, move |act, ctx| {
             if act.peer_status != PeerStatus::Ready {
                 info!(target: "network", "Handshake timeout expired for {}", act.peer_info);
+                if let Some(peer_id) = act.peer_id() {
+                    act.peer_manager_addr.do_send(PeerRequest::ReputationEvent(
+                        peer_id,
+                        PeerReputationEvent::Timeout,
+                    ));
+                }
                 ctx.stop();
             }
         });
@@ -690,6 +724,15 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for Peer {
         near_metrics::inc_counter(&metrics::PEER_MESSAGE_RECEIVED_TOTAL);
 
         self.tracker.increment_received(msg.len() as u64);
+        let compressed_len = if codec::is_compressed(&msg) { Some(msg.len()) } else { None };
+        let msg = match codec::decompress_peer_message(msg, self.compress_messages) {
+            Ok(msg) => msg,
+            Err(err) => {
+                info!(target: "network", "Received invalid compressed data from {}: {}", self.peer_info, err);
+                self.ban_peer(ctx, ReasonForBan::Abusive);
+                return;
+            }
+        };
         if codec::is_forward_tx(&msg).unwrap_or(false) {
             let r = self.txns_since_last_block.load(Ordering::Acquire);
             if r > MAX_TXNS_PER_BLOCK_MESSAGE {
@@ -747,6 +790,10 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for Peer {
             msg.len() as u64,
         );
 
+        if let Some(compressed_len) = compressed_len {
+            codec::record_compressed_message("received", &peer_msg, msg.len(), compressed_len);
+        }
+
         if let PeerMessage::HandshakeV2(handshake) = peer_msg {
             peer_msg = PeerMessage::Handshake(handshake.into());
         }
@@ -794,6 +841,7 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for Peer {
 
                 let target_version = std::cmp::min(handshake.version, PROTOCOL_VERSION);
                 self.protocol_version = target_version;
+                self.compress_messages = codec::supports_compression(self.protocol_version);
 
                 if handshake.chain_info.genesis_id != self.genesis_id {
                     debug!(target: "network", "Received connection from node with different genesis.");
@@ -1029,14 +1077,21 @@ impl Handler<SendMessage> for Peer {
     }
 }
 
-impl Handler<Arc<SendMessage>> for Peer {
+impl Handler<Arc<BroadcastMessage>> for Peer {
     type Result = ();
 
     #[perf]
-    fn handle(&mut self, msg: Arc<SendMessage>, _: &mut Self::Context) {
+    fn handle(&mut self, msg: Arc<BroadcastMessage>, _: &mut Self::Context) {
         #[cfg(feature = "delay_detector")]
         let _d = DelayDetector::new("send message".into());
-        self.send_message(&msg.as_ref().message);
+        if !self.should_send(&msg.message) {
+            return;
+        }
+        let bytes = match &msg.compressed_bytes {
+            Some(compressed_bytes) if self.compress_messages => compressed_bytes.clone(),
+            _ => msg.bytes.clone(),
+        };
+        self.send_bytes(&msg.message, bytes);
     }
 }
 
@@ -33,7 +33,7 @@ use near_primitives::utils::from_timestamp;
 use near_store::Store;
 use rand::thread_rng;
 
-use crate::codec::Codec;
+use crate::codec::{peer_message_to_bytes, try_compress_peer_message, Codec};
 use crate::peer::Peer;
 use crate::peer_store::{PeerStore, TrustLevel};
 #[cfg(feature = "test_features")]
@@ -47,14 +47,14 @@ use crate::routing::{
 
 use crate::edge_verifier::EdgeVerifier;
 use crate::types::{
-    AccountOrPeerIdOrHash, Ban, BlockedPorts, Consolidate, ConsolidateResponse, EdgeList,
-    FullPeerInfo, GetRoutingTable, InboundTcpConnect, KnownPeerState, KnownPeerStatus,
+    AccountOrPeerIdOrHash, Ban, BlockedPorts, BroadcastMessage, Consolidate, ConsolidateResponse,
+    EdgeList, FullPeerInfo, GetRoutingTable, InboundTcpConnect, KnownPeerState, KnownPeerStatus,
     KnownProducer, NetworkClientMessages, NetworkConfig, NetworkInfo, NetworkRequests,
     NetworkResponses, NetworkViewClientMessages, NetworkViewClientResponses, OutboundTcpConnect,
-    PeerIdOrHash, PeerInfo, PeerManagerRequest, PeerMessage, PeerRequest, PeerResponse, PeerType,
-    PeersRequest, PeersResponse, Ping, Pong, QueryPeerStats, RawRoutedMessage, ReasonForBan,
-    RoutedMessage, RoutedMessageBody, RoutedMessageFrom, SendMessage, StateResponseInfo, StopMsg,
-    SyncData, Unregister,
+    PeerIdOrHash, PeerInfo, PeerManagerRequest, PeerMessage, PeerReputationEvent, PeerRequest,
+    PeerResponse, PeerType, PeersRequest, PeersResponse, Ping, Pong, QueryPeerStats,
+    RawRoutedMessage, ReasonForBan, RoutedMessage, RoutedMessageBody, RoutedMessageFrom,
+    SendMessage, StateResponseInfo, StopMsg, SyncData, Unregister,
 };
 #[cfg(feature = "test_features")]
 use crate::types::{GetPeerId, GetPeerIdResult, SetAdvOptions};
@@ -90,6 +90,12 @@ const BROADCAST_EDGES_INTERVAL: Duration = Duration::from_millis(50);
 const BROAD_CAST_EDGES_MAX_WORK_ALLOVED: Duration = Duration::from_millis(50);
 /// Delay syncinc for 1 second to avoid race condition
 const WAIT_FOR_SYNC_DELAY: Duration = Duration::from_secs(1);
+/// Outbound connections established faster than this count as low latency.
+const LOW_LATENCY_CONNECT: Duration = Duration::from_millis(100);
+/// How much more reputation an inbound peer needs to replace an active peer at max capacity.
+const INBOUND_REPLACEMENT_REPUTATION_MARGIN: i32 = 100;
+/// Minimum time between two replacements of active peers by inbound ones.
+const INBOUND_REPLACEMENT_INTERVAL: Duration = Duration::from_secs(60);
 
 macro_rules! unwrap_or_error(($obj: expr, $error: expr) => (match $obj {
     Ok(result) => result,
@@ -152,6 +158,8 @@ pub struct PeerManagerActor {
     peer_counter: Arc<AtomicUsize>,
     scheduled_routing_table_update: bool,
     edge_verifier_requests_in_progress: u64,
+    /// When an active peer was last replaced by an inbound one.
+    last_inbound_replacement: Option<Instant>,
 
     #[cfg(feature = "test_features")]
     adv_disable_edge_propagation: bool,
@@ -205,6 +213,7 @@ impl PeerManagerActor {
             peer_counter: Arc::new(AtomicUsize::new(0)),
             scheduled_routing_table_update: false,
             edge_verifier_requests_in_progress: 0,
+            last_inbound_replacement: None,
             #[cfg(feature = "test_features")]
             adv_disable_edge_propagation: false,
             #[cfg(feature = "test_features")]
@@ -740,10 +749,10 @@ impl PeerManagerActor {
         (sent_bps, received_bps)
     }
 
-    /// Get a random peer we are not connected to from the known list.
+    /// Get a random peer we are not connected to from the known list, preferring peers with
+    /// higher reputation.
     fn sample_random_peer(&self, ignore_fn: impl Fn(&KnownPeerState) -> bool) -> Option<PeerInfo> {
-        let unconnected_peers = self.peer_store.unconnected_peers(ignore_fn);
-        unconnected_peers.choose(&mut rand::thread_rng()).cloned()
+        self.peer_store.sample_unconnected_peer(ignore_fn)
     }
 
     /// Query current peers for more peers.
@@ -930,17 +939,33 @@ impl PeerManagerActor {
     }
 
     /// Select one peer and send signal to stop connection to it gracefully.
+    /// See `eviction_candidate` for the selection process.
+    fn try_stop_active_connection(&self) {
+        debug!(target: "network", "Trying to stop an active connection. Number of active connections: {}", self.active_peers.len());
+
+        if let Some(peer_id) = self.eviction_candidate() {
+            self.stop_active_connection(&peer_id);
+        }
+    }
+
+    fn stop_active_connection(&self, peer_id: &PeerId) {
+        if let Some(active_peer) = self.active_peers.get(peer_id) {
+            debug!(target: "network", "Stop active connection: {:?}", peer_id);
+            active_peer.addr.do_send(PeerManagerRequest::UnregisterPeer);
+        }
+    }
+
+    /// Select the active peer that is the best to disconnect from.
     /// Selection process:
-    ///     Create a safe set of peers, and among the remaining peers select one at random.
+    ///     Create a safe set of peers, and among the remaining peers select the one with the
+    ///     lowest reputation, breaking ties at random.
     ///     If the number of outbound connections is less or equal than minimum_outbound_connections,
     ///         add all outbound connections to the safe set.
     ///     While the length of the safe set is less than safe_set_size:
     ///         Among all the peers we have received a message within the last peer_recent_time_window,
     ///             find the one we connected earlier and add it to the safe set.
     ///         else break
-    fn try_stop_active_connection(&self) {
-        debug!(target: "network", "Trying to stop an active connection. Number of active connections: {}", self.active_peers.len());
-
+    fn eviction_candidate(&self) -> Option<PeerId> {
         // Build safe set
         let mut safe_set = HashSet::new();
 
@@ -993,7 +1018,7 @@ impl PeerManagerActor {
         }
 
         // Build valid candidate list to choose the peer to be removed. All peers outside the safe set.
-        let candidates = self
+        let mut candidates = self
             .active_peers
             .keys()
             .filter_map(
@@ -1007,12 +1032,26 @@ impl PeerManagerActor {
             )
             .collect::<Vec<_>>();
 
-        if let Some(peer_id) = candidates.choose(&mut rand::thread_rng()) {
-            if let Some(active_peer) = self.active_peers.get(&peer_id) {
-                debug!(target: "network", "Stop active connection: {:?}", peer_id);
-                active_peer.addr.do_send(PeerManagerRequest::UnregisterPeer);
-            }
+        candidates.shuffle(&mut rand::thread_rng());
+        candidates.into_iter().min_by_key(|peer_id| self.peer_store.reputation(peer_id))
+    }
+
+    /// Select the active peer to replace with an inbound peer when at max capacity, if any.
+    /// Only a peer we were connected to before replaces another, if its reputation is higher by at
+    /// least `INBOUND_REPLACEMENT_REPUTATION_MARGIN`, and at most once per
+    /// `INBOUND_REPLACEMENT_INTERVAL`, so that peers can't churn our connections.
+    fn inbound_replacement_candidate(&self, peer_id: &PeerId) -> Option<PeerId> {
+        if self
+            .last_inbound_replacement
+            .map_or(false, |last| last.elapsed() < INBOUND_REPLACEMENT_INTERVAL)
+        {
+            return None;
         }
+        let reputation = self.peer_store.known_reputation(peer_id)?;
+        self.eviction_candidate().filter(|candidate| {
+            reputation
+                >= self.peer_store.reputation(candidate) + INBOUND_REPLACEMENT_REPUTATION_MARGIN
+        })
     }
 
     /// Periodically monitor list of peers and:
@@ -1020,6 +1059,7 @@ impl PeerManagerActor {
     ///  - bootstrap outbound connections from known peers,
     ///  - unban peers that have been banned for awhile,
     ///  - remove expired peers,
+    ///  - save reputation of peers.
     fn monitor_peers(&mut self, ctx: &mut Context<Self>) {
         let mut to_unban = vec![];
         for (peer_id, peer_state) in self.peer_store.iter() {
@@ -1070,6 +1110,10 @@ impl PeerManagerActor {
             "Failed to remove expired peers"
         );
 
+        if let Err(err) = self.peer_store.save_reputations() {
+            error!(target: "network", "Failed to save peer reputations: {}", err);
+        }
+
         // Reschedule the bootstrap peer task, starting of as quick as possible with exponential backoff.
         let wait = if self.monitor_peers_attempts >= EXPONENTIAL_BACKOFF_LIMIT {
             // This is expected to be 60 seconds
@@ -1120,9 +1164,22 @@ impl PeerManagerActor {
     fn broadcast_message(&self, ctx: &mut Context<Self>, msg: SendMessage) {
         // TODO(MarX, #1363): Implement smart broadcasting. (MST)
 
+        if self.active_peers.is_empty() {
+            return;
+        }
+        let bytes = match peer_message_to_bytes(&msg.message) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                error!(target: "network", "Error converting message to bytes: {}", err);
+                return;
+            }
+        };
+        // Compress the message once rather than in the actor of every peer.
+        let compressed_bytes = try_compress_peer_message(&msg.message, &bytes);
+
         // Change message to reference counted to allow sharing with all actors
         // without cloning.
-        let msg = Arc::new(msg);
+        let msg = Arc::new(BroadcastMessage { message: msg.message, bytes, compressed_bytes });
         let mut requests: futures::stream::FuturesUnordered<_> =
             self.active_peers.values().map(|peer| peer.addr.send(Arc::clone(&msg))).collect();
 
@@ -1462,6 +1519,10 @@ impl Actor for PeerManagerActor {
             .then(move |_, _, _| actix::fut::ready(()))
             .spawn(ctx);
 
+        if let Err(err) = self.peer_store.save_reputations() {
+            error!(target: "network", "Failed to save peer reputations: {}", err);
+        }
+
         Running::Stop
     }
 }
@@ -1560,6 +1621,10 @@ impl Handler<NetworkRequests> for PeerManagerActor {
                 self.try_ban_peer(ctx, &peer_id, ban_reason);
                 NetworkResponses::NoResponse
             }
+            NetworkRequests::ReportPeer { peer_id, event } => {
+                self.peer_store.update_reputation(&peer_id, event);
+                NetworkResponses::NoResponse
+            }
             NetworkRequests::AnnounceAccount(announce_account) => {
                 self.announce_account(ctx, announce_account);
                 NetworkResponses::NoResponse
@@ -1930,12 +1995,19 @@ impl Handler<OutboundTcpConnect> for PeerManagerActor {
             // Why exactly a second? It was hard-coded in a library we used
             // before, so we keep it to preserve behavior. Removing the timeout
             // completely was observed to break stuff for real on the testnet.
+            let connect_start = Instant::now();
             tokio::time::timeout(Duration::from_secs(1), TcpStream::connect(addr))
                 .into_actor(self)
                 .then(move |res, act, ctx| match res {
                     Ok(res) => match res {
                         Ok(stream) => {
                             debug!(target: "network", "Connecting to {}", msg.peer_info);
+                            if connect_start.elapsed() < LOW_LATENCY_CONNECT {
+                                act.peer_store.update_reputation(
+                                    &msg.peer_info.id,
+                                    PeerReputationEvent::LowLatency,
+                                );
+                            }
                             let edge_info = act.propose_edge(msg.peer_info.id.clone(), None);
 
                             act.try_connect_peer(
@@ -1956,6 +2028,8 @@ impl Handler<OutboundTcpConnect> for PeerManagerActor {
                     Err(err) => {
                         info!(target: "network", "Error connecting to {}: {}", addr, err);
                         act.outgoing_peers.remove(&msg.peer_info.id);
+                        act.peer_store
+                            .update_reputation(&msg.peer_info.id, PeerReputationEvent::Timeout);
                         actix::fut::ready(())
                     }
                 })
@@ -2001,11 +2075,19 @@ impl Handler<Consolidate> for PeerManagerActor {
             }
         }
 
-        if msg.peer_type == PeerType::Inbound && !self.is_inbound_allowed() {
-            // TODO(1896): Gracefully drop inbound connection for other peer.
-            debug!(target: "network", "Inbound connection dropped (network at max capacity).");
-            return ConsolidateResponse::Reject;
-        }
+        // Make room for the peer if it has much better reputation than the worst active peer.
+        let replaced_peer = if msg.peer_type == PeerType::Inbound && !self.is_inbound_allowed() {
+            match self.inbound_replacement_candidate(&msg.peer_info.id) {
+                Some(peer_id) => Some(peer_id),
+                None => {
+                    // TODO(1896): Gracefully drop inbound connection for other peer.
+                    debug!(target: "network", "Inbound connection dropped (network at max capacity).");
+                    return ConsolidateResponse::Reject;
+                }
+            }
+        } else {
+            None
+        };
 
         if msg.other_edge_info.nonce == 0 {
             debug!(target: "network", "Invalid nonce. It must be greater than 0. nonce={}", msg.other_edge_info.nonce);
@@ -2035,6 +2117,12 @@ impl Handler<Consolidate> for PeerManagerActor {
 
         let edge_info_response = if require_response { Some(edge_info.clone()) } else { None };
 
+        if let Some(peer_id) = replaced_peer {
+            debug!(target: "network", "Network at max capacity, replacing {:?} with {:?}", peer_id, msg.peer_info.id);
+            self.last_inbound_replacement = Some(Instant::now());
+            self.stop_active_connection(&peer_id);
+        }
+
         // TODO: double check that address is connectable and add account id.
         self.register_peer(
             FullPeerInfo {
@@ -2189,6 +2277,10 @@ impl Handler<PeerRequest> for PeerManagerActor {
                 }
                 PeerResponse::NoResponse
             }
+            PeerRequest::ReputationEvent(peer_id, event) => {
+                self.peer_store.update_reputation(&peer_id, event);
+                PeerResponse::NoResponse
+            }
         }
     }
 }
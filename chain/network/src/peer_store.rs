@@ -1,6 +1,6 @@
 use std::collections::{
     hash_map::{Entry, Iter},
-    HashMap,
+    HashMap, HashSet,
 };
 use std::convert::TryInto;
 use std::net::SocketAddr;
@@ -16,7 +16,10 @@ use near_primitives::network::PeerId;
 use near_primitives::utils::to_timestamp;
 use near_store::{ColPeers, Store};
 
-use crate::types::{KnownPeerState, KnownPeerStatus, NetworkConfig, PeerInfo, ReasonForBan};
+use crate::types::{
+    KnownPeerState, KnownPeerStatus, NetworkConfig, PeerInfo, PeerReputationEvent, ReasonForBan,
+    MIN_PEER_REPUTATION,
+};
 
 /// Level of trust we have about a new (PeerId, Addr) pair.
 #[derive(Eq, PartialEq, Debug, Clone)]
@@ -52,6 +55,8 @@ pub struct PeerStore {
     // It can happens that some peers don't have known address, so
     // they will not be present in this list, otherwise they will be present.
     addr_peers: HashMap<SocketAddr, VerifiedPeer>,
+    // Peers whose reputation changed since it was last saved to storage.
+    changed_reputations: HashSet<PeerId>,
 }
 
 impl PeerStore {
@@ -99,6 +104,7 @@ impl PeerStore {
                 if peer_state.status.is_banned() {
                     current_peer_state.status = peer_state.status;
                 }
+                current_peer_state.reputation = peer_state.reputation;
                 continue;
             }
 
@@ -109,7 +115,7 @@ impl PeerStore {
                 }
             }
         }
-        Ok(PeerStore { store, peer_states, addr_peers })
+        Ok(PeerStore { store, peer_states, addr_peers, changed_reputations: HashSet::default() })
     }
 
     pub fn len(&self) -> usize {
@@ -162,6 +168,7 @@ impl PeerStore {
         if let Some(peer_state) = self.peer_states.get_mut(peer_id) {
             peer_state.last_seen = to_timestamp(Utc::now());
             peer_state.status = KnownPeerStatus::Banned(ban_reason, to_timestamp(Utc::now()));
+            peer_state.update_reputation(PeerReputationEvent::InvalidData);
             let mut store_update = self.store.store_update();
             store_update.set_ser(ColPeers, &peer_id.try_to_vec()?, peer_state)?;
             store_update.commit().map_err(|err| err.into())
@@ -181,6 +188,40 @@ impl PeerStore {
         }
     }
 
+    /// Returns reputation of the peer, unknown peers have a neutral one.
+    pub fn reputation(&self, peer_id: &PeerId) -> i32 {
+        self.peer_states.get(peer_id).map_or(0, |peer_state| peer_state.reputation)
+    }
+
+    /// Returns reputation of the peer if we were connected to it before, so that its reputation
+    /// reflects how it behaved.
+    pub fn known_reputation(&self, peer_id: &PeerId) -> Option<i32> {
+        self.peer_states
+            .get(peer_id)
+            .filter(|peer_state| peer_state.status != KnownPeerStatus::Unknown)
+            .map(|peer_state| peer_state.reputation)
+    }
+
+    /// Updates reputation of the peer in memory. Use `save_reputations` to persist it, reputation
+    /// changes too often to write every update to storage.
+    pub fn update_reputation(&mut self, peer_id: &PeerId, event: PeerReputationEvent) {
+        if let Some(peer_state) = self.peer_states.get_mut(peer_id) {
+            peer_state.update_reputation(event);
+            self.changed_reputations.insert(peer_id.clone());
+        }
+    }
+
+    /// Saves peers whose reputation changed since the last call.
+    pub fn save_reputations(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut store_update = self.store.store_update();
+        for peer_id in self.changed_reputations.drain() {
+            if let Some(peer_state) = self.peer_states.get(&peer_id) {
+                store_update.set_ser(ColPeers, &peer_id.try_to_vec()?, peer_state)?;
+            }
+        }
+        store_update.commit().map_err(|err| err.into())
+    }
+
     fn find_peers<F>(&self, mut filter: F, count: u32) -> Vec<PeerInfo>
     where
         F: FnMut(&KnownPeerState) -> bool,
@@ -204,14 +245,25 @@ impl PeerStore {
     /// Return unconnected or peers with unknown status that we can try to connect to.
     /// Peers with unknown addresses are filtered out.
     pub fn unconnected_peers(&self, ignore_fn: impl Fn(&KnownPeerState) -> bool) -> Vec<PeerInfo> {
-        self.find_peers(
-            |p| {
-                (p.status == KnownPeerStatus::NotConnected || p.status == KnownPeerStatus::Unknown)
-                    && !ignore_fn(p)
-                    && p.peer_info.addr.is_some()
-            },
-            0,
-        )
+        self.find_peers(|p| is_connectable(p) && !ignore_fn(p), 0)
+    }
+
+    /// Return random unconnected peer that we can try to connect to. The chance of a peer being
+    /// picked grows with its reputation, peers with the lowest reputation are still picked
+    /// now and then, so that they can improve it.
+    pub fn sample_unconnected_peer(
+        &self,
+        ignore_fn: impl Fn(&KnownPeerState) -> bool,
+    ) -> Option<PeerInfo> {
+        let peers = self
+            .peer_states
+            .values()
+            .filter(|p| is_connectable(p) && !ignore_fn(p))
+            .collect::<Vec<_>>();
+        peers
+            .choose_weighted(&mut thread_rng(), |p| (p.reputation - MIN_PEER_REPUTATION + 1) as u32)
+            .ok()
+            .map(|p| p.peer_info.clone())
     }
 
     /// Return healthy known peers up to given amount.
@@ -379,6 +431,12 @@ impl PeerStore {
     }
 }
 
+fn is_connectable(peer_state: &KnownPeerState) -> bool {
+    (peer_state.status == KnownPeerStatus::NotConnected
+        || peer_state.status == KnownPeerStatus::Unknown)
+        && peer_state.peer_info.addr.is_some()
+}
+
 #[cfg(test)]
 mod test {
     use near_crypto::{KeyType, SecretKey};
@@ -427,6 +485,66 @@ mod test {
         }
     }
 
+    #[test]
+    fn reputation_store() {
+        let tmp_dir = tempfile::Builder::new().prefix("_test_store_reputation").tempdir().unwrap();
+        let peer_info_good = gen_peer_info(0);
+        let peer_info_bad = gen_peer_info(1);
+        let boot_nodes = vec![peer_info_good.clone(), peer_info_bad.clone()];
+        {
+            let store = create_store(tmp_dir.path());
+            let mut peer_store = PeerStore::new(store, &boot_nodes).unwrap();
+            peer_store.update_reputation(&peer_info_good.id, PeerReputationEvent::ValidBlock);
+            peer_store.update_reputation(&peer_info_bad.id, PeerReputationEvent::Timeout);
+            peer_store.save_reputations().unwrap();
+            peer_store.peer_ban(&peer_info_bad.id, ReasonForBan::BadBlock).unwrap();
+            peer_store.peer_unban(&peer_info_bad.id).unwrap();
+        }
+        {
+            let store_new = create_store(tmp_dir.path());
+            let peer_store_new = PeerStore::new(store_new, &boot_nodes).unwrap();
+            assert_eq!(
+                peer_store_new.reputation(&peer_info_good.id),
+                PeerReputationEvent::ValidBlock.score()
+            );
+            assert_eq!(
+                peer_store_new.reputation(&peer_info_bad.id),
+                PeerReputationEvent::Timeout.score() + PeerReputationEvent::InvalidData.score()
+            );
+        }
+    }
+
+    #[test]
+    fn known_reputation() {
+        let store = create_test_store();
+        let peer_info_a = gen_peer_info(0);
+        let peer_info_b = gen_peer_info(1);
+        let mut peer_store = PeerStore::new(store, &[peer_info_a.clone()]).unwrap();
+        peer_store.update_reputation(&peer_info_a.id, PeerReputationEvent::ValidBlock);
+        assert_eq!(peer_store.known_reputation(&peer_info_a.id), None);
+        assert_eq!(peer_store.known_reputation(&peer_info_b.id), None);
+
+        peer_store.peer_connected(&peer_info_a).unwrap();
+        peer_store.peer_disconnected(&peer_info_a.id).unwrap();
+        assert_eq!(
+            peer_store.known_reputation(&peer_info_a.id),
+            Some(PeerReputationEvent::ValidBlock.score())
+        );
+    }
+
+    #[test]
+    fn reputation_bounds() {
+        let store = create_test_store();
+        let peer_info = gen_peer_info(0);
+        let mut peer_store = PeerStore::new(store, &[peer_info.clone()]).unwrap();
+        for _ in 0..1000 {
+            peer_store.update_reputation(&peer_info.id, PeerReputationEvent::InvalidData);
+        }
+        assert_eq!(peer_store.reputation(&peer_info.id), MIN_PEER_REPUTATION);
+        // Even the worst peer can still be picked, there is no other one.
+        assert_eq!(peer_store.sample_unconnected_peer(|_| false), Some(peer_info));
+    }
+
     fn check_exist(
         peer_store: &PeerStore,
         peer_id: &PeerId,
@@ -25,6 +25,8 @@ use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::sharding::{PartialEncodedChunk, PartialEncodedChunkWithArcReceipts};
 use near_primitives::syncing::{EpochSyncFinalizationResponse, EpochSyncResponse};
 use near_primitives::transaction::SignedTransaction;
+#[cfg(feature = "sandbox")]
+use near_primitives::types::BlockHeightDelta;
 use near_primitives::types::{AccountId, BlockReference, EpochId, ShardId};
 use near_primitives::version::{
     ProtocolVersion, OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION, PROTOCOL_VERSION,
@@ -431,6 +433,17 @@ pub struct SendMessage {
     pub message: PeerMessage,
 }
 
+/// Message to send to all active peers, serialized and compressed once for all of them.
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+pub struct BroadcastMessage {
+    pub message: PeerMessage,
+    pub bytes: Vec<u8>,
+    /// Compressed `bytes`, for peers which negotiated compression. `None` if the message is not
+    /// worth compressing.
+    pub compressed_bytes: Option<Vec<u8>>,
+}
+
 /// Actor message to consolidate potential new peer.
 /// Returns if connection should be kept or dropped.
 pub struct Consolidate {
@@ -519,6 +532,7 @@ pub enum PeerRequest {
     RouteBack(Box<RoutedMessageBody>, CryptoHash),
     UpdatePeerInfo(PeerInfo),
     ReceivedMessage(PeerId, Instant),
+    ReputationEvent(PeerId, PeerReputationEvent),
 }
 
 impl Message for PeerRequest {
@@ -599,6 +613,11 @@ pub enum NetworkRequests {
         peer_id: PeerId,
         ban_reason: ReasonForBan,
     },
+    /// Update reputation of given peer.
+    ReportPeer {
+        peer_id: PeerId,
+        event: PeerReputationEvent,
+    },
     /// Announce account
     AnnounceAccount(AnnounceAccount),
 
@@ -798,8 +817,12 @@ pub enum NetworkClientResponses {
     NoResponse,
     /// Valid transaction inserted into mempool as response to Transaction.
     ValidTx,
+    /// Valid chunk part as response to chunk messages.
+    ValidChunkPart,
     /// Invalid transaction inserted into mempool as response to Transaction.
     InvalidTx(InvalidTxError),
+    /// Valid transaction that didn't fit into the full mempool.
+    TransactionPoolFull,
     /// The request is routed to other shards
     RequestRouted,
     /// The node being queried does not track the shard needed and therefore cannot provide userful
@@ -813,6 +836,11 @@ pub enum NetworkClientResponses {
 #[derive(Eq, PartialEq, Debug)]
 pub enum SandboxResponse {
     SandboxPatchStateFinished(bool),
+    SandboxFastForwardBlocksLeft(BlockHeightDelta),
+    SandboxShiftTimeFailed(String),
+    SandboxSnapshotTaken(u64),
+    SandboxRestoreFinished(bool),
+    SandboxRestoreFailed(String),
 }
 
 impl<A, M> MessageResponse<A, M> for NetworkClientResponses
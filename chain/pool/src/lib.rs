@@ -1,3 +1,6 @@
+#[macro_use]
+extern crate lazy_static;
+
 use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 
 use crate::types::{PoolIterator, PoolKey, TransactionGroup};
@@ -7,18 +10,77 @@ use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::AccountId;
 use rand::RngCore;
+use serde::{Deserialize, Serialize};
 use std::ops::Bound;
 
+mod metrics;
 pub mod types;
 
+/// Limits of a single transaction pool.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct TransactionPoolConfig {
+    /// Maximum number of transactions in the pool.
+    pub max_transactions: usize,
+    /// Maximum total size of the transactions in the pool, in bytes.
+    pub max_size_bytes: u64,
+    /// Maximum number of transactions of a single signer account in the pool.
+    pub max_transactions_per_signer: usize,
+}
+
+impl Default for TransactionPoolConfig {
+    fn default() -> Self {
+        TransactionPoolConfig {
+            max_transactions: 100_000,
+            max_size_bytes: 100 * 1024 * 1024,
+            max_transactions_per_signer: 1_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertTransactionResult {
+    /// Transaction was inserted into the pool.
+    Success,
+    /// Transaction is already in the pool.
+    Duplicate,
+    /// Transaction doesn't fit into the pool, or its signer already has the maximum number of
+    /// transactions in the pool.
+    NoSpaceLeft,
+}
+
+/// Bookkeeping of a transaction in the pool, used to enforce the limits.
+struct PoolEntry {
+    key: PoolKey,
+    signer_id: AccountId,
+    /// Position of the transaction in the order of insertion into the pool.
+    age: u64,
+    size: u64,
+}
+
 /// Transaction pool: keeps track of transactions that were not yet accepted into the block chain.
+///
+/// When the pool is full, inserting a transaction evicts the oldest transactions in the pool.
+/// Transactions which stayed in the pool the longest are the most likely to have expired or to
+/// never become executable, and the limit per signer keeps a single account from pushing out
+/// everyone else.
 pub struct TransactionPool {
     /// Transactions are grouped by a pair of (account ID, signer public key).
     /// NOTE: It's more efficient on average to keep transactions unsorted and with potentially
     /// conflicting nonce than to create a BTreeMap for every transaction.
     pub transactions: BTreeMap<PoolKey, Vec<SignedTransaction>>,
-    /// Set of all hashes to quickly check if the given transaction is in the pool.
-    pub unique_transactions: HashSet<CryptoHash>,
+    /// All transactions in the pool by hash, to quickly check if the given transaction is in the
+    /// pool.
+    unique_transactions: HashMap<CryptoHash, PoolEntry>,
+    /// Number of transactions in the pool per signer account.
+    signer_transaction_counts: HashMap<AccountId, usize>,
+    /// Transactions in the pool by their age, oldest first.
+    transactions_by_age: BTreeMap<u64, CryptoHash>,
+    /// Age of the next transaction inserted into the pool.
+    next_age: u64,
+    /// Total size of the transactions in the pool.
+    total_size: u64,
+    config: TransactionPoolConfig,
     /// A uniquely generated key seed to randomize PoolKey order.
     key_seed: Vec<u8>,
     /// The key after which the pool iterator starts. Doesn't have to be present in the pool.
@@ -26,11 +88,16 @@ pub struct TransactionPool {
 }
 
 impl TransactionPool {
-    pub fn new() -> Self {
+    pub fn new(config: TransactionPoolConfig) -> Self {
         Self {
             key_seed: rand::thread_rng().next_u64().to_le_bytes().to_vec(),
             transactions: BTreeMap::new(),
-            unique_transactions: HashSet::new(),
+            unique_transactions: HashMap::new(),
+            signer_transaction_counts: HashMap::new(),
+            transactions_by_age: BTreeMap::new(),
+            next_age: 0,
+            total_size: 0,
+            config,
             last_used_key: CryptoHash::default(),
         }
     }
@@ -43,17 +110,82 @@ impl TransactionPool {
     }
 
     /// Insert a signed transaction into the pool that passed validation.
-    pub fn insert_transaction(&mut self, signed_transaction: SignedTransaction) -> bool {
-        if !self.unique_transactions.insert(signed_transaction.get_hash()) {
-            return false;
+    /// Evicts transactions if the pool is full, see `TransactionPool`.
+    pub fn insert_transaction(
+        &mut self,
+        signed_transaction: SignedTransaction,
+    ) -> InsertTransactionResult {
+        let tx_hash = signed_transaction.get_hash();
+        if self.unique_transactions.contains_key(&tx_hash) {
+            return InsertTransactionResult::Duplicate;
         }
         let signer_id = &signed_transaction.transaction.signer_id;
-        let signer_public_key = &signed_transaction.transaction.public_key;
-        self.transactions
-            .entry(self.key(signer_id, signer_public_key))
-            .or_insert_with(Vec::new)
-            .push(signed_transaction);
-        true
+        let size = signed_transaction.get_size();
+        if size > self.config.max_size_bytes {
+            near_metrics::inc_counter_vec(&metrics::TRANSACTION_POOL_REJECTED, &["too_large"]);
+            return InsertTransactionResult::NoSpaceLeft;
+        }
+        if self.signer_transaction_counts.get(signer_id).copied().unwrap_or(0)
+            >= self.config.max_transactions_per_signer
+        {
+            near_metrics::inc_counter_vec(&metrics::TRANSACTION_POOL_REJECTED, &["signer_limit"]);
+            return InsertTransactionResult::NoSpaceLeft;
+        }
+
+        let signer_id = signer_id.clone();
+        let key = self.key(&signer_id, &signed_transaction.transaction.public_key);
+        let age = self.next_age;
+        self.next_age += 1;
+        self.total_size += size;
+        *self.signer_transaction_counts.entry(signer_id.clone()).or_insert(0) += 1;
+        self.transactions_by_age.insert(age, tx_hash);
+        self.unique_transactions.insert(tx_hash, PoolEntry { key, signer_id, age, size });
+        self.transactions.entry(key).or_insert_with(Vec::new).push(signed_transaction);
+
+        while self.len() > self.config.max_transactions
+            || self.total_size > self.config.max_size_bytes
+        {
+            if self.evict_transaction() == tx_hash {
+                near_metrics::inc_counter_vec(&metrics::TRANSACTION_POOL_REJECTED, &["pool_limit"]);
+                return InsertTransactionResult::NoSpaceLeft;
+            }
+            near_metrics::inc_counter(&metrics::TRANSACTION_POOL_EVICTED);
+        }
+        InsertTransactionResult::Success
+    }
+
+    /// Removes the oldest transaction in the pool and returns its hash. The pool must not be
+    /// empty.
+    fn evict_transaction(&mut self) -> CryptoHash {
+        let (_, &tx_hash) =
+            self.transactions_by_age.iter().next().expect("evicting from an empty pool");
+        let key = self.forget_transaction(&tx_hash).expect("transactions by age are in the pool");
+        let mut remove_entry = false;
+        if let Some(v) = self.transactions.get_mut(&key) {
+            v.retain(|tx| tx.get_hash() != tx_hash);
+            remove_entry = v.is_empty();
+        }
+        if remove_entry {
+            self.transactions.remove(&key);
+        }
+        tx_hash
+    }
+
+    /// Removes the bookkeeping of the transaction, but not the transaction itself.
+    /// Returns the key of its group if the transaction was in the pool.
+    fn forget_transaction(&mut self, tx_hash: &CryptoHash) -> Option<PoolKey> {
+        let entry = self.unique_transactions.remove(tx_hash)?;
+        self.total_size -= entry.size;
+        self.transactions_by_age.remove(&entry.age);
+        let count = self
+            .signer_transaction_counts
+            .get_mut(&entry.signer_id)
+            .expect("signers of transactions in the pool are counted");
+        *count -= 1;
+        if *count == 0 {
+            self.signer_transaction_counts.remove(&entry.signer_id);
+        }
+        Some(entry.key)
     }
 
     /// Returns a pool iterator wrapper that implements an iterator like trait to iterate over
@@ -68,13 +200,9 @@ impl TransactionPool {
     pub fn remove_transactions(&mut self, transactions: &[SignedTransaction]) {
         let mut grouped_transactions = HashMap::new();
         for tx in transactions {
-            if self.unique_transactions.contains(&tx.get_hash()) {
-                let signer_id = &tx.transaction.signer_id;
-                let signer_public_key = &tx.transaction.public_key;
-                grouped_transactions
-                    .entry(self.key(signer_id, signer_public_key))
-                    .or_insert_with(HashSet::new)
-                    .insert(tx.get_hash());
+            let tx_hash = tx.get_hash();
+            if let Some(key) = self.forget_transaction(&tx_hash) {
+                grouped_transactions.entry(key).or_insert_with(HashSet::new).insert(tx_hash);
             }
         }
         for (key, hashes) in grouped_transactions {
@@ -86,9 +214,6 @@ impl TransactionPool {
             if remove_entry {
                 self.transactions.remove(&key);
             }
-            for hash in hashes {
-                self.unique_transactions.remove(&hash);
-            }
         }
     }
 
@@ -106,6 +231,11 @@ impl TransactionPool {
     pub fn is_empty(&self) -> bool {
         self.unique_transactions.is_empty()
     }
+
+    /// Total size of the transactions in the pool, in bytes.
+    pub fn size_bytes(&self) -> u64 {
+        self.total_size
+    }
 }
 
 /// PoolIterator is a structure to pull transactions from the pool.
@@ -172,7 +302,7 @@ impl<'a> PoolIterator for PoolIteratorWrapper<'a> {
             while let Some(sorted_group) = self.sorted_groups.pop_front() {
                 if sorted_group.transactions.is_empty() {
                     for hash in sorted_group.removed_transaction_hashes {
-                        self.pool.unique_transactions.remove(&hash);
+                        self.pool.forget_transaction(&hash);
                     }
                 } else {
                     self.sorted_groups.push_back(sorted_group);
@@ -191,7 +321,7 @@ impl<'a> Drop for PoolIteratorWrapper<'a> {
     fn drop(&mut self) {
         for group in self.sorted_groups.drain(..) {
             for hash in group.removed_transaction_hashes {
-                self.pool.unique_transactions.remove(&hash);
+                self.pool.forget_transaction(&hash);
             }
             if !group.transactions.is_empty() {
                 self.pool.transactions.insert(group.key, group.transactions);
@@ -241,7 +371,7 @@ mod tests {
         mut transactions: Vec<SignedTransaction>,
         expected_weight: u32,
     ) -> (Vec<u64>, TransactionPool) {
-        let mut pool = TransactionPool::new();
+        let mut pool = TransactionPool::new(TransactionPoolConfig::default());
         let mut rng = thread_rng();
         transactions.shuffle(&mut rng);
         for tx in transactions {
@@ -352,7 +482,7 @@ mod tests {
             })
             .collect::<Vec<_>>();
 
-        let mut pool = TransactionPool::new();
+        let mut pool = TransactionPool::new(TransactionPoolConfig::default());
         let mut rng = thread_rng();
         transactions.shuffle(&mut rng);
         for tx in transactions.clone() {
@@ -453,4 +583,120 @@ mod tests {
         new_nonces.sort();
         assert_ne!(nonces, new_nonces);
     }
+
+    fn signers_and_nonces(pool: &mut TransactionPool) -> Vec<(String, u64)> {
+        let mut transactions: Vec<_> = prepare_transactions(pool, 100)
+            .iter()
+            .map(|tx| (tx.transaction.signer_id.to_string(), tx.transaction.nonce))
+            .collect();
+        transactions.sort();
+        transactions
+    }
+
+    /// Check that a full pool evicts its oldest transactions, regardless of their signer and
+    /// nonce.
+    #[test]
+    fn test_max_transactions_evicts_oldest() {
+        let config = TransactionPoolConfig { max_transactions: 4, ..Default::default() };
+        let mut pool = TransactionPool::new(config);
+        let mut transactions = generate_transactions("alice.near", "alice.near", 3, 4);
+        transactions.extend(generate_transactions("bob.near", "bob.near", 1, 2));
+        transactions.extend(generate_transactions("alice.near", "alice.near", 1, 2));
+        let results: Vec<_> =
+            transactions.iter().map(|tx| pool.insert_transaction(tx.clone())).collect();
+        assert_eq!(results, vec![InsertTransactionResult::Success; 6]);
+        assert_eq!(pool.len(), 4);
+        // Bob's transactions are newer than Alice's first ones, so they stayed in the pool.
+        assert_eq!(
+            pool.insert_transaction(transactions[2].clone()),
+            InsertTransactionResult::Duplicate
+        );
+        assert_eq!(
+            signers_and_nonces(&mut pool),
+            vec![
+                ("alice.near".to_string(), 1),
+                ("alice.near".to_string(), 2),
+                ("bob.near".to_string(), 1),
+                ("bob.near".to_string(), 2)
+            ]
+        );
+        assert!(pool.is_empty());
+        assert_eq!(pool.size_bytes(), 0);
+    }
+
+    /// Check that transactions removed from the pool don't count towards its age order.
+    #[test]
+    fn test_eviction_skips_removed_transactions() {
+        let config = TransactionPoolConfig { max_transactions: 2, ..Default::default() };
+        let mut pool = TransactionPool::new(config);
+        let transactions = generate_transactions("alice.near", "alice.near", 1, 3);
+        for tx in &transactions[..2] {
+            assert_eq!(pool.insert_transaction(tx.clone()), InsertTransactionResult::Success);
+        }
+        pool.remove_transactions(&transactions[..1]);
+        let tx = generate_transactions("bob.near", "bob.near", 1, 1).pop().unwrap();
+        assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Success);
+        assert_eq!(
+            pool.insert_transaction(transactions[2].clone()),
+            InsertTransactionResult::Success
+        );
+        assert_eq!(
+            signers_and_nonces(&mut pool),
+            vec![("alice.near".to_string(), 3), ("bob.near".to_string(), 1)]
+        );
+    }
+
+    /// Check that the total size of the pool is limited.
+    #[test]
+    fn test_max_size_bytes() {
+        let transactions = generate_transactions("alice.near", "alice.near", 1, 3);
+        let tx_size = transactions[0].get_size();
+        let config = TransactionPoolConfig { max_size_bytes: tx_size * 2, ..Default::default() };
+        let mut pool = TransactionPool::new(config);
+        let results: Vec<_> =
+            transactions.into_iter().map(|tx| pool.insert_transaction(tx)).collect();
+        assert_eq!(results, vec![InsertTransactionResult::Success; 3]);
+        assert_eq!(pool.len(), 2);
+        assert_eq!(pool.size_bytes(), tx_size * 2);
+        assert_eq!(
+            signers_and_nonces(&mut pool),
+            vec![("alice.near".to_string(), 2), ("alice.near".to_string(), 3)]
+        );
+
+        let config = TransactionPoolConfig { max_size_bytes: tx_size - 1, ..Default::default() };
+        let mut pool = TransactionPool::new(config);
+        let tx = generate_transactions("alice.near", "alice.near", 1, 1).pop().unwrap();
+        assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::NoSpaceLeft);
+        assert!(pool.is_empty());
+    }
+
+    /// Check that a single signer can't take over the pool, even with several access keys.
+    #[test]
+    fn test_max_transactions_per_signer() {
+        let config = TransactionPoolConfig { max_transactions_per_signer: 3, ..Default::default() };
+        let mut pool = TransactionPool::new(config);
+        let mut transactions = generate_transactions("alice.near", "alice.near", 1, 2);
+        transactions.extend(generate_transactions("alice.near", "bob.near", 21, 22));
+        let results: Vec<_> =
+            transactions.iter().map(|tx| pool.insert_transaction(tx.clone())).collect();
+        assert_eq!(
+            results,
+            vec![
+                InsertTransactionResult::Success,
+                InsertTransactionResult::Success,
+                InsertTransactionResult::Success,
+                InsertTransactionResult::NoSpaceLeft
+            ]
+        );
+        let tx = generate_transactions("bob.near", "bob.near", 1, 1).pop().unwrap();
+        assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Success);
+
+        // Transactions removed from the pool free up the signer's slots.
+        pool.remove_transactions(&transactions[..1]);
+        assert_eq!(pool.len(), 3);
+        assert_eq!(
+            pool.insert_transaction(transactions[3].clone()),
+            InsertTransactionResult::Success
+        );
+    }
 }
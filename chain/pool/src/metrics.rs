@@ -0,0 +1,15 @@
+use near_metrics::{try_create_int_counter, try_create_int_counter_vec, IntCounter, IntCounterVec};
+
+lazy_static! {
+    pub static ref TRANSACTION_POOL_EVICTED: near_metrics::Result<IntCounter> =
+        try_create_int_counter(
+            "near_transaction_pool_evicted_total",
+            "Total number of transactions evicted from a full transaction pool"
+        );
+    pub static ref TRANSACTION_POOL_REJECTED: near_metrics::Result<IntCounterVec> =
+        try_create_int_counter_vec(
+            "near_transaction_pool_rejected_total",
+            "Total number of transactions not inserted into the transaction pool due to its limits",
+            &["reason"]
+        );
+}
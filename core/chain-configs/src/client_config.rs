@@ -1,9 +1,11 @@
 //! Chain Client Configuration
 use std::cmp::min;
+use std::path::PathBuf;
 use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 
+use near_primitives::shard_layout::ShardUId;
 use near_primitives::types::{AccountId, BlockHeightDelta, Gas, NumBlocks, NumSeats, ShardId};
 use near_primitives::version::Version;
 
@@ -53,6 +55,9 @@ pub struct ClientConfig {
     pub header_sync_expected_height_per_second: u64,
     /// How long to wait for a response during state sync
     pub state_sync_timeout: Duration,
+    /// Directory with the state parts dumped by `state-viewer dump_state_parts`. State sync loads
+    /// the state from there instead of requesting it from peers, if the dump has the shard.
+    pub state_sync_parts_dir: Option<PathBuf>,
     /// Minimum number of peers to start syncing.
     pub min_num_peers: usize,
     /// Period between logging summary information.
@@ -85,8 +90,11 @@ pub struct ClientConfig {
     pub gc_blocks_limit: NumBlocks,
     /// Accounts that this client tracks
     pub tracked_accounts: Vec<AccountId>,
-    /// Shards that this client tracks
+    /// Shards that this client tracks. If not empty, all shards are tracked.
     pub tracked_shards: Vec<ShardId>,
+    /// Shards of particular shard layouts that this client tracks, used instead of
+    /// `tracked_shards` if not empty. See `TrackedConfig::Shards`.
+    pub tracked_shard_uids: Vec<ShardUId>,
     /// Not clear old data, set `true` for archive nodes.
     pub archive: bool,
     /// Number of threads for ViewClientActor pool.
@@ -101,6 +109,12 @@ pub struct ClientConfig {
     /// genesis file.  The value only affects the RPCs without influencing the
     /// protocol thus changing it per-node doesn’t affect the blockchain.
     pub max_gas_burnt_view: Option<Gas>,
+    /// Maximum number of transactions in the transaction pool of a shard.
+    pub tx_pool_max_transactions: usize,
+    /// Maximum total size of the transactions in the transaction pool of a shard, in bytes.
+    pub tx_pool_max_size_bytes: u64,
+    /// Maximum number of transactions of a single signer in the transaction pool of a shard.
+    pub tx_pool_max_transactions_per_signer: usize,
 }
 
 impl ClientConfig {
@@ -132,6 +146,7 @@ impl ClientConfig {
             header_sync_progress_timeout: Duration::from_secs(2),
             header_sync_stall_ban_timeout: Duration::from_secs(30),
             state_sync_timeout: Duration::from_secs(TEST_STATE_SYNC_TIMEOUT),
+            state_sync_parts_dir: None,
             header_sync_expected_height_per_second: 1,
             min_num_peers: 1,
             log_summary_period: Duration::from_secs(10),
@@ -152,6 +167,7 @@ impl ClientConfig {
             gc_blocks_limit: 100,
             tracked_accounts: vec![],
             tracked_shards: vec![],
+            tracked_shard_uids: vec![],
             archive,
             log_summary_style: LogSummaryStyle::Colored,
             view_client_threads: 1,
@@ -159,6 +175,9 @@ impl ClientConfig {
             view_client_throttle_period: Duration::from_secs(1),
             trie_viewer_state_size_limit: None,
             max_gas_burnt_view: None,
+            tx_pool_max_transactions: 100_000,
+            tx_pool_max_size_bytes: 100 * 1024 * 1024,
+            tx_pool_max_transactions_per_signer: 1_000,
         }
     }
 }
@@ -43,3 +43,17 @@ impl From<ParseKeyTypeError> for ParseSignatureError {
         }
     }
 }
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum KeyFileError {
+    #[error("invalid key derivation parameters")]
+    InvalidKdfParams,
+    #[error("invalid encoding of {0}")]
+    InvalidEncoding(&'static str),
+    #[error("wrong passphrase or corrupted key file")]
+    Decryption,
+    #[error("invalid secret key: {0}")]
+    InvalidSecretKey(#[from] ParseKeyError),
+    #[error("secret key doesn't match the public key")]
+    KeyMismatch,
+}
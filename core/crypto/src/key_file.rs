@@ -1,14 +1,27 @@
-use std::fs::File;
-use std::io::{Read, Write};
+use std::fs::{self, File};
+use std::io::{self, Write};
 use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
 
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
 
+use crate::errors::KeyFileError;
 use crate::{PublicKey, SecretKey};
 
 use near_account_id::AccountId;
 
+/// Environment variable with the passphrase of encrypted key files.
+pub const KEY_PASSPHRASE_ENV: &str = "NEAR_KEY_PASSPHRASE";
+/// Environment variable with the path of a file holding the passphrase of encrypted key files.
+pub const KEY_PASSPHRASE_FILE_ENV: &str = "NEAR_KEY_PASSPHRASE_FILE";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
 #[derive(Serialize, Deserialize)]
 pub struct KeyFile {
     pub account_id: AccountId,
@@ -18,21 +31,243 @@ pub struct KeyFile {
 
 impl KeyFile {
     pub fn write_to_file(&self, path: &Path) {
-        let mut file = File::create(path).expect("Failed to create / write a key file.");
-        let mut perm =
-            file.metadata().expect("Failed to retrieve key file metadata.").permissions();
-        perm.set_mode(u32::from(libc::S_IWUSR | libc::S_IRUSR));
-        file.set_permissions(perm).expect("Failed to set permissions for a key file.");
-        let str = serde_json::to_string_pretty(self).expect("Error serializing the key file.");
-        if let Err(err) = file.write_all(str.as_bytes()) {
-            panic!("Failed to write a key file {}", err);
-        }
+        write_key_file(path, self);
     }
 
+    /// Reads the key file, decrypting it if it's encrypted. The passphrase is taken from
+    /// `NEAR_KEY_PASSPHRASE`, from the file named by `NEAR_KEY_PASSPHRASE_FILE` or, if neither
+    /// is set, asked for on the terminal.
     pub fn from_file(path: &Path) -> Self {
-        let mut file = File::open(path).expect("Could not open key file.");
-        let mut content = String::new();
-        file.read_to_string(&mut content).expect("Could not read from key file.");
-        serde_json::from_str(&content).expect("Failed to deserialize KeyFile")
+        let content = fs::read_to_string(path).expect("Could not read from key file.");
+        let value: serde_json::Value =
+            serde_json::from_str(&content).expect("Failed to deserialize KeyFile");
+        if value.get("encrypted_secret_key").is_none() {
+            return serde_json::from_value(value).expect("Failed to deserialize KeyFile");
+        }
+        let encrypted_key_file: EncryptedKeyFile =
+            serde_json::from_value(value).expect("Failed to deserialize EncryptedKeyFile");
+        let passphrase = read_passphrase(&format!("Passphrase of {}: ", path.display()), false)
+            .expect("Failed to read the key file passphrase");
+        encrypted_key_file
+            .decrypt(&passphrase)
+            .unwrap_or_else(|err| panic!("Failed to decrypt key file {}: {}", path.display(), err))
+    }
+
+    pub fn encrypt(&self, passphrase: &str) -> EncryptedKeyFile {
+        self.encrypt_with_kdf_params(passphrase, ScryptParams::default())
+    }
+
+    /// Encrypts the secret key with a key derived from `passphrase`. The account id and the
+    /// public key stay readable but are authenticated too.
+    pub fn encrypt_with_kdf_params(
+        &self,
+        passphrase: &str,
+        params: ScryptParams,
+    ) -> EncryptedKeyFile {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        let kdf = Kdf::Scrypt {
+            log_n: params.log_n,
+            r: params.r,
+            p: params.p,
+            salt: bs58::encode(salt).into_string(),
+        };
+        let cipher = kdf.cipher(passphrase).expect("Invalid key derivation parameters");
+        let secret_key = self.secret_key.to_string();
+        let ciphertext = cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: secret_key.as_bytes(),
+                    aad: associated_data(&self.account_id, &self.public_key).as_bytes(),
+                },
+            )
+            .expect("Failed to encrypt the secret key");
+        EncryptedKeyFile {
+            account_id: self.account_id.clone(),
+            public_key: self.public_key.clone(),
+            encrypted_secret_key: EncryptedSecretKey {
+                kdf,
+                nonce: bs58::encode(nonce).into_string(),
+                ciphertext: bs58::encode(ciphertext).into_string(),
+            },
+        }
+    }
+}
+
+/// Cost of deriving the encryption key from a passphrase.
+#[derive(Clone, Copy, Debug)]
+pub struct ScryptParams {
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+}
+
+impl Default for ScryptParams {
+    /// Recommended interactive parameters, taking about 100ms and 32MB to derive the key.
+    fn default() -> Self {
+        Self { log_n: 15, r: 8, p: 1 }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kdf", rename_all = "lowercase")]
+pub enum Kdf {
+    Scrypt { log_n: u8, r: u32, p: u32, salt: String },
+}
+
+impl Kdf {
+    fn cipher(&self, passphrase: &str) -> Result<XChaCha20Poly1305, KeyFileError> {
+        let mut key = [0u8; 32];
+        match self {
+            Kdf::Scrypt { log_n, r, p, salt } => {
+                let salt = decode("salt", salt)?;
+                let params = scrypt::Params::new(*log_n, *r, *p)
+                    .map_err(|_| KeyFileError::InvalidKdfParams)?;
+                scrypt::scrypt(passphrase.as_bytes(), &salt, &params, &mut key)
+                    .map_err(|_| KeyFileError::InvalidKdfParams)?;
+            }
+        }
+        Ok(XChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+}
+
+/// Secret key encrypted with XChaCha20-Poly1305 under a key derived from a passphrase.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EncryptedSecretKey {
+    #[serde(flatten)]
+    pub kdf: Kdf,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EncryptedKeyFile {
+    pub account_id: AccountId,
+    pub public_key: PublicKey,
+    pub encrypted_secret_key: EncryptedSecretKey,
+}
+
+impl EncryptedKeyFile {
+    pub fn write_to_file(&self, path: &Path) {
+        write_key_file(path, self);
+    }
+
+    pub fn decrypt(&self, passphrase: &str) -> Result<KeyFile, KeyFileError> {
+        let encrypted = &self.encrypted_secret_key;
+        let nonce = decode("nonce", &encrypted.nonce)?;
+        if nonce.len() != NONCE_LEN {
+            return Err(KeyFileError::InvalidEncoding("nonce"));
+        }
+        let ciphertext = decode("ciphertext", &encrypted.ciphertext)?;
+        let secret_key = encrypted
+            .kdf
+            .cipher(passphrase)?
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: associated_data(&self.account_id, &self.public_key).as_bytes(),
+                },
+            )
+            .map_err(|_| KeyFileError::Decryption)?;
+        let secret_key: SecretKey = String::from_utf8(secret_key)
+            .map_err(|_| KeyFileError::InvalidEncoding("secret key"))?
+            .parse()?;
+        if secret_key.public_key() != self.public_key {
+            return Err(KeyFileError::KeyMismatch);
+        }
+        Ok(KeyFile {
+            account_id: self.account_id.clone(),
+            public_key: self.public_key.clone(),
+            secret_key,
+        })
+    }
+}
+
+fn associated_data(account_id: &AccountId, public_key: &PublicKey) -> String {
+    format!("{}:{}", account_id, public_key)
+}
+
+fn decode(what: &'static str, value: &str) -> Result<Vec<u8>, KeyFileError> {
+    bs58::decode(value).into_vec().map_err(|_| KeyFileError::InvalidEncoding(what))
+}
+
+/// Writes a key file readable only by its owner. The file is replaced at once, so that
+/// rewriting a key file in place, e.g. to encrypt it, can't lose the key.
+fn write_key_file(path: &Path, key_file: &impl Serialize) {
+    let tmp_path = path.with_extension("tmp");
+    let mut file = File::create(&tmp_path).expect("Failed to create / write a key file.");
+    let mut perm = file.metadata().expect("Failed to retrieve key file metadata.").permissions();
+    perm.set_mode(u32::from(libc::S_IWUSR | libc::S_IRUSR));
+    file.set_permissions(perm).expect("Failed to set permissions for a key file.");
+    let str = serde_json::to_string_pretty(key_file).expect("Error serializing the key file.");
+    if let Err(err) = file.write_all(str.as_bytes()).and_then(|()| file.sync_all()) {
+        panic!("Failed to write a key file {}", err);
+    }
+    fs::rename(&tmp_path, path).expect("Failed to create / write a key file.");
+}
+
+/// Reads a key file passphrase from `NEAR_KEY_PASSPHRASE`, from the first line of the file named
+/// by `NEAR_KEY_PASSPHRASE_FILE` or, if neither is set, from the terminal. A new passphrase
+/// typed in on the terminal has to be confirmed.
+pub fn read_passphrase(prompt: &str, new: bool) -> io::Result<String> {
+    if let Ok(passphrase) = std::env::var(KEY_PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    if let Ok(passphrase_file) = std::env::var(KEY_PASSPHRASE_FILE_ENV) {
+        let content = fs::read_to_string(passphrase_file)?;
+        return Ok(content.lines().next().unwrap_or_default().to_string());
+    }
+    let passphrase = rpassword::read_password_from_tty(Some(prompt))?;
+    if new && rpassword::read_password_from_tty(Some("Repeat the passphrase: "))? != passphrase {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Passphrases don't match"));
+    }
+    Ok(passphrase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyType;
+
+    fn key_file() -> KeyFile {
+        let secret_key = SecretKey::from_seed(KeyType::ED25519, "test");
+        KeyFile {
+            account_id: "test".parse().unwrap(),
+            public_key: secret_key.public_key(),
+            secret_key,
+        }
+    }
+
+    fn fast_params() -> ScryptParams {
+        ScryptParams { log_n: 4, r: 8, p: 1 }
+    }
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let key_file = key_file();
+        let encrypted = key_file.encrypt_with_kdf_params("passphrase", fast_params());
+        let decrypted = encrypted.decrypt("passphrase").unwrap();
+        assert_eq!(decrypted.account_id, key_file.account_id);
+        assert_eq!(decrypted.secret_key, key_file.secret_key);
+
+        assert!(matches!(encrypted.decrypt("wrong"), Err(KeyFileError::Decryption)));
+        let mut tampered = encrypted;
+        tampered.account_id = "other".parse().unwrap();
+        assert!(matches!(tampered.decrypt("passphrase"), Err(KeyFileError::Decryption)));
+    }
+
+    #[test]
+    fn test_encrypted_key_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("validator_key.json");
+        let encrypted = key_file().encrypt_with_kdf_params("passphrase", fast_params());
+        encrypted.write_to_file(&path);
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(!content.contains(&key_file().secret_key.to_string()));
+        assert_eq!(serde_json::from_str::<EncryptedKeyFile>(&content).unwrap(), encrypted);
     }
 }
@@ -1,5 +1,8 @@
-pub use errors::{ParseKeyError, ParseKeyTypeError, ParseSignatureError};
-pub use key_file::KeyFile;
+pub use errors::{KeyFileError, ParseKeyError, ParseKeyTypeError, ParseSignatureError};
+pub use key_file::{
+    read_passphrase, EncryptedKeyFile, EncryptedSecretKey, Kdf, KeyFile, ScryptParams,
+    KEY_PASSPHRASE_ENV, KEY_PASSPHRASE_FILE_ENV,
+};
 pub use signature::{
     ED25519PublicKey, KeyType, PublicKey, Secp256K1PublicKey, Secp256K1Signature, SecretKey,
     Signature,
@@ -56,7 +56,8 @@
 //! ```
 
 pub use prometheus::{
-    Encoder, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, Result, TextEncoder,
+    Encoder, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Result,
+    TextEncoder,
 };
 use prometheus::{HistogramOpts, HistogramTimer, Opts};
 
@@ -98,6 +99,15 @@ pub fn try_create_int_gauge(name: &str, help: &str) -> Result<IntGauge> {
     Ok(gauge)
 }
 
+/// Attempts to crate an `IntGaugeVec`, returning `Err` if the registry does not accept the gauge
+/// (potentially due to naming conflict).
+pub fn try_create_int_gauge_vec(name: &str, help: &str, labels: &[&str]) -> Result<IntGaugeVec> {
+    let opts = Opts::new(name, help);
+    let gauge = IntGaugeVec::new(opts, labels)?;
+    prometheus::register(Box::new(gauge.clone()))?;
+    Ok(gauge)
+}
+
 /// Attempts to crate a `Histogram`, returning `Err` if the registry does not accept the counter
 /// (potentially due to naming conflict).
 pub fn try_create_histogram(name: &str, help: &str) -> Result<Histogram> {
@@ -179,6 +189,14 @@ pub fn inc_counter_vec(counter: &Result<IntCounterVec>, label_values: &[&str]) {
     }
 }
 
+pub fn inc_counter_vec_by(counter: &Result<IntCounterVec>, label_values: &[&str], value: u64) {
+    if let Ok(counter) = counter {
+        counter.with_label_values(label_values).inc_by(value);
+    } else {
+        error!(target: "metrics", "Failed to fetch counter");
+    }
+}
+
 pub fn inc_counter_opt(counter: Option<&IntCounter>) {
     if let Some(counter) = counter {
         counter.inc();
@@ -67,7 +67,9 @@ fn create_block() -> Block {
         &signer,
         CryptoHash::default(),
         CryptoHash::default(),
+        None,
     )
+    .unwrap()
 }
 
 fn create_account() -> Account {
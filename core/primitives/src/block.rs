@@ -22,7 +22,7 @@ use crate::sharding::{
 use crate::types::NumBlocks;
 use crate::types::{Balance, BlockHeight, EpochId, Gas, NumShards, StateRoot};
 use crate::utils::to_timestamp;
-use crate::validator_signer::{EmptyValidatorSigner, ValidatorSigner};
+use crate::validator_signer::{EmptyValidatorSigner, SigningError, ValidatorSigner};
 use crate::version::{ProtocolVersion, SHARD_CHUNK_HEADER_UPGRADE_VERSION};
 use std::ops::Index;
 
@@ -190,6 +190,10 @@ impl Block {
     }
 
     /// Produces new block from header of previous block, current state root and set of transactions.
+    ///
+    /// The block is timestamped with `timestamp_override` instead of the current time if it is
+    /// set, which the sandbox uses to move the chain forward in time. Fails if the signer fails to
+    /// compute the random value or refuses to sign the header.
     pub fn produce(
         protocol_version: ProtocolVersion,
         prev: &BlockHeader,
@@ -211,7 +215,8 @@ impl Block {
         signer: &dyn ValidatorSigner,
         next_bp_hash: CryptoHash,
         block_merkle_root: CryptoHash,
-    ) -> Self {
+        timestamp_override: Option<DateTime<Utc>>,
+    ) -> Result<Self, SigningError> {
         // Collect aggregate of validators and gas usage/limits from chunks.
         let mut validator_proposals = vec![];
         let mut gas_used = 0;
@@ -241,10 +246,10 @@ impl Block {
 
         let new_total_supply = prev.total_supply() + minted_amount.unwrap_or(0) - balance_burnt;
 
-        let now = to_timestamp(Utc::now());
+        let now = to_timestamp(timestamp_override.unwrap_or_else(Utc::now));
         let time = if now <= prev.raw_timestamp() { prev.raw_timestamp() + 1 } else { now };
 
-        let (vrf_value, vrf_proof) = signer.compute_vrf_with_proof(prev.random_value().as_ref());
+        let (vrf_value, vrf_proof) = signer.compute_vrf_with_proof(prev.random_value().as_ref())?;
         let random_value = hash(vrf_value.0.as_ref());
 
         let last_ds_final_block =
@@ -297,16 +302,16 @@ impl Block {
             block_merkle_root,
             #[cfg(feature = "protocol_feature_block_header_v3")]
             prev.height(),
-        );
+        )?;
 
-        Self::block_from_protocol_version(
+        Ok(Self::block_from_protocol_version(
             protocol_version,
             header,
             chunks,
             challenges,
             vrf_value,
             vrf_proof,
-        )
+        ))
     }
 
     pub fn verify_gas_price(
@@ -11,7 +11,7 @@ use crate::network::PeerId;
 use crate::types::validator_stake::{ValidatorStake, ValidatorStakeIter, ValidatorStakeV1};
 use crate::types::{AccountId, Balance, BlockHeight, EpochId, MerkleHash, NumBlocks};
 use crate::utils::{from_timestamp, to_timestamp};
-use crate::validator_signer::ValidatorSigner;
+use crate::validator_signer::{SigningError, ValidatorSigner};
 use crate::version::{ProtocolVersion, PROTOCOL_VERSION};
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Debug, Clone, Eq, PartialEq)]
@@ -204,10 +204,10 @@ impl Approval {
         parent_height: BlockHeight,
         target_height: BlockHeight,
         signer: &dyn ValidatorSigner,
-    ) -> Self {
+    ) -> Result<Self, SigningError> {
         let inner = ApprovalInner::new(&parent_hash, parent_height, target_height);
-        let signature = signer.sign_approval(&inner, target_height);
-        Approval { inner, target_height, signature, account_id: signer.validator_id().clone() }
+        let signature = signer.sign_approval(&inner, target_height)?;
+        Ok(Approval { inner, target_height, signature, account_id: signer.validator_id().clone() })
     }
 
     pub fn get_data_for_sig(inner: &ApprovalInner, target_height: BlockHeight) -> Vec<u8> {
@@ -362,7 +362,7 @@ impl BlockHeader {
         next_bp_hash: CryptoHash,
         block_merkle_root: CryptoHash,
         #[cfg(feature = "protocol_feature_block_header_v3")] prev_height: BlockHeight,
-    ) -> Self {
+    ) -> Result<Self, SigningError> {
         let inner_lite = BlockHeaderInnerLite {
             height,
             epoch_id,
@@ -404,14 +404,14 @@ impl BlockHeader {
                 prev_hash,
                 &inner_lite.try_to_vec().expect("Failed to serialize"),
                 &inner_rest.try_to_vec().expect("Failed to serialize"),
-            );
-            Self::BlockHeaderV1(Box::new(BlockHeaderV1 {
+            )?;
+            Ok(Self::BlockHeaderV1(Box::new(BlockHeaderV1 {
                 prev_hash,
                 inner_lite,
                 inner_rest,
                 signature,
                 hash,
-            }))
+            })))
         } else if last_header_v2_version.is_none()
             || protocol_version <= last_header_v2_version.unwrap()
         {
@@ -438,14 +438,14 @@ impl BlockHeader {
                 prev_hash,
                 &inner_lite.try_to_vec().expect("Failed to serialize"),
                 &inner_rest.try_to_vec().expect("Failed to serialize"),
-            );
-            Self::BlockHeaderV2(Box::new(BlockHeaderV2 {
+            )?;
+            Ok(Self::BlockHeaderV2(Box::new(BlockHeaderV2 {
                 prev_hash,
                 inner_lite,
                 inner_rest,
                 signature,
                 hash,
-            }))
+            })))
         } else {
             #[cfg(not(feature = "protocol_feature_block_header_v3"))]
             unreachable!();
@@ -474,14 +474,14 @@ impl BlockHeader {
                     prev_hash,
                     &inner_lite.try_to_vec().expect("Failed to serialize"),
                     &inner_rest.try_to_vec().expect("Failed to serialize"),
-                );
-                Self::BlockHeaderV3(Box::new(BlockHeaderV3 {
+                )?;
+                Ok(Self::BlockHeaderV3(Box::new(BlockHeaderV3 {
                     prev_hash,
                     inner_lite,
                     inner_rest,
                     signature,
                     hash,
-                }))
+                })))
             }
         }
     }
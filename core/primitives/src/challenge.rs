@@ -7,7 +7,7 @@ use crate::hash::{hash, CryptoHash};
 use crate::merkle::MerklePath;
 use crate::sharding::{EncodedShardChunk, ShardChunk, ShardChunkHeader};
 use crate::types::AccountId;
-use crate::validator_signer::ValidatorSigner;
+use crate::validator_signer::{SigningError, ValidatorSigner};
 
 /// Serialized TrieNodeWithSize
 pub type StateItem = Vec<u8>;
@@ -93,9 +93,12 @@ impl Challenge {
         self.hash = hash(&self.body.try_to_vec().expect("Failed to serialize"));
     }
 
-    pub fn produce(body: ChallengeBody, signer: &dyn ValidatorSigner) -> Self {
-        let (hash, signature) = signer.sign_challenge(&body);
-        Self { body, account_id: signer.validator_id().clone(), signature, hash }
+    pub fn produce(
+        body: ChallengeBody,
+        signer: &dyn ValidatorSigner,
+    ) -> Result<Self, SigningError> {
+        let (hash, signature) = signer.sign_challenge(&body)?;
+        Ok(Self { body, account_id: signer.validator_id().clone(), signature, hash })
     }
 }
 
@@ -100,6 +100,14 @@ impl AllEpochConfig {
             &self.genesis_epoch_config
         }
     }
+
+    /// Returns the shard layouts of all the protocol versions.
+    pub fn shard_layouts(&self) -> Vec<&ShardLayout> {
+        vec![
+            &self.genesis_epoch_config.shard_layout,
+            &self.simple_nightshade_epoch_config.shard_layout,
+        ]
+    }
 }
 
 /// Additional configuration parameters for the new validator selection
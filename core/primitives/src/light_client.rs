@@ -0,0 +1,259 @@
+//! Verification of the light client blocks, as described in
+//! <https://nomicon.io/ChainSpec/LightClient.html>.
+
+use std::fmt;
+
+use borsh::BorshSerialize;
+
+use crate::block_header::{Approval, ApprovalInner, BlockHeaderInnerLite};
+use crate::hash::{hash, CryptoHash};
+use crate::merkle::combine_hash;
+use crate::types::validator_stake::ValidatorStake;
+use crate::types::Balance;
+use crate::views::validator_stake_view::ValidatorStakeView;
+use crate::views::{BlockHeaderInnerLiteView, LightClientBlockLiteView, LightClientBlockView};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LightClientBlockError {
+    /// The number of the approvals doesn't match the number of the block producers.
+    InvalidApprovalsCount { approvals: usize, block_producers: usize },
+    /// An approval is not signed by the block producer at its position.
+    InvalidSignature,
+    /// The approvals have less than 2/3 of the stake of the block producers.
+    NotEnoughApprovals { approved_stake: Balance, total_stake: Balance },
+    /// The block producers of the next epoch are missing.
+    MissingNextBlockProducers,
+    /// The block producers of the next epoch don't match `next_bp_hash` of the block.
+    InvalidNextBlockProducers,
+}
+
+impl fmt::Display for LightClientBlockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidApprovalsCount { approvals, block_producers } => {
+                write!(f, "{} approvals don't match {} block producers", approvals, block_producers)
+            }
+            Self::InvalidSignature => write!(f, "Invalid approval signature"),
+            Self::NotEnoughApprovals { approved_stake, total_stake } => write!(
+                f,
+                "Approved stake {} is not more than 2/3 of the total stake {}",
+                approved_stake, total_stake
+            ),
+            Self::MissingNextBlockProducers => write!(f, "Next block producers are missing"),
+            Self::InvalidNextBlockProducers => {
+                write!(f, "Next block producers don't match the next_bp_hash")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LightClientBlockError {}
+
+fn block_hash(
+    inner_lite: &BlockHeaderInnerLiteView,
+    inner_rest_hash: CryptoHash,
+    prev_block_hash: CryptoHash,
+) -> CryptoHash {
+    let inner_lite = BlockHeaderInnerLite::from(inner_lite.clone());
+    combine_hash(
+        combine_hash(hash(&inner_lite.try_to_vec().unwrap()), inner_rest_hash),
+        prev_block_hash,
+    )
+}
+
+impl LightClientBlockView {
+    /// Hash of the block this light client block is built for.
+    pub fn current_block_hash(&self) -> CryptoHash {
+        block_hash(&self.inner_lite, self.inner_rest_hash, self.prev_block_hash)
+    }
+
+    /// Hash of the block after the one this light client block is built for.
+    pub fn next_block_hash(&self) -> CryptoHash {
+        combine_hash(self.next_block_inner_hash, self.current_block_hash())
+    }
+}
+
+impl LightClientBlockLiteView {
+    /// Hash of the block this header is built for.
+    pub fn hash(&self) -> CryptoHash {
+        block_hash(&self.inner_lite, self.inner_rest_hash, self.prev_block_hash)
+    }
+}
+
+/// Checks that more than 2/3 of the stake of `epoch_block_producers`, the ordered block producers
+/// of the epoch of the block, endorsed the next block, and that the block producers of the next
+/// epoch, if required, match the block.
+pub fn validate_light_client_block(
+    block: &LightClientBlockView,
+    epoch_block_producers: &[ValidatorStakeView],
+    require_next_block_producers: bool,
+) -> Result<(), LightClientBlockError> {
+    // The light client blocks are only built for blocks followed by two blocks at the next heights.
+    let approval_message = Approval::get_data_for_sig(
+        &ApprovalInner::Endorsement(block.next_block_hash()),
+        block.inner_lite.height + 2,
+    );
+
+    if block.approvals_after_next.len() != epoch_block_producers.len() {
+        return Err(LightClientBlockError::InvalidApprovalsCount {
+            approvals: block.approvals_after_next.len(),
+            block_producers: epoch_block_producers.len(),
+        });
+    }
+
+    let mut approved_stake = 0;
+    let mut total_stake = 0;
+    for (maybe_signature, block_producer) in
+        block.approvals_after_next.iter().zip(epoch_block_producers.iter())
+    {
+        let block_producer = ValidatorStake::from(block_producer.clone());
+        total_stake += block_producer.stake();
+        if let Some(signature) = maybe_signature {
+            if !signature.verify(&approval_message, block_producer.public_key()) {
+                return Err(LightClientBlockError::InvalidSignature);
+            }
+            approved_stake += block_producer.stake();
+        }
+    }
+    if approved_stake * 3 <= total_stake * 2 {
+        return Err(LightClientBlockError::NotEnoughApprovals { approved_stake, total_stake });
+    }
+
+    match &block.next_bps {
+        Some(next_bps) => {
+            if !next_bp_hash_matches(next_bps, &block.inner_lite.next_bp_hash) {
+                return Err(LightClientBlockError::InvalidNextBlockProducers);
+            }
+        }
+        None if require_next_block_producers => {
+            return Err(LightClientBlockError::MissingNextBlockProducers)
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+/// The block producers are hashed in the versioned format since `BlockHeaderV3`, and in the
+/// first version before.
+fn next_bp_hash_matches(next_bps: &[ValidatorStakeView], next_bp_hash: &CryptoHash) -> bool {
+    let next_bps: Vec<ValidatorStake> = next_bps.iter().cloned().map(Into::into).collect();
+    if hash(&next_bps.try_to_vec().unwrap()) == *next_bp_hash {
+        return true;
+    }
+    let next_bps_v1: Vec<_> = next_bps.into_iter().map(|bp| bp.into_v1()).collect();
+    hash(&next_bps_v1.try_to_vec().unwrap()) == *next_bp_hash
+}
+
+#[cfg(test)]
+mod tests {
+    use borsh::BorshSerialize;
+    use near_crypto::KeyType;
+
+    use crate::block_header::ApprovalInner;
+    use crate::hash::hash;
+    use crate::types::validator_stake::ValidatorStake;
+    use crate::types::Balance;
+    use crate::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
+    use crate::views::validator_stake_view::ValidatorStakeView;
+    use crate::views::{BlockHeaderInnerLiteView, LightClientBlockView};
+
+    use super::{validate_light_client_block, LightClientBlockError};
+
+    fn block_producer(signer: &InMemoryValidatorSigner, stake: Balance) -> ValidatorStakeView {
+        let account_id = signer.validator_id().clone();
+        #[cfg(feature = "protocol_feature_block_header_v3")]
+        let validator_stake = ValidatorStake::new_v1(account_id, signer.public_key(), stake);
+        #[cfg(not(feature = "protocol_feature_block_header_v3"))]
+        let validator_stake = ValidatorStake::new(account_id, signer.public_key(), stake);
+        validator_stake.into()
+    }
+
+    fn light_client_block(
+        signers: &[InMemoryValidatorSigner],
+        next_bps: Vec<ValidatorStakeView>,
+    ) -> LightClientBlockView {
+        let next_bp_stakes: Vec<ValidatorStake> =
+            next_bps.iter().cloned().map(Into::into).collect();
+        let mut block = LightClientBlockView {
+            prev_block_hash: hash(&[1]),
+            next_block_inner_hash: hash(&[2]),
+            inner_lite: BlockHeaderInnerLiteView {
+                height: 10,
+                epoch_id: hash(&[3]),
+                next_epoch_id: hash(&[4]),
+                prev_state_root: hash(&[5]),
+                outcome_root: hash(&[6]),
+                timestamp: 0,
+                timestamp_nanosec: 0,
+                next_bp_hash: hash(&next_bp_stakes.try_to_vec().unwrap()),
+                block_merkle_root: hash(&[7]),
+            },
+            inner_rest_hash: hash(&[8]),
+            next_bps: Some(next_bps),
+            approvals_after_next: vec![],
+        };
+        let approval = ApprovalInner::Endorsement(block.next_block_hash());
+        block.approvals_after_next = signers
+            .iter()
+            .map(|signer| Some(signer.sign_approval(&approval, 12).unwrap()))
+            .collect();
+        block
+    }
+
+    #[test]
+    fn test_validate_light_client_block() {
+        let signers: Vec<_> = (0..3)
+            .map(|i| {
+                let account_id = format!("test{}", i);
+                InMemoryValidatorSigner::from_seed(
+                    account_id.parse().unwrap(),
+                    KeyType::ED25519,
+                    &account_id,
+                )
+            })
+            .collect();
+        let bps: Vec<_> = signers.iter().map(|signer| block_producer(signer, 100)).collect();
+        let block = light_client_block(&signers, bps.clone());
+        assert_eq!(validate_light_client_block(&block, &bps, true), Ok(()));
+
+        let mut not_enough_approvals = block.clone();
+        not_enough_approvals.approvals_after_next[2] = None;
+        assert_eq!(
+            validate_light_client_block(&not_enough_approvals, &bps, true),
+            Err(LightClientBlockError::NotEnoughApprovals {
+                approved_stake: 200,
+                total_stake: 300
+            })
+        );
+
+        // Approvals of a part of the block producers can't pass as approvals of all of them.
+        let mut truncated_approvals = block.clone();
+        truncated_approvals.approvals_after_next.truncate(2);
+        assert_eq!(
+            validate_light_client_block(&truncated_approvals, &bps, true),
+            Err(LightClientBlockError::InvalidApprovalsCount { approvals: 2, block_producers: 3 })
+        );
+
+        let mut invalid_signature = block.clone();
+        invalid_signature.approvals_after_next.swap(0, 1);
+        assert_eq!(
+            validate_light_client_block(&invalid_signature, &bps, true),
+            Err(LightClientBlockError::InvalidSignature)
+        );
+
+        let mut invalid_next_bps = block.clone();
+        invalid_next_bps.next_bps = Some(bps[..2].to_vec());
+        assert_eq!(
+            validate_light_client_block(&invalid_next_bps, &bps, true),
+            Err(LightClientBlockError::InvalidNextBlockProducers)
+        );
+
+        let mut no_next_bps = block;
+        no_next_bps.next_bps = None;
+        assert_eq!(
+            validate_light_client_block(&no_next_bps, &bps, true),
+            Err(LightClientBlockError::MissingNextBlockProducers)
+        );
+        assert_eq!(validate_light_client_block(&no_next_bps, &bps, false), Ok(()));
+    }
+}
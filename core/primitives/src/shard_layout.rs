@@ -114,6 +114,15 @@ impl ShardLayout {
         }
     }
 
+    /// Returns whether the shards of this layout were split from the shards of a previous layout.
+    #[inline]
+    pub fn has_parent_shard_layout(&self) -> bool {
+        match self {
+            Self::V0(_) => false,
+            Self::V1(v1) => v1.to_parent_shard_map.is_some(),
+        }
+    }
+
     #[inline]
     /// Only calls this function for shard layout that has parent shard layouts
     /// Returns error if `shard_id` is an invalid shard id in the current layout
@@ -199,7 +208,7 @@ fn is_top_level_account(top_account: &AccountId, account: &AccountId) -> bool {
     }
 }
 
-#[derive(Hash, Clone, Debug, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Serialize, Deserialize, Hash, Clone, Debug, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ShardUId {
     pub version: ShardVersion,
     pub shard_id: u32,
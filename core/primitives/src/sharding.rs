@@ -10,7 +10,7 @@ use crate::receipt::Receipt;
 use crate::transaction::SignedTransaction;
 use crate::types::validator_stake::{ValidatorStake, ValidatorStakeIter, ValidatorStakeV1};
 use crate::types::{Balance, BlockHeight, Gas, MerkleHash, ShardId, StateRoot};
-use crate::validator_signer::ValidatorSigner;
+use crate::validator_signer::{SigningError, ValidatorSigner};
 #[cfg(feature = "protocol_feature_block_header_v3")]
 use crate::version::ProtocolFeature;
 use crate::version::{ProtocolVersion, ProtocolVersionRange, SHARD_CHUNK_HEADER_UPGRADE_VERSION};
@@ -221,7 +221,7 @@ impl ShardChunkHeaderV2 {
         tx_root: CryptoHash,
         validator_proposals: Vec<ValidatorStakeV1>,
         signer: &dyn ValidatorSigner,
-    ) -> Self {
+    ) -> Result<Self, SigningError> {
         let inner = ShardChunkHeaderInnerV1 {
             prev_block_hash,
             prev_state_root,
@@ -238,8 +238,8 @@ impl ShardChunkHeaderV2 {
             validator_proposals,
         };
         let hash = Self::compute_hash(&inner);
-        let signature = signer.sign_chunk_hash(&hash);
-        Self { inner, height_included: 0, signature, hash }
+        let signature = signer.sign_chunk_hash(&hash, height, shard_id)?;
+        Ok(Self { inner, height_included: 0, signature, hash })
     }
 }
 
@@ -287,7 +287,7 @@ impl ShardChunkHeaderV3 {
         tx_root: CryptoHash,
         validator_proposals: Vec<ValidatorStake>,
         signer: &dyn ValidatorSigner,
-    ) -> Self {
+    ) -> Result<Self, SigningError> {
         let inner = ShardChunkHeaderInner::V2(ShardChunkHeaderInnerV2 {
             prev_block_hash,
             prev_state_root,
@@ -304,8 +304,8 @@ impl ShardChunkHeaderV3 {
             validator_proposals,
         });
         let hash = Self::compute_hash(&inner);
-        let signature = signer.sign_chunk_hash(&hash);
-        Self { inner, height_included: 0, signature, hash }
+        let signature = signer.sign_chunk_hash(&hash, height, shard_id)?;
+        Ok(Self { inner, height_included: 0, signature, hash })
     }
 }
 
@@ -579,7 +579,7 @@ impl ShardChunkHeaderV1 {
         tx_root: CryptoHash,
         validator_proposals: Vec<ValidatorStakeV1>,
         signer: &dyn ValidatorSigner,
-    ) -> Self {
+    ) -> Result<Self, SigningError> {
         let inner = ShardChunkHeaderInnerV1 {
             prev_block_hash,
             prev_state_root,
@@ -596,8 +596,8 @@ impl ShardChunkHeaderV1 {
             validator_proposals,
         };
         let hash = Self::compute_hash(&inner);
-        let signature = signer.sign_chunk_hash(&hash);
-        Self { inner, height_included: 0, signature, hash }
+        let signature = signer.sign_chunk_hash(&hash, height, shard_id)?;
+        Ok(Self { inner, height_included: 0, signature, hash })
     }
 }
 
@@ -1127,7 +1127,8 @@ impl EncodedShardChunk {
                 tx_root,
                 validator_proposals,
                 signer,
-            );
+            )
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
             let chunk = EncodedShardChunkV1 { header, content };
             Ok((Self::V1(chunk), merkle_paths))
         } else if block_header_v3_version.is_none()
@@ -1151,7 +1152,8 @@ impl EncodedShardChunk {
                 tx_root,
                 validator_proposals,
                 signer,
-            );
+            )
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
             let chunk = EncodedShardChunkV2 { header: ShardChunkHeader::V2(header), content };
             Ok((Self::V2(chunk), merkle_paths))
         } else {
@@ -1174,7 +1176,8 @@ impl EncodedShardChunk {
                     tx_root,
                     validator_proposals,
                     signer,
-                );
+                )
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
                 let chunk = EncodedShardChunkV2 { header: ShardChunkHeader::V3(header), content };
                 Ok((Self::V2(chunk), merkle_paths))
             }
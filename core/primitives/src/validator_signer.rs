@@ -1,17 +1,23 @@
-use std::path::Path;
-use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
-use borsh::BorshSerialize;
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
 
 use near_crypto::{InMemorySigner, KeyType, PublicKey, Signature, Signer};
 
 use crate::block::{Approval, ApprovalInner, BlockHeader};
+use crate::block_header::BlockHeaderInnerLite;
 use crate::challenge::ChallengeBody;
 use crate::hash::{hash, CryptoHash};
 use crate::network::{AnnounceAccount, PeerId};
 use crate::sharding::ChunkHash;
 use crate::telemetry::TelemetryInfo;
-use crate::types::{AccountId, BlockHeight, EpochId};
+use crate::types::{AccountId, BlockHeight, EpochId, ShardId};
 
 /// Validator signer that is used to sign blocks and approvals.
 pub trait ValidatorSigner: Sync + Send {
@@ -22,7 +28,7 @@ pub trait ValidatorSigner: Sync + Send {
     fn public_key(&self) -> PublicKey;
 
     /// Serializes telemetry info to JSON and signs it, returning JSON with "signature" field.
-    fn sign_telemetry(&self, info: &TelemetryInfo) -> serde_json::Value;
+    fn sign_telemetry(&self, info: &TelemetryInfo) -> Result<serde_json::Value, SigningError>;
 
     /// Signs given parts of the header.
     fn sign_block_header_parts(
@@ -30,16 +36,29 @@ pub trait ValidatorSigner: Sync + Send {
         prev_hash: CryptoHash,
         inner_lite: &[u8],
         inner_rest: &[u8],
-    ) -> (CryptoHash, Signature);
+    ) -> Result<(CryptoHash, Signature), SigningError>;
 
-    /// Signs given inner of the chunk header.
-    fn sign_chunk_hash(&self, chunk_hash: &ChunkHash) -> Signature;
+    /// Signs given inner of the chunk header. The chunk is produced at `height_created` for
+    /// `shard_id`.
+    fn sign_chunk_hash(
+        &self,
+        chunk_hash: &ChunkHash,
+        height_created: BlockHeight,
+        shard_id: ShardId,
+    ) -> Result<Signature, SigningError>;
 
     /// Signs approval of given parent hash and reference hash.
-    fn sign_approval(&self, inner: &ApprovalInner, target_height: BlockHeight) -> Signature;
+    fn sign_approval(
+        &self,
+        inner: &ApprovalInner,
+        target_height: BlockHeight,
+    ) -> Result<Signature, SigningError>;
 
     /// Signs challenge body.
-    fn sign_challenge(&self, challenge_body: &ChallengeBody) -> (CryptoHash, Signature);
+    fn sign_challenge(
+        &self,
+        challenge_body: &ChallengeBody,
+    ) -> Result<(CryptoHash, Signature), SigningError>;
 
     /// Signs account announce.
     fn sign_account_announce(
@@ -47,15 +66,15 @@ pub trait ValidatorSigner: Sync + Send {
         account_id: &AccountId,
         peer_id: &PeerId,
         epoch_id: &EpochId,
-    ) -> Signature;
+    ) -> Result<Signature, SigningError>;
 
     fn compute_vrf_with_proof(
         &self,
         data: &[u8],
-    ) -> (near_crypto::vrf::Value, near_crypto::vrf::Proof);
+    ) -> Result<(near_crypto::vrf::Value, near_crypto::vrf::Proof), SigningError>;
 
     /// Used by test infrastructure, only implement if make sense for testing otherwise raise `unimplemented`.
-    fn write_to_file(&self, path: &Path);
+    fn write_to_file(&self, path: &Path) -> io::Result<()>;
 }
 
 /// Test-only signer that "signs" everything with 0s.
@@ -75,8 +94,8 @@ impl ValidatorSigner for EmptyValidatorSigner {
         PublicKey::empty(KeyType::ED25519)
     }
 
-    fn sign_telemetry(&self, _info: &TelemetryInfo) -> serde_json::Value {
-        serde_json::Value::default()
+    fn sign_telemetry(&self, _info: &TelemetryInfo) -> Result<serde_json::Value, SigningError> {
+        Ok(serde_json::Value::default())
     }
 
     fn sign_block_header_parts(
@@ -84,22 +103,34 @@ impl ValidatorSigner for EmptyValidatorSigner {
         prev_hash: CryptoHash,
         inner_lite: &[u8],
         inner_rest: &[u8],
-    ) -> (CryptoHash, Signature) {
+    ) -> Result<(CryptoHash, Signature), SigningError> {
         let hash = BlockHeader::compute_hash(prev_hash, inner_lite, inner_rest);
-        (hash, Signature::default())
+        Ok((hash, Signature::default()))
     }
 
-    fn sign_chunk_hash(&self, _chunk_hash: &ChunkHash) -> Signature {
-        Signature::default()
+    fn sign_chunk_hash(
+        &self,
+        _chunk_hash: &ChunkHash,
+        _height_created: BlockHeight,
+        _shard_id: ShardId,
+    ) -> Result<Signature, SigningError> {
+        Ok(Signature::default())
     }
 
-    fn sign_approval(&self, _inner: &ApprovalInner, _target_height: BlockHeight) -> Signature {
-        Signature::default()
+    fn sign_approval(
+        &self,
+        _inner: &ApprovalInner,
+        _target_height: BlockHeight,
+    ) -> Result<Signature, SigningError> {
+        Ok(Signature::default())
     }
 
-    fn sign_challenge(&self, challenge_body: &ChallengeBody) -> (CryptoHash, Signature) {
+    fn sign_challenge(
+        &self,
+        challenge_body: &ChallengeBody,
+    ) -> Result<(CryptoHash, Signature), SigningError> {
         let hash = hash(&challenge_body.try_to_vec().expect("Failed to serialize"));
-        (hash, Signature::default())
+        Ok((hash, Signature::default()))
     }
 
     fn sign_account_announce(
@@ -107,18 +138,18 @@ impl ValidatorSigner for EmptyValidatorSigner {
         _account_id: &AccountId,
         _peer_id: &PeerId,
         _epoch_id: &EpochId,
-    ) -> Signature {
-        Signature::default()
+    ) -> Result<Signature, SigningError> {
+        Ok(Signature::default())
     }
 
     fn compute_vrf_with_proof(
         &self,
         _data: &[u8],
-    ) -> (near_crypto::vrf::Value, near_crypto::vrf::Proof) {
+    ) -> Result<(near_crypto::vrf::Value, near_crypto::vrf::Proof), SigningError> {
         unimplemented!()
     }
 
-    fn write_to_file(&self, _path: &Path) {
+    fn write_to_file(&self, _path: &Path) -> io::Result<()> {
         unimplemented!()
     }
 }
@@ -160,11 +191,11 @@ impl ValidatorSigner for InMemoryValidatorSigner {
         self.signer.public_key()
     }
 
-    fn sign_telemetry(&self, info: &TelemetryInfo) -> serde_json::Value {
+    fn sign_telemetry(&self, info: &TelemetryInfo) -> Result<serde_json::Value, SigningError> {
         let mut value = serde_json::to_value(info).expect("Telemetry must serialize to JSON");
         let content = serde_json::to_string(&value).expect("Telemetry must serialize to JSON");
         value["signature"] = format!("{}", self.signer.sign(content.as_bytes())).into();
-        value
+        Ok(value)
     }
 
     fn sign_block_header_parts(
@@ -172,23 +203,35 @@ impl ValidatorSigner for InMemoryValidatorSigner {
         prev_hash: CryptoHash,
         inner_lite: &[u8],
         inner_rest: &[u8],
-    ) -> (CryptoHash, Signature) {
+    ) -> Result<(CryptoHash, Signature), SigningError> {
         let hash = BlockHeader::compute_hash(prev_hash, inner_lite, inner_rest);
-        (hash, self.signer.sign(hash.as_ref()))
+        Ok((hash, self.signer.sign(hash.as_ref())))
     }
 
-    fn sign_chunk_hash(&self, chunk_hash: &ChunkHash) -> Signature {
-        self.signer.sign(chunk_hash.as_ref())
+    fn sign_chunk_hash(
+        &self,
+        chunk_hash: &ChunkHash,
+        _height_created: BlockHeight,
+        _shard_id: ShardId,
+    ) -> Result<Signature, SigningError> {
+        Ok(self.signer.sign(chunk_hash.as_ref()))
     }
 
-    fn sign_approval(&self, inner: &ApprovalInner, target_height: BlockHeight) -> Signature {
-        self.signer.sign(&Approval::get_data_for_sig(&inner, target_height))
+    fn sign_approval(
+        &self,
+        inner: &ApprovalInner,
+        target_height: BlockHeight,
+    ) -> Result<Signature, SigningError> {
+        Ok(self.signer.sign(&Approval::get_data_for_sig(&inner, target_height)))
     }
 
-    fn sign_challenge(&self, challenge_body: &ChallengeBody) -> (CryptoHash, Signature) {
+    fn sign_challenge(
+        &self,
+        challenge_body: &ChallengeBody,
+    ) -> Result<(CryptoHash, Signature), SigningError> {
         let hash = hash(&challenge_body.try_to_vec().expect("Failed to serialize"));
         let signature = self.signer.sign(hash.as_ref());
-        (hash, signature)
+        Ok((hash, signature))
     }
 
     fn sign_account_announce(
@@ -196,19 +239,395 @@ impl ValidatorSigner for InMemoryValidatorSigner {
         account_id: &AccountId,
         peer_id: &PeerId,
         epoch_id: &EpochId,
-    ) -> Signature {
+    ) -> Result<Signature, SigningError> {
         let hash = AnnounceAccount::build_header_hash(&account_id, &peer_id, epoch_id);
-        self.signer.sign(hash.as_ref())
+        Ok(self.signer.sign(hash.as_ref()))
     }
 
     fn compute_vrf_with_proof(
         &self,
         data: &[u8],
-    ) -> (near_crypto::vrf::Value, near_crypto::vrf::Proof) {
-        self.signer.compute_vrf_with_proof(data)
+    ) -> Result<(near_crypto::vrf::Value, near_crypto::vrf::Proof), SigningError> {
+        Ok(self.signer.compute_vrf_with_proof(data))
     }
 
-    fn write_to_file(&self, path: &Path) {
+    fn write_to_file(&self, path: &Path) -> io::Result<()> {
         self.signer.write_to_file(path);
+        Ok(())
+    }
+}
+
+/// Kind of messages a validator must never sign twice for the same height.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignedMessageKind {
+    Approval,
+    BlockHeader,
+    Chunk(ShardId),
+}
+
+impl fmt::Display for SignedMessageKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignedMessageKind::Approval => write!(f, "approval"),
+            SignedMessageKind::BlockHeader => write!(f, "block header"),
+            SignedMessageKind::Chunk(shard_id) => write!(f, "chunk of shard {}", shard_id),
+        }
+    }
+}
+
+/// The last message of some kind signed by a validator.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SignedMessage {
+    /// Target height for approvals, block height for headers and height created for chunks.
+    pub height: BlockHeight,
+    /// Hash of the signed message. `None` if an imported history had a different message at
+    /// the same height, so that nothing more can be signed at it.
+    pub hash: Option<CryptoHash>,
+}
+
+/// Signing a message would conflict with a message signed before.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DoubleSignError {
+    pub kind: SignedMessageKind,
+    pub height: BlockHeight,
+    pub last_signed: SignedMessage,
+}
+
+impl fmt::Display for DoubleSignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at height {} conflicts with the {} signed at height {}",
+            self.kind, self.height, self.kind, self.last_signed.height
+        )
+    }
+}
+
+impl std::error::Error for DoubleSignError {}
+
+/// A message was not signed, so whatever it was for can't be produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SigningError {
+    /// Signing would conflict with a message signed before.
+    DoubleSign(DoubleSignError),
+    /// The signature couldn't be made, e.g. the signing history couldn't be persisted.
+    Failed(String),
+}
+
+impl fmt::Display for SigningError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SigningError::DoubleSign(err) => {
+                write!(f, "Refusing to sign, it would be a double sign: {}", err)
+            }
+            SigningError::Failed(err) => write!(f, "Failed to sign: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for SigningError {}
+
+impl From<DoubleSignError> for SigningError {
+    fn from(err: DoubleSignError) -> Self {
+        SigningError::DoubleSign(err)
+    }
+}
+
+/// Highest signed message of every kind. It's all a validator needs to remember to never sign
+/// conflicting messages, and it has to move together with the validator key.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SigningHistory {
+    pub approval: Option<SignedMessage>,
+    pub block_header: Option<SignedMessage>,
+    pub chunks: BTreeMap<ShardId, SignedMessage>,
+}
+
+impl SigningHistory {
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Reads the history at `path`, or starts an empty one if the file doesn't exist yet.
+    pub fn from_file_or_default(path: &Path) -> io::Result<Self> {
+        match Self::from_file(path) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            result => result,
+        }
+    }
+
+    /// Writes the history through a temporary file and syncs it before replacing `path`, so
+    /// that a crash never loses a record of a message that was already signed.
+    pub fn write_to_file(&self, path: &Path) -> io::Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    }
+
+    /// Records that the message with `hash` is about to be signed. Returns whether the history
+    /// changed, which is not the case when exactly the same message is signed again.
+    pub fn record(
+        &mut self,
+        kind: SignedMessageKind,
+        height: BlockHeight,
+        hash: CryptoHash,
+    ) -> Result<bool, DoubleSignError> {
+        let last_signed = match kind {
+            SignedMessageKind::Approval => self.approval,
+            SignedMessageKind::BlockHeader => self.block_header,
+            SignedMessageKind::Chunk(shard_id) => self.chunks.get(&shard_id).copied(),
+        };
+        match last_signed {
+            Some(last) if last.height == height && last.hash == Some(hash) => return Ok(false),
+            Some(last) if last.height >= height => {
+                return Err(DoubleSignError { kind, height, last_signed: last });
+            }
+            _ => {}
+        }
+        let signed = SignedMessage { height, hash: Some(hash) };
+        match kind {
+            SignedMessageKind::Approval => self.approval = Some(signed),
+            SignedMessageKind::BlockHeader => self.block_header = Some(signed),
+            SignedMessageKind::Chunk(shard_id) => {
+                self.chunks.insert(shard_id, signed);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Merges in the history exported from another node running with the same key, keeping the
+    /// highest record of every kind.
+    pub fn merge(&mut self, other: &SigningHistory) {
+        fn merge_record(ours: &mut Option<SignedMessage>, theirs: Option<SignedMessage>) {
+            match (ours.as_mut(), theirs) {
+                (_, None) => {}
+                (None, theirs) => *ours = theirs,
+                (Some(ours), Some(theirs)) => {
+                    if theirs.height > ours.height {
+                        *ours = theirs;
+                    } else if theirs.height == ours.height && theirs.hash != ours.hash {
+                        ours.hash = None;
+                    }
+                }
+            }
+        }
+        merge_record(&mut self.approval, other.approval);
+        merge_record(&mut self.block_header, other.block_header);
+        for (shard_id, theirs) in other.chunks.iter() {
+            let mut ours = self.chunks.get(shard_id).copied();
+            merge_record(&mut ours, Some(*theirs));
+            self.chunks.insert(*shard_id, ours.unwrap());
+        }
+    }
+}
+
+/// Signer which refuses to sign an approval, a block header or a chunk conflicting with one
+/// signed before, e.g. by another node running with the same key. The history of signed
+/// messages is persisted before every signature is handed out.
+///
+/// A refusal is returned as a `SigningError`, and the node skips producing the message.
+pub struct DoubleSignProtectedValidatorSigner {
+    signer: Arc<dyn ValidatorSigner>,
+    history_path: PathBuf,
+    history: Mutex<SigningHistory>,
+}
+
+impl DoubleSignProtectedValidatorSigner {
+    pub fn new(signer: Arc<dyn ValidatorSigner>, history_path: &Path) -> io::Result<Self> {
+        let history = SigningHistory::from_file_or_default(history_path)?;
+        Ok(Self { signer, history_path: history_path.to_path_buf(), history: Mutex::new(history) })
+    }
+
+    pub fn history(&self) -> SigningHistory {
+        self.history.lock().unwrap().clone()
+    }
+
+    /// Records the message, or returns why it can't be signed. The history is only updated
+    /// once it's persisted.
+    fn record(
+        &self,
+        kind: SignedMessageKind,
+        height: BlockHeight,
+        hash: CryptoHash,
+    ) -> Result<(), SigningError> {
+        let mut history = self.history.lock().unwrap();
+        let mut updated = history.clone();
+        if updated.record(kind, height, hash)? {
+            updated.write_to_file(&self.history_path).map_err(|err| {
+                SigningError::Failed(format!(
+                    "Failed to write signing history {}: {}",
+                    self.history_path.display(),
+                    err
+                ))
+            })?;
+            *history = updated;
+        }
+        Ok(())
+    }
+}
+
+impl ValidatorSigner for DoubleSignProtectedValidatorSigner {
+    fn validator_id(&self) -> &AccountId {
+        self.signer.validator_id()
+    }
+
+    fn public_key(&self) -> PublicKey {
+        self.signer.public_key()
+    }
+
+    fn sign_telemetry(&self, info: &TelemetryInfo) -> Result<serde_json::Value, SigningError> {
+        self.signer.sign_telemetry(info)
+    }
+
+    fn sign_block_header_parts(
+        &self,
+        prev_hash: CryptoHash,
+        inner_lite: &[u8],
+        inner_rest: &[u8],
+    ) -> Result<(CryptoHash, Signature), SigningError> {
+        let height = BlockHeaderInnerLite::try_from_slice(inner_lite)
+            .map_err(|err| {
+                SigningError::Failed(format!(
+                    "Failed to deserialize inner lite of the block header: {}",
+                    err
+                ))
+            })?
+            .height;
+        let hash = BlockHeader::compute_hash(prev_hash, inner_lite, inner_rest);
+        self.record(SignedMessageKind::BlockHeader, height, hash)?;
+        self.signer.sign_block_header_parts(prev_hash, inner_lite, inner_rest)
+    }
+
+    fn sign_chunk_hash(
+        &self,
+        chunk_hash: &ChunkHash,
+        height_created: BlockHeight,
+        shard_id: ShardId,
+    ) -> Result<Signature, SigningError> {
+        self.record(SignedMessageKind::Chunk(shard_id), height_created, chunk_hash.0)?;
+        self.signer.sign_chunk_hash(chunk_hash, height_created, shard_id)
+    }
+
+    fn sign_approval(
+        &self,
+        inner: &ApprovalInner,
+        target_height: BlockHeight,
+    ) -> Result<Signature, SigningError> {
+        let hash = hash(&Approval::get_data_for_sig(inner, target_height));
+        self.record(SignedMessageKind::Approval, target_height, hash)?;
+        self.signer.sign_approval(inner, target_height)
+    }
+
+    fn sign_challenge(
+        &self,
+        challenge_body: &ChallengeBody,
+    ) -> Result<(CryptoHash, Signature), SigningError> {
+        self.signer.sign_challenge(challenge_body)
+    }
+
+    fn sign_account_announce(
+        &self,
+        account_id: &AccountId,
+        peer_id: &PeerId,
+        epoch_id: &EpochId,
+    ) -> Result<Signature, SigningError> {
+        self.signer.sign_account_announce(account_id, peer_id, epoch_id)
+    }
+
+    fn compute_vrf_with_proof(
+        &self,
+        data: &[u8],
+    ) -> Result<(near_crypto::vrf::Value, near_crypto::vrf::Proof), SigningError> {
+        self.signer.compute_vrf_with_proof(data)
+    }
+
+    fn write_to_file(&self, path: &Path) -> io::Result<()> {
+        self.signer.write_to_file(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signing_history_record() {
+        let mut history = SigningHistory::default();
+        let kind = SignedMessageKind::Approval;
+        assert_eq!(history.record(kind, 10, hash(&[1])), Ok(true));
+        assert_eq!(history.record(kind, 10, hash(&[1])), Ok(false));
+        let last_signed = SignedMessage { height: 10, hash: Some(hash(&[1])) };
+        assert_eq!(
+            history.record(kind, 10, hash(&[2])),
+            Err(DoubleSignError { kind, height: 10, last_signed })
+        );
+        assert_eq!(
+            history.record(kind, 9, hash(&[1])),
+            Err(DoubleSignError { kind, height: 9, last_signed })
+        );
+        assert_eq!(history.record(kind, 11, hash(&[2])), Ok(true));
+
+        assert_eq!(history.record(SignedMessageKind::Chunk(0), 10, hash(&[3])), Ok(true));
+        assert_eq!(history.record(SignedMessageKind::Chunk(1), 10, hash(&[4])), Ok(true));
+        assert!(history.record(SignedMessageKind::Chunk(1), 10, hash(&[3])).is_err());
+    }
+
+    #[test]
+    fn test_signing_history_merge() {
+        let mut ours = SigningHistory::default();
+        ours.record(SignedMessageKind::Approval, 10, hash(&[1])).unwrap();
+        ours.record(SignedMessageKind::BlockHeader, 10, hash(&[1])).unwrap();
+        let mut theirs = SigningHistory::default();
+        theirs.record(SignedMessageKind::Approval, 12, hash(&[2])).unwrap();
+        theirs.record(SignedMessageKind::BlockHeader, 10, hash(&[2])).unwrap();
+        theirs.record(SignedMessageKind::Chunk(0), 5, hash(&[3])).unwrap();
+
+        ours.merge(&theirs);
+        assert_eq!(ours.approval, theirs.approval);
+        assert_eq!(ours.block_header, Some(SignedMessage { height: 10, hash: None }));
+        assert_eq!(ours.chunks, theirs.chunks);
+        assert!(ours.record(SignedMessageKind::BlockHeader, 10, hash(&[1])).is_err());
+        assert!(ours.record(SignedMessageKind::BlockHeader, 10, hash(&[2])).is_err());
+    }
+
+    #[test]
+    fn test_protected_signer_refuses_double_sign() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("signing_history.json");
+        let signer: Arc<dyn ValidatorSigner> = Arc::new(InMemoryValidatorSigner::from_seed(
+            "test".parse().unwrap(),
+            KeyType::ED25519,
+            "test",
+        ));
+
+        let protected = DoubleSignProtectedValidatorSigner::new(signer.clone(), &path).unwrap();
+        let inner = ApprovalInner::Endorsement(hash(&[1]));
+        assert_eq!(protected.sign_approval(&inner, 10), signer.sign_approval(&inner, 10));
+        assert_eq!(SigningHistory::from_file(&path).unwrap(), protected.history());
+
+        // Another node restarted with the same key and history.
+        let protected = DoubleSignProtectedValidatorSigner::new(signer.clone(), &path).unwrap();
+        assert_eq!(protected.sign_approval(&inner, 10), signer.sign_approval(&inner, 10));
+        let last_signed =
+            SignedMessage { height: 10, hash: Some(hash(&Approval::get_data_for_sig(&inner, 10))) };
+        assert_eq!(
+            protected.sign_approval(&ApprovalInner::Skip(8), 10),
+            Err(SigningError::DoubleSign(DoubleSignError {
+                kind: SignedMessageKind::Approval,
+                height: 10,
+                last_signed
+            }))
+        );
+        // The signer keeps working after refusing a message.
+        assert!(protected.sign_approval(&inner, 11).is_ok());
+
+        // Block headers which can't be deserialized are refused rather than panicking.
+        assert!(matches!(
+            protected.sign_block_header_parts(hash(&[2]), &[1, 2, 3], &[]),
+            Err(SigningError::Failed(_))
+        ));
     }
 }
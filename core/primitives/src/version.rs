@@ -13,7 +13,7 @@ pub struct Version {
 pub type DbVersion = u32;
 
 /// Current version of the database.
-pub const DB_VERSION: DbVersion = 28;
+pub const DB_VERSION: DbVersion = 29;
 
 /// Protocol version type.
 pub use near_primitives_core::types::ProtocolVersion;
@@ -123,6 +123,10 @@ pub enum ProtocolFeature {
     ChunkOnlyProducers,
     #[cfg(feature = "protocol_feature_routing_exchange_algorithm")]
     RoutingExchangeAlgorithm,
+    /// Compress large network messages. Only changes how peers talk to each other, but peers
+    /// agree on it through the protocol version in the handshake.
+    #[cfg(feature = "protocol_feature_peer_message_compression")]
+    PeerMessageCompression,
 }
 
 /// Current latest stable version of the protocol.
@@ -133,7 +137,7 @@ pub const PROTOCOL_VERSION: ProtocolVersion = 48;
 
 /// Current latest nightly version of the protocol.
 #[cfg(feature = "nightly_protocol")]
-pub const PROTOCOL_VERSION: ProtocolVersion = 119;
+pub const PROTOCOL_VERSION: ProtocolVersion = 120;
 
 impl ProtocolFeature {
     pub const fn protocol_version(self) -> ProtocolVersion {
@@ -165,6 +169,8 @@ impl ProtocolFeature {
             ProtocolFeature::ChunkOnlyProducers => 115,
             #[cfg(feature = "protocol_feature_routing_exchange_algorithm")]
             ProtocolFeature::RoutingExchangeAlgorithm => 117,
+            #[cfg(feature = "protocol_feature_peer_message_compression")]
+            ProtocolFeature::PeerMessageCompression => 120,
         }
     }
 }
@@ -41,7 +41,8 @@ use crate::sharding::{ShardChunkHeaderInnerV2, ShardChunkHeaderV3};
 use crate::transaction::{
     Action, AddKeyAction, CreateAccountAction, DeleteAccountAction, DeleteKeyAction,
     DeployContractAction, ExecutionMetadata, ExecutionOutcome, ExecutionOutcomeWithIdAndProof,
-    ExecutionStatus, FunctionCallAction, SignedTransaction, StakeAction, TransferAction,
+    ExecutionStatus, FunctionCallAction, PartialExecutionStatus, SignedTransaction, StakeAction,
+    TransferAction,
 };
 use crate::types::{
     AccountId, AccountWithPublicKey, Balance, BlockHeight, CompiledContractCache, EpochHeight,
@@ -213,6 +214,16 @@ pub struct ViewStateResult {
     pub proof: TrieProofPath,
 }
 
+/// A page of the contract state, see `QueryRequest::ViewStatePaginated`.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct ViewStatePageResult {
+    pub values: Vec<StateItem>,
+    /// Key to request the next page from, serialized in base64. `None` if this is the last page.
+    pub next_key: Option<String>,
+    /// State root of the shard the proofs of the items lead to.
+    pub state_root: StateRoot,
+}
+
 #[derive(
     BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default,
 )]
@@ -252,6 +263,7 @@ pub enum QueryResponseKind {
     CallResult(CallResult),
     AccessKey(AccessKeyView),
     AccessKeyList(AccessKeyList),
+    ViewStatePaginated(ViewStatePageResult),
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -281,6 +293,20 @@ pub enum QueryRequest {
         #[serde(rename = "args_base64", with = "base64_format")]
         args: FunctionArgs,
     },
+    /// Up to `limit` items of the contract state with keys starting with `prefix`, beginning from
+    /// `start_key` if given. A `limit` of 0 is treated as 1. Unlike `ViewState`, works for
+    /// contracts of any size.
+    ViewStatePaginated {
+        account_id: AccountId,
+        #[serde(rename = "prefix_base64", with = "base64_format")]
+        prefix: StoreKey,
+        #[serde(rename = "start_key_base64", default, with = "option_base64_format")]
+        start_key: Option<Vec<u8>>,
+        limit: u64,
+        /// Whether to return the trie proof of every item.
+        #[serde(default)]
+        include_proof: bool,
+    },
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq, Clone)]
@@ -1123,6 +1149,41 @@ impl From<ExecutionOutcomeWithIdAndProof> for ExecutionOutcomeWithIdView {
     }
 }
 
+impl ExecutionOutcomeWithIdView {
+    /// Hashes of the outcome which are merkelized into the outcome root of the chunk, same as
+    /// `ExecutionOutcomeWithId::to_hashes`. Fails if the success value is not valid base64.
+    pub fn to_hashes(&self) -> Result<Vec<CryptoHash>, Box<dyn std::error::Error>> {
+        let status = match &self.outcome.status {
+            ExecutionStatusView::Unknown => PartialExecutionStatus::Unknown,
+            ExecutionStatusView::Failure(_) => PartialExecutionStatus::Failure,
+            ExecutionStatusView::SuccessValue(value) => {
+                PartialExecutionStatus::SuccessValue(from_base64(value)?)
+            }
+            ExecutionStatusView::SuccessReceiptId(receipt_id) => {
+                PartialExecutionStatus::SuccessReceiptId(*receipt_id)
+            }
+        };
+        let mut result = vec![
+            self.id,
+            hash(
+                &(
+                    self.outcome.receipt_ids.clone(),
+                    self.outcome.gas_burnt,
+                    self.outcome.tokens_burnt,
+                    self.outcome.executor_id.clone(),
+                    status,
+                )
+                    .try_to_vec()
+                    .expect("Failed to serialize"),
+            ),
+        ];
+        for log in self.outcome.logs.iter() {
+            result.push(hash(log.as_bytes()));
+        }
+        Ok(result)
+    }
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug)]
 #[serde(untagged)]
 pub enum FinalExecutionOutcomeViewEnum {
@@ -1168,6 +1229,20 @@ pub struct FinalExecutionOutcomeWithReceiptView {
     pub receipts: Vec<ReceiptView>,
 }
 
+/// Outcome of a transaction executed on top of the current state without being submitted.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SimulatedTransactionView {
+    /// Final outcome of the transaction and the executed receipts.
+    #[serde(flatten)]
+    pub final_outcome: FinalExecutionOutcomeView,
+    /// Executed receipts, in the execution order.
+    pub receipts: Vec<ReceiptView>,
+    /// Receipts to the shards the node doesn't track, which were not executed.
+    pub unprocessed_receipts: Vec<ReceiptView>,
+    /// Changes of the state made by the transaction and the executed receipts.
+    pub state_changes: StateChangesView,
+}
+
 impl From<FinalExecutionOutcomeWithReceiptView> for FinalExecutionOutcomeView {
     fn from(final_outcome_view: FinalExecutionOutcomeWithReceiptView) -> Self {
         final_outcome_view.final_outcome
@@ -1502,7 +1577,7 @@ pub struct GasPriceView {
 ///
 /// [serializable view]: ./index.html
 /// [`StateChangesRequest`]: ../types/struct.StateChangesRequest.html
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "changes_type", rename_all = "snake_case")]
 pub enum StateChangesRequestView {
     AccountChanges {
@@ -1663,6 +1738,22 @@ pub enum StateChangeValueView {
     },
 }
 
+impl StateChangeValueView {
+    /// Returns the account whose state changed.
+    pub fn affected_account_id(&self) -> &AccountId {
+        match self {
+            Self::AccountUpdate { account_id, .. }
+            | Self::AccountDeletion { account_id }
+            | Self::AccessKeyUpdate { account_id, .. }
+            | Self::AccessKeyDeletion { account_id, .. }
+            | Self::DataUpdate { account_id, .. }
+            | Self::DataDeletion { account_id, .. }
+            | Self::ContractCodeUpdate { account_id, .. }
+            | Self::ContractCodeDeletion { account_id } => account_id,
+        }
+    }
+}
+
 impl From<StateChangeValue> for StateChangeValueView {
     fn from(state_change: StateChangeValue) -> Self {
         match state_change {
@@ -7,8 +7,8 @@ use std::sync::RwLock;
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use rocksdb::{
-    BlockBasedOptions, Cache, ColumnFamily, ColumnFamilyDescriptor, Direction, Env, IteratorMode,
-    Options, ReadOptions, WriteBatch, DB,
+    BlockBasedOptions, Cache, ColumnFamily, ColumnFamilyDescriptor, DBCompactionStyle,
+    DBCompressionType, Direction, Env, IteratorMode, Options, ReadOptions, WriteBatch, DB,
 };
 use strum::EnumIter;
 use tracing::warn;
@@ -20,15 +20,25 @@ use crate::db::refcount::merge_refcounted_records;
 use std::path::Path;
 use std::sync::atomic::Ordering;
 
+pub use lsm::{LsmDB, LsmDBOptions};
+
+mod lsm;
 pub(crate) mod refcount;
 pub(crate) mod v6_to_v7;
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct DBError(rocksdb::Error);
+pub enum DBError {
+    RocksDB(rocksdb::Error),
+    /// I/O or corruption error reported by [`LsmDB`].
+    IO(String),
+}
 
 impl std::fmt::Display for DBError {
     fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        self.0.fmt(formatter)
+        match self {
+            DBError::RocksDB(err) => err.fmt(formatter),
+            DBError::IO(err) => err.fmt(formatter),
+        }
     }
 }
 
@@ -36,7 +46,13 @@ impl std::error::Error for DBError {}
 
 impl From<rocksdb::Error> for DBError {
     fn from(err: rocksdb::Error) -> Self {
-        DBError(err)
+        DBError::RocksDB(err)
+    }
+}
+
+impl From<io::Error> for DBError {
+    fn from(err: io::Error) -> Self {
+        DBError::IO(err.to_string())
     }
 }
 
@@ -192,6 +208,22 @@ impl DBCol {
     }
 }
 
+/// Parses the column from its variant name, with or without the `Col` prefix, e.g. `ColState` or
+/// `State`.
+impl std::str::FromStr for DBCol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use strum::IntoEnumIterator;
+        DBCol::iter()
+            .find(|col| {
+                let name = format!("{:?}", col);
+                name == s || name.strip_prefix("Col") == Some(s)
+            })
+            .ok_or_else(|| format!("unknown column `{}`", s))
+    }
+}
+
 // List of columns for which GC should be implemented
 lazy_static! {
     pub static ref SHOULD_COL_GC: Vec<bool> = {
@@ -251,6 +283,7 @@ pub const HEADER_HEAD_KEY: &[u8; 11] = b"HEADER_HEAD";
 pub const FINAL_HEAD_KEY: &[u8; 10] = b"FINAL_HEAD";
 pub const LATEST_KNOWN_KEY: &[u8; 12] = b"LATEST_KNOWN";
 pub const LARGEST_TARGET_HEIGHT_KEY: &[u8; 21] = b"LARGEST_TARGET_HEIGHT";
+pub const EPOCH_SYNC_HASH_KEY: &[u8; 15] = b"EPOCH_SYNC_HASH";
 pub const VERSION_KEY: &[u8; 7] = b"VERSION";
 pub const GENESIS_JSON_HASH_KEY: &[u8; 17] = b"GENESIS_JSON_HASH";
 pub const GENESIS_STATE_ROOTS_KEY: &[u8; 19] = b"GENESIS_STATE_ROOTS";
@@ -461,9 +494,8 @@ pub trait Database: Sync + Send {
         key_prefix: &'a [u8],
     ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a>;
     fn write(&self, batch: DBTransaction) -> Result<(), DBError>;
-    fn as_rocksdb(&self) -> Option<&RocksDB> {
-        None
-    }
+    /// Creates a consistent point-in-time copy of the database in `path`, which must not exist.
+    fn checkpoint(&self, path: &Path) -> Result<(), DBError>;
 }
 
 impl Database for RocksDB {
@@ -557,8 +589,9 @@ impl Database for RocksDB {
         Ok(self.db.write(batch)?)
     }
 
-    fn as_rocksdb(&self) -> Option<&RocksDB> {
-        Some(self)
+    fn checkpoint(&self, path: &Path) -> Result<(), DBError> {
+        let checkpoint = rocksdb::checkpoint::Checkpoint::new(&self.db)?;
+        Ok(checkpoint.create_checkpoint(path)?)
     }
 }
 
@@ -619,6 +652,10 @@ impl Database for TestDB {
         }
         Ok(())
     }
+
+    fn checkpoint(&self, _path: &Path) -> Result<(), DBError> {
+        Err(DBError::IO("in-memory database does not support checkpoints".to_string()))
+    }
 }
 
 /// DB level options
@@ -684,6 +721,21 @@ fn rocksdb_column_options(col: DBCol) -> Options {
     opts
 }
 
+/// Column options of the cold store. Data there is only ever appended and rarely read, so we use
+/// universal compaction to keep write amplification low and compress everything with zstd.
+fn rocksdb_cold_column_options(col: DBCol) -> Options {
+    let mut opts = Options::default();
+    opts.set_block_based_table_factory(&rocksdb_block_based_options());
+    opts.set_compaction_style(DBCompactionStyle::Universal);
+    opts.set_target_file_size_base(1024 * 1024 * 256);
+    opts.set_compression_type(DBCompressionType::Zstd);
+    if col.is_rc() {
+        opts.set_merge_operator("refcount merge", RocksDB::refcount_merge, RocksDB::refcount_merge);
+        opts.set_compaction_filter("empty value filter", RocksDB::empty_value_compaction_filter);
+    }
+    opts
+}
+
 impl RocksDB {
     /// Returns version of the database state on disk.
     pub fn get_version<P: AsRef<std::path::Path>>(path: P) -> Result<DbVersion, DBError> {
@@ -705,6 +757,32 @@ impl RocksDB {
         RocksDBOptions::default().read_write(path)
     }
 
+    /// Opens the database at `path` to read it, with the column options of [`RocksDB::new`], so
+    /// that the refcounted columns are read through the refcount merge operator. Read only opens
+    /// of the rocksdb crate don't take column options, so the database is opened for writing,
+    /// but never written to and nothing missing is created.
+    pub fn new_for_reading<P: AsRef<std::path::Path>>(path: P) -> Result<Self, DBError> {
+        let mut options = rocksdb_options();
+        options.create_if_missing(false);
+        options.create_missing_column_families(false);
+        options.set_disable_auto_compactions(true);
+        RocksDBOptions::default().rocksdb_options(options).read_write(path)
+    }
+
+    /// Opens the cold store of an archival node, see [`rocksdb_cold_column_options`].
+    pub fn new_cold<P: AsRef<std::path::Path>>(path: P) -> Result<Self, DBError> {
+        use strum::IntoEnumIterator;
+        let cf_descriptors = DBCol::iter()
+            .map(|col| {
+                ColumnFamilyDescriptor::new(
+                    format!("col{}", col as usize),
+                    rocksdb_cold_column_options(col),
+                )
+            })
+            .collect();
+        RocksDBOptions::default().cf_descriptors(cf_descriptors).read_write(path)
+    }
+
     /// Checks if there is enough memory left to perform a write. Not having enough memory left can
     /// lead to difficult to recover from state, thus a PreWriteCheckErr is pretty much
     /// unrecoverable in most cases.
@@ -777,7 +855,10 @@ impl TestDB {
 mod tests {
     use crate::db::DBCol::ColState;
     use crate::db::{rocksdb_read_options, DBError, Database, RocksDB};
-    use crate::{create_store, DBCol};
+    use crate::{
+        create_store, create_store_with_config, open_read_only_store, DBCol, StoreBackend,
+        StoreConfig,
+    };
 
     impl RocksDB {
         #[cfg(not(feature = "single_thread_rocksdb"))]
@@ -808,25 +889,53 @@ mod tests {
         store.pre_write_check().unwrap()
     }
 
+    #[test]
+    fn test_parse_column() {
+        assert_eq!("ColState".parse::<DBCol>(), Ok(ColState));
+        assert_eq!("BlockHeader".parse::<DBCol>(), Ok(DBCol::ColBlockHeader));
+        assert!("Col".parse::<DBCol>().is_err());
+    }
+
     #[test]
     fn test_clear_column() {
-        let tmp_dir = tempfile::Builder::new().prefix("_test_clear_column").tempdir().unwrap();
-        let store = create_store(tmp_dir.path());
-        assert_eq!(store.get(ColState, &[1]).unwrap(), None);
-        {
-            let mut store_update = store.store_update();
-            store_update.update_refcount(ColState, &[1], &[1], 1);
-            store_update.update_refcount(ColState, &[2], &[2], 1);
-            store_update.update_refcount(ColState, &[3], &[3], 1);
-            store_update.commit().unwrap();
+        for backend in vec![StoreBackend::RocksDB, StoreBackend::Lsm] {
+            let tmp_dir = tempfile::Builder::new().prefix("_test_clear_column").tempdir().unwrap();
+            let store = create_store_with_config(
+                tmp_dir.path(),
+                &StoreConfig { backend, ..Default::default() },
+            );
+            assert_eq!(store.get(ColState, &[1]).unwrap(), None);
+            {
+                let mut store_update = store.store_update();
+                store_update.update_refcount(ColState, &[1], &[1], 1);
+                store_update.update_refcount(ColState, &[2], &[2], 1);
+                store_update.update_refcount(ColState, &[3], &[3], 1);
+                store_update.commit().unwrap();
+            }
+            assert_eq!(store.get(ColState, &[1]).unwrap(), Some(vec![1]));
+            {
+                let mut store_update = store.store_update();
+                store_update.delete_all(ColState);
+                store_update.commit().unwrap();
+            }
+            assert_eq!(store.get(ColState, &[1]).unwrap(), None);
         }
-        assert_eq!(store.get(ColState, &[1]).unwrap(), Some(vec![1]));
+    }
+
+    #[test]
+    fn test_read_only_store_merges_refcounts() {
+        let tmp_dir = tempfile::Builder::new().prefix("_test_read_only_store").tempdir().unwrap();
         {
-            let mut store_update = store.store_update();
-            store_update.delete_all(ColState);
-            store_update.commit().unwrap();
+            let store = create_store(tmp_dir.path());
+            for _ in 0..2 {
+                let mut store_update = store.store_update();
+                store_update.update_refcount(ColState, &[1], &[1], 1);
+                store_update.commit().unwrap();
+            }
         }
-        assert_eq!(store.get(ColState, &[1]).unwrap(), None);
+        let store = open_read_only_store(tmp_dir.path(), &StoreConfig::default()).unwrap();
+        assert_eq!(store.get(ColState, &[1]).unwrap(), Some(vec![1]));
+        assert_eq!(store.iter(ColState).count(), 1);
     }
 
     #[test]
@@ -0,0 +1,1074 @@
+//! Embedded log-structured merge store implemented in pure Rust.
+//!
+//! This is an alternative to [`RocksDB`](super::RocksDB) that does not need the C++ toolchain.
+//! The database directory contains:
+//!
+//! * `MANIFEST` -- list of live sorted tables and the current write-ahead log;
+//! * `<id>.log` -- write-ahead log with all the batches committed since the last flush;
+//! * `<id>.sst` -- immutable sorted tables with the flushed data;
+//! * `LOCK` -- guards the directory from being opened for writing by two processes.
+//!
+//! Every committed [`DBTransaction`] is appended to the write-ahead log and applied to the
+//! in-memory table. Once the in-memory table grows beyond
+//! [`memtable_size`](LsmDBOptions::memtable_size) it is flushed into a new sorted table and the log
+//! is rotated.
+//!
+//! Sorted tables are compacted by size tiers on a background thread, so writers are never blocked
+//! by a compaction. A table is in tier `n` if it is up to [`tables_per_tier`]`^(n + 1)` times
+//! larger than the in-memory table. Once there are [`tables_per_tier`] adjacent tables in the same
+//! tier, they are merged into one table of the next tier. Every record is thus rewritten only a
+//! logarithmic number of times.
+//!
+//! Refcounted columns are merged at write time, so the tables only contain merged records.
+//!
+//! [`tables_per_tier`]: LsmDBOptions::tables_per_tier
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::JoinHandle;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use fs2::FileExt;
+use tracing::{error, warn};
+
+use near_primitives::hash::hash;
+
+use crate::db::refcount::merge_refcounted_records;
+use crate::db::{DBCol, DBError, DBOp, DBTransaction, Database, RocksDB, NUM_COLS};
+
+const MANIFEST_FILE: &str = "MANIFEST";
+const LOCK_FILE: &str = "LOCK";
+/// Marks the end of a well-formed sorted table.
+const TABLE_MAGIC: u64 = u64::from_le_bytes(*b"near-lsm");
+/// Every `INDEX_INTERVAL`-th record of a sorted table is kept in its in-memory index.
+const INDEX_INTERVAL: usize = 16;
+/// Size of a log record header: payload length and payload checksum.
+const LOG_HEADER_SIZE: usize = 4 + 32;
+
+/// Key and value of a record. `None` value is a tombstone.
+type Entry = (Vec<u8>, Option<Vec<u8>>);
+
+/// Options for configuring [`LsmDB`](LsmDB).
+///
+/// ```rust
+/// use near_store::db::LsmDBOptions;
+///
+/// let db = LsmDBOptions::default()
+///     .memtable_size(bytesize::ByteSize::mb(16))
+///     .tables_per_tier(4)
+///     .read_only("/db/path");
+/// ```
+pub struct LsmDBOptions {
+    memtable_size: bytesize::ByteSize,
+    tables_per_tier: usize,
+    sync_writes: bool,
+}
+
+/// Sets [`LsmDBOptions::memtable_size`] to 64 MB, [`LsmDBOptions::tables_per_tier`] to 4 and
+/// disables [`LsmDBOptions::sync_writes`].
+impl Default for LsmDBOptions {
+    fn default() -> Self {
+        LsmDBOptions {
+            memtable_size: bytesize::ByteSize::mb(64),
+            tables_per_tier: 4,
+            sync_writes: false,
+        }
+    }
+}
+
+impl LsmDBOptions {
+    /// Size of the in-memory table after which it is flushed into a sorted table on disk.
+    pub fn memtable_size(mut self, size: bytesize::ByteSize) -> Self {
+        self.memtable_size = size;
+        self
+    }
+
+    /// Number of adjacent sorted tables of similar size which are merged into one.
+    pub fn tables_per_tier(mut self, tables_per_tier: usize) -> Self {
+        self.tables_per_tier = std::cmp::max(tables_per_tier, 2);
+        self
+    }
+
+    /// Whether to fsync the write-ahead log after every write. Without it, a machine crash may
+    /// lose the latest writes but never leaves the database inconsistent.
+    pub fn sync_writes(mut self, sync_writes: bool) -> Self {
+        self.sync_writes = sync_writes;
+        self
+    }
+
+    /// Opens a read only database. It doesn't take the directory lock, so it may be used on the
+    /// database of a running node.
+    pub fn read_only<P: AsRef<Path>>(self, path: P) -> Result<LsmDB, DBError> {
+        LsmDB::open(path.as_ref(), self, true)
+    }
+
+    /// Opens the database in read/write mode, creating it if it does not exist.
+    pub fn read_write<P: AsRef<Path>>(self, path: P) -> Result<LsmDB, DBError> {
+        LsmDB::open(path.as_ref(), self, false)
+    }
+
+    /// Tier of a sorted table of the given size, see the [module documentation](self).
+    fn tier(&self, table_size: u64) -> u32 {
+        let mut tier = 0;
+        let mut limit = std::cmp::max(self.memtable_size.as_u64(), 1);
+        while table_size > limit {
+            tier += 1;
+            limit = limit.saturating_mul(self.tables_per_tier as u64);
+        }
+        tier
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+struct Manifest {
+    next_file_id: u64,
+    log_id: u64,
+    /// Ids of live sorted tables from the oldest to the newest.
+    tables: Vec<u64>,
+}
+
+impl Manifest {
+    fn allocate_file_id(&mut self) -> u64 {
+        let id = self.next_file_id;
+        self.next_file_id += 1;
+        id
+    }
+}
+
+pub struct LsmDB {
+    shared: Arc<Shared>,
+    read_only: bool,
+    /// Wakes up the compaction thread after a flush. Dropping it stops the thread.
+    compaction_requests: Mutex<Option<Sender<()>>>,
+    compaction_thread: Option<JoinHandle<()>>,
+    /// Held for the lifetime of a writable database.
+    _lock: Option<File>,
+}
+
+/// State shared with the compaction thread.
+struct Shared {
+    path: PathBuf,
+    options: LsmDBOptions,
+    inner: RwLock<Inner>,
+    /// Held while merging tables, so that only one compaction runs at a time and the merged
+    /// tables stay in place until they are replaced.
+    compaction: Mutex<()>,
+}
+
+struct Inner {
+    manifest: Manifest,
+    memtable: Vec<BTreeMap<Vec<u8>, Option<Vec<u8>>>>,
+    memtable_bytes: u64,
+    /// Sorted tables from the oldest to the newest.
+    tables: Vec<Arc<Table>>,
+    log: Option<File>,
+}
+
+impl LsmDB {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, DBError> {
+        LsmDBOptions::default().read_write(path)
+    }
+
+    /// Returns whether `path` contains a database created by this backend.
+    pub fn exists<P: AsRef<Path>>(path: P) -> bool {
+        path.as_ref().join(MANIFEST_FILE).exists()
+    }
+
+    fn open(path: &Path, options: LsmDBOptions, read_only: bool) -> Result<Self, DBError> {
+        let manifest_path = path.join(MANIFEST_FILE);
+        let lock = if read_only {
+            if !manifest_path.exists() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no database found at {}", path.display()),
+                )
+                .into());
+            }
+            None
+        } else {
+            fs::create_dir_all(path)?;
+            let lock = File::create(path.join(LOCK_FILE))?;
+            lock.try_lock_exclusive().map_err(|err| {
+                io::Error::new(
+                    err.kind(),
+                    format!("database at {} is used by another process", path.display()),
+                )
+            })?;
+            Some(lock)
+        };
+
+        let manifest = if manifest_path.exists() {
+            Manifest::try_from_slice(&fs::read(&manifest_path)?)?
+        } else {
+            let mut manifest = Manifest::default();
+            manifest.log_id = manifest.allocate_file_id();
+            write_manifest(path, &manifest)?;
+            manifest
+        };
+
+        let tables = manifest
+            .tables
+            .iter()
+            .map(|id| Table::open(table_path(path, *id)).map(Arc::new))
+            .collect::<io::Result<Vec<_>>>()?;
+        let current_log = log_path(path, manifest.log_id);
+        let mut inner = Inner {
+            manifest,
+            memtable: (0..NUM_COLS).map(|_| BTreeMap::new()).collect(),
+            memtable_bytes: 0,
+            tables,
+            log: None,
+        };
+        let valid_len = inner.replay_log(&current_log)?;
+        if !read_only {
+            let mut log = OpenOptions::new().create(true).write(true).open(&current_log)?;
+            if log.metadata()?.len() > valid_len {
+                warn!(target: "store", "Truncating torn write at the end of {}", current_log.display());
+                log.set_len(valid_len)?;
+            }
+            log.seek(SeekFrom::End(0))?;
+            inner.log = Some(log);
+            remove_stale_files(path, &inner.manifest)?;
+        }
+
+        let shared = Arc::new(Shared {
+            path: path.to_path_buf(),
+            options,
+            inner: RwLock::new(inner),
+            compaction: Mutex::new(()),
+        });
+        let (compaction_requests, compaction_thread) = if read_only {
+            (None, None)
+        } else {
+            let (sender, receiver) = mpsc::channel();
+            let shared = Arc::clone(&shared);
+            let thread = std::thread::Builder::new()
+                .name("lsm-compaction".to_string())
+                .spawn(move || run_compactions(shared, receiver))?;
+            // Tables left by a previous run may already need merging.
+            let _ = sender.send(());
+            (Some(sender), Some(thread))
+        };
+
+        Ok(LsmDB {
+            shared,
+            read_only,
+            compaction_requests: Mutex::new(compaction_requests),
+            compaction_thread,
+            _lock: lock,
+        })
+    }
+
+    /// Flushes the in-memory table to disk and merges all sorted tables into one.
+    pub fn compact(&self) -> Result<(), DBError> {
+        let _compaction = self.shared.compaction.lock().unwrap();
+        self.shared.flush(&mut self.shared.inner.write().unwrap())?;
+        self.shared.merge_tables(|tables| Some(0..tables.len()).filter(|range| range.len() > 1))?;
+        Ok(())
+    }
+
+    /// Copies the database into `path`, which must not exist. Sorted tables are immutable, so
+    /// they are hard linked when `path` is on the same file system.
+    fn create_checkpoint(&self, path: &Path) -> io::Result<()> {
+        // Writers and compactions take the write lock to change the manifest or the log, so
+        // they don't change under a read lock.
+        let inner = self.shared.inner.read().unwrap();
+        fs::create_dir(path)?;
+        for id in &inner.manifest.tables {
+            let (src, dst) = (table_path(&self.shared.path, *id), table_path(path, *id));
+            if fs::hard_link(&src, &dst).is_err() {
+                fs::copy(&src, &dst)?;
+            }
+        }
+        let current_log = log_path(&self.shared.path, inner.manifest.log_id);
+        if current_log.exists() {
+            fs::copy(&current_log, log_path(path, inner.manifest.log_id))?;
+        }
+        write_manifest(path, &inner.manifest)
+    }
+
+    fn iter_raw<'a>(
+        &'a self,
+        col: DBCol,
+        key_prefix: &[u8],
+    ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+        let inner = self.shared.inner.read().unwrap();
+        let iter = match inner.merge_iter(col, key_prefix) {
+            Ok(iter) => iter,
+            Err(err) => {
+                error!(target: "store", "Failed to read {:?} from the database: {}", col, err);
+                return Box::new(std::iter::empty());
+            }
+        };
+        // The iterator can't return errors, like the RocksDB one it stops at the first error.
+        Box::new(iter.filter_map(move |item| match item {
+            Ok((key, value)) => {
+                value.map(|value| (key.into_boxed_slice(), value.into_boxed_slice()))
+            }
+            Err(err) => {
+                error!(target: "store", "Failed to read {:?} from the database: {}", col, err);
+                None
+            }
+        }))
+    }
+}
+
+impl Shared {
+    /// Writes the in-memory table into a new sorted table. Returns whether there was anything
+    /// to flush.
+    fn flush(&self, inner: &mut Inner) -> io::Result<bool> {
+        if inner.memtable_bytes == 0 {
+            return Ok(false);
+        }
+        let table_id = inner.manifest.allocate_file_id();
+        let records = inner.memtable.iter().enumerate().flat_map(|(col, entries)| {
+            entries.iter().map(move |(key, value)| Ok((col as u8, key, value.as_ref())))
+        });
+        let table = write_table(&table_path(&self.path, table_id), records)?;
+
+        let old_log_id = inner.manifest.log_id;
+        inner.manifest.log_id = inner.manifest.allocate_file_id();
+        let log = File::create(log_path(&self.path, inner.manifest.log_id))?;
+        inner.manifest.tables.push(table_id);
+        write_manifest(&self.path, &inner.manifest)?;
+        fs::remove_file(log_path(&self.path, old_log_id))?;
+
+        inner.log = Some(log);
+        inner.tables.push(Arc::new(table));
+        inner.memtable.iter_mut().for_each(BTreeMap::clear);
+        inner.memtable_bytes = 0;
+        Ok(true)
+    }
+
+    /// Merges adjacent tables of the same tier until no tier has too many tables.
+    fn compact_tiers(&self) -> io::Result<()> {
+        let _compaction = self.compaction.lock().unwrap();
+        let options = &self.options;
+        while self.merge_tables(|tables| {
+            let mut start = 0;
+            for end in 1..=tables.len() {
+                if end == tables.len()
+                    || options.tier(tables[end].size) != options.tier(tables[start].size)
+                {
+                    if end - start >= options.tables_per_tier {
+                        return Some(start..end);
+                    }
+                    start = end;
+                }
+            }
+            None
+        })? {}
+        Ok(())
+    }
+
+    /// Merges the range of tables chosen by `pick` into one table, without blocking readers and
+    /// writers while merging. Returns whether any tables were merged. Must be called with
+    /// `self.compaction` held.
+    fn merge_tables(
+        &self,
+        pick: impl FnOnce(&[Arc<Table>]) -> Option<Range<usize>>,
+    ) -> io::Result<bool> {
+        let (range, tables, table_id) = {
+            let mut inner = self.inner.write().unwrap();
+            let range = match pick(&inner.tables) {
+                Some(range) => range,
+                None => return Ok(false),
+            };
+            let tables = inner.tables[range.clone()].to_vec();
+            (range, tables, inner.manifest.allocate_file_id())
+        };
+        // Older tables may still have records shadowed by the tombstones, unless the oldest
+        // table is merged too.
+        let keep_tombstones = range.start > 0;
+        let records = (0..NUM_COLS as u8).flat_map(|col| {
+            let sources = tables
+                .iter()
+                .rev()
+                .map(|table| {
+                    let iter = table.iter_from(col, &[])?;
+                    Source::new(Box::new(iter))
+                })
+                .collect::<io::Result<Vec<_>>>();
+            let merged: Box<dyn Iterator<Item = io::Result<Entry>>> = match sources {
+                Ok(sources) => Box::new(MergeIter { sources, keep_tombstones }),
+                Err(err) => Box::new(std::iter::once(Err(err))),
+            };
+            merged.map(move |item| item.map(|(key, value)| (col, key, value)))
+        });
+        let table = write_table(&table_path(&self.path, table_id), records)?;
+
+        let old_tables = {
+            let mut inner = self.inner.write().unwrap();
+            let old_tables: Vec<u64> =
+                inner.manifest.tables.splice(range.clone(), std::iter::once(table_id)).collect();
+            write_manifest(&self.path, &inner.manifest)?;
+            inner.tables.splice(range, std::iter::once(Arc::new(table)));
+            old_tables
+        };
+        for id in old_tables {
+            fs::remove_file(table_path(&self.path, id))?;
+        }
+        Ok(true)
+    }
+}
+
+fn run_compactions(shared: Arc<Shared>, requests: Receiver<()>) {
+    while requests.recv().is_ok() {
+        while requests.try_recv().is_ok() {}
+        if let Err(err) = shared.compact_tiers() {
+            error!(target: "store", "Failed to compact {}: {}", shared.path.display(), err);
+        }
+    }
+}
+
+impl Inner {
+    fn get_raw(&self, col: DBCol, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        if let Some(value) = self.memtable[col as usize].get(key) {
+            return Ok(value.clone());
+        }
+        for table in self.tables.iter().rev() {
+            if let Some(value) = table.get(col as u8, key)? {
+                return Ok(value);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Iterates over live records of the column that start with `key_prefix`. The returned
+    /// iterator doesn't borrow `self`, so it stays valid after the lock is released.
+    fn merge_iter(&self, col: DBCol, key_prefix: &[u8]) -> io::Result<MergeIter<'static>> {
+        let col = col as u8;
+        let mut sources = Vec::with_capacity(self.tables.len() + 1);
+        let memtable: Vec<Entry> = self.memtable[col as usize]
+            .range(key_prefix.to_vec()..)
+            .take_while(|(key, _)| key.starts_with(key_prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        sources.push(Source::new(Box::new(memtable.into_iter().map(Ok)))?);
+        for table in self.tables.iter().rev() {
+            let prefix = key_prefix.to_vec();
+            let iter = table.iter_from(col, key_prefix)?;
+            sources.push(Source::new(Box::new(iter.take_while(move |item| {
+                item.as_ref().map_or(true, |(key, _)| key.starts_with(&prefix))
+            })))?);
+        }
+        Ok(MergeIter { sources, keep_tombstones: false })
+    }
+
+    /// Replays the write-ahead log into the in-memory table. Returns the length of the valid
+    /// prefix of the log; anything after it is a torn write.
+    fn replay_log(&mut self, path: &Path) -> io::Result<u64> {
+        let mut data = Vec::new();
+        match File::open(path) {
+            Ok(mut file) => {
+                file.read_to_end(&mut data)?;
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(err),
+        }
+        let mut offset = 0;
+        while offset + LOG_HEADER_SIZE <= data.len() {
+            let len = (&data[offset..offset + 4]).read_u32::<LittleEndian>()? as usize;
+            let checksum = &data[offset + 4..offset + LOG_HEADER_SIZE];
+            let start = offset + LOG_HEADER_SIZE;
+            if start + len > data.len() || hash(&data[start..start + len]).0[..] != *checksum {
+                break;
+            }
+            let changes = decode_changes(&data[start..start + len])?;
+            self.apply(changes);
+            offset = start + len;
+        }
+        Ok(offset as u64)
+    }
+
+    fn apply(&mut self, changes: Vec<(u8, Vec<u8>, Option<Vec<u8>>)>) {
+        for (col, key, value) in changes {
+            self.memtable_bytes +=
+                (key.len() + value.as_ref().map_or(0, |value| value.len())) as u64;
+            self.memtable[col as usize].insert(key, value);
+        }
+    }
+}
+
+impl Database for LsmDB {
+    fn get(&self, col: DBCol, key: &[u8]) -> Result<Option<Vec<u8>>, DBError> {
+        let result = self.shared.inner.read().unwrap().get_raw(col, key)?;
+        Ok(RocksDB::get_with_rc_logic(col, result))
+    }
+
+    fn iter<'a>(&'a self, col: DBCol) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+        RocksDB::iter_with_rc_logic(col, self.iter_raw(col, &[]))
+    }
+
+    fn iter_without_rc_logic<'a>(
+        &'a self,
+        col: DBCol,
+    ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+        self.iter_raw(col, &[])
+    }
+
+    fn iter_prefix<'a>(
+        &'a self,
+        col: DBCol,
+        key_prefix: &'a [u8],
+    ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+        RocksDB::iter_with_rc_logic(col, self.iter_raw(col, key_prefix))
+    }
+
+    fn write(&self, transaction: DBTransaction) -> Result<(), DBError> {
+        if self.read_only {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "database is opened in read only mode",
+            )
+            .into());
+        }
+        let mut inner = self.shared.inner.write().unwrap();
+
+        // Resolve refcount merges and column deletions into plain puts and deletes, so that the
+        // log replay is idempotent. `overlay` holds values written earlier in this transaction.
+        let mut overlay: HashMap<(DBCol, Vec<u8>), Option<Vec<u8>>> = HashMap::new();
+        let mut changes = Vec::with_capacity(transaction.ops.len());
+        for op in transaction.ops {
+            match op {
+                DBOp::Insert { col, key, value } => {
+                    overlay.insert((col, key.clone()), Some(value.clone()));
+                    changes.push((col as u8, key, Some(value)));
+                }
+                DBOp::UpdateRefcount { col, key, value } => {
+                    assert!(col.is_rc());
+                    let mut merged = match overlay.get(&(col, key.clone())) {
+                        Some(current) => current.clone(),
+                        None => inner.get_raw(col, &key)?,
+                    }
+                    .unwrap_or_default();
+                    merge_refcounted_records(&mut merged, &value);
+                    let merged = if merged.is_empty() { None } else { Some(merged) };
+                    overlay.insert((col, key.clone()), merged.clone());
+                    changes.push((col as u8, key, merged));
+                }
+                DBOp::Delete { col, key } => {
+                    overlay.insert((col, key.clone()), None);
+                    changes.push((col as u8, key, None));
+                }
+                DBOp::DeleteAll { col } => {
+                    let mut keys = inner
+                        .merge_iter(col, &[])?
+                        .map(|item| item.map(|(key, _)| key))
+                        .collect::<io::Result<Vec<_>>>()?;
+                    keys.extend(
+                        overlay
+                            .iter()
+                            .filter(|((c, _), value)| *c == col && value.is_some())
+                            .map(|((_, key), _)| key.clone()),
+                    );
+                    for key in keys {
+                        overlay.insert((col, key.clone()), None);
+                        changes.push((col as u8, key, None));
+                    }
+                }
+            }
+        }
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let payload = encode_changes(&changes);
+        let mut record = Vec::with_capacity(LOG_HEADER_SIZE + payload.len());
+        record.write_u32::<LittleEndian>(payload.len() as u32)?;
+        record.extend_from_slice(&hash(&payload).0);
+        record.extend_from_slice(&payload);
+        let log = inner.log.as_mut().expect("writable database always has a log");
+        log.write_all(&record)?;
+        if self.shared.options.sync_writes {
+            log.sync_data()?;
+        }
+
+        inner.apply(changes);
+        if inner.memtable_bytes >= self.shared.options.memtable_size.as_u64()
+            && self.shared.flush(&mut inner)?
+        {
+            if let Some(requests) = self.compaction_requests.lock().unwrap().as_ref() {
+                let _ = requests.send(());
+            }
+        }
+        Ok(())
+    }
+
+    fn checkpoint(&self, path: &Path) -> Result<(), DBError> {
+        Ok(self.create_checkpoint(path)?)
+    }
+}
+
+impl Drop for LsmDB {
+    fn drop(&mut self) {
+        // Stops the compaction thread once it finishes the current merge.
+        if let Ok(requests) = self.compaction_requests.get_mut() {
+            requests.take();
+        }
+        if let Some(thread) = self.compaction_thread.take() {
+            if thread.join().is_err() {
+                warn!(target: "store", "Compaction thread of {} panicked", self.shared.path.display());
+            }
+        }
+        if let Ok(mut inner) = self.shared.inner.write() {
+            if let Some(log) = inner.log.as_mut() {
+                if let Err(err) = log.sync_all() {
+                    warn!(target: "store", "Failed to sync write-ahead log on close: {}", err);
+                }
+            }
+        }
+    }
+}
+
+/// Immutable sorted table on disk.
+///
+/// Records are sorted by column and key and are followed by a borsh-encoded sparse index, the
+/// offset of the index and [`TABLE_MAGIC`]. Each record is
+/// `col: u8, key_len: u32, key, has_value: u8[, value_len: u32, value]`.
+struct Table {
+    path: PathBuf,
+    /// `(col, key, offset)` of every `INDEX_INTERVAL`-th record.
+    index: Vec<(u8, Vec<u8>, u64)>,
+    data_end: u64,
+    /// Size of the file in bytes.
+    size: u64,
+    file: Mutex<File>,
+}
+
+impl Table {
+    fn open(path: PathBuf) -> io::Result<Self> {
+        let mut file = File::open(&path)?;
+        let len = file.metadata()?.len();
+        if len < 16 {
+            return Err(corrupted(&path));
+        }
+        file.seek(SeekFrom::Start(len - 16))?;
+        let data_end = file.read_u64::<LittleEndian>()?;
+        if file.read_u64::<LittleEndian>()? != TABLE_MAGIC || data_end > len - 16 {
+            return Err(corrupted(&path));
+        }
+        let mut index = vec![0; (len - 16 - data_end) as usize];
+        file.seek(SeekFrom::Start(data_end))?;
+        file.read_exact(&mut index)?;
+        let index = BorshDeserialize::try_from_slice(&index)?;
+        Ok(Table { path, index, data_end, size: len, file: Mutex::new(file) })
+    }
+
+    /// Offset of the last indexed record which is not greater than `(col, key)`.
+    fn seek_offset(&self, col: u8, key: &[u8]) -> u64 {
+        let pos = self.index.partition_point(|(c, k, _)| (*c, k.as_slice()) <= (col, key));
+        if pos == 0 {
+            0
+        } else {
+            self.index[pos - 1].2
+        }
+    }
+
+    /// Returns `Some(None)` if the table has a tombstone for the key.
+    fn get(&self, col: u8, key: &[u8]) -> io::Result<Option<Option<Vec<u8>>>> {
+        let mut offset = self.seek_offset(col, key);
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start(offset))?;
+        let mut reader = BufReader::new(&*file);
+        while offset < self.data_end {
+            let (c, k, value, size) = read_record(&mut reader)?;
+            offset += size;
+            match (c, k.as_slice()).cmp(&(col, key)) {
+                std::cmp::Ordering::Less => continue,
+                std::cmp::Ordering::Equal => return Ok(Some(value)),
+                std::cmp::Ordering::Greater => break,
+            }
+        }
+        Ok(None)
+    }
+
+    /// Iterates over the records of the column starting from the first key not less than `from`.
+    fn iter_from(&self, col: u8, from: &[u8]) -> io::Result<TableIter> {
+        let offset = self.seek_offset(col, from);
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(TableIter {
+            reader: BufReader::new(file),
+            offset,
+            data_end: self.data_end,
+            col,
+            from: from.to_vec(),
+        })
+    }
+}
+
+struct TableIter {
+    reader: BufReader<File>,
+    offset: u64,
+    data_end: u64,
+    col: u8,
+    from: Vec<u8>,
+}
+
+impl Iterator for TableIter {
+    type Item = io::Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.offset < self.data_end {
+            let (col, key, value, size) = match read_record(&mut self.reader) {
+                Ok(record) => record,
+                Err(err) => {
+                    self.offset = self.data_end;
+                    return Some(Err(err));
+                }
+            };
+            self.offset += size;
+            if (col, key.as_slice()) < (self.col, self.from.as_slice()) {
+                continue;
+            }
+            if col > self.col {
+                self.offset = self.data_end;
+                return None;
+            }
+            return Some(Ok((key, value)));
+        }
+        None
+    }
+}
+
+/// Peekable source of sorted records.
+struct Source<'a> {
+    head: Option<Entry>,
+    rest: Box<dyn Iterator<Item = io::Result<Entry>> + 'a>,
+}
+
+impl<'a> Source<'a> {
+    fn new(mut rest: Box<dyn Iterator<Item = io::Result<Entry>> + 'a>) -> io::Result<Self> {
+        Ok(Source { head: rest.next().transpose()?, rest })
+    }
+
+    fn advance(&mut self) -> io::Result<Option<Entry>> {
+        let next = self.rest.next().transpose()?;
+        Ok(std::mem::replace(&mut self.head, next))
+    }
+}
+
+/// Merges sorted sources ordered from the newest to the oldest. For equal keys, the record from
+/// the newest source wins. Tombstones are skipped unless `keep_tombstones` is set. Iteration
+/// stops after the first error.
+struct MergeIter<'a> {
+    sources: Vec<Source<'a>>,
+    keep_tombstones: bool,
+}
+
+impl<'a> MergeIter<'a> {
+    /// Takes the record from the source `newest` and skips the same key in older sources.
+    fn take(&mut self, newest: usize) -> io::Result<Entry> {
+        let (key, value) = self.sources[newest].advance()?.expect("source has a record");
+        for source in self.sources.iter_mut() {
+            while source.head.as_ref().map_or(false, |(k, _)| k == &key) {
+                source.advance()?;
+            }
+        }
+        Ok((key, value))
+    }
+}
+
+impl<'a> Iterator for MergeIter<'a> {
+    type Item = io::Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let newest = self
+                .sources
+                .iter()
+                .enumerate()
+                .filter_map(|(i, source)| source.head.as_ref().map(|(key, _)| (i, key)))
+                .min_by(|(_, a), (_, b)| a.cmp(b))
+                .map(|(i, _)| i)?;
+            match self.take(newest) {
+                Ok((key, value)) => {
+                    if value.is_some() || self.keep_tombstones {
+                        return Some(Ok((key, value)));
+                    }
+                }
+                Err(err) => {
+                    self.sources.clear();
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+fn write_table<K, V>(
+    path: &Path,
+    records: impl Iterator<Item = io::Result<(u8, K, Option<V>)>>,
+) -> io::Result<Table>
+where
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    let tmp_path = path.with_extension("tmp");
+    let mut writer = io::BufWriter::new(File::create(&tmp_path)?);
+    let mut index = Vec::new();
+    let mut offset = 0u64;
+    for (i, record) in records.enumerate() {
+        let (col, key, value) = record?;
+        let key = key.as_ref();
+        if i % INDEX_INTERVAL == 0 {
+            index.push((col, key.to_vec(), offset));
+        }
+        writer.write_u8(col)?;
+        writer.write_u32::<LittleEndian>(key.len() as u32)?;
+        writer.write_all(key)?;
+        offset += 1 + 4 + key.len() as u64 + 1;
+        match value {
+            Some(value) => {
+                let value = value.as_ref();
+                writer.write_u8(1)?;
+                writer.write_u32::<LittleEndian>(value.len() as u32)?;
+                writer.write_all(value)?;
+                offset += 4 + value.len() as u64;
+            }
+            None => writer.write_u8(0)?,
+        }
+    }
+    writer.write_all(&index.try_to_vec()?)?;
+    writer.write_u64::<LittleEndian>(offset)?;
+    writer.write_u64::<LittleEndian>(TABLE_MAGIC)?;
+    writer.into_inner().map_err(|err| err.into_error())?.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Table::open(path.to_path_buf())
+}
+
+/// Reads one table record and returns it together with its size in bytes.
+fn read_record<R: Read>(reader: &mut R) -> io::Result<(u8, Vec<u8>, Option<Vec<u8>>, u64)> {
+    let col = reader.read_u8()?;
+    let mut key = vec![0; reader.read_u32::<LittleEndian>()? as usize];
+    reader.read_exact(&mut key)?;
+    let mut size = 1 + 4 + key.len() as u64 + 1;
+    let value = if reader.read_u8()? != 0 {
+        let mut value = vec![0; reader.read_u32::<LittleEndian>()? as usize];
+        reader.read_exact(&mut value)?;
+        size += 4 + value.len() as u64;
+        Some(value)
+    } else {
+        None
+    };
+    Ok((col, key, value, size))
+}
+
+fn encode_changes(changes: &[(u8, Vec<u8>, Option<Vec<u8>>)]) -> Vec<u8> {
+    changes.try_to_vec().expect("Borsh serializer is not expected to ever fail")
+}
+
+fn decode_changes(bytes: &[u8]) -> io::Result<Vec<(u8, Vec<u8>, Option<Vec<u8>>)>> {
+    BorshDeserialize::try_from_slice(bytes)
+}
+
+fn write_manifest(path: &Path, manifest: &Manifest) -> io::Result<()> {
+    let tmp_path = path.join(format!("{}.tmp", MANIFEST_FILE));
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(&manifest.try_to_vec()?)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path.join(MANIFEST_FILE))?;
+    File::open(path)?.sync_all()
+}
+
+/// Removes tables and logs left behind by a flush or compaction interrupted by a crash.
+fn remove_stale_files(path: &Path, manifest: &Manifest) -> io::Result<()> {
+    for entry in fs::read_dir(path)? {
+        let entry_path = entry?.path();
+        let id = match entry_path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(stem) => match stem.parse::<u64>() {
+                Ok(id) => id,
+                Err(_) => continue,
+            },
+            None => continue,
+        };
+        let is_live = match entry_path.extension().and_then(|ext| ext.to_str()) {
+            Some("sst") => manifest.tables.contains(&id),
+            Some("log") => manifest.log_id == id,
+            Some("tmp") => false,
+            _ => true,
+        };
+        if !is_live {
+            warn!(target: "store", "Removing stale database file {}", entry_path.display());
+            fs::remove_file(entry_path)?;
+        }
+    }
+    Ok(())
+}
+
+fn table_path(path: &Path, id: u64) -> PathBuf {
+    path.join(format!("{:06}.sst", id))
+}
+
+fn log_path(path: &Path, id: u64) -> PathBuf {
+    path.join(format!("{:06}.log", id))
+}
+
+fn corrupted(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{} is not a valid sorted table. Database must be corrupted.", path.display()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LsmDB, LsmDBOptions};
+    use crate::db::{DBCol, Database};
+    use crate::Store;
+    use std::sync::Arc;
+
+    fn open(path: &std::path::Path, memtable_size: u64) -> Store {
+        let db = LsmDBOptions::default()
+            .memtable_size(bytesize::ByteSize::b(memtable_size))
+            .tables_per_tier(2)
+            .read_write(path)
+            .unwrap();
+        Store::new(Arc::pin(db))
+    }
+
+    #[test]
+    fn test_get_put_delete() {
+        let tmp_dir = tempfile::Builder::new().prefix("_test_lsm_get_put").tempdir().unwrap();
+        let store = open(tmp_dir.path(), 1 << 20);
+        let mut store_update = store.store_update();
+        store_update.set(DBCol::ColBlockMisc, b"a", b"1");
+        store_update.set(DBCol::ColBlockMisc, b"b", b"2");
+        store_update.set(DBCol::ColBlock, b"a", b"3");
+        store_update.commit().unwrap();
+        assert_eq!(store.get(DBCol::ColBlockMisc, b"a").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(store.get(DBCol::ColBlock, b"a").unwrap(), Some(b"3".to_vec()));
+        assert_eq!(store.get(DBCol::ColBlock, b"b").unwrap(), None);
+
+        let mut store_update = store.store_update();
+        store_update.delete(DBCol::ColBlockMisc, b"a");
+        store_update.commit().unwrap();
+        assert_eq!(store.get(DBCol::ColBlockMisc, b"a").unwrap(), None);
+        let keys: Vec<_> = store.iter(DBCol::ColBlockMisc).map(|(key, _)| key.to_vec()).collect();
+        assert_eq!(keys, vec![b"b".to_vec()]);
+    }
+
+    #[test]
+    fn test_reopen_replays_log_and_tables() {
+        let tmp_dir = tempfile::Builder::new().prefix("_test_lsm_reopen").tempdir().unwrap();
+        {
+            // A tiny memtable forces a flush and a compaction on almost every write.
+            let store = open(tmp_dir.path(), 16);
+            for i in 0u32..100 {
+                let mut store_update = store.store_update();
+                store_update.set(DBCol::ColBlockHeight, &i.to_be_bytes(), &[i as u8; 8]);
+                if i % 3 == 0 {
+                    store_update.delete(DBCol::ColBlockHeader, &(i / 3).to_be_bytes());
+                } else {
+                    store_update.set(DBCol::ColBlockHeader, &i.to_be_bytes(), &[1]);
+                }
+                store_update.commit().unwrap();
+            }
+        }
+        let store = open(tmp_dir.path(), 1 << 20);
+        for i in 0u32..100 {
+            assert_eq!(
+                store.get(DBCol::ColBlockHeight, &i.to_be_bytes()).unwrap(),
+                Some(vec![i as u8; 8])
+            );
+        }
+        let heights: Vec<_> = store.iter(DBCol::ColBlockHeight).map(|(key, _)| key).collect();
+        assert_eq!(heights.len(), 100);
+        assert!(heights.windows(2).all(|w| w[0] < w[1]));
+        let prefix = 0u32.to_be_bytes();
+        assert_eq!(store.iter_prefix(DBCol::ColBlockHeight, &prefix[..3]).count(), 100);
+        assert_eq!(store.iter_prefix(DBCol::ColBlockHeight, &[1]).count(), 0);
+    }
+
+    #[test]
+    fn test_refcount() {
+        let tmp_dir = tempfile::Builder::new().prefix("_test_lsm_refcount").tempdir().unwrap();
+        let store = open(tmp_dir.path(), 64);
+        for _ in 0..2 {
+            let mut store_update = store.store_update();
+            store_update.update_refcount(DBCol::ColState, &[1], &[1], 1);
+            store_update.commit().unwrap();
+        }
+        let mut store_update = store.store_update();
+        store_update.update_refcount(DBCol::ColState, &[1], &[1], -1);
+        store_update.update_refcount(DBCol::ColState, &[2], &[2], 1);
+        store_update.update_refcount(DBCol::ColState, &[2], &[2], 1);
+        store_update.commit().unwrap();
+        assert_eq!(store.get(DBCol::ColState, &[1]).unwrap(), Some(vec![1]));
+        assert_eq!(store.get(DBCol::ColState, &[2]).unwrap(), Some(vec![2]));
+
+        let mut store_update = store.store_update();
+        store_update.update_refcount(DBCol::ColState, &[1], &[1], -1);
+        store_update.commit().unwrap();
+        assert_eq!(store.get(DBCol::ColState, &[1]).unwrap(), None);
+        assert_eq!(store.iter_without_rc_logic(DBCol::ColState).count(), 1);
+
+        let mut store_update = store.store_update();
+        store_update.delete_all(DBCol::ColState);
+        store_update.commit().unwrap();
+        assert_eq!(store.iter(DBCol::ColState).count(), 0);
+    }
+
+    #[test]
+    fn test_compaction_keeps_deletions() {
+        let tmp_dir = tempfile::Builder::new().prefix("_test_lsm_compaction").tempdir().unwrap();
+        let db = LsmDBOptions::default()
+            .memtable_size(bytesize::ByteSize::b(16))
+            .tables_per_tier(2)
+            .read_write(tmp_dir.path())
+            .unwrap();
+        let write = |key: u32, value: Option<&[u8]>| {
+            let mut transaction = db.transaction();
+            match value {
+                Some(value) => transaction.put(DBCol::ColBlockMisc, key.to_be_bytes(), value),
+                None => transaction.delete(DBCol::ColBlockMisc, key.to_be_bytes()),
+            }
+            db.write(transaction).unwrap();
+        };
+        write(0, Some(&[0; 32]));
+        for i in 1..50u32 {
+            write(i, Some(&[1; 16]));
+        }
+        write(0, None);
+        for i in 50..100u32 {
+            write(i, Some(&[1; 16]));
+        }
+
+        // Merging all tables but the oldest one must keep the tombstone shadowing it.
+        {
+            let _compaction = db.shared.compaction.lock().unwrap();
+            db.shared
+                .merge_tables(|tables| Some(1..tables.len()).filter(|range| range.len() > 1))
+                .unwrap();
+        }
+        assert_eq!(db.get(DBCol::ColBlockMisc, &0u32.to_be_bytes()).unwrap(), None);
+
+        db.compact().unwrap();
+        assert_eq!(db.shared.inner.read().unwrap().tables.len(), 1);
+        assert_eq!(db.get(DBCol::ColBlockMisc, &0u32.to_be_bytes()).unwrap(), None);
+        assert_eq!(db.iter(DBCol::ColBlockMisc).count(), 99);
+    }
+
+    #[test]
+    fn test_read_only() {
+        let tmp_dir = tempfile::Builder::new().prefix("_test_lsm_read_only").tempdir().unwrap();
+        assert!(LsmDBOptions::default().read_only(tmp_dir.path()).is_err());
+        let db = LsmDB::new(tmp_dir.path()).unwrap();
+        let mut transaction = db.transaction();
+        transaction.put(DBCol::ColBlockMisc, b"key", b"value");
+        db.write(transaction).unwrap();
+        // Opening for writing a second time must fail while the first instance is alive.
+        assert!(LsmDB::new(tmp_dir.path()).is_err());
+
+        let read_only = LsmDBOptions::default().read_only(tmp_dir.path()).unwrap();
+        assert_eq!(read_only.get(DBCol::ColBlockMisc, b"key").unwrap(), Some(b"value".to_vec()));
+        assert!(read_only.write(read_only.transaction()).is_err());
+    }
+}
@@ -4,7 +4,7 @@ extern crate lazy_static;
 use std::fs::File;
 use std::io::{Read, Write};
 use std::ops::Deref;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::{fmt, io};
@@ -12,10 +12,11 @@ use std::{fmt, io};
 use borsh::{BorshDeserialize, BorshSerialize};
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use cached::{Cached, SizedCache};
+use serde::{Deserialize, Serialize};
 
 pub use db::DBCol::{self, *};
 pub use db::{
-    CHUNK_TAIL_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY,
+    CHUNK_TAIL_KEY, EPOCH_SYNC_HASH_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY,
     LARGEST_TARGET_HEIGHT_KEY, LATEST_KNOWN_KEY, NUM_COLS, SHOULD_COL_GC, SKIP_COL_GC, TAIL_KEY,
 };
 use near_crypto::PublicKey;
@@ -32,16 +33,20 @@ use near_primitives::types::{AccountId, CompiledContractCache, StateRoot};
 pub use crate::db::refcount::decode_value_with_rc;
 use crate::db::refcount::encode_value_with_rc;
 use crate::db::{
-    DBOp, DBTransaction, Database, RocksDB, GENESIS_JSON_HASH_KEY, GENESIS_STATE_ROOTS_KEY,
+    DBError, DBOp, DBTransaction, Database, LsmDB, LsmDBOptions, RocksDB, GENESIS_JSON_HASH_KEY,
+    GENESIS_STATE_ROOTS_KEY,
 };
 pub use crate::trie::{
     iterator::TrieIterator, split_state, update::TrieUpdate, update::TrieUpdateIterator,
     update::TrieUpdateValuePtr, ApplyStatePartResult, KeyForStateChanges, PartialStorage,
-    ShardTries, Trie, TrieChanges, WrappedTrieChanges,
+    ShardTrieCacheSize, ShardTries, Trie, TrieCacheConfig, TrieChanges, WrappedTrieChanges,
 };
 
 pub mod db;
+mod metrics;
 pub mod migrations;
+pub mod snapshot;
+pub mod stats;
 pub mod test_utils;
 mod trie;
 
@@ -146,8 +151,9 @@ impl Store {
         self.storage.write(transaction).map_err(|e| e.into())
     }
 
-    pub fn get_rocksdb(&self) -> Option<&RocksDB> {
-        self.storage.as_rocksdb()
+    /// Creates a consistent point-in-time copy of the store in `path`, which must not exist.
+    pub fn checkpoint(&self, path: &Path) -> Result<(), io::Error> {
+        self.storage.checkpoint(path).map_err(|e| e.into())
     }
 }
 
@@ -293,11 +299,128 @@ pub fn read_with_cache<'a, T: BorshDeserialize + 'a>(
     Ok(None)
 }
 
+/// Opens the store at `path` with the backend which created it. A new store is created with
+/// RocksDB, use [`create_store_with_config`] to choose another backend.
 pub fn create_store(path: &Path) -> Arc<Store> {
-    let db = Arc::pin(RocksDB::new(path).expect("Failed to open the database"));
+    let db: Pin<Arc<dyn Database>> = if LsmDB::exists(path) {
+        Arc::pin(LsmDB::new(path).expect("Failed to open the database"))
+    } else {
+        Arc::pin(RocksDB::new(path).expect("Failed to open the database"))
+    };
+    Arc::new(Store::new(db))
+}
+
+/// Opens the append-optimized cold store of an archival node at `path`.
+pub fn create_cold_store(path: &Path) -> Arc<Store> {
+    let db = Arc::pin(RocksDB::new_cold(path).expect("Failed to open the cold store"));
+    Arc::new(Store::new(db))
+}
+
+/// Reads an object from `storage` through `cache` and falls back to `cold_storage`, if any, when
+/// `storage` does not have it.
+pub fn read_with_cache_or_cold<'a, T: BorshDeserialize + 'a>(
+    storage: &Store,
+    cold_storage: Option<&Store>,
+    col: DBCol,
+    cache: &'a mut SizedCache<Vec<u8>, T>,
+    key: &[u8],
+) -> io::Result<Option<&'a T>> {
+    let key_vec = key.to_vec();
+    if cache.cache_get(&key_vec).is_some() {
+        return Ok(Some(cache.cache_get(&key_vec).unwrap()));
+    }
+    let result = match storage.get_ser(col, key)? {
+        Some(result) => Some(result),
+        None => match cold_storage {
+            Some(cold_storage) => cold_storage.get_ser(col, key)?,
+            None => None,
+        },
+    };
+    if let Some(result) = result {
+        cache.cache_set(key_vec.clone(), result);
+        return Ok(cache.cache_get(&key_vec));
+    }
+    Ok(None)
+}
+
+/// Storage engine behind [`Store`].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreBackend {
+    RocksDB,
+    /// Pure Rust log-structured merge store, see [`LsmDB`].
+    Lsm,
+}
+
+impl Default for StoreBackend {
+    fn default() -> Self {
+        StoreBackend::RocksDB
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct StoreConfig {
+    pub backend: StoreBackend,
+    /// Directory to take a [`snapshot`] of the store into at the start of every epoch, relative to
+    /// the home directory. Snapshots are disabled if not set.
+    pub snapshots_dir: Option<PathBuf>,
+    /// Number of the most recent snapshots to keep.
+    pub snapshots_to_keep: usize,
+    pub trie_cache: TrieCacheConfig,
+    /// Directory of the cold store of an archival node, relative to the home directory. If set,
+    /// archival nodes garbage collect blocks, chunks and outcomes older than the GC horizon from
+    /// the main store by moving them there.
+    pub cold_store_dir: Option<PathBuf>,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        StoreConfig {
+            backend: StoreBackend::default(),
+            snapshots_dir: None,
+            snapshots_to_keep: 2,
+            trie_cache: TrieCacheConfig::default(),
+            cold_store_dir: None,
+        }
+    }
+}
+
+/// Opens the store at `path` with the backend from `config`.
+///
+/// Panics if the directory already contains a database created by another backend.
+pub fn create_store_with_config(path: &Path, config: &StoreConfig) -> Arc<Store> {
+    let db: Pin<Arc<dyn Database>> = match config.backend {
+        StoreBackend::RocksDB => {
+            assert!(
+                !LsmDB::exists(path),
+                "Database at {} was created by the `lsm` backend",
+                path.display()
+            );
+            Arc::pin(RocksDB::new(path).expect("Failed to open the database"))
+        }
+        StoreBackend::Lsm => {
+            assert!(
+                !path.join("CURRENT").exists(),
+                "Database at {} was created by the `rocksdb` backend",
+                path.display()
+            );
+            Arc::pin(LsmDB::new(path).expect("Failed to open the database"))
+        }
+    };
     Arc::new(Store::new(db))
 }
 
+/// Opens the store at `path` in read only mode, so that it can be inspected while the node is
+/// running.
+pub fn open_read_only_store(path: &Path, config: &StoreConfig) -> Result<Arc<Store>, DBError> {
+    let db: Pin<Arc<dyn Database>> = match config.backend {
+        StoreBackend::RocksDB => Arc::pin(RocksDB::new_for_reading(path)?),
+        StoreBackend::Lsm => Arc::pin(LsmDBOptions::default().read_only(path)?),
+    };
+    Ok(Arc::new(Store::new(db)))
+}
+
 /// Reads an object from Trie.
 /// # Errors
 /// see StorageError
@@ -520,11 +643,76 @@ impl CompiledContractCache for StoreCompiledContractCache {
     }
 }
 
+/// Cache for compiled contracts code which reads the contracts compiled before from the Store,
+/// but keeps the newly compiled ones in memory only. Used to execute code which is not a part
+/// of the chain, so that it does not write to the Store.
+pub struct InMemoryCompiledContractCache {
+    pub store: Arc<Store>,
+    compiled: std::sync::Mutex<std::collections::HashMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl InMemoryCompiledContractCache {
+    pub fn new(store: Arc<Store>) -> Self {
+        Self { store, compiled: Default::default() }
+    }
+}
+
+impl CompiledContractCache for InMemoryCompiledContractCache {
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<(), std::io::Error> {
+        self.compiled.lock().unwrap().insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, std::io::Error> {
+        if let Some(value) = self.compiled.lock().unwrap().get(key) {
+            return Ok(Some(value.clone()));
+        }
+        self.store.get(DBCol::ColCachedContractCode, key)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use cached::SizedCache;
+
+    use crate::test_utils::create_test_store;
+    use crate::{read_with_cache_or_cold, DBCol, Store};
+
     #[test]
     fn test_no_cache_disabled() {
         #[cfg(feature = "no_cache")]
         panic!("no cache is enabled");
     }
+
+    #[test]
+    fn test_read_with_cache_or_cold() {
+        let store = create_test_store();
+        let cold_store = create_test_store();
+        let mut store_update = store.store_update();
+        store_update.set_ser(DBCol::ColBlockMisc, b"hot", &1u64).unwrap();
+        store_update.set_ser(DBCol::ColBlockMisc, b"both", &2u64).unwrap();
+        store_update.commit().unwrap();
+        let mut store_update = cold_store.store_update();
+        store_update.set_ser(DBCol::ColBlockMisc, b"cold", &3u64).unwrap();
+        store_update.set_ser(DBCol::ColBlockMisc, b"both", &4u64).unwrap();
+        store_update.commit().unwrap();
+
+        let mut cache: SizedCache<Vec<u8>, u64> = SizedCache::with_size(10);
+        let mut read = |cold_store: Option<&Store>, key: &[u8]| {
+            read_with_cache_or_cold(&store, cold_store, DBCol::ColBlockMisc, &mut cache, key)
+                .unwrap()
+                .copied()
+        };
+        assert_eq!(read(None, b"cold"), None);
+        assert_eq!(read(Some(&*cold_store), b"hot"), Some(1));
+        assert_eq!(read(Some(&*cold_store), b"both"), Some(2));
+        assert_eq!(read(Some(&*cold_store), b"cold"), Some(3));
+        assert_eq!(read(Some(&*cold_store), b"missing"), None);
+
+        // The values read from the cold store are cached.
+        let mut store_update = cold_store.store_update();
+        store_update.delete(DBCol::ColBlockMisc, b"cold");
+        store_update.commit().unwrap();
+        assert_eq!(read(None, b"cold"), Some(3));
+    }
 }
@@ -0,0 +1,29 @@
+use near_metrics::{
+    try_create_int_counter_vec, try_create_int_gauge_vec, IntCounterVec, IntGaugeVec,
+};
+
+lazy_static! {
+    pub static ref TRIE_CACHE_HITS: near_metrics::Result<IntCounterVec> =
+        try_create_int_counter_vec(
+            "near_trie_cache_hits_total",
+            "Number of trie nodes and values read from the trie cache",
+            &["shard_uid", "is_view"]
+        );
+    pub static ref TRIE_CACHE_MISSES: near_metrics::Result<IntCounterVec> =
+        try_create_int_counter_vec(
+            "near_trie_cache_misses_total",
+            "Number of trie nodes and values missing from the trie cache",
+            &["shard_uid", "is_view"]
+        );
+    pub static ref TRIE_CACHE_EVICTIONS: near_metrics::Result<IntCounterVec> =
+        try_create_int_counter_vec(
+            "near_trie_cache_evictions_total",
+            "Number of trie nodes and values evicted from the trie cache to fit its size limit",
+            &["shard_uid", "is_view"]
+        );
+    pub static ref TRIE_CACHE_SIZE: near_metrics::Result<IntGaugeVec> = try_create_int_gauge_vec(
+        "near_trie_cache_size_bytes",
+        "Total size of the trie nodes and values in the trie cache",
+        &["shard_uid", "is_view"]
+    );
+}
@@ -35,14 +35,14 @@ use crate::db::DBCol::{
     ColBlockHeader, ColBlockHeight, ColBlockMerkleTree, ColBlockMisc, ColBlockOrdinal, ColChunks,
     ColPartialChunks, ColStateParts,
 };
-use crate::db::{DBCol, RocksDB, GENESIS_JSON_HASH_KEY, VERSION_KEY};
+use crate::db::{DBCol, LsmDB, LsmDBOptions, RocksDB, GENESIS_JSON_HASH_KEY, VERSION_KEY};
 use crate::migrations::v6_to_v7::{
     col_state_refcount_8byte, migrate_col_transaction_refcount, migrate_receipts_refcount,
 };
 use crate::migrations::v8_to_v9::{
     recompute_col_rc, repair_col_receipt_id_to_shard_id, repair_col_transactions,
 };
-use crate::trie::{TrieCache, TrieCachingStorage};
+use crate::trie::{TrieCache, TrieCacheConfig, TrieCachingStorage};
 use crate::{create_store, Store, StoreUpdate, Trie, TrieUpdate, FINAL_HEAD_KEY, HEAD_KEY};
 use std::path::Path;
 
@@ -50,9 +50,22 @@ pub mod v6_to_v7;
 pub mod v8_to_v9;
 
 pub fn get_store_version(path: &Path) -> DbVersion {
+    if LsmDB::exists(path) {
+        let db = LsmDBOptions::default().read_only(path).expect("Failed to open the database");
+        return get_store_version_from_store(&Store::new(Arc::pin(db)))
+            .expect("Failed to find version. Database must be corrupted.");
+    }
     RocksDB::get_version(path).expect("Failed to open the database")
 }
 
+/// Returns version of an already opened store, regardless of its backend.
+pub fn get_store_version_from_store(store: &Store) -> Option<DbVersion> {
+    store.get(DBCol::ColDbVersion, VERSION_KEY).expect("Failed to read the database").map(|value| {
+        serde_json::from_slice(&value)
+            .expect("Failed to parse version. Database must be corrupted.")
+    })
+}
+
 fn set_store_version_inner(store_update: &mut StoreUpdate, db_version: u32) {
     store_update.set(
         DBCol::ColDbVersion,
@@ -416,8 +429,8 @@ pub fn migrate_13_to_14(path: &Path) {
 /// Make execution outcome ids in `ColOutcomeIds` ordered by replaying the chunks.
 pub fn migrate_14_to_15(path: &Path) {
     let store = create_store(path);
-    let trie_store =
-        Box::new(TrieCachingStorage::new(store.clone(), TrieCache::new(), ShardUId::default()));
+    let cache = TrieCache::new(&TrieCacheConfig::default(), ShardUId::default(), false);
+    let trie_store = Box::new(TrieCachingStorage::new(store.clone(), cache, ShardUId::default()));
     let trie = Rc::new(Trie::new(trie_store, ShardUId::default()));
 
     let mut store_update = store.store_update();
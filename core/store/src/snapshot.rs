@@ -0,0 +1,428 @@
+//! Snapshots of the store taken while the node is running.
+//!
+//! Every snapshot is a directory `snapshot-<height>` holding a [`Store::checkpoint`] of the
+//! database in `data` and a [`SnapshotInfo`] in `snapshot.json`. Snapshots are taken by the client
+//! at the start of every epoch with a [`Snapshotter`], and a node can be bootstrapped from one with
+//! `neard restore`.
+//!
+//! Small databases, e.g. of sandbox nodes, can also be copied into memory with [`StoreDump`] and
+//! reverted to that copy while the node is running.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use tracing::{error, info, warn};
+
+use near_primitives::block::Tip;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{BlockHeight, EpochId};
+use near_primitives::version::{DbVersion, DB_VERSION};
+
+use crate::db::LsmDB;
+use crate::migrations::get_store_version_from_store;
+use crate::{
+    get_genesis_hash, open_read_only_store, DBCol, Store, StoreBackend, StoreConfig,
+    FINAL_HEAD_KEY, HEAD_KEY,
+};
+
+pub const SNAPSHOT_INFO_FILE: &str = "snapshot.json";
+pub const SNAPSHOT_DATA_DIR: &str = "data";
+const SNAPSHOT_DIR_PREFIX: &str = "snapshot-";
+/// Directory a snapshot is assembled in, so that a crash never leaves a partial snapshot behind.
+const SNAPSHOT_TMP_DIR: &str = "snapshot.tmp";
+
+/// Metadata of a snapshot, stored next to its data.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SnapshotInfo {
+    pub db_version: DbVersion,
+    /// Hash of the genesis the store was initialized with, see [`get_genesis_hash`].
+    pub genesis_hash: CryptoHash,
+    /// Epoch of the head block.
+    pub epoch_id: EpochId,
+    pub head_height: BlockHeight,
+    pub head_hash: CryptoHash,
+    pub final_head_height: BlockHeight,
+    pub final_head_hash: CryptoHash,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("{0}")]
+    IO(#[from] io::Error),
+    #[error("snapshot is inconsistent: {0}")]
+    Corrupted(String),
+    #[error("snapshot has database version {version}, but this binary supports versions up to {supported}")]
+    UnsupportedVersion { version: DbVersion, supported: DbVersion },
+    #[error("snapshot was created with genesis {found}, but the node is configured with genesis {expected}")]
+    GenesisMismatch { expected: CryptoHash, found: CryptoHash },
+}
+
+impl SnapshotInfo {
+    /// Describes the current state of `store`.
+    pub fn from_store(store: &Store) -> io::Result<Self> {
+        let db_version = get_store_version_from_store(store).ok_or_else(|| not_found("version"))?;
+        let genesis_hash = get_genesis_hash(store)?.ok_or_else(|| not_found("genesis hash"))?;
+        let head: Tip =
+            store.get_ser(DBCol::ColBlockMisc, HEAD_KEY)?.ok_or_else(|| not_found("head"))?;
+        let final_head: Tip = store
+            .get_ser(DBCol::ColBlockMisc, FINAL_HEAD_KEY)?
+            .ok_or_else(|| not_found("final head"))?;
+        Ok(SnapshotInfo {
+            db_version,
+            genesis_hash,
+            epoch_id: head.epoch_id,
+            head_height: head.height,
+            head_hash: head.last_block_hash,
+            final_head_height: final_head.height,
+            final_head_hash: final_head.last_block_hash,
+        })
+    }
+
+    /// Reads the metadata of the snapshot in `snapshot_dir`.
+    pub fn read(snapshot_dir: &Path) -> io::Result<Self> {
+        let path = snapshot_dir.join(SNAPSHOT_INFO_FILE);
+        serde_json::from_slice(&fs::read(&path)?).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to parse {}: {}", path.display(), err),
+            )
+        })
+    }
+}
+
+/// Takes a snapshot of `store` in `snapshots_dir` and removes all but the `keep` most recent
+/// snapshots. Returns the directory of the new snapshot.
+///
+/// The metadata is read from the checkpoint, so the store may be written to meanwhile. Only one
+/// snapshot may be taken in `snapshots_dir` at a time.
+pub fn create_snapshot(store: &Store, snapshots_dir: &Path, keep: usize) -> io::Result<PathBuf> {
+    let tmp_dir = snapshots_dir.join(SNAPSHOT_TMP_DIR);
+    if tmp_dir.exists() {
+        fs::remove_dir_all(&tmp_dir)?;
+    }
+    fs::create_dir_all(&tmp_dir)?;
+    store.checkpoint(&tmp_dir.join(SNAPSHOT_DATA_DIR))?;
+    let info = SnapshotInfo::from_store(&open_snapshot_data(&tmp_dir)?)?;
+    let snapshot_dir = snapshots_dir.join(format!("{}{}", SNAPSHOT_DIR_PREFIX, info.head_height));
+    if snapshot_dir.exists() {
+        fs::remove_dir_all(&tmp_dir)?;
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("snapshot {} already exists", snapshot_dir.display()),
+        ));
+    }
+    fs::write(tmp_dir.join(SNAPSHOT_INFO_FILE), serde_json::to_vec_pretty(&info)?)?;
+    fs::rename(&tmp_dir, &snapshot_dir)?;
+    info!(target: "store", "Created store snapshot {} at #{} {}", snapshot_dir.display(), info.head_height, info.head_hash);
+
+    let snapshots = list_snapshots(snapshots_dir)?;
+    let num_stale = snapshots.len().saturating_sub(std::cmp::max(keep, 1));
+    for (_, stale_dir) in snapshots.into_iter().take(num_stale) {
+        info!(target: "store", "Removing store snapshot {}", stale_dir.display());
+        fs::remove_dir_all(stale_dir)?;
+    }
+    Ok(snapshot_dir)
+}
+
+/// Opens the database of the snapshot in `snapshot_dir` for reading, whichever backend created it.
+fn open_snapshot_data(snapshot_dir: &Path) -> io::Result<Arc<Store>> {
+    let data_dir = snapshot_dir.join(SNAPSHOT_DATA_DIR);
+    let backend = if LsmDB::exists(&data_dir) { StoreBackend::Lsm } else { StoreBackend::RocksDB };
+    open_read_only_store(&data_dir, &StoreConfig { backend, ..StoreConfig::default() })
+        .map_err(|err| err.into())
+}
+
+/// Takes snapshots on a separate thread, one at a time, so that the chain isn't blocked while the
+/// store is copied.
+#[derive(Default)]
+pub struct Snapshotter {
+    in_progress: Arc<AtomicBool>,
+}
+
+impl Snapshotter {
+    /// Starts taking a snapshot with [`create_snapshot`]. Skips it if the previous one is still
+    /// being taken.
+    pub fn start(&self, store: Arc<Store>, snapshots_dir: PathBuf, keep: usize) {
+        if self.in_progress.swap(true, Ordering::AcqRel) {
+            warn!(target: "store", "Skipping store snapshot, the previous one is still being taken");
+            return;
+        }
+        let in_progress = Arc::clone(&self.in_progress);
+        let result =
+            std::thread::Builder::new().name("store-snapshot".to_string()).spawn(move || {
+                if let Err(err) = create_snapshot(&store, &snapshots_dir, keep) {
+                    error!(target: "store", "Failed to snapshot the store: {}", err);
+                }
+                in_progress.store(false, Ordering::Release);
+            });
+        if let Err(err) = result {
+            error!(target: "store", "Failed to start a store snapshot: {}", err);
+            self.in_progress.store(false, Ordering::Release);
+        }
+    }
+}
+
+/// Returns heights and directories of the snapshots in `snapshots_dir`, from the oldest to the
+/// newest.
+pub fn list_snapshots(snapshots_dir: &Path) -> io::Result<Vec<(BlockHeight, PathBuf)>> {
+    let mut snapshots = vec![];
+    for entry in fs::read_dir(snapshots_dir)? {
+        let path = entry?.path();
+        let height = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(SNAPSHOT_DIR_PREFIX))
+            .and_then(|height| height.parse::<BlockHeight>().ok());
+        if let Some(height) = height {
+            snapshots.push((height, path));
+        }
+    }
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+/// Checks that the snapshot database `store` matches its metadata `info` and can be reused by a
+/// node with genesis `genesis_hash`.
+///
+/// Snapshots with an older database version are accepted, they are migrated when the node starts.
+pub fn validate_snapshot(
+    store: &Store,
+    info: &SnapshotInfo,
+    genesis_hash: &CryptoHash,
+) -> Result<(), SnapshotError> {
+    let db_version = get_store_version_from_store(store)
+        .ok_or_else(|| SnapshotError::Corrupted("database has no version".to_string()))?;
+    if db_version != info.db_version {
+        return Err(SnapshotError::Corrupted(format!(
+            "database has version {}, but {} is recorded in {}",
+            db_version, info.db_version, SNAPSHOT_INFO_FILE
+        )));
+    }
+    if db_version > DB_VERSION {
+        return Err(SnapshotError::UnsupportedVersion {
+            version: db_version,
+            supported: DB_VERSION,
+        });
+    }
+    let stored_genesis_hash = get_genesis_hash(store)?
+        .ok_or_else(|| SnapshotError::Corrupted("database has no genesis hash".to_string()))?;
+    if stored_genesis_hash != *genesis_hash {
+        return Err(SnapshotError::GenesisMismatch {
+            expected: *genesis_hash,
+            found: stored_genesis_hash,
+        });
+    }
+    Ok(())
+}
+
+/// Copies the database of the snapshot in `snapshot_dir` to `store_path`, which must not exist.
+///
+/// Sorted tables are never modified in place, so they are hard linked when possible. Other files
+/// are copied, since the database appends to them.
+pub fn restore_snapshot(snapshot_dir: &Path, store_path: &Path) -> io::Result<()> {
+    if store_path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", store_path.display()),
+        ));
+    }
+    fs::create_dir_all(store_path)?;
+    let result = copy_data(&snapshot_dir.join(SNAPSHOT_DATA_DIR), store_path);
+    if result.is_err() {
+        let _ = fs::remove_dir_all(store_path);
+    }
+    result
+}
+
+fn copy_data(from: &Path, to: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(from)? {
+        let src = entry?.path();
+        let dst = to.join(src.file_name().expect("read_dir returns file names"));
+        let is_table = src.extension().map_or(false, |ext| ext == "sst");
+        if !is_table || fs::hard_link(&src, &dst).is_err() {
+            fs::copy(&src, &dst)?;
+        }
+    }
+    Ok(())
+}
+
+/// In-memory copy of every column of a store.
+pub struct StoreDump {
+    columns: Vec<(DBCol, Vec<(Box<[u8]>, Box<[u8]>)>)>,
+}
+
+impl StoreDump {
+    /// Copies the whole `store` into memory, refcounted values included as is.
+    pub fn take(store: &Store) -> Self {
+        let columns =
+            DBCol::iter().map(|col| (col, store.iter_without_rc_logic(col).collect())).collect();
+        StoreDump { columns }
+    }
+
+    /// Total size of the copied keys and values, in bytes.
+    pub fn size_bytes(&self) -> u64 {
+        self.columns
+            .iter()
+            .flat_map(|(_, values)| values.iter())
+            .map(|(key, value)| (key.len() + value.len()) as u64)
+            .sum()
+    }
+
+    /// Replaces the contents of `store` with the dump in a single transaction.
+    ///
+    /// The caller is responsible for dropping everything cached from the replaced contents.
+    pub fn restore(&self, store: &Store) -> io::Result<()> {
+        let mut store_update = store.store_update();
+        for (col, values) in self.columns.iter() {
+            store_update.delete_all(*col);
+            for (key, value) in values {
+                store_update.set(*col, key, value);
+            }
+        }
+        store_update.commit()
+    }
+}
+
+fn not_found(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("store has no {}", what))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use near_primitives::block::Tip;
+    use near_primitives::hash::{hash, CryptoHash};
+    use near_primitives::types::EpochId;
+    use near_primitives::version::DB_VERSION;
+
+    use super::{
+        create_snapshot, list_snapshots, restore_snapshot, validate_snapshot, SnapshotError,
+        SnapshotInfo, Snapshotter, StoreDump,
+    };
+    use crate::db::LsmDB;
+    use crate::migrations::set_store_version;
+    use crate::test_utils::create_test_store;
+    use crate::{set_genesis_hash, DBCol, Store, FINAL_HEAD_KEY, HEAD_KEY};
+
+    fn set_head(store: &Store, height: u64) {
+        let tip = Tip {
+            height,
+            last_block_hash: hash(&height.to_le_bytes()),
+            prev_block_hash: CryptoHash::default(),
+            epoch_id: EpochId::default(),
+            next_epoch_id: EpochId::default(),
+        };
+        let mut store_update = store.store_update();
+        store_update.set_ser(DBCol::ColBlockMisc, HEAD_KEY, &tip).unwrap();
+        store_update.set_ser(DBCol::ColBlockMisc, FINAL_HEAD_KEY, &tip).unwrap();
+        store_update.commit().unwrap();
+    }
+
+    #[test]
+    fn test_create_and_restore_snapshot() {
+        let tmp_dir = tempfile::Builder::new().prefix("_test_snapshot").tempdir().unwrap();
+        let store = Store::new(Arc::pin(LsmDB::new(tmp_dir.path().join("data")).unwrap()));
+        let snapshots_dir = tmp_dir.path().join("snapshots");
+        let genesis_hash = hash(b"genesis");
+        set_store_version(&store, DB_VERSION);
+        let mut store_update = store.store_update();
+        set_genesis_hash(&mut store_update, &genesis_hash);
+        store_update.commit().unwrap();
+
+        for height in 1..=3 {
+            set_head(&store, height);
+            create_snapshot(&store, &snapshots_dir, 2).unwrap();
+        }
+        let snapshots = list_snapshots(&snapshots_dir).unwrap();
+        assert_eq!(snapshots.iter().map(|(height, _)| *height).collect::<Vec<_>>(), vec![2, 3]);
+
+        // Writes after the snapshot don't affect it.
+        set_head(&store, 4);
+        let snapshot_dir = &snapshots[1].1;
+        let info = SnapshotInfo::read(snapshot_dir).unwrap();
+        assert_eq!(info.head_height, 3);
+        assert_eq!(info.genesis_hash, genesis_hash);
+
+        let restored_path = tmp_dir.path().join("restored");
+        restore_snapshot(snapshot_dir, &restored_path).unwrap();
+        assert!(restore_snapshot(snapshot_dir, &restored_path).is_err());
+        let restored = Store::new(Arc::pin(LsmDB::new(&restored_path).unwrap()));
+        assert_eq!(SnapshotInfo::from_store(&restored).unwrap(), info);
+        validate_snapshot(&restored, &info, &genesis_hash).unwrap();
+        assert!(matches!(
+            validate_snapshot(&restored, &info, &hash(b"other genesis")),
+            Err(SnapshotError::GenesisMismatch { .. })
+        ));
+
+        set_store_version(&restored, DB_VERSION + 1);
+        let info = SnapshotInfo { db_version: DB_VERSION + 1, ..info };
+        assert!(matches!(
+            validate_snapshot(&restored, &info, &genesis_hash),
+            Err(SnapshotError::UnsupportedVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn test_snapshotter() {
+        let tmp_dir = tempfile::Builder::new().prefix("_test_snapshotter").tempdir().unwrap();
+        let store =
+            Arc::new(Store::new(Arc::pin(LsmDB::new(tmp_dir.path().join("data")).unwrap())));
+        let snapshots_dir = tmp_dir.path().join("snapshots");
+        set_store_version(&store, DB_VERSION);
+        let mut store_update = store.store_update();
+        set_genesis_hash(&mut store_update, &hash(b"genesis"));
+        store_update.commit().unwrap();
+        set_head(&store, 1);
+
+        let snapshotter = Snapshotter::default();
+        snapshotter.start(store.clone(), snapshots_dir.clone(), 2);
+        // Writes while the snapshot is taken don't make it inconsistent with its metadata.
+        set_head(&store, 2);
+        for _ in 0..100 {
+            if !snapshotter.in_progress.load(Ordering::Acquire) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        let snapshots = list_snapshots(&snapshots_dir).unwrap();
+        assert_eq!(snapshots.len(), 1);
+        let (height, snapshot_dir) = &snapshots[0];
+        assert_eq!(SnapshotInfo::read(snapshot_dir).unwrap().head_height, *height);
+    }
+
+    #[test]
+    fn test_store_dump() {
+        let store = create_test_store();
+        let mut store_update = store.store_update();
+        store_update.set(DBCol::ColBlockMisc, &[1], &[1]);
+        store_update.update_refcount(DBCol::ColState, &[1], &[1], 2);
+        store_update.commit().unwrap();
+        let dump = StoreDump::take(&store);
+        assert!(dump.size_bytes() >= 4);
+
+        let mut store_update = store.store_update();
+        store_update.set(DBCol::ColBlockMisc, &[1], &[2]);
+        store_update.set(DBCol::ColBlockMisc, &[2], &[2]);
+        store_update.update_refcount(DBCol::ColState, &[1], &[1], -1);
+        store_update.update_refcount(DBCol::ColState, &[2], &[2], 1);
+        store_update.commit().unwrap();
+
+        dump.restore(&store).unwrap();
+        assert_eq!(store.get(DBCol::ColBlockMisc, &[1]).unwrap(), Some(vec![1]));
+        assert_eq!(store.get(DBCol::ColBlockMisc, &[2]).unwrap(), None);
+        assert_eq!(store.get(DBCol::ColState, &[2]).unwrap(), None);
+        // The refcount is restored too, so the value survives a single decrement.
+        let mut store_update = store.store_update();
+        store_update.update_refcount(DBCol::ColState, &[1], &[1], -1);
+        store_update.commit().unwrap();
+        assert_eq!(store.get(DBCol::ColState, &[1]).unwrap(), Some(vec![1]));
+    }
+}
@@ -0,0 +1,118 @@
+//! Offline statistics about the space taken by the database columns.
+
+use std::collections::BTreeMap;
+
+use strum::IntoEnumIterator;
+
+use crate::db::refcount::decode_value_with_rc;
+use crate::{DBCol, Store};
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct ColumnStats {
+    pub num_keys: u64,
+    pub total_key_size: u64,
+    /// For refcounted columns, value size doesn't include the refcount.
+    pub total_value_size: u64,
+    pub max_value_size: u64,
+    /// For refcounted columns, number of values per refcount bucket. Positive refcounts are
+    /// grouped in power-of-two buckets keyed by the lower bound, e.g. `4` holds refcounts 4..=7.
+    /// Non-positive refcounts are not grouped and indicate a bug in garbage collection.
+    pub refcounts: BTreeMap<i64, u64>,
+}
+
+impl ColumnStats {
+    pub fn avg_key_size(&self) -> f64 {
+        if self.num_keys == 0 {
+            0.0
+        } else {
+            self.total_key_size as f64 / self.num_keys as f64
+        }
+    }
+
+    pub fn avg_value_size(&self) -> f64 {
+        if self.num_keys == 0 {
+            0.0
+        } else {
+            self.total_value_size as f64 / self.num_keys as f64
+        }
+    }
+
+    fn add(&mut self, key: &[u8], value: &[u8]) {
+        self.num_keys += 1;
+        self.total_key_size += key.len() as u64;
+        self.total_value_size += value.len() as u64;
+        self.max_value_size = std::cmp::max(self.max_value_size, value.len() as u64);
+    }
+}
+
+/// Returns the lower bound of the bucket of `rc` in [`ColumnStats::refcounts`].
+pub fn refcount_bucket(rc: i64) -> i64 {
+    if rc <= 0 {
+        rc
+    } else {
+        1 << (63 - rc.leading_zeros())
+    }
+}
+
+/// Walks over the whole column and collects its statistics.
+pub fn column_stats(store: &Store, col: DBCol) -> ColumnStats {
+    let mut stats = ColumnStats::default();
+    if col.is_rc() {
+        for (key, value) in store.iter_without_rc_logic(col) {
+            let (value, rc) = decode_value_with_rc(&value);
+            stats.add(&key, value.unwrap_or_default());
+            *stats.refcounts.entry(refcount_bucket(rc)).or_default() += 1;
+        }
+    } else {
+        for (key, value) in store.iter(col) {
+            stats.add(&key, &value);
+        }
+    }
+    stats
+}
+
+/// Collects statistics of every column.
+pub fn all_column_stats(store: &Store) -> Vec<(DBCol, ColumnStats)> {
+    DBCol::iter().map(|col| (col, column_stats(store, col))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{column_stats, refcount_bucket};
+    use crate::test_utils::create_test_store;
+    use crate::DBCol;
+
+    #[test]
+    fn test_refcount_bucket() {
+        assert_eq!(refcount_bucket(-3), -3);
+        assert_eq!(refcount_bucket(1), 1);
+        assert_eq!(refcount_bucket(3), 2);
+        assert_eq!(refcount_bucket(4), 4);
+        assert_eq!(refcount_bucket(1000), 512);
+    }
+
+    #[test]
+    fn test_column_stats() {
+        let store = create_test_store();
+        let mut store_update = store.store_update();
+        store_update.set(DBCol::ColBlockMisc, &[1], &[1, 2, 3]);
+        store_update.set(DBCol::ColBlockMisc, &[2, 2], &[1]);
+        store_update.update_refcount(DBCol::ColState, &[1], &[1, 1], 1);
+        store_update.update_refcount(DBCol::ColState, &[2], &[2], 3);
+        store_update.update_refcount(DBCol::ColState, &[3], &[3], 1);
+        store_update.commit().unwrap();
+
+        let stats = column_stats(&store, DBCol::ColBlockMisc);
+        assert_eq!(stats.num_keys, 2);
+        assert_eq!(stats.total_key_size, 3);
+        assert_eq!(stats.total_value_size, 4);
+        assert_eq!(stats.max_value_size, 3);
+        assert_eq!(stats.avg_value_size(), 2.0);
+        assert!(stats.refcounts.is_empty());
+
+        let stats = column_stats(&store, DBCol::ColState);
+        assert_eq!(stats.num_keys, 3);
+        assert_eq!(stats.total_value_size, 4);
+        assert_eq!(stats.refcounts.into_iter().collect::<Vec<_>>(), vec![(1, 2), (2, 1)]);
+    }
+}
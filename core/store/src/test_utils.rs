@@ -4,7 +4,6 @@ use std::sync::Arc;
 use rand::seq::SliceRandom;
 use rand::Rng;
 
-use crate::db::TestDB;
 use crate::{ShardTries, Store};
 use near_primitives::account::id::AccountId;
 use near_primitives::hash::CryptoHash;
@@ -13,12 +12,76 @@ use near_primitives::shard_layout::{ShardUId, ShardVersion};
 use near_primitives::types::NumShards;
 use std::str::from_utf8;
 
-/// Creates an in-memory database.
+/// Creates an in-memory database. With the `lsm_test_store` feature, creates an [`LsmDB`] in a
+/// temporary directory instead, so that the tests using it run against that backend.
+///
+/// [`LsmDB`]: crate::db::LsmDB
 pub fn create_test_store() -> Arc<Store> {
-    let db = Arc::pin(TestDB::new());
+    #[cfg(not(feature = "lsm_test_store"))]
+    let db = Arc::pin(crate::db::TestDB::new());
+    #[cfg(feature = "lsm_test_store")]
+    let db = Arc::pin(lsm_test_store::TempLsmDB::new());
     Arc::new(Store::new(db))
 }
 
+#[cfg(feature = "lsm_test_store")]
+mod lsm_test_store {
+    use std::path::Path;
+
+    use crate::db::{DBCol, DBError, DBTransaction, Database, LsmDB, LsmDBOptions};
+
+    /// [`LsmDB`] in a temporary directory, which is removed together with the database.
+    pub(super) struct TempLsmDB {
+        db: LsmDB,
+        _dir: tempfile::TempDir,
+    }
+
+    impl TempLsmDB {
+        pub(super) fn new() -> Self {
+            let dir = tempfile::Builder::new().prefix("lsm_test_store").tempdir().unwrap();
+            // A small in-memory table makes tests exercise flushes and compactions too.
+            let db = LsmDBOptions::default()
+                .memtable_size(bytesize::ByteSize::kb(64))
+                .read_write(dir.path())
+                .unwrap();
+            TempLsmDB { db, _dir: dir }
+        }
+    }
+
+    impl Database for TempLsmDB {
+        fn get(&self, col: DBCol, key: &[u8]) -> Result<Option<Vec<u8>>, DBError> {
+            self.db.get(col, key)
+        }
+
+        fn iter<'a>(&'a self, col: DBCol) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+            self.db.iter(col)
+        }
+
+        fn iter_without_rc_logic<'a>(
+            &'a self,
+            col: DBCol,
+        ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+            self.db.iter_without_rc_logic(col)
+        }
+
+        fn iter_prefix<'a>(
+            &'a self,
+            col: DBCol,
+            key_prefix: &'a [u8],
+        ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+            self.db.iter_prefix(col, key_prefix)
+        }
+
+        fn write(&self, transaction: DBTransaction) -> Result<(), DBError> {
+            self.db.write(transaction)
+        }
+
+        fn checkpoint(&self, path: &Path) -> Result<(), DBError> {
+            self.db.checkpoint(path)
+        }
+    }
+}
+
 /// Creates a Trie using an in-memory database.
 pub fn create_tries() -> ShardTries {
     let store = create_test_store();
@@ -59,7 +59,7 @@ impl<'a> TrieIterator<'a> {
             key_nibbles: Vec::with_capacity(64),
             root: *root,
         };
-        let node = trie.retrieve_node(root)?;
+        let node = trie.retrieve_node_at_depth(root, 0)?;
         r.descend_into_node(node);
         Ok(r)
     }
@@ -78,7 +78,7 @@ impl<'a> TrieIterator<'a> {
         self.key_nibbles.clear();
         let mut hash = self.root;
         loop {
-            let node = self.trie.retrieve_node(&hash)?;
+            let node = self.trie.retrieve_node_at_depth(&hash, self.trail.len())?;
             self.trail.push(Crumb { status: CrumbStatus::Entering, node });
             let Crumb { status, node } = self.trail.last_mut().unwrap();
             match &node.node {
@@ -276,7 +276,7 @@ impl<'a> TrieIterator<'a> {
                     if self.key_nibbles[prefix..] >= path_end[prefix..] {
                         break;
                     }
-                    let node = self.trie.retrieve_node(&hash)?;
+                    let node = self.trie.retrieve_node_at_depth(&hash, self.trail.len())?;
                     self.descend_into_node(node);
                     nodes_list.push(TrieTraversalItem { hash, key: None });
                 }
@@ -311,10 +311,12 @@ impl<'a> Iterator for TrieIterator<'a> {
                 IterStep::PopTrail => {
                     self.trail.pop();
                 }
-                IterStep::Descend(hash) => match self.trie.retrieve_node(&hash) {
-                    Ok(node) => self.descend_into_node(node),
-                    Err(e) => return Some(Err(e)),
-                },
+                IterStep::Descend(hash) => {
+                    match self.trie.retrieve_node_at_depth(&hash, self.trail.len()) {
+                        Ok(node) => self.descend_into_node(node),
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
                 IterStep::Continue => {}
                 IterStep::Value(hash) => {
                     return Some(
@@ -19,11 +19,12 @@ use crate::trie::insert_delete::NodesStorage;
 use crate::trie::iterator::TrieIterator;
 use crate::trie::nibble_slice::NibbleSlice;
 pub use crate::trie::shard_tries::{KeyForStateChanges, ShardTries, WrappedTrieChanges};
+pub use crate::trie::trie_storage::{ShardTrieCacheSize, TrieCacheConfig};
 use crate::trie::trie_storage::{
     TouchedNodesCounter, TrieMemoryPartialStorage, TrieRecordingStorage, TrieStorage,
 };
 pub(crate) use crate::trie::trie_storage::{TrieCache, TrieCachingStorage};
-use crate::StorageError;
+use crate::{DBCol, StorageError, StoreUpdate};
 
 mod insert_delete;
 pub mod iterator;
@@ -461,6 +462,26 @@ impl TrieChanges {
     pub fn empty(old_root: StateRoot) -> Self {
         TrieChanges { old_root, new_root: old_root, insertions: vec![], deletions: vec![] }
     }
+
+    /// Adds the nodes and values deleted by these changes to `store_update`, which must be of
+    /// another store than the trie, e.g. the cold store keeping the state history of an archival
+    /// node.
+    pub fn deletions_into(&self, shard_uid: ShardUId, store_update: &mut StoreUpdate) {
+        for TrieRefcountChange { trie_node_or_value_hash, trie_node_or_value, rc } in
+            self.deletions.iter()
+        {
+            let key = TrieCachingStorage::get_key_from_shard_uid_and_hash(
+                shard_uid,
+                trie_node_or_value_hash,
+            );
+            store_update.update_refcount(
+                DBCol::ColState,
+                key.as_ref(),
+                trie_node_or_value,
+                *rc as i64,
+            );
+        }
+    }
 }
 
 /// Result of applying state part to Trie.
@@ -604,7 +625,25 @@ impl Trie {
             return Ok(TrieNodeWithSize::empty());
         }
         let bytes = self.retrieve_raw_bytes(hash)?;
-        match RawTrieNodeWithSize::decode(&bytes) {
+        Self::decode_node(hash, &bytes)
+    }
+
+    /// Same as `retrieve_node`, for a trie node `depth` levels below the root, so that the top
+    /// levels of the trie are pinned in the cache.
+    fn retrieve_node_at_depth(
+        &self,
+        hash: &CryptoHash,
+        depth: usize,
+    ) -> Result<TrieNodeWithSize, StorageError> {
+        if *hash == Trie::empty_root() {
+            return Ok(TrieNodeWithSize::empty());
+        }
+        let bytes = self.retrieve_trie_node(hash, depth)?;
+        Self::decode_node(hash, &bytes)
+    }
+
+    fn decode_node(hash: &CryptoHash, bytes: &[u8]) -> Result<TrieNodeWithSize, StorageError> {
+        match RawTrieNodeWithSize::decode(bytes) {
             Ok(value) => Ok(TrieNodeWithSize::from_raw(value)),
             Err(_) => Err(StorageError::StorageInconsistentState(format!(
                 "Failed to decode node {}",
@@ -618,11 +657,16 @@ impl Trie {
         self.storage.retrieve_raw_bytes(hash)
     }
 
+    fn retrieve_trie_node(&self, hash: &CryptoHash, depth: usize) -> Result<Vec<u8>, StorageError> {
+        self.counter.increment();
+        self.storage.retrieve_trie_node(hash, depth)
+    }
+
     pub fn retrieve_root_node(&self, root: &StateRoot) -> Result<StateRootNode, StorageError> {
         if *root == Trie::empty_root() {
             return Ok(StateRootNode::empty());
         }
-        let data = self.retrieve_raw_bytes(root)?;
+        let data = self.retrieve_trie_node(root, 0)?;
         match RawTrieNodeWithSize::decode(&data) {
             Ok(value) => {
                 let memory_usage = TrieNodeWithSize::from_raw(value).memory_usage;
@@ -635,21 +679,29 @@ impl Trie {
         }
     }
 
+    /// Looks up the value reference of `key`. If `path` is given, the serialized nodes visited on
+    /// the way are appended to it.
     fn lookup(
         &self,
         root: &CryptoHash,
         mut key: NibbleSlice<'_>,
+        mut path: Option<&mut Vec<Vec<u8>>>,
     ) -> Result<Option<(u32, CryptoHash)>, StorageError> {
         let mut hash = *root;
+        let mut depth = 0;
 
         loop {
             if hash == Trie::empty_root() {
                 return Ok(None);
             }
-            let bytes = self.retrieve_raw_bytes(&hash)?;
+            let bytes = self.retrieve_trie_node(&hash, depth)?;
+            depth += 1;
             let node = RawTrieNodeWithSize::decode(&bytes).map_err(|_| {
                 StorageError::StorageInconsistentState("RawTrieNode decode failed".to_string())
             })?;
+            if let Some(path) = path.as_mut() {
+                path.push(bytes);
+            }
 
             match node.node {
                 RawTrieNode::Leaf(existing_key, value_length, value_hash) => {
@@ -696,7 +748,66 @@ impl Trie {
         key: &[u8],
     ) -> Result<Option<(u32, CryptoHash)>, StorageError> {
         let key = NibbleSlice::new(key);
-        self.lookup(root, key)
+        self.lookup(root, key, None)
+    }
+
+    /// Returns the serialized nodes on the path from `root` to the value of `key`, which prove
+    /// the value to anyone who knows `root`, see [`Trie::verify_proof`]. Returns `None` if there
+    /// is no such key.
+    pub fn get_proof(
+        &self,
+        root: &CryptoHash,
+        key: &[u8],
+    ) -> Result<Option<Vec<Vec<u8>>>, StorageError> {
+        let mut path = vec![];
+        let value_ref = self.lookup(root, NibbleSlice::new(key), Some(&mut path))?;
+        Ok(value_ref.map(|_| path))
+    }
+
+    /// Checks that `proof`, as returned by [`Trie::get_proof`], proves that `key` has `value` in
+    /// the trie with root `root`. Doesn't need access to the storage.
+    pub fn verify_proof(root: &CryptoHash, key: &[u8], value: &[u8], proof: &[Vec<u8>]) -> bool {
+        let value_ref = (value.len() as u32, hash(value));
+        let mut key = NibbleSlice::new(key);
+        let mut expected_hash = *root;
+        for (i, bytes) in proof.iter().enumerate() {
+            let is_last = i + 1 == proof.len();
+            if hash(bytes) != expected_hash {
+                return false;
+            }
+            let node = match RawTrieNodeWithSize::decode(bytes) {
+                Ok(node) => node.node,
+                Err(_) => return false,
+            };
+            match node {
+                RawTrieNode::Leaf(existing_key, value_length, value_hash) => {
+                    return is_last
+                        && NibbleSlice::from_encoded(&existing_key).0 == key
+                        && (value_length, value_hash) == value_ref;
+                }
+                RawTrieNode::Extension(existing_key, child) => {
+                    let existing_key = NibbleSlice::from_encoded(&existing_key).0;
+                    if !key.starts_with(&existing_key) {
+                        return false;
+                    }
+                    key = key.mid(existing_key.len());
+                    expected_hash = child;
+                }
+                RawTrieNode::Branch(children, node_value) => {
+                    if key.is_empty() {
+                        return is_last && node_value == Some(value_ref);
+                    }
+                    match children[key.at(0) as usize] {
+                        Some(child) => {
+                            key = key.mid(1);
+                            expected_hash = child;
+                        }
+                        None => return false,
+                    }
+                }
+            }
+        }
+        false
     }
 
     pub fn get(&self, root: &CryptoHash, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError> {
@@ -865,6 +976,32 @@ mod tests {
         assert_eq!(other_iter.next().unwrap().unwrap().0, b"x".to_vec());
     }
 
+    #[test]
+    fn test_trie_proofs() {
+        let tries = create_tries_complex(SHARD_VERSION, 2);
+        let shard_uid = ShardUId { version: SHARD_VERSION, shard_id: 0 };
+        let trie = tries.get_trie_for_shard(shard_uid);
+        let changes = vec![
+            (b"doge".to_vec(), Some(b"coin".to_vec())),
+            (b"docu".to_vec(), Some(b"value".to_vec())),
+            (b"do".to_vec(), Some(b"verb".to_vec())),
+            (b"horse".to_vec(), Some(b"stallion".to_vec())),
+            (b"dog".to_vec(), Some(b"puppy".to_vec())),
+            (b"h".to_vec(), Some(b"value".to_vec())),
+        ];
+        let root = test_populate_trie(&tries, &Trie::empty_root(), shard_uid, changes.clone());
+        for (key, value) in changes {
+            let value = value.unwrap();
+            let proof = trie.get_proof(&root, &key).unwrap().unwrap();
+            assert!(Trie::verify_proof(&root, &key, &value, &proof));
+            assert!(!Trie::verify_proof(&root, &key, b"other value", &proof));
+            assert!(!Trie::verify_proof(&root, b"dogs", &value, &proof));
+            assert!(!Trie::verify_proof(&root, &key, &value, &proof[1..]));
+            assert!(!Trie::verify_proof(&CryptoHash::default(), &key, &value, &proof));
+        }
+        assert_eq!(trie.get_proof(&root, b"dogs").unwrap(), None);
+    }
+
     #[test]
     fn test_trie_leaf_into_branch() {
         let tries = create_tries_complex(SHARD_VERSION, 2);
@@ -12,12 +12,15 @@ use near_primitives::types::{
 };
 
 use crate::db::{DBCol, DBOp, DBTransaction};
-use crate::trie::trie_storage::{TrieCache, TrieCachingStorage};
+use crate::trie::trie_storage::{TrieCache, TrieCacheConfig, TrieCachingStorage};
 use crate::trie::{TrieRefcountChange, POISONED_LOCK_ERR};
 use crate::{StorageError, Store, StoreUpdate, Trie, TrieChanges, TrieUpdate};
 
 struct ShardTriesInner {
     store: Arc<Store>,
+    /// Cold store of an archival node, which the nodes missing from `store` are read from.
+    cold_store: Option<Arc<Store>>,
+    trie_cache_config: TrieCacheConfig,
     /// Cache reserved for client actor to use
     caches: RwLock<HashMap<ShardUId, TrieCache>>,
     /// Cache for readers.
@@ -28,19 +31,52 @@ struct ShardTriesInner {
 pub struct ShardTries(Arc<ShardTriesInner>);
 
 impl ShardTries {
-    fn get_new_cache(shards: &[ShardUId]) -> HashMap<ShardUId, TrieCache> {
-        shards.iter().map(|&shard_id| (shard_id, TrieCache::new())).collect()
+    fn get_new_cache(
+        config: &TrieCacheConfig,
+        shards: &[ShardUId],
+        is_view: bool,
+    ) -> HashMap<ShardUId, TrieCache> {
+        shards
+            .iter()
+            .map(|&shard_uid| (shard_uid, TrieCache::new(config, shard_uid, is_view)))
+            .collect()
     }
 
     pub fn new(store: Arc<Store>, shard_version: ShardVersion, num_shards: NumShards) -> Self {
+        Self::with_config(store, &TrieCacheConfig::default(), shard_version, num_shards)
+    }
+
+    pub fn with_config(
+        store: Arc<Store>,
+        trie_cache_config: &TrieCacheConfig,
+        shard_version: ShardVersion,
+        num_shards: NumShards,
+    ) -> Self {
         assert_ne!(num_shards, 0);
         let shards: Vec<_> = (0..num_shards)
             .map(|shard_id| ShardUId { version: shard_version, shard_id: shard_id as u32 })
             .collect();
         ShardTries(Arc::new(ShardTriesInner {
             store,
-            caches: RwLock::new(Self::get_new_cache(&shards)),
-            view_caches: RwLock::new(Self::get_new_cache(&shards)),
+            cold_store: None,
+            trie_cache_config: trie_cache_config.clone(),
+            caches: RwLock::new(Self::get_new_cache(trie_cache_config, &shards, false)),
+            view_caches: RwLock::new(Self::get_new_cache(trie_cache_config, &shards, true)),
+        }))
+    }
+
+    /// Returns the tries reading the nodes garbage collected from the store from `cold_store`,
+    /// where an archival node keeps the state history.
+    pub fn with_cold_store(self, cold_store: Option<Arc<Store>>) -> Self {
+        let caches = |caches: &RwLock<HashMap<ShardUId, TrieCache>>| {
+            RwLock::new(caches.read().expect(POISONED_LOCK_ERR).clone())
+        };
+        ShardTries(Arc::new(ShardTriesInner {
+            store: self.0.store.clone(),
+            cold_store,
+            trie_cache_config: self.0.trie_cache_config.clone(),
+            caches: caches(&self.0.caches),
+            view_caches: caches(&self.0.view_caches),
         }))
     }
 
@@ -51,12 +87,12 @@ impl ShardTries {
     // add new shards to ShardTries, only used when shard layout changes and we are building
     // states for new shards
     pub fn add_new_shards(&self, shards: &[ShardUId]) {
-        let add_empty_caches = |old_caches: &RwLock<HashMap<ShardUId, TrieCache>>| {
+        let add_empty_caches = |old_caches: &RwLock<HashMap<ShardUId, TrieCache>>, is_view| {
             let mut caches = old_caches.write().expect(POISONED_LOCK_ERR);
-            caches.extend(shards.iter().map(|shard| (*shard, TrieCache::new())));
+            caches.extend(Self::get_new_cache(&self.0.trie_cache_config, shards, is_view));
         };
-        add_empty_caches(&self.0.caches);
-        add_empty_caches(&self.0.view_caches);
+        add_empty_caches(&self.0.caches, false);
+        add_empty_caches(&self.0.view_caches, true);
     }
 
     pub fn new_trie_update(&self, shard_uid: ShardUId, state_root: CryptoHash) -> TrieUpdate {
@@ -70,14 +106,17 @@ impl ShardTries {
     fn get_trie_for_shard_internal(&self, shard_uid: ShardUId, is_view: bool) -> Trie {
         let caches_to_use = if is_view { &self.0.view_caches } else { &self.0.caches };
         let caches = caches_to_use.read().expect(POISONED_LOCK_ERR);
-        let store = Box::new(TrieCachingStorage::new(
-            self.0.store.clone(),
-            caches
-                .get(&shard_uid)
-                .unwrap_or_else(|| panic!("cache for shard {:?} must exist", shard_uid))
-                .clone(),
-            shard_uid,
-        ));
+        let store = Box::new(
+            TrieCachingStorage::new(
+                self.0.store.clone(),
+                caches
+                    .get(&shard_uid)
+                    .unwrap_or_else(|| panic!("cache for shard {:?} must exist", shard_uid))
+                    .clone(),
+                shard_uid,
+            )
+            .with_cold_store(self.0.cold_store.clone()),
+        );
         Trie::new(store, shard_uid)
     }
 
@@ -1,12 +1,14 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 
-use cached::{Cached, SizedCache};
+use serde::{Deserialize, Serialize};
 
+use near_metrics::{IntCounter, IntGauge};
 use near_primitives::hash::CryptoHash;
 
 use crate::db::refcount::decode_value_with_rc;
+use crate::metrics;
 use crate::trie::POISONED_LOCK_ERR;
 use crate::{ColState, StorageError, Store};
 use near_primitives::shard_layout::ShardUId;
@@ -14,16 +16,190 @@ use std::cell::RefCell;
 use std::convert::TryFrom;
 use std::io::ErrorKind;
 
+/// Configuration of the trie node caches, the `trie_cache` section of the store config.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct TrieCacheConfig {
+    /// Maximum total size of the nodes and values cached for a shard, in bytes.
+    pub default_max_bytes: u64,
+    /// Maximum sizes for particular shards, overriding `default_max_bytes`.
+    pub per_shard_max_bytes: Vec<ShardTrieCacheSize>,
+    /// Number of the top levels of the trie whose nodes are evicted only when no other nodes are
+    /// left in the cache. The root is the first level. Pinning is disabled if zero.
+    pub pinned_levels: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ShardTrieCacheSize {
+    pub shard_uid: ShardUId,
+    pub max_bytes: u64,
+}
+
+impl Default for TrieCacheConfig {
+    fn default() -> Self {
+        Self { default_max_bytes: 50_000_000, per_shard_max_bytes: vec![], pinned_levels: 0 }
+    }
+}
+
+impl TrieCacheConfig {
+    pub fn shard_max_bytes(&self, shard_uid: ShardUId) -> u64 {
+        if cfg!(feature = "no_cache") {
+            return 0;
+        }
+        self.per_shard_max_bytes
+            .iter()
+            .find(|size| size.shard_uid == shard_uid)
+            .map_or(self.default_max_bytes, |size| size.max_bytes)
+    }
+}
+
+/// Values in the least recently used order.
+#[derive(Default)]
+struct LruValues {
+    /// Values by hash, with the tick of the last use.
+    values: HashMap<CryptoHash, (u64, Vec<u8>)>,
+    /// Hashes by the tick of the last use.
+    order: BTreeMap<u64, CryptoHash>,
+    total_bytes: u64,
+}
+
+impl LruValues {
+    fn get(&mut self, hash: &CryptoHash, tick: u64) -> Option<&Vec<u8>> {
+        let (last_used, value) = self.values.get_mut(hash)?;
+        self.order.remove(&*last_used);
+        self.order.insert(tick, *hash);
+        *last_used = tick;
+        Some(value)
+    }
+
+    fn insert(&mut self, hash: CryptoHash, value: Vec<u8>, tick: u64) {
+        self.remove(&hash);
+        self.total_bytes += entry_size(&value);
+        self.order.insert(tick, hash);
+        self.values.insert(hash, (tick, value));
+    }
+
+    fn remove(&mut self, hash: &CryptoHash) -> Option<Vec<u8>> {
+        let (last_used, value) = self.values.remove(hash)?;
+        self.order.remove(&last_used);
+        self.total_bytes -= entry_size(&value);
+        Some(value)
+    }
+
+    /// Removes the least recently used value.
+    fn pop(&mut self) -> Option<Vec<u8>> {
+        let hash = *self.order.values().next()?;
+        self.remove(&hash)
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Size of a cached value, including its key.
+fn entry_size(value: &[u8]) -> u64 {
+    (value.len() + std::mem::size_of::<CryptoHash>()) as u64
+}
+
+struct TrieCacheInner {
+    max_bytes: u64,
+    pinned_levels: usize,
+    /// Counter of the cache uses, to order the values by the last use.
+    tick: u64,
+    values: LruValues,
+    /// Nodes of the top `pinned_levels` levels, evicted after all the `values`.
+    pinned: LruValues,
+    hits: Option<IntCounter>,
+    misses: Option<IntCounter>,
+    evictions: Option<IntCounter>,
+    size: Option<IntGauge>,
+}
+
+impl TrieCacheInner {
+    fn get(&mut self, hash: &CryptoHash) -> Option<Vec<u8>> {
+        self.tick += 1;
+        let tick = self.tick;
+        let value = match self.values.get(hash, tick) {
+            Some(value) => Some(value.clone()),
+            None => self.pinned.get(hash, tick).cloned(),
+        };
+        let counter = if value.is_some() { &self.hits } else { &self.misses };
+        near_metrics::inc_counter_opt(counter.as_ref());
+        value
+    }
+
+    /// Caches the value. An already cached value stays pinned if it was pinned.
+    fn put(&mut self, hash: CryptoHash, value: Vec<u8>, pin: bool) {
+        if value.len() >= TRIE_LIMIT_CACHED_VALUE_SIZE || entry_size(&value) > self.max_bytes {
+            return;
+        }
+        self.tick += 1;
+        if pin || self.pinned.values.contains_key(&hash) {
+            self.values.remove(&hash);
+            self.pinned.insert(hash, value, self.tick);
+        } else {
+            self.values.insert(hash, value, self.tick);
+        }
+        while self.values.total_bytes + self.pinned.total_bytes > self.max_bytes {
+            if self.values.pop().or_else(|| self.pinned.pop()).is_none() {
+                break;
+            }
+            near_metrics::inc_counter_opt(self.evictions.as_ref());
+        }
+        self.update_size();
+    }
+
+    fn remove(&mut self, hash: &CryptoHash) {
+        if self.values.remove(hash).or_else(|| self.pinned.remove(hash)).is_some() {
+            self.update_size();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+        self.pinned.clear();
+        self.update_size();
+    }
+
+    fn update_size(&self) {
+        if let Some(size) = &self.size {
+            size.set((self.values.total_bytes + self.pinned.total_bytes) as i64);
+        }
+    }
+}
+
+/// Cache of the trie nodes and values of one shard, limited by their total size.
 #[derive(Clone)]
-pub struct TrieCache(Arc<Mutex<SizedCache<CryptoHash, Vec<u8>>>>);
+pub struct TrieCache(Arc<Mutex<TrieCacheInner>>);
 
 impl TrieCache {
-    pub fn new() -> Self {
-        Self(Arc::new(Mutex::new(SizedCache::with_size(TRIE_MAX_CACHE_SIZE))))
+    /// Creates the cache of the shard. `is_view` tells the caches of the view client apart in the
+    /// metrics.
+    pub fn new(config: &TrieCacheConfig, shard_uid: ShardUId, is_view: bool) -> Self {
+        let shard_uid_label = format!("s{}.v{}", shard_uid.shard_id, shard_uid.version);
+        let labels = [shard_uid_label.as_str(), if is_view { "true" } else { "false" }];
+        let counter = |metric: &near_metrics::Result<near_metrics::IntCounterVec>| {
+            metric.as_ref().ok().map(|metric| metric.with_label_values(&labels))
+        };
+        Self(Arc::new(Mutex::new(TrieCacheInner {
+            max_bytes: config.shard_max_bytes(shard_uid),
+            pinned_levels: config.pinned_levels,
+            tick: 0,
+            values: LruValues::default(),
+            pinned: LruValues::default(),
+            hits: counter(&metrics::TRIE_CACHE_HITS),
+            misses: counter(&metrics::TRIE_CACHE_MISSES),
+            evictions: counter(&metrics::TRIE_CACHE_EVICTIONS),
+            size: metrics::TRIE_CACHE_SIZE
+                .as_ref()
+                .ok()
+                .map(|metric| metric.with_label_values(&labels)),
+        })))
     }
 
     pub fn clear(&self) {
-        self.0.lock().expect(POISONED_LOCK_ERR).cache_clear()
+        self.0.lock().expect(POISONED_LOCK_ERR).clear()
     }
 
     pub fn update_cache(&self, ops: Vec<(CryptoHash, Option<Vec<u8>>)>) {
@@ -31,14 +207,12 @@ impl TrieCache {
         for (hash, opt_value_rc) in ops {
             if let Some(value_rc) = opt_value_rc {
                 if let (Some(value), _rc) = decode_value_with_rc(&value_rc) {
-                    if value.len() < TRIE_LIMIT_CACHED_VALUE_SIZE {
-                        guard.cache_set(hash, value.to_vec());
-                    }
+                    guard.put(hash, value.to_vec(), false);
                 } else {
-                    guard.cache_remove(&hash);
+                    guard.remove(&hash);
                 }
             } else {
-                guard.cache_remove(&hash);
+                guard.remove(&hash);
             }
         }
     }
@@ -50,6 +224,16 @@ pub trait TrieStorage {
     /// StorageError if the storage fails internally or the hash is not present.
     fn retrieve_raw_bytes(&self, hash: &CryptoHash) -> Result<Vec<u8>, StorageError>;
 
+    /// Same as `retrieve_raw_bytes`, for a trie node `depth` levels below the root, which is
+    /// at depth zero.
+    fn retrieve_trie_node(
+        &self,
+        hash: &CryptoHash,
+        _depth: usize,
+    ) -> Result<Vec<u8>, StorageError> {
+        self.retrieve_raw_bytes(hash)
+    }
+
     fn as_caching_storage(&self) -> Option<&TrieCachingStorage> {
         None
     }
@@ -118,26 +302,28 @@ impl TrieStorage for TrieMemoryPartialStorage {
     }
 }
 
-/// Maximum number of cache entries.
-#[cfg(not(feature = "no_cache"))]
-const TRIE_MAX_CACHE_SIZE: usize = 10000;
-
-#[cfg(feature = "no_cache")]
-const TRIE_MAX_CACHE_SIZE: usize = 1;
-
 /// Values above this size (in bytes) are never cached.
 /// Note that Trie inner nodes are always smaller than this.
 const TRIE_LIMIT_CACHED_VALUE_SIZE: usize = 4000;
 
 pub struct TrieCachingStorage {
     pub(crate) store: Arc<Store>,
+    /// Store that the nodes missing from `store` are read from, see `with_cold_store`.
+    pub(crate) cold_store: Option<Arc<Store>>,
     pub(crate) cache: TrieCache,
     pub(crate) shard_uid: ShardUId,
 }
 
 impl TrieCachingStorage {
     pub fn new(store: Arc<Store>, cache: TrieCache, shard_uid: ShardUId) -> TrieCachingStorage {
-        TrieCachingStorage { store, cache, shard_uid }
+        TrieCachingStorage { store, cold_store: None, cache, shard_uid }
+    }
+
+    /// Sets the cold store of an archival node, which keeps the nodes garbage collected from the
+    /// store, so that old states can still be read.
+    pub fn with_cold_store(mut self, cold_store: Option<Arc<Store>>) -> Self {
+        self.cold_store = cold_store;
+        self
     }
 
     pub(crate) fn get_shard_uid_and_hash_from_key(
@@ -162,21 +348,27 @@ impl TrieCachingStorage {
     }
 }
 
-impl TrieStorage for TrieCachingStorage {
-    fn retrieve_raw_bytes(&self, hash: &CryptoHash) -> Result<Vec<u8>, StorageError> {
+impl TrieCachingStorage {
+    /// Reads the value through the cache. Trie nodes at `depth` within the pinned levels are
+    /// pinned in the cache.
+    fn retrieve(&self, hash: &CryptoHash, depth: Option<usize>) -> Result<Vec<u8>, StorageError> {
         let mut guard = self.cache.0.lock().expect(POISONED_LOCK_ERR);
-        if let Some(val) = guard.cache_get(hash) {
-            Ok(val.clone())
+        if let Some(val) = guard.get(hash) {
+            Ok(val)
         } else {
             let key = Self::get_key_from_shard_uid_and_hash(self.shard_uid, hash);
-            let val = self
+            let mut val = self
                 .store
                 .get(ColState, key.as_ref())
                 .map_err(|_| StorageError::StorageInternalError)?;
+            if let (None, Some(cold_store)) = (&val, &self.cold_store) {
+                val = cold_store
+                    .get(ColState, key.as_ref())
+                    .map_err(|_| StorageError::StorageInternalError)?;
+            }
             if let Some(val) = val {
-                if val.len() < TRIE_LIMIT_CACHED_VALUE_SIZE {
-                    guard.cache_set(*hash, val.clone());
-                }
+                let pin = depth.map_or(false, |depth| depth < guard.pinned_levels);
+                guard.put(*hash, val.clone(), pin);
                 Ok(val)
             } else {
                 // not StorageError::TrieNodeMissing because it's only for TrieMemoryPartialStorage
@@ -184,6 +376,16 @@ impl TrieStorage for TrieCachingStorage {
             }
         }
     }
+}
+
+impl TrieStorage for TrieCachingStorage {
+    fn retrieve_raw_bytes(&self, hash: &CryptoHash) -> Result<Vec<u8>, StorageError> {
+        self.retrieve(hash, None)
+    }
+
+    fn retrieve_trie_node(&self, hash: &CryptoHash, depth: usize) -> Result<Vec<u8>, StorageError> {
+        self.retrieve(hash, Some(depth))
+    }
 
     fn as_caching_storage(&self) -> Option<&TrieCachingStorage> {
         Some(self)
@@ -210,3 +412,106 @@ impl TouchedNodesCounter {
         self.counter.load(Ordering::SeqCst)
     }
 }
+
+#[cfg(all(test, not(feature = "no_cache")))]
+mod tests {
+    use near_primitives::hash::hash;
+    use near_primitives::shard_layout::ShardUId;
+
+    use super::{entry_size, ShardTrieCacheSize, TrieCache, TrieCacheConfig};
+    use crate::test_utils::{create_test_store, test_populate_trie};
+    use crate::{ShardTries, Trie};
+
+    fn new_cache(max_bytes: u64, pinned_levels: usize) -> TrieCache {
+        let config = TrieCacheConfig {
+            default_max_bytes: max_bytes,
+            per_shard_max_bytes: vec![],
+            pinned_levels,
+        };
+        TrieCache::new(&config, ShardUId::default(), false)
+    }
+
+    #[test]
+    fn test_shard_max_bytes() {
+        let shard_uid = ShardUId { version: 1, shard_id: 2 };
+        let config = TrieCacheConfig {
+            default_max_bytes: 100,
+            per_shard_max_bytes: vec![ShardTrieCacheSize { shard_uid, max_bytes: 200 }],
+            pinned_levels: 0,
+        };
+        assert_eq!(config.shard_max_bytes(shard_uid), 200);
+        assert_eq!(config.shard_max_bytes(ShardUId::default()), 100);
+    }
+
+    #[test]
+    fn test_trie_cache_evicts_least_recently_used() {
+        let value = vec![1; 100];
+        let cache = new_cache(3 * entry_size(&value), 0);
+        let mut cache = cache.0.lock().unwrap();
+        for i in 0..3 {
+            cache.put(hash(&[i]), value.clone(), false);
+        }
+        assert_eq!(cache.get(&hash(&[0])), Some(value.clone()));
+        cache.put(hash(&[3]), value.clone(), false);
+        assert_eq!(cache.get(&hash(&[1])), None);
+        assert_eq!(cache.get(&hash(&[0])), Some(value.clone()));
+        assert_eq!(cache.values.total_bytes, 3 * entry_size(&value));
+
+        cache.remove(&hash(&[0]));
+        assert_eq!(cache.get(&hash(&[0])), None);
+        assert_eq!(cache.values.total_bytes, 2 * entry_size(&value));
+    }
+
+    #[test]
+    fn test_trie_cache_pinned_nodes() {
+        let value = vec![1; 100];
+        let cache = new_cache(2 * entry_size(&value), 1);
+        let mut cache = cache.0.lock().unwrap();
+        cache.put(hash(&[0]), value.clone(), true);
+        cache.put(hash(&[1]), value.clone(), false);
+        cache.put(hash(&[2]), value.clone(), false);
+        assert_eq!(cache.get(&hash(&[0])), Some(value.clone()));
+        assert_eq!(cache.get(&hash(&[1])), None);
+
+        // Updating a pinned node keeps it pinned.
+        cache.put(hash(&[0]), value.clone(), false);
+        cache.put(hash(&[3]), value.clone(), false);
+        assert_eq!(cache.get(&hash(&[0])), Some(value.clone()));
+        assert_eq!(cache.get(&hash(&[2])), None);
+    }
+
+    #[test]
+    fn test_trie_iterator_pins_nodes() {
+        let config = TrieCacheConfig { pinned_levels: 1, ..TrieCacheConfig::default() };
+        let tries = ShardTries::with_config(create_test_store(), &config, 0, 1);
+        let shard_uid = ShardUId::default();
+        let changes = vec![
+            (b"aa".to_vec(), Some(vec![1])),
+            (b"ab".to_vec(), Some(vec![2])),
+            (b"b".to_vec(), Some(vec![3])),
+        ];
+        let state_root = test_populate_trie(&tries, &Trie::empty_root(), shard_uid, changes);
+
+        let trie = tries.get_view_trie_for_shard(shard_uid);
+        assert_eq!(trie.iter(&state_root).unwrap().count(), 3);
+        let cache = trie.storage.as_caching_storage().unwrap().cache.0.lock().unwrap();
+        assert_eq!(cache.pinned.values.keys().collect::<Vec<_>>(), vec![&state_root]);
+        assert!(!cache.values.values.is_empty());
+    }
+
+    #[test]
+    fn test_trie_reads_cold_store() {
+        let cold_store = create_test_store();
+        let cold_tries = ShardTries::new(cold_store.clone(), 0, 1);
+        let shard_uid = ShardUId::default();
+        let changes = vec![(b"a".to_vec(), Some(vec![1])), (b"b".to_vec(), Some(vec![2]))];
+        let state_root = test_populate_trie(&cold_tries, &Trie::empty_root(), shard_uid, changes);
+
+        let tries = ShardTries::new(create_test_store(), 0, 1);
+        assert!(tries.get_view_trie_for_shard(shard_uid).get(&state_root, b"a").is_err());
+        let tries = tries.with_cold_store(Some(cold_store));
+        let trie = tries.get_view_trie_for_shard(shard_uid);
+        assert_eq!(trie.get(&state_root, b"a").unwrap(), Some(vec![1]));
+        assert_eq!(trie.get(&state_root, b"b").unwrap(), Some(vec![2]));
+    }
+}
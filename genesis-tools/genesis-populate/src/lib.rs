@@ -24,7 +24,8 @@ use near_primitives::state_record::StateRecord;
 use near_primitives::types::chunk_extra::ChunkExtra;
 use near_primitives::types::{AccountId, Balance, EpochId, ShardId, StateChangeCause, StateRoot};
 use near_store::{
-    create_store, get_account, set_access_key, set_account, set_code, Store, TrieUpdate,
+    create_store, get_account, set_access_key, set_account, set_code, Store, TrieCacheConfig,
+    TrieUpdate,
 };
 use nearcore::{get_store_path, NightshadeRuntime, TrackedConfig};
 
@@ -71,6 +72,7 @@ impl GenesisBuilder {
             None,
             None,
             RuntimeConfigStore::new(Some(&genesis.config.runtime_config)),
+            &TrieCacheConfig::default(),
         );
         Self {
             home_dir: home_dir.to_path_buf(),
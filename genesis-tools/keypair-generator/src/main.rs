@@ -3,7 +3,7 @@ use std::path::{Path, PathBuf};
 
 use clap::{App, AppSettings, Arg, SubCommand};
 
-use near_crypto::{InMemorySigner, KeyType, SecretKey, Signer};
+use near_crypto::{read_passphrase, InMemorySigner, KeyFile, KeyType, SecretKey, Signer};
 use nearcore::get_default_home;
 
 fn generate_key_to_file(account_id: &str, key: SecretKey, path: PathBuf) {
@@ -11,6 +11,20 @@ fn generate_key_to_file(account_id: &str, key: SecretKey, path: PathBuf) {
     signer.write_to_file(path.as_path());
 }
 
+fn key_file_args<'a, 'b>() -> [Arg<'a, 'b>; 2] {
+    [
+        Arg::with_name("key-file")
+            .long("key-file")
+            .required(true)
+            .takes_value(true)
+            .help("Key file, e.g. `~/.near/validator_key.json`."),
+        Arg::with_name("output")
+            .long("output")
+            .takes_value(true)
+            .help("Where to write the result. (default overwrites the key file)"),
+    ]
+}
+
 fn main() {
     let default_home = get_default_home();
     let matches = App::new("Key-pairs generator")
@@ -46,6 +60,16 @@ fn main() {
             SubCommand::with_name("node-key").about("Generate key for the node communication."),
         )
         .subcommand(SubCommand::with_name("validator-key").about("Generate staking key."))
+        .subcommand(
+            SubCommand::with_name("encrypt-key")
+                .about("Encrypt the secret key of a key file with a passphrase.")
+                .args(&key_file_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("decrypt-key")
+                .about("Decrypt the secret key of an encrypted key file.")
+                .args(&key_file_args()),
+        )
         .get_matches();
 
     let home_dir = matches.value_of("home").map(|dir| Path::new(dir)).unwrap();
@@ -101,6 +125,22 @@ fn main() {
                 generate_key_to_file("node", key, path);
             }
         }
+        ("encrypt-key", Some(args)) => {
+            let key_file_path = Path::new(args.value_of("key-file").unwrap());
+            let output = args.value_of("output").map_or(key_file_path, Path::new);
+            let key_file = KeyFile::from_file(key_file_path);
+            let passphrase =
+                read_passphrase("New passphrase: ", true).expect("Failed to read the passphrase");
+            key_file.encrypt(&passphrase).write_to_file(output);
+            println!("Encrypted key of {} written to {}", key_file.account_id, output.display());
+        }
+        ("decrypt-key", Some(args)) => {
+            let key_file_path = Path::new(args.value_of("key-file").unwrap());
+            let output = args.value_of("output").map_or(key_file_path, Path::new);
+            let key_file = KeyFile::from_file(key_file_path);
+            key_file.write_to_file(output);
+            println!("Decrypted key of {} written to {}", key_file.account_id, output.display());
+        }
         (_, _) => unreachable!(),
     }
 }
@@ -68,7 +68,9 @@ fn test_verify_block_double_sign_challenge() {
         &signer,
         b1.header().next_bp_hash().clone(),
         block_merkle_tree.root(),
-    );
+        None,
+    )
+    .unwrap();
     let epoch_id = b1.header().epoch_id().clone();
     let valid_challenge = Challenge::produce(
         ChallengeBody::BlockDoubleSign(BlockDoubleSign {
@@ -76,7 +78,8 @@ fn test_verify_block_double_sign_challenge() {
             right_block_header: b1.header().try_to_vec().unwrap(),
         }),
         &signer,
-    );
+    )
+    .unwrap();
     let runtime_adapter = env.clients[1].chain.runtime_adapter.clone();
     assert_eq!(
         &validate_challenge(&*runtime_adapter, &epoch_id, &genesis.hash(), &valid_challenge)
@@ -90,7 +93,8 @@ fn test_verify_block_double_sign_challenge() {
             right_block_header: b1.header().try_to_vec().unwrap(),
         }),
         &signer,
-    );
+    )
+    .unwrap();
     let runtime_adapter = env.clients[1].chain.runtime_adapter.clone();
     assert!(validate_challenge(&*runtime_adapter, &epoch_id, &genesis.hash(), &invalid_challenge,)
         .is_err());
@@ -101,7 +105,8 @@ fn test_verify_block_double_sign_challenge() {
             right_block_header: b3.header().try_to_vec().unwrap(),
         }),
         &signer,
-    );
+    )
+    .unwrap();
     let runtime_adapter = env.clients[1].chain.runtime_adapter.clone();
     assert!(validate_challenge(&*runtime_adapter, &epoch_id, &genesis.hash(), &invalid_challenge,)
         .is_err());
@@ -255,7 +260,8 @@ fn challenge(
             merkle_proof: merkle_paths[shard_id].clone(),
         }),
         &*env.clients[0].validator_signer.as_ref().unwrap().clone(),
-    );
+    )
+    .unwrap();
     let runtime_adapter = env.clients[0].chain.runtime_adapter.clone();
     validate_challenge(
         &*runtime_adapter,
@@ -371,7 +377,9 @@ fn test_verify_chunk_invalid_state_challenge() {
         &validator_signer,
         *last_block.header().next_bp_hash(),
         block_merkle_tree.root(),
-    );
+        None,
+    )
+    .unwrap();
 
     let challenge_body = {
         use near_chain::chain::{ChainUpdate, OrphanBlockPool};
@@ -394,6 +402,7 @@ fn test_verify_chunk_invalid_state_challenge() {
             &genesis_block,
             transaction_validity_period,
             None,
+            chrono::Duration::zero(),
         );
 
         chain_update.create_chunk_state_challenge(&last_block, &block, &block.chunks()[0]).unwrap()
@@ -422,7 +431,7 @@ fn test_verify_chunk_invalid_state_challenge() {
         );
     }
     let challenge =
-        Challenge::produce(ChallengeBody::ChunkState(challenge_body), &validator_signer);
+        Challenge::produce(ChallengeBody::ChunkState(challenge_body), &validator_signer).unwrap();
     let runtime_adapter = client.chain.runtime_adapter.clone();
     assert_eq!(
         validate_challenge(
@@ -549,7 +558,8 @@ fn test_block_challenge() {
             merkle_proof: merkle_paths[shard_id as usize].clone(),
         }),
         &*env.clients[0].validator_signer.as_ref().unwrap().clone(),
-    );
+    )
+    .unwrap();
     env.clients[0].process_challenge(challenge.clone()).unwrap();
     env.produce_block(0, 2);
     assert_eq!(env.clients[0].chain.get_block_by_height(2).unwrap().challenges(), &[challenge]);
@@ -609,11 +619,13 @@ fn test_fishermen_challenge() {
     let challenge = Challenge::produce(
         challenge_body.clone(),
         &*env.clients[1].validator_signer.as_ref().unwrap().clone(),
-    );
+    )
+    .unwrap();
     let challenge1 = Challenge::produce(
         challenge_body,
         &*env.clients[2].validator_signer.as_ref().unwrap().clone(),
-    );
+    )
+    .unwrap();
     assert!(env.clients[0].process_challenge(challenge1).is_err());
     env.clients[0].process_challenge(challenge.clone()).unwrap();
     env.produce_block(0, 12);
@@ -1,10 +1,12 @@
-use std::collections::{HashSet, VecDeque};
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::convert::TryFrom;
 use std::iter::FromIterator;
 use std::path::Path;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use actix::System;
 use futures::{future, FutureExt};
@@ -12,6 +14,7 @@ use near_primitives::num_rational::Rational;
 
 use near_actix_test_utils::run_actix;
 use near_chain::chain::{ApplyStatePartsRequest, NUM_EPOCHS_TO_KEEP_STORE_DATA};
+use near_chain::state_dump;
 use near_chain::types::LatestKnown;
 use near_chain::validate::validate_chunk_with_chunk_extra;
 use near_chain::{
@@ -19,6 +22,7 @@ use near_chain::{
 };
 use near_chain_configs::{ClientConfig, Genesis};
 use near_chunks::{ChunkStatus, ShardsManager};
+use near_client::sync::{StateSync, StateSyncResult};
 use near_client::test_utils::{
     create_chunk_on_height, run_catchup, setup_client, setup_mock, setup_mock_all_validators,
     TestEnv,
@@ -361,7 +365,9 @@ fn receive_network_block() {
                 &signer,
                 last_block.header.next_bp_hash,
                 block_merkle_tree.root(),
-            );
+                None,
+            )
+            .unwrap();
             client.do_send(NetworkClientMessages::Block(block, PeerInfo::random().id, false));
             future::ready(())
         }));
@@ -439,7 +445,9 @@ fn produce_block_with_approvals() {
                 &signer1,
                 last_block.header.next_bp_hash,
                 block_merkle_tree.root(),
-            );
+                None,
+            )
+            .unwrap();
             client.do_send(NetworkClientMessages::Block(
                 block.clone(),
                 PeerInfo::random().id,
@@ -460,7 +468,8 @@ fn produce_block_with_approvals() {
                     block.header().height(),
                     10, // the height at which "test1" is producing
                     &signer,
-                );
+                )
+                .unwrap();
                 client
                     .do_send(NetworkClientMessages::BlockApproval(approval, PeerInfo::random().id));
             }
@@ -621,7 +630,9 @@ fn invalid_blocks_common(is_requested: bool) {
                 &signer,
                 last_block.header.next_bp_hash,
                 block_merkle_tree.root(),
-            );
+                None,
+            )
+            .unwrap();
             // Send block with invalid chunk mask
             let mut block = valid_block.clone();
             block.mut_header().get_mut().inner_rest.chunk_mask = vec![];
@@ -1059,7 +1070,8 @@ fn test_invalid_approvals() {
                     KeyType::ED25519,
                     account_id.as_ref(),
                 )
-                .sign_approval(&ApprovalInner::Endorsement(*genesis.hash()), 1),
+                .sign_approval(&ApprovalInner::Endorsement(*genesis.hash()), 1)
+                .unwrap(),
             )
         })
         .collect();
@@ -1449,6 +1461,80 @@ fn test_gc_long_epoch() {
     }
 }
 
+/// Archival nodes with a cold store answer queries about states garbage collected from the store.
+#[test]
+fn test_query_state_moved_to_cold_store() {
+    let epoch_length = 5;
+    let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    genesis.config.epoch_length = epoch_length;
+    let mut chain_genesis = ChainGenesis::test();
+    chain_genesis.epoch_length = epoch_length;
+    let runtime = nearcore::NightshadeRuntime::test(Path::new("."), create_test_store(), &genesis)
+        .with_cold_store(Some(create_test_store()));
+    let mut env = TestEnv::builder(chain_genesis)
+        .runtime_adapters(vec![Arc::new(runtime) as Arc<dyn RuntimeAdapter>])
+        .build();
+    let signer = InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+    let genesis_hash = *env.clients[0].chain.get_block_by_height(0).unwrap().hash();
+    let tx = SignedTransaction::create_account(
+        1,
+        "test0".parse().unwrap(),
+        "test_account".parse().unwrap(),
+        NEAR_BASE,
+        signer.public_key(),
+        &signer,
+        genesis_hash,
+    );
+    env.clients[0].process_tx(tx, false, false);
+    for i in 1..5 {
+        env.produce_block(0, i);
+    }
+    let old_block = env.clients[0].chain.get_block_by_height(4).unwrap().clone();
+    // Change the account, so that its state at the old block is garbage collected.
+    let tx = SignedTransaction::send_money(
+        2,
+        "test0".parse().unwrap(),
+        "test_account".parse().unwrap(),
+        &signer,
+        NEAR_BASE,
+        *old_block.hash(),
+    );
+    env.clients[0].process_tx(tx, false, false);
+    for i in 5..=epoch_length * (NUM_EPOCHS_TO_KEEP_STORE_DATA + 2) {
+        env.produce_block(0, i);
+    }
+    assert!(!env.clients[0]
+        .chain
+        .store()
+        .store()
+        .exists(near_store::db::DBCol::ColBlock, old_block.hash().as_ref())
+        .unwrap());
+
+    let query_balance = |client: &Client, block: &Block| {
+        let response = client
+            .runtime_adapter
+            .query(
+                ShardUId::default(),
+                &block.chunks()[0].prev_state_root(),
+                block.header().height(),
+                block.header().raw_timestamp(),
+                block.header().prev_hash(),
+                block.hash(),
+                block.header().epoch_id(),
+                &QueryRequest::ViewAccount { account_id: "test_account".parse().unwrap() },
+            )
+            .unwrap();
+        match response.kind {
+            QueryResponseKind::ViewAccount(account_view) => account_view.amount,
+            _ => panic!("Wrong return value"),
+        }
+    };
+    assert_eq!(query_balance(&env.clients[0], &old_block), NEAR_BASE);
+    let head = env.clients[0].chain.head().unwrap();
+    let head_block = env.clients[0].chain.get_block(&head.last_block_hash).unwrap().clone();
+    assert_eq!(query_balance(&env.clients[0], &head_block), 2 * NEAR_BASE);
+}
+
 #[test]
 fn test_gc_block_skips() {
     let mut chain_genesis = ChainGenesis::test();
@@ -2371,6 +2457,163 @@ fn test_catchup_gas_price_change() {
     assert_eq!(chunk_extra_after_sync, expected_chunk_extra);
 }
 
+/// Produces the blocks of the first epoch and a block of the second one on two nodes and dumps
+/// the state of the first node at the start of the second epoch. Returns the blocks, the directory
+/// with the dump and the sync hash.
+fn setup_state_dump(env: &mut TestEnv) -> (Vec<Block>, tempfile::TempDir, CryptoHash) {
+    let mut blocks = vec![];
+    for i in 1..=6 {
+        let block = env.clients[0].produce_block(i).unwrap().unwrap();
+        blocks.push(block.clone());
+        env.process_block(0, block.clone(), Provenance::PRODUCED);
+        env.process_block(1, block, Provenance::NONE);
+    }
+    assert!(env.clients[1].chain.get_chunk_extra(blocks[4].hash(), &ShardUId::default()).is_err());
+
+    let dir = tempfile::Builder::new().prefix("state_parts").tempdir().unwrap();
+    let sync_hash = *blocks[5].hash();
+    env.clients[0].chain.dump_state_parts(dir.path(), 0, sync_hash).unwrap();
+    assert!(state_dump::has_state_dump(dir.path(), &sync_hash, 0));
+    (blocks, dir, sync_hash)
+}
+
+/// Runs `rounds` rounds of the state sync of shard 0 at `sync_hash` on `client`, applying the
+/// state parts right away. Returns whether the state sync completed.
+fn run_state_sync(
+    client: &mut Client,
+    state_sync: &mut StateSync,
+    sync_hash: CryptoHash,
+    rounds: usize,
+) -> bool {
+    let mut new_shard_sync = HashMap::new();
+    let applied = Cell::new(false);
+    let rt = Arc::clone(&client.runtime_adapter);
+    let apply_state_parts = |msg: ApplyStatePartsRequest| {
+        use borsh::BorshSerialize;
+        let store = rt.get_store();
+
+        for part_id in 0..msg.num_parts {
+            let key = StatePartKey(msg.sync_hash, msg.shard_id, part_id).try_to_vec().unwrap();
+            let part = store.get(ColStateParts, &key).unwrap().unwrap();
+            rt.apply_state_part(
+                msg.shard_id,
+                &msg.state_root,
+                part_id,
+                msg.num_parts,
+                &part,
+                &msg.epoch_id,
+            )
+            .unwrap();
+        }
+        applied.set(true);
+    };
+    for _ in 0..rounds {
+        let result = state_sync
+            .run(
+                &None,
+                sync_hash,
+                &mut new_shard_sync,
+                &mut client.chain,
+                &client.runtime_adapter,
+                &vec![],
+                vec![0],
+                &apply_state_parts,
+                &|_| panic!("the shard layout doesn't change"),
+            )
+            .unwrap();
+        if let StateSyncResult::Completed = result {
+            return true;
+        }
+        if applied.replace(false) {
+            state_sync.set_apply_result(0, Ok(()));
+        }
+    }
+    false
+}
+
+/// Dumps the state parts on one node and state syncs another node from the dump.
+#[test]
+fn test_state_sync_from_dump() {
+    init_test_logger();
+    let epoch_length = 5;
+    let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    genesis.config.epoch_length = epoch_length;
+    let chain_genesis = ChainGenesis::from(&genesis);
+    let mut env = TestEnv::builder(chain_genesis)
+        .clients_count(2)
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 2))
+        .build();
+    let (blocks, dir, sync_hash) = setup_state_dump(&mut env);
+
+    let mut state_sync = StateSync::new(
+        env.network_adapters[1].clone(),
+        Duration::from_secs(60),
+        Some(dir.path().to_path_buf()),
+    );
+    assert!(run_state_sync(&mut env.clients[1], &mut state_sync, sync_hash, 10));
+    let chunk_extra_after_sync = env.clients[1]
+        .chain
+        .get_chunk_extra(blocks[4].hash(), &ShardUId::default())
+        .unwrap()
+        .clone();
+    let expected_chunk_extra = env.clients[0]
+        .chain
+        .get_chunk_extra(blocks[4].hash(), &ShardUId::default())
+        .unwrap()
+        .clone();
+    assert_eq!(chunk_extra_after_sync, expected_chunk_extra);
+    // Nothing was requested from peers.
+    while let Some(request) = env.network_adapters[1].pop() {
+        assert!(!matches!(
+            request,
+            NetworkRequests::StateRequestHeader { .. } | NetworkRequests::StateRequestPart { .. }
+        ));
+    }
+}
+
+/// Parts of the state dump which don't match the state root of the header are requested from
+/// peers instead.
+#[test]
+fn test_state_sync_from_invalid_dump() {
+    init_test_logger();
+    run_actix(async {
+        let epoch_length = 5;
+        let mut genesis =
+            Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+        genesis.config.epoch_length = epoch_length;
+        let chain_genesis = ChainGenesis::from(&genesis);
+        let mut env = TestEnv::builder(chain_genesis)
+            .clients_count(2)
+            .runtime_adapters(create_nightshade_runtimes(&genesis, 2))
+            .build();
+        let (_, dir, sync_hash) = setup_state_dump(&mut env);
+        let part_path = state_dump::shard_dump_dir(dir.path(), &sync_hash, 0).join("part0");
+        std::fs::write(part_path, vec![1, 2, 3]).unwrap();
+
+        let mut state_sync = StateSync::new(
+            env.network_adapters[1].clone(),
+            Duration::from_secs(60),
+            Some(dir.path().to_path_buf()),
+        );
+        assert!(!run_state_sync(&mut env.clients[1], &mut state_sync, sync_hash, 5));
+        let mut requested_parts = vec![];
+        while let Some(request) = env.network_adapters[1].pop() {
+            match request {
+                NetworkRequests::StateRequestHeader { .. } => panic!("header is in the dump"),
+                NetworkRequests::StateRequestPart {
+                    shard_id, sync_hash: hash, part_id, ..
+                } => {
+                    assert_eq!((shard_id, hash), (0, sync_hash));
+                    requested_parts.push(part_id);
+                }
+                _ => {}
+            }
+        }
+        assert!(requested_parts.contains(&0));
+        System::current().stop();
+    });
+}
+
 #[test]
 fn test_block_execution_outcomes() {
     let epoch_length = 5;
@@ -2994,11 +3237,13 @@ fn test_header_version_downgrade() {
         match header {
             BlockHeader::BlockHeaderV1(ref mut header) => {
                 header.inner_rest.latest_protocol_version = PROTOCOL_VERSION;
-                let (hash, signature) = validator_signer.sign_block_header_parts(
-                    header.prev_hash,
-                    &header.inner_lite.try_to_vec().expect("Failed to serialize"),
-                    &header.inner_rest.try_to_vec().expect("Failed to serialize"),
-                );
+                let (hash, signature) = validator_signer
+                    .sign_block_header_parts(
+                        header.prev_hash,
+                        &header.inner_lite.try_to_vec().expect("Failed to serialize"),
+                        &header.inner_rest.try_to_vec().expect("Failed to serialize"),
+                    )
+                    .unwrap();
                 header.hash = hash;
                 header.signature = signature;
             }
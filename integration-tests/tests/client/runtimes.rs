@@ -52,7 +52,7 @@ fn test_pending_approvals() {
     let signer =
         InMemoryValidatorSigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
     let parent_hash = hash(&[1]);
-    let approval = Approval::new(parent_hash, 0, 1, &signer);
+    let approval = Approval::new(parent_hash, 0, 1, &signer).unwrap();
     let peer_id = PeerId::random();
     env.clients[0].collect_block_approval(&approval, ApprovalType::PeerApproval(peer_id.clone()));
     let approvals =
@@ -75,7 +75,7 @@ fn test_invalid_approvals() {
         InMemoryValidatorSigner::from_seed("random".parse().unwrap(), KeyType::ED25519, "random");
     let parent_hash = hash(&[1]);
     // Approval not from a validator. Should be dropped
-    let approval = Approval::new(parent_hash, 1, 3, &signer);
+    let approval = Approval::new(parent_hash, 1, 3, &signer).unwrap();
     let peer_id = PeerId::random();
     env.clients[0].collect_block_approval(&approval, ApprovalType::PeerApproval(peer_id.clone()));
     assert_eq!(env.clients[0].pending_approvals.cache_size(), 0);
@@ -83,7 +83,7 @@ fn test_invalid_approvals() {
     let signer =
         InMemoryValidatorSigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "random");
     let genesis_hash = *env.clients[0].chain.genesis().hash();
-    let approval = Approval::new(genesis_hash, 0, 1, &signer);
+    let approval = Approval::new(genesis_hash, 0, 1, &signer).unwrap();
     env.clients[0].collect_block_approval(&approval, ApprovalType::PeerApproval(peer_id));
     assert_eq!(env.clients[0].pending_approvals.cache_size(), 0);
 }
@@ -171,7 +171,11 @@ fn test_process_partial_encoded_chunk_with_missing_block() {
         PartialEncodedChunk::V2(mock_chunk),
     ));
     match result {
-        Ok(accepted_blocks) => assert!(accepted_blocks.is_empty()),
+        Ok(processed) => {
+            assert!(processed.accepted_blocks.is_empty());
+            // Parts stored until their block arrives are not validated yet.
+            assert!(!processed.new_valid_parts);
+        }
         Err(e) => panic!("Client::process_partial_encoded_chunk failed with {:?}", e),
     }
 
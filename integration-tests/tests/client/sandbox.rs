@@ -1,17 +1,25 @@
 use std::path::Path;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use actix::System;
+
+use near_actix_test_utils::run_actix;
 use near_chain::{ChainGenesis, Provenance, RuntimeAdapter};
 use near_chain_configs::Genesis;
-use near_client::test_utils::TestEnv;
+use near_client::test_utils::{setup_no_network, TestEnv};
+use near_client::GetBlock;
 use near_crypto::{InMemorySigner, KeyType};
+use near_logger_utils::init_test_logger;
+use near_network::types::{NetworkSandboxMessage, SandboxResponse};
+use near_network::{NetworkClientMessages, NetworkClientResponses};
 use near_primitives::account::Account;
 use near_primitives::serialize::{from_base64, to_base64};
 use near_primitives::state_record::StateRecord;
 use near_primitives::transaction::{
     Action, DeployContractAction, FunctionCallAction, SignedTransaction,
 };
-use near_primitives::types::{AccountId, BlockHeight, Nonce};
+use near_primitives::types::{AccountId, BlockHeight, BlockId, BlockReference, Nonce};
 use near_store::test_utils::create_test_store;
 use nearcore::config::GenesisExt;
 
@@ -109,3 +117,159 @@ fn test_patch_account() {
     let test1_after = env.query_account("test1".parse().unwrap());
     assert_eq!(test1_after.amount, 10);
 }
+
+#[test]
+#[cfg(feature = "sandbox")]
+fn test_shift_block_timestamp() {
+    let (mut env, _signer) = test_setup();
+    let prev_timestamp = env.clients[0].chain.head_header().unwrap().timestamp();
+
+    let one_year = chrono::Duration::days(365);
+    env.clients[0].chain.sandbox_time_offset = one_year;
+    do_blocks(&mut env, 9, 10);
+    let timestamp = env.clients[0].chain.head_header().unwrap().timestamp();
+    assert!(timestamp >= prev_timestamp + one_year);
+}
+
+/// Fast forward produces the requested blocks without waiting for the block production delays,
+/// and moves the block timestamps as if the blocks were produced on time.
+#[test]
+#[cfg(feature = "sandbox")]
+fn test_fast_forward() {
+    init_test_logger();
+    run_actix(async {
+        let (client, view_client) =
+            setup_no_network(vec!["test".parse().unwrap()], "test".parse().unwrap(), true, false);
+        actix::spawn(async move {
+            let head = view_client.send(GetBlock::latest()).await.unwrap().unwrap();
+            let delta_height = 20;
+            client
+                .send(NetworkClientMessages::Sandbox(NetworkSandboxMessage::SandboxFastForward(
+                    delta_height,
+                )))
+                .await
+                .unwrap();
+            let started = Instant::now();
+            loop {
+                let status = client
+                    .send(NetworkClientMessages::Sandbox(
+                        NetworkSandboxMessage::SandboxFastForwardStatus,
+                    ))
+                    .await
+                    .unwrap();
+                if status
+                    == NetworkClientResponses::SandboxResult(
+                        SandboxResponse::SandboxFastForwardBlocksLeft(0),
+                    )
+                {
+                    break;
+                }
+                assert!(started.elapsed() < Duration::from_secs(60), "fast forward is stuck");
+                actix_rt::time::sleep(Duration::from_millis(10)).await;
+            }
+
+            let new_head = view_client.send(GetBlock::latest()).await.unwrap().unwrap();
+            assert!(new_head.header.height >= head.header.height + delta_height);
+            // The test client has a minimal block production delay of 100ms.
+            assert!(
+                new_head.header.timestamp_nanosec
+                    >= head.header.timestamp_nanosec + delta_height * 100_000_000
+            );
+
+            let response = client
+                .send(NetworkClientMessages::Sandbox(NetworkSandboxMessage::SandboxShiftTime(
+                    u64::MAX,
+                )))
+                .await
+                .unwrap();
+            assert!(matches!(
+                response,
+                NetworkClientResponses::SandboxResult(SandboxResponse::SandboxShiftTimeFailed(_))
+            ));
+            System::current().stop();
+        });
+    });
+}
+
+#[test]
+#[cfg(feature = "sandbox")]
+fn test_snapshot_and_restore() {
+    let (mut env, _signer) = test_setup();
+    let head = env.clients[0].chain.head().unwrap();
+    let state = env.query_state("test0".parse().unwrap());
+    let snapshot_id = env.clients[0].sandbox_snapshot();
+
+    env.clients[0].chain.patch_state(vec![StateRecord::Data {
+        account_id: "test0".parse().unwrap(),
+        data_key: from_base64(&state[0].key).unwrap(),
+        value: b"world".to_vec(),
+    }]);
+    do_blocks(&mut env, 9, 20);
+    assert_eq!(env.query_state("test0".parse().unwrap())[0].value, to_base64(b"world"));
+
+    assert!(!env.clients[0].sandbox_restore(snapshot_id + 1).unwrap());
+    assert!(env.clients[0].sandbox_restore(snapshot_id).unwrap());
+    assert_eq!(env.clients[0].chain.head().unwrap(), head);
+    assert_eq!(env.query_state("test0".parse().unwrap()), state);
+
+    // The restored chain keeps growing from the snapshot head.
+    do_blocks(&mut env, 9, 12);
+    assert_eq!(env.clients[0].chain.head().unwrap().height, 11);
+}
+
+/// Blocks of the reverted chain are not served by the view client after a restore.
+#[test]
+#[cfg(feature = "sandbox")]
+fn test_restore_resets_view_client() {
+    init_test_logger();
+    run_actix(async {
+        let (client, view_client) =
+            setup_no_network(vec!["test".parse().unwrap()], "test".parse().unwrap(), true, false);
+        actix::spawn(async move {
+            let snapshot_id = match client
+                .send(NetworkClientMessages::Sandbox(NetworkSandboxMessage::SandboxSnapshot))
+                .await
+                .unwrap()
+            {
+                NetworkClientResponses::SandboxResult(SandboxResponse::SandboxSnapshotTaken(
+                    snapshot_id,
+                )) => snapshot_id,
+                response => panic!("Unexpected response {:?}", response),
+            };
+            // The snapshot head is at most the current head, so the block at `height` is reverted.
+            let head = view_client.send(GetBlock::latest()).await.unwrap().unwrap();
+            let height = head.header.height + 2;
+            let wait_for_height = || async {
+                let started = Instant::now();
+                while view_client.send(GetBlock::latest()).await.unwrap().unwrap().header.height
+                    < height
+                {
+                    assert!(started.elapsed() < Duration::from_secs(60), "no blocks produced");
+                    actix_rt::time::sleep(Duration::from_millis(10)).await;
+                }
+                view_client
+                    .send(GetBlock(BlockReference::BlockId(BlockId::Height(height))))
+                    .await
+                    .unwrap()
+                    .unwrap()
+            };
+            let reverted_block = wait_for_height().await;
+
+            let response = client
+                .send(NetworkClientMessages::Sandbox(NetworkSandboxMessage::SandboxRestore(
+                    snapshot_id,
+                )))
+                .await
+                .unwrap();
+            assert_eq!(
+                response,
+                NetworkClientResponses::SandboxResult(SandboxResponse::SandboxRestoreFinished(
+                    true
+                ))
+            );
+            let block = wait_for_height().await;
+            assert_ne!(block.header.hash, reverted_block.header.hash);
+            System::current().stop();
+        });
+    });
+}
@@ -20,7 +20,7 @@ use near_primitives::transaction::{PartialExecutionStatus, SignedTransaction};
 use near_primitives::types::{
     BlockId, BlockReference, EpochId, EpochReference, Finality, TransactionOrReceiptId,
 };
-use near_primitives::views::{ExecutionOutcomeView, ExecutionStatusView};
+use near_primitives::views::{ExecutionOutcomeView, ExecutionStatusView, FinalExecutionStatus};
 
 use crate::node_cluster::NodeCluster;
 
@@ -442,6 +442,75 @@ fn test_send_tx_sync_returns_transaction_hash() {
     });
 }
 
+/// Simulating a transaction returns its outcome without submitting it.
+#[test]
+fn test_simulate_tx() {
+    init_integration_logger();
+
+    let cluster = NodeCluster::new(1, |index| format!("simulate_tx{}", index))
+        .set_num_shards(1)
+        .set_num_validator_seats(1)
+        .set_num_lightclients(0)
+        .set_epoch_length(10)
+        .set_genesis_height(0);
+
+    cluster.exec_until_stop(|genesis, rpc_addrs, clients| async move {
+        let view_client = clients[0].1.clone();
+
+        let genesis_hash = *genesis_block(&genesis).hash();
+        let signer =
+            InMemorySigner::from_seed("near.0".parse().unwrap(), KeyType::ED25519, "near.0");
+        let transaction = SignedTransaction::send_money(
+            1,
+            "near.0".parse().unwrap(),
+            "near.1".parse().unwrap(),
+            &signer,
+            10000,
+            genesis_hash,
+        );
+
+        let client = new_client(&format!("http://{}", rpc_addrs[0]));
+        let bytes = transaction.try_to_vec().unwrap();
+
+        spawn_interruptible(async move {
+            loop {
+                let res = view_client.send(GetBlock::latest()).await;
+                if let Ok(Ok(block)) = res {
+                    if block.header.height > 10 {
+                        let response =
+                            client.EXPERIMENTAL_simulate_tx(to_base64(&bytes)).await.unwrap();
+                        assert_eq!(
+                            response["final_outcome"]["status"],
+                            serde_json::json!({ "SuccessValue": "" })
+                        );
+                        assert_eq!(
+                            response["final_outcome"]["transaction_outcome"]["id"],
+                            transaction.get_hash().to_string()
+                        );
+                        assert!(!response["state_changes"].as_array().unwrap().is_empty());
+
+                        // The nonce of the simulated transaction is not used up.
+                        let outcome = client.broadcast_tx_commit(to_base64(&bytes)).await.unwrap();
+                        assert!(matches!(outcome.status, FinalExecutionStatus::SuccessValue(_)));
+
+                        // Simulating a transaction with a used nonce fails.
+                        let err =
+                            client.EXPERIMENTAL_simulate_tx(to_base64(&bytes)).await.unwrap_err();
+                        assert_eq!(
+                            err.data.unwrap()["TxExecutionError"]["InvalidTxError"]["InvalidNonce"]
+                                ["tx_nonce"],
+                            1
+                        );
+                        System::current().stop();
+                        break;
+                    }
+                }
+                sleep(std::time::Duration::from_millis(500)).await;
+            }
+        });
+    });
+}
+
 #[test]
 fn test_send_tx_sync_to_lightclient_must_be_routed() {
     init_integration_logger();
@@ -85,6 +85,7 @@ fn add_blocks(
                     prev.header().height() + 1,
                     signer,
                 )
+                .unwrap()
                 .signature,
             )],
             Rational::from_integer(0),
@@ -96,7 +97,9 @@ fn add_blocks(
             signer,
             next_bp_hash,
             block_merkle_tree.root(),
-        );
+            None,
+        )
+        .unwrap();
         block_merkle_tree.insert(*block.hash());
         let _ = client.do_send(NetworkClientMessages::Block(
             block.clone(),
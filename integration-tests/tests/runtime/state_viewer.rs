@@ -5,6 +5,7 @@ use near_primitives::{
     account::Account,
     hash::hash as sha256,
     hash::CryptoHash,
+    serialize::from_base64,
     views::{StateItem, ViewApplyState},
 };
 use near_primitives::{
@@ -13,7 +14,7 @@ use near_primitives::{
     types::{EpochId, StateChangeCause},
     version::PROTOCOL_VERSION,
 };
-use near_store::set_account;
+use near_store::{set_account, Trie};
 use node_runtime::state_viewer::errors;
 use node_runtime::state_viewer::*;
 use testlib::runtime_utils::{alice_account, encode_int};
@@ -151,6 +152,78 @@ fn test_view_state() {
     );
 }
 
+#[test]
+fn test_view_state_paginated() {
+    let (_, tries, root) = get_runtime_and_trie();
+    let shard_uid = TEST_SHARD_UID;
+    let mut state_update = tries.new_trie_update(shard_uid, root);
+    for key in [b"test1", b"test2", b"test3"].iter() {
+        state_update.set(
+            TrieKey::ContractData { account_id: alice_account(), key: key.to_vec() },
+            b"123".to_vec(),
+        );
+    }
+    state_update.set(
+        TrieKey::ContractData { account_id: alice_account(), key: b"xyz".to_vec() },
+        b"321".to_vec(),
+    );
+    state_update.commit(StateChangeCause::InitialState);
+    let trie_changes = state_update.finalize().unwrap().0;
+    let (db_changes, new_root) = tries.apply_all(&trie_changes, shard_uid).unwrap();
+    db_changes.commit().unwrap();
+
+    let state_update = tries.new_trie_update(shard_uid, new_root);
+    let trie_viewer = TrieViewer::new(Some(0), None);
+    let page = trie_viewer
+        .view_state_paginated(&state_update, &alice_account(), b"test", None, 2, true)
+        .unwrap();
+    assert_eq!(page.state_root, new_root);
+    assert_eq!(
+        page.values.iter().map(|item| item.key.as_str()).collect::<Vec<_>>(),
+        ["dGVzdDE=", "dGVzdDI="]
+    );
+    assert_eq!(page.next_key.as_deref(), Some("dGVzdDM="));
+    for item in page.values {
+        let key = TrieKey::ContractData {
+            account_id: alice_account(),
+            key: from_base64(&item.key).unwrap(),
+        }
+        .to_vec();
+        let proof: Vec<_> = item.proof.iter().map(|node| from_base64(node).unwrap()).collect();
+        assert!(Trie::verify_proof(&new_root, &key, b"123", &proof));
+    }
+
+    let start_key = from_base64("dGVzdDM=").unwrap();
+    let page = trie_viewer
+        .view_state_paginated(&state_update, &alice_account(), b"test", Some(&start_key), 2, false)
+        .unwrap();
+    assert_eq!(
+        page.values,
+        [StateItem { key: "dGVzdDM=".to_string(), value: "MTIz".to_string(), proof: vec![] }]
+    );
+    assert_eq!(page.next_key, None);
+
+    // A zero limit still makes progress.
+    let page = trie_viewer
+        .view_state_paginated(&state_update, &alice_account(), b"test", None, 0, false)
+        .unwrap();
+    assert_eq!(
+        page.values,
+        [StateItem { key: "dGVzdDE=".to_string(), value: "MTIz".to_string(), proof: vec![] }]
+    );
+    assert_eq!(page.next_key.as_deref(), Some("dGVzdDI="));
+
+    let result = trie_viewer.view_state_paginated(
+        &state_update,
+        &"david".parse().unwrap(),
+        b"",
+        None,
+        2,
+        false,
+    );
+    assert!(matches!(result, Err(errors::ViewStateError::AccountDoesNotExist { .. })));
+}
+
 #[test]
 fn test_view_state_too_large() {
     let (_, tries, root) = get_runtime_and_trie();
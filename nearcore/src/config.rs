@@ -2,7 +2,7 @@ use std::convert::TryInto;
 use std::fs;
 use std::fs::File;
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -22,20 +22,25 @@ use near_network::test_utils::open_port;
 use near_network::types::ROUTED_MESSAGE_TTL;
 use near_network::utils::blacklist_from_iter;
 use near_network::NetworkConfig;
+use near_pool::TransactionPoolConfig;
 use near_primitives::account::{AccessKey, Account};
 use near_primitives::hash::CryptoHash;
 use near_primitives::runtime::config::RuntimeConfig;
-use near_primitives::shard_layout::ShardLayout;
+use near_primitives::shard_layout::{ShardLayout, ShardUId};
 use near_primitives::state_record::StateRecord;
 use near_primitives::types::{
     AccountId, AccountInfo, Balance, BlockHeightDelta, EpochHeight, Gas, NumBlocks, NumSeats,
     NumShards, ShardId,
 };
 use near_primitives::utils::{generate_random_string, get_num_seats_per_shard};
-use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
+use near_primitives::validator_signer::{
+    DoubleSignProtectedValidatorSigner, InMemoryValidatorSigner, ValidatorSigner,
+};
 use near_primitives::version::PROTOCOL_VERSION;
+use near_remote_signer::RemoteValidatorSigner;
 #[cfg(feature = "rosetta_rpc")]
 use near_rosetta_rpc::RosettaRpcConfig;
+use near_store::StoreConfig;
 use near_telemetry::TelemetryConfig;
 
 /// Initial balance used in tests.
@@ -135,6 +140,7 @@ pub const CONFIG_FILENAME: &str = "config.json";
 pub const GENESIS_CONFIG_FILENAME: &str = "genesis.json";
 pub const NODE_KEY_FILE: &str = "node_key.json";
 pub const VALIDATOR_KEY_FILE: &str = "validator_key.json";
+pub const SIGNING_HISTORY_FILE: &str = "validator_signing_history.json";
 
 pub const MAINNET_TELEMETRY_URL: &str = "https://explorer.mainnet.near.org/api/nodes";
 pub const NETWORK_TELEMETRY_URL: &str = "https://explorer.{}.near.org/api/nodes";
@@ -360,6 +366,10 @@ pub struct Consensus {
     /// How much to wait for a state sync response before re-requesting
     #[serde(default = "default_state_sync_timeout")]
     pub state_sync_timeout: Duration,
+    /// Directory with the state parts dumped by `state-viewer dump_state_parts`, to state sync
+    /// from instead of requesting the state from peers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_sync_parts_dir: Option<PathBuf>,
     /// Expected increase of header head weight per second during header sync
     #[serde(default = "default_header_sync_expected_height_per_second")]
     pub header_sync_expected_height_per_second: u64,
@@ -393,6 +403,7 @@ impl Default for Consensus {
             header_sync_progress_timeout: default_header_sync_progress_timeout(),
             header_sync_stall_ban_timeout: default_header_sync_stall_ban_timeout(),
             state_sync_timeout: default_state_sync_timeout(),
+            state_sync_parts_dir: None,
             header_sync_expected_height_per_second: default_header_sync_expected_height_per_second(
             ),
             sync_check_period: default_sync_check_period(),
@@ -408,6 +419,13 @@ pub struct Config {
     pub genesis_file: String,
     pub genesis_records_file: Option<String>,
     pub validator_key_file: String,
+    /// File with the heights of the last messages signed with the validator key, used to never
+    /// sign conflicting ones. Protection is disabled if not set, and in sandbox builds.
+    pub signing_history_file: Option<String>,
+    /// Address of a remote signer holding the validator key, `unix:<path>`. If set,
+    /// `validator_key_file` is not used and the node connects to the signer when it starts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_signer_addr: Option<String>,
     pub node_key_file: String,
     #[cfg(feature = "json_rpc")]
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -420,6 +438,9 @@ pub struct Config {
     pub consensus: Consensus,
     pub tracked_accounts: Vec<AccountId>,
     pub tracked_shards: Vec<ShardId>,
+    /// Tracks only the given shards, e.g. `[{"version": 1, "shard_id": 2}]`. Takes precedence
+    /// over `tracked_shards`, which tracks all the shards if not empty.
+    pub tracked_shard_uids: Vec<ShardUId>,
     pub archive: bool,
     pub log_summary_style: LogSummaryStyle,
     #[serde(default = "default_gc_blocks_limit")]
@@ -434,6 +455,10 @@ pub struct Config {
     /// If set, overrides value in genesis configuration.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_gas_burnt_view: Option<Gas>,
+    /// Storage backend options.
+    pub store: StoreConfig,
+    /// Limits of the transaction pool of every shard.
+    pub tx_pool: TransactionPoolConfig,
 }
 
 impl Default for Config {
@@ -442,6 +467,8 @@ impl Default for Config {
             genesis_file: GENESIS_CONFIG_FILENAME.to_string(),
             genesis_records_file: None,
             validator_key_file: VALIDATOR_KEY_FILE.to_string(),
+            signing_history_file: Some(SIGNING_HISTORY_FILE.to_string()),
+            remote_signer_addr: None,
             node_key_file: NODE_KEY_FILE.to_string(),
             #[cfg(feature = "json_rpc")]
             rpc: Some(RpcConfig::default()),
@@ -452,6 +479,7 @@ impl Default for Config {
             consensus: Consensus::default(),
             tracked_accounts: vec![],
             tracked_shards: vec![],
+            tracked_shard_uids: vec![],
             archive: false,
             log_summary_style: LogSummaryStyle::Colored,
             gc_blocks_limit: default_gc_blocks_limit(),
@@ -460,6 +488,8 @@ impl Default for Config {
             view_client_throttle_period: default_view_client_throttle_period(),
             trie_viewer_state_size_limit: default_trie_viewer_state_size_limit(),
             max_gas_burnt_view: None,
+            store: StoreConfig::default(),
+            tx_pool: TransactionPoolConfig::default(),
         }
     }
 }
@@ -624,6 +654,7 @@ pub struct NearConfig {
     #[cfg(feature = "rosetta_rpc")]
     pub rosetta_rpc_config: Option<RosettaRpcConfig>,
     pub telemetry_config: TelemetryConfig,
+    pub store_config: StoreConfig,
     pub genesis: Genesis,
     pub validator_signer: Option<Arc<dyn ValidatorSigner>>,
 }
@@ -657,6 +688,7 @@ impl NearConfig {
                     .consensus
                     .header_sync_expected_height_per_second,
                 state_sync_timeout: config.consensus.state_sync_timeout,
+                state_sync_parts_dir: config.consensus.state_sync_parts_dir.clone(),
                 min_num_peers: config.consensus.min_num_peers,
                 log_summary_period: Duration::from_secs(10),
                 produce_empty_blocks: config.consensus.produce_empty_blocks,
@@ -673,6 +705,7 @@ impl NearConfig {
                 doosmslug_step_period: config.consensus.doomslug_step_period,
                 tracked_accounts: config.tracked_accounts,
                 tracked_shards: config.tracked_shards,
+                tracked_shard_uids: config.tracked_shard_uids,
                 archive: config.archive,
                 log_summary_style: config.log_summary_style,
                 gc_blocks_limit: config.gc_blocks_limit,
@@ -681,6 +714,9 @@ impl NearConfig {
                 view_client_throttle_period: config.view_client_throttle_period,
                 trie_viewer_state_size_limit: config.trie_viewer_state_size_limit,
                 max_gas_burnt_view: config.max_gas_burnt_view,
+                tx_pool_max_transactions: config.tx_pool.max_transactions,
+                tx_pool_max_size_bytes: config.tx_pool.max_size_bytes,
+                tx_pool_max_transactions_per_signer: config.tx_pool.max_transactions_per_signer,
             },
             network_config: NetworkConfig {
                 public_key: network_key_pair.public_key,
@@ -727,6 +763,7 @@ impl NearConfig {
                 archive: config.archive,
             },
             telemetry_config: config.telemetry,
+            store_config: config.store,
             #[cfg(feature = "json_rpc")]
             rpc_config: config.rpc,
             #[cfg(feature = "rosetta_rpc")]
@@ -746,6 +783,21 @@ impl NearConfig {
 }
 
 impl NearConfig {
+    /// Connects to the remote signer of the node in `dir`, if one is configured, and signs with it
+    /// from then on.
+    pub fn connect_remote_signer(&mut self, dir: &Path) {
+        let remote_signer_addr = match &self.config.remote_signer_addr {
+            Some(remote_signer_addr) => remote_signer_addr,
+            None => return,
+        };
+        let addr = remote_signer_addr.parse().expect("Failed to parse the remote signer address");
+        let signer = RemoteValidatorSigner::connect(addr)
+            .unwrap_or_else(|err| panic!("Failed to connect to the remote signer: {}", err));
+        let signer = protect_validator_signer(&self.config, dir, Arc::new(signer));
+        self.network_config.account_id = Some(signer.validator_id().clone());
+        self.validator_signer = Some(signer);
+    }
+
     /// Test tool to save configs back to the folder.
     /// Useful for dynamic creating testnet configs and then saving them in different folders.
     pub fn save_to_dir(&self, dir: &Path) {
@@ -754,7 +806,9 @@ impl NearConfig {
         self.config.write_to_file(&dir.join(CONFIG_FILENAME));
 
         if let Some(validator_signer) = &self.validator_signer {
-            validator_signer.write_to_file(&dir.join(&self.config.validator_key_file));
+            validator_signer
+                .write_to_file(&dir.join(&self.config.validator_key_file))
+                .expect("Failed to write the validator key");
         }
 
         let network_signer = InMemorySigner::from_secret_key(
@@ -810,7 +864,7 @@ fn add_account_with_key(
 fn generate_validator_key(account_id: AccountId, path: &Path) {
     let signer = InMemoryValidatorSigner::from_random(account_id.clone(), KeyType::ED25519);
     info!(target: "near", "Use key {} for {} to stake.", signer.public_key(), account_id);
-    signer.write_to_file(path);
+    signer.write_to_file(path).expect("Failed to write the validator key");
 }
 
 pub fn mainnet_genesis() -> Genesis {
@@ -940,7 +994,9 @@ pub fn init_configs(
             } else {
                 InMemoryValidatorSigner::from_random(account_id.clone(), KeyType::ED25519)
             };
-            signer.write_to_file(&dir.join(config.validator_key_file));
+            signer
+                .write_to_file(&dir.join(config.validator_key_file))
+                .expect("Failed to write the validator key");
 
             let network_signer =
                 InMemorySigner::from_random("node".parse().unwrap(), KeyType::ED25519);
@@ -1088,7 +1144,9 @@ pub fn init_testnet_configs(
         let node_dir = dir.join(format!("{}{}", prefix, i));
         fs::create_dir_all(node_dir.clone()).expect("Failed to create directory");
 
-        validator_signers[i].write_to_file(&node_dir.join(&configs[i].validator_key_file));
+        validator_signers[i]
+            .write_to_file(&node_dir.join(&configs[i].validator_key_file))
+            .expect("Failed to write the validator key");
         network_signers[i].write_to_file(&node_dir.join(&configs[i].node_key_file));
 
         genesis.to_file(&node_dir.join(&configs[i].genesis_file));
@@ -1172,7 +1230,10 @@ impl From<NodeKeyFile> for KeyFile {
     }
 }
 
-pub fn load_config_without_genesis_records(dir: &Path) -> NearConfig {
+/// Reads the config and the genesis config of the node in `dir`, without the genesis records.
+/// Unlike [`load_config`], it doesn't load the keys of the node, so it's enough for commands which
+/// only work with the store.
+pub fn load_config_without_keys(dir: &Path) -> (Config, Genesis) {
     let config = Config::from_file(&dir.join(CONFIG_FILENAME));
     let genesis_config = GenesisConfig::from_file(&dir.join(&config.genesis_file));
     let genesis_records_file = if let Some(genesis_records_file) = &config.genesis_records_file {
@@ -1180,21 +1241,42 @@ pub fn load_config_without_genesis_records(dir: &Path) -> NearConfig {
     } else {
         dir.join(&config.genesis_file)
     };
-    let validator_signer = if dir.join(&config.validator_key_file).exists() {
-        let signer =
-            Arc::new(InMemoryValidatorSigner::from_file(&dir.join(&config.validator_key_file)))
-                as Arc<dyn ValidatorSigner>;
-        Some(signer)
-    } else {
-        None
-    };
+    (config, Genesis::new_with_path(genesis_config, genesis_records_file))
+}
+
+/// Protects `signer` against double signing with the signing history, if it's configured.
+fn protect_validator_signer(
+    config: &Config,
+    dir: &Path,
+    signer: Arc<dyn ValidatorSigner>,
+) -> Arc<dyn ValidatorSigner> {
+    match &config.signing_history_file {
+        // Sandbox nodes restore old states and produce the same heights again.
+        Some(signing_history_file) if !cfg!(feature = "sandbox") => {
+            let signer =
+                DoubleSignProtectedValidatorSigner::new(signer, &dir.join(signing_history_file))
+                    .expect("Failed to read the signing history");
+            Arc::new(signer)
+        }
+        _ => signer,
+    }
+}
+
+/// Loads the config of the node in `dir`. The remote signer, if configured, is only connected to
+/// by [`NearConfig::connect_remote_signer`] when the node starts.
+pub fn load_config_without_genesis_records(dir: &Path) -> NearConfig {
+    let (config, genesis) = load_config_without_keys(dir);
+    let validator_signer =
+        if config.remote_signer_addr.is_none() && dir.join(&config.validator_key_file).exists() {
+            let signer =
+                Arc::new(InMemoryValidatorSigner::from_file(&dir.join(&config.validator_key_file)))
+                    as Arc<dyn ValidatorSigner>;
+            Some(protect_validator_signer(&config, dir, signer))
+        } else {
+            None
+        };
     let network_signer = NodeKeyFile::from_file(&dir.join(&config.node_key_file));
-    NearConfig::new(
-        config,
-        Genesis::new_with_path(genesis_config, genesis_records_file),
-        network_signer.into(),
-        validator_signer,
-    )
+    NearConfig::new(config, genesis, network_signer.into(), validator_signer)
 }
 
 pub fn load_config(dir: &Path) -> NearConfig {
@@ -25,13 +25,13 @@ use near_store::migrations::{
     set_store_version,
 };
 use near_store::migrations::{migrate_20_to_21, migrate_26_to_27};
-use near_store::{create_store, Store};
+use near_store::{create_store, create_store_with_config, Store};
 use near_telemetry::TelemetryActor;
 
 pub use crate::config::{init_configs, load_config, load_test_config, NearConfig, NEAR_BASE};
 use crate::migrations::{
     migrate_12_to_13, migrate_18_to_19, migrate_19_to_20, migrate_22_to_23, migrate_23_to_24,
-    migrate_24_to_25,
+    migrate_24_to_25, migrate_28_to_29,
 };
 pub use crate::runtime::NightshadeRuntime;
 pub use crate::shard_tracker::TrackedConfig;
@@ -244,6 +244,11 @@ pub fn apply_store_migrations(path: &Path, near_config: &NearConfig) {
         let store = create_store(&path);
         set_store_version(&store, 28);
     }
+    if db_version <= 28 {
+        // version 28 => 29: add reputation to ColPeers
+        info!(target: "near", "Migrate DB from version 28 to 29");
+        migrate_28_to_29(&path);
+    }
     #[cfg(feature = "nightly_protocol")]
     {
         let store = create_store(&path);
@@ -270,7 +275,7 @@ pub fn init_and_migrate_store(home_dir: &Path, near_config: &NearConfig) -> Arc<
     if store_exists {
         apply_store_migrations(&path, near_config);
     }
-    let store = create_store(&path);
+    let store = create_store_with_config(&path, &near_config.store_config);
     if !store_exists {
         set_store_version(&store, near_primitives::version::DB_VERSION);
     }
@@ -284,7 +289,8 @@ pub struct NearNode {
     pub rpc_servers: Vec<(&'static str, actix_web::dev::Server)>,
 }
 
-pub fn start_with_config(home_dir: &Path, config: NearConfig) -> NearNode {
+pub fn start_with_config(home_dir: &Path, mut config: NearConfig) -> NearNode {
+    config.connect_remote_signer(home_dir);
     let store = init_and_migrate_store(home_dir, &config);
 
     let runtime = Arc::new(NightshadeRuntime::with_config(
@@ -479,6 +479,38 @@ pub fn migrate_24_to_25(path: &Path) {
     set_store_version(&store, 25);
 }
 
+pub fn migrate_28_to_29(path: &Path) {
+    use near_network::types::{KnownPeerState, KnownPeerStatus, PeerInfo};
+
+    #[derive(BorshDeserialize)]
+    struct OldKnownPeerState {
+        peer_info: PeerInfo,
+        status: KnownPeerStatus,
+        first_seen: u64,
+        last_seen: u64,
+    }
+
+    let store = create_store(path);
+    let mut store_update = store.store_update();
+    for (key, value) in store.iter(DBCol::ColPeers) {
+        let old_peer_state =
+            OldKnownPeerState::try_from_slice(&value).expect("BorshDeserialize should not fail");
+        let peer_state = KnownPeerState {
+            peer_info: old_peer_state.peer_info,
+            status: old_peer_state.status,
+            first_seen: old_peer_state.first_seen,
+            last_seen: old_peer_state.last_seen,
+            reputation: 0,
+        };
+        store_update
+            .set_ser(DBCol::ColPeers, &key, &peer_state)
+            .expect("BorshSerialize should not fail");
+    }
+    store_update.commit().expect("Failed to migrate");
+
+    set_store_version(&store, 29);
+}
+
 lazy_static_include::lazy_static_include_bytes! {
     /// File with account ids and deltas that need to be applied in order to fix storage usage
     /// difference between actual and stored usage, introduced due to bug in access key deletion,
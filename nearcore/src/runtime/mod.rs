@@ -1,7 +1,7 @@
 use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 
 use borsh::ser::BorshSerialize;
@@ -10,7 +10,8 @@ use tracing::{debug, error, info, warn};
 
 use near_chain::chain::NUM_EPOCHS_TO_KEEP_STORE_DATA;
 use near_chain::types::{
-    ApplySplitStateResult, ApplyTransactionResult, BlockHeaderInfo, ValidatorInfoIdentifier,
+    ApplySplitStateResult, ApplyTransactionResult, BlockHeaderInfo, SimulateTransactionResult,
+    ValidatorInfoIdentifier,
 };
 use near_chain::{BlockHeader, Error, ErrorKind, RuntimeAdapter};
 #[cfg(feature = "protocol_feature_block_header_v3")]
@@ -41,14 +42,15 @@ use near_primitives::types::{
 use near_primitives::version::ProtocolVersion;
 use near_primitives::views::{
     AccessKeyInfoView, CallResult, EpochValidatorInfo, QueryRequest, QueryResponse,
-    QueryResponseKind, ViewApplyState, ViewStateResult,
+    QueryResponseKind, ViewApplyState, ViewStatePageResult, ViewStateResult,
 };
 use near_vm_runner::precompile_contract;
 
 use near_store::{
-    get_genesis_hash, get_genesis_state_roots, set_genesis_hash, set_genesis_state_roots,
-    ApplyStatePartResult, ColState, PartialStorage, ShardTries, Store, StoreCompiledContractCache,
-    StoreUpdate, Trie, WrappedTrieChanges,
+    create_cold_store, get_genesis_hash, get_genesis_state_roots, set_genesis_hash,
+    set_genesis_state_roots, ApplyStatePartResult, ColState, InMemoryCompiledContractCache,
+    PartialStorage, ShardTries, Store, StoreCompiledContractCache, StoreUpdate, Trie,
+    TrieCacheConfig, WrappedTrieChanges,
 };
 use node_runtime::adapter::ViewRuntimeAdapter;
 use node_runtime::state_viewer::TrieViewer;
@@ -67,7 +69,9 @@ use near_primitives::runtime::migration_data::{MigrationData, MigrationFlags};
 use near_primitives::shard_layout::{
     account_id_to_shard_id, account_id_to_shard_uid, ShardLayout, ShardUId,
 };
-use near_primitives::syncing::{get_num_state_parts, STATE_PART_MEMORY_LIMIT};
+use near_primitives::syncing::{
+    get_epoch_sync_data_hash, get_num_state_parts, STATE_PART_MEMORY_LIMIT,
+};
 use near_store::split_state::get_delayed_receipts;
 use node_runtime::near_primitives::shard_layout::ShardLayoutError;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
@@ -136,6 +140,10 @@ pub struct NightshadeRuntime {
     runtime_config_store: RuntimeConfigStore,
 
     store: Arc<Store>,
+    /// Store that old data of an archival node is moved to by garbage collection.
+    cold_store: Option<Arc<Store>>,
+    /// Directory of store snapshots and the number of snapshots to keep.
+    store_snapshots_config: Option<(PathBuf, usize)>,
     tries: ShardTries,
     trie_viewer: TrieViewer,
     pub runtime: Runtime,
@@ -143,6 +151,9 @@ pub struct NightshadeRuntime {
     shard_tracker: ShardTracker,
     genesis_state_roots: Vec<StateRoot>,
     migration_data: Arc<MigrationData>,
+    /// See `RuntimeAdapter::sandbox_resets`.
+    #[cfg(feature = "sandbox")]
+    sandbox_resets: std::sync::atomic::AtomicU64,
 }
 
 impl NightshadeRuntime {
@@ -155,6 +166,7 @@ impl NightshadeRuntime {
             None,
             None,
             RuntimeConfigStore::test(),
+            &TrieCacheConfig::default(),
         )
     }
 
@@ -165,7 +177,7 @@ impl NightshadeRuntime {
         trie_viewer_state_size_limit: Option<u64>,
         max_gas_burnt_view: Option<Gas>,
     ) -> Self {
-        Self::new(
+        let mut runtime = Self::new(
             home_dir,
             store,
             &config.genesis,
@@ -173,7 +185,29 @@ impl NightshadeRuntime {
             trie_viewer_state_size_limit,
             max_gas_burnt_view,
             RuntimeConfigStore::new(Some(&config.genesis.config.runtime_config)),
-        )
+            &config.store_config.trie_cache,
+        );
+        if config.client_config.archive {
+            let cold_store = config
+                .store_config
+                .cold_store_dir
+                .as_ref()
+                .map(|cold_store_dir| create_cold_store(&home_dir.join(cold_store_dir)));
+            runtime = runtime.with_cold_store(cold_store);
+        }
+        runtime.store_snapshots_config =
+            config.store_config.snapshots_dir.as_ref().map(|snapshots_dir| {
+                (home_dir.join(snapshots_dir), config.store_config.snapshots_to_keep)
+            });
+        runtime
+    }
+
+    /// Sets the cold store that garbage collection moves old data of an archival node to. Old
+    /// states are read from there.
+    pub fn with_cold_store(mut self, cold_store: Option<Arc<Store>>) -> Self {
+        self.tries = self.tries.with_cold_store(cold_store.clone());
+        self.cold_store = cold_store;
+        self
     }
 
     pub fn new(
@@ -184,6 +218,7 @@ impl NightshadeRuntime {
         trie_viewer_state_size_limit: Option<u64>,
         max_gas_burnt_view: Option<Gas>,
         runtime_config_store: RuntimeConfigStore,
+        trie_cache_config: &TrieCacheConfig,
     ) -> Self {
         let runtime = Runtime::new();
         let trie_viewer = TrieViewer::new(trie_viewer_state_size_limit, max_gas_burnt_view);
@@ -197,8 +232,9 @@ impl NightshadeRuntime {
         );
         let state_roots =
             Self::initialize_genesis_state_if_needed(store.clone(), home_dir, genesis);
-        let tries = ShardTries::new(
+        let tries = ShardTries::with_config(
             store.clone(),
+            trie_cache_config,
             genesis_config.shard_layout.version(),
             genesis.config.num_block_producer_seats_per_shard.len() as NumShards,
         );
@@ -211,6 +247,8 @@ impl NightshadeRuntime {
             genesis_config,
             runtime_config_store,
             store,
+            cold_store: None,
+            store_snapshots_config: None,
             tries,
             runtime,
             trie_viewer,
@@ -218,6 +256,8 @@ impl NightshadeRuntime {
             shard_tracker,
             genesis_state_roots: state_roots,
             migration_data: Arc::new(load_migration_data(&genesis.config.chain_id)),
+            #[cfg(feature = "sandbox")]
+            sandbox_resets: std::sync::atomic::AtomicU64::new(0),
         }
     }
 
@@ -649,6 +689,14 @@ impl RuntimeAdapter for NightshadeRuntime {
         self.store.clone()
     }
 
+    fn get_cold_store(&self) -> Option<Arc<Store>> {
+        self.cold_store.clone()
+    }
+
+    fn get_store_snapshots_config(&self) -> Option<(PathBuf, usize)> {
+        self.store_snapshots_config.clone()
+    }
+
     fn get_tries(&self) -> ShardTries {
         self.tries.clone()
     }
@@ -1225,13 +1273,14 @@ impl RuntimeAdapter for NightshadeRuntime {
             cur_epoch_info,
             next_epoch_info,
         ) = self.get_epoch_sync_data(prev_epoch_last_block_hash, epoch_id, next_epoch_id)?;
-        let mut data = prev_epoch_first_block_info.try_to_vec().unwrap();
-        data.extend(prev_epoch_prev_last_block_info.try_to_vec().unwrap());
-        data.extend(prev_epoch_last_block_info.try_to_vec().unwrap());
-        data.extend(prev_epoch_info.try_to_vec().unwrap());
-        data.extend(cur_epoch_info.try_to_vec().unwrap());
-        data.extend(next_epoch_info.try_to_vec().unwrap());
-        Ok(hash(data.as_slice()))
+        Ok(get_epoch_sync_data_hash(
+            &prev_epoch_first_block_info,
+            &prev_epoch_prev_last_block_info,
+            &prev_epoch_last_block_info,
+            &prev_epoch_info,
+            &cur_epoch_info,
+            &next_epoch_info,
+        ))
     }
 
     // TODO #3488 this likely to be updated
@@ -1506,6 +1555,36 @@ impl RuntimeAdapter for NightshadeRuntime {
                     block_hash: *block_hash,
                 })
             }
+            QueryRequest::ViewStatePaginated {
+                account_id,
+                prefix,
+                start_key,
+                limit,
+                include_proof,
+            } => {
+                let view_state_page_result = self
+                    .view_state_paginated(
+                        &shard_uid,
+                        *state_root,
+                        account_id,
+                        prefix.as_ref(),
+                        start_key.as_deref(),
+                        *limit,
+                        *include_proof,
+                    )
+                    .map_err(|err| {
+                        near_chain::near_chain_primitives::error::QueryError::from_view_state_error(
+                            err,
+                            block_height,
+                            *block_hash,
+                        )
+                    })?;
+                Ok(QueryResponse {
+                    kind: QueryResponseKind::ViewStatePaginated(view_state_page_result),
+                    block_height,
+                    block_hash: *block_hash,
+                })
+            }
             QueryRequest::ViewAccessKeyList { account_id } => {
                 let access_key_list =
                     self.view_access_keys(&shard_uid, *state_root, account_id).map_err(|err| {
@@ -1548,6 +1627,67 @@ impl RuntimeAdapter for NightshadeRuntime {
         }
     }
 
+    fn simulate_transaction(
+        &self,
+        epoch_id: &EpochId,
+        state_roots: &HashMap<ShardId, StateRoot>,
+        block_height: BlockHeight,
+        block_timestamp: u64,
+        prev_block_hash: &CryptoHash,
+        gas_price: Balance,
+        random_seed: CryptoHash,
+        transaction: &SignedTransaction,
+    ) -> Result<Result<SimulateTransactionResult, InvalidTxError>, Error> {
+        let epoch_height = {
+            let mut epoch_manager = self.epoch_manager.as_ref().write().expect(POISONED_LOCK_ERR);
+            epoch_manager.get_epoch_info(epoch_id)?.epoch_height()
+        };
+        let current_protocol_version = self.get_epoch_protocol_version(epoch_id)?;
+        let shard_layout = self.get_shard_layout(epoch_id)?;
+        let apply_state = ApplyState {
+            block_index: block_height,
+            prev_block_hash: *prev_block_hash,
+            block_hash: CryptoHash::default(),
+            epoch_id: epoch_id.clone(),
+            epoch_height,
+            gas_price,
+            block_timestamp,
+            gas_limit: None,
+            random_seed,
+            current_protocol_version,
+            config: self.runtime_config_store.get_config(current_protocol_version).clone(),
+            cache: Some(Arc::new(InMemoryCompiledContractCache::new(self.store.clone()))),
+            is_new_chunk: true,
+            migration_data: Arc::clone(&self.migration_data),
+            migration_flags: MigrationFlags::default(),
+        };
+
+        let mut state_updates = BTreeMap::new();
+        for (shard_id, state_root) in state_roots {
+            let shard_uid = ShardUId::from_shard_id_and_layout(*shard_id, &shard_layout);
+            state_updates
+                .insert(*shard_id, self.tries.new_trie_update_view(shard_uid, *state_root));
+        }
+
+        match self.runtime.simulate_transaction(
+            state_updates,
+            &shard_layout,
+            &apply_state,
+            transaction,
+            &self.epoch_manager,
+        ) {
+            Ok(result) => Ok(Ok(SimulateTransactionResult {
+                outcomes: result.outcomes,
+                receipts: result.receipts,
+                unprocessed_receipts: result.unprocessed_receipts,
+                state_changes: result.state_changes,
+            })),
+            Err(RuntimeError::InvalidTxError(err)) => Ok(Err(err)),
+            Err(RuntimeError::StorageError(err)) => Err(ErrorKind::StorageError(err).into()),
+            Err(err) => Err(ErrorKind::Other(format!("{:?}", err)).into()),
+        }
+    }
+
     fn get_validator_info(
         &self,
         epoch_id: ValidatorInfoIdentifier,
@@ -1806,6 +1946,17 @@ impl RuntimeAdapter for NightshadeRuntime {
         let mut epoch_manager = self.epoch_manager.as_ref().write().expect(POISONED_LOCK_ERR);
         Ok(epoch_manager.will_shard_layout_change(parent_hash)?)
     }
+
+    #[cfg(feature = "sandbox")]
+    fn sandbox_reset_caches(&self) {
+        self.epoch_manager.as_ref().write().expect(POISONED_LOCK_ERR).reset_caches();
+        self.sandbox_resets.fetch_add(1, std::sync::atomic::Ordering::AcqRel);
+    }
+
+    #[cfg(feature = "sandbox")]
+    fn sandbox_resets(&self) -> u64 {
+        self.sandbox_resets.load(std::sync::atomic::Ordering::Acquire)
+    }
 }
 
 impl node_runtime::adapter::ViewRuntimeAdapter for NightshadeRuntime {
@@ -1900,6 +2051,27 @@ impl node_runtime::adapter::ViewRuntimeAdapter for NightshadeRuntime {
         let state_update = self.tries.new_trie_update_view(*shard_uid, state_root);
         self.trie_viewer.view_state(&state_update, account_id, prefix)
     }
+
+    fn view_state_paginated(
+        &self,
+        shard_uid: &ShardUId,
+        state_root: MerkleHash,
+        account_id: &AccountId,
+        prefix: &[u8],
+        start_key: Option<&[u8]>,
+        limit: u64,
+        include_proof: bool,
+    ) -> Result<ViewStatePageResult, node_runtime::state_viewer::errors::ViewStateError> {
+        let state_update = self.tries.new_trie_update_view(*shard_uid, state_root);
+        self.trie_viewer.view_state_paginated(
+            &state_update,
+            account_id,
+            prefix,
+            start_key,
+            limit,
+            include_proof,
+        )
+    }
 }
 
 #[cfg(test)]
@@ -2050,6 +2222,7 @@ mod test {
                 None,
                 None,
                 RuntimeConfigStore::free(),
+                &TrieCacheConfig::default(),
             );
             let (_store, state_roots) = runtime.genesis_state();
             let genesis_hash = hash(&vec![0]);
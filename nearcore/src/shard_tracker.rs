@@ -5,13 +5,20 @@ use near_chain_configs::ClientConfig;
 use near_epoch_manager::EpochManager;
 use near_primitives::errors::EpochError;
 use near_primitives::hash::CryptoHash;
-use near_primitives::shard_layout::account_id_to_shard_id;
+use near_primitives::shard_layout::{account_id_to_shard_id, ShardLayout, ShardUId};
 use near_primitives::types::{AccountId, EpochId, ShardId};
 
 const POISONED_LOCK_ERR: &str = "The lock was poisoned.";
 
 pub enum TrackedConfig {
     Accounts(Vec<AccountId>),
+    /// Tracks the given shards of every shard layout.
+    ///
+    /// If none of the shards of a layout is listed, its shards whose ancestors are listed in the
+    /// closest listed ancestor layout are tracked instead, so that the node keeps tracking the
+    /// same part of the state after any number of reshardings. Shard layout versions increase by
+    /// one with every resharding.
+    Shards(Vec<ShardUId>),
     AllShards,
 }
 
@@ -21,7 +28,9 @@ impl TrackedConfig {
     }
 
     pub fn from_config(config: &ClientConfig) -> Self {
-        if config.tracked_shards.is_empty() {
+        if !config.tracked_shard_uids.is_empty() {
+            TrackedConfig::Shards(config.tracked_shard_uids.clone())
+        } else if config.tracked_shards.is_empty() {
             TrackedConfig::Accounts(config.tracked_accounts.clone())
         } else {
             TrackedConfig::AllShards
@@ -29,6 +38,37 @@ impl TrackedConfig {
     }
 }
 
+/// Returns whether `shard_id` of `shard_layout` is one of `tracked_shards`, see
+/// `TrackedConfig::Shards`. The ancestors of the shard are looked up in the parent layouts
+/// among `shard_layouts`, through as many reshardings as needed.
+fn is_shard_tracked(
+    tracked_shards: &[ShardUId],
+    shard_id: ShardId,
+    shard_layout: &ShardLayout,
+    shard_layouts: &[&ShardLayout],
+) -> bool {
+    let version = shard_layout.version();
+    if tracked_shards.iter().any(|tracked| tracked.version == version) {
+        let shard_uid = ShardUId::from_shard_id_and_layout(shard_id, shard_layout);
+        return tracked_shards.contains(&shard_uid);
+    }
+    let parent_version = match version.checked_sub(1) {
+        Some(parent_version) if shard_layout.has_parent_shard_layout() => parent_version,
+        _ => return false,
+    };
+    let parent_shard_id = match shard_layout.get_parent_shard_id(shard_id) {
+        Ok(parent_shard_id) => parent_shard_id,
+        Err(_) => return false,
+    };
+    match shard_layouts.iter().find(|layout| layout.version() == parent_version) {
+        Some(parent_layout) => {
+            is_shard_tracked(tracked_shards, parent_shard_id, parent_layout, shard_layouts)
+        }
+        None => tracked_shards
+            .contains(&ShardUId { version: parent_version, shard_id: parent_shard_id as u32 }),
+    }
+}
+
 // bit mask for which shard to track
 type BitMask = Vec<bool>;
 
@@ -37,7 +77,7 @@ type BitMask = Vec<bool>;
 /// are in and `tracked_shards`.
 pub struct ShardTracker {
     tracked_config: TrackedConfig,
-    /// Stores shard tracking information by epoch, only useful if TrackedState == Accounts
+    /// Stores shard tracking information by epoch, only useful if TrackedState is Accounts or Shards
     tracking_shards: AppendOnlyMap<EpochId, BitMask>,
     /// Epoch manager that for given block hash computes the epoch id.
     epoch_manager: Arc<RwLock<EpochManager>>,
@@ -67,6 +107,24 @@ impl ShardTracker {
                 });
                 Ok(tracking_mask.get(shard_id as usize).copied().unwrap_or(false))
             }
+            TrackedConfig::Shards(tracked_shards) => {
+                let mut epoch_manager = self.epoch_manager.write().expect(POISONED_LOCK_ERR);
+                let shard_layout = epoch_manager.get_shard_layout(epoch_id)?.clone();
+                let tracking_mask = self.tracking_shards.get_or_insert(epoch_id, || {
+                    let shard_layouts = epoch_manager.get_all_shard_layouts();
+                    (0..shard_layout.num_shards())
+                        .map(|shard_id| {
+                            is_shard_tracked(
+                                tracked_shards,
+                                shard_id,
+                                &shard_layout,
+                                &shard_layouts,
+                            )
+                        })
+                        .collect()
+                });
+                Ok(tracking_mask.get(shard_id as usize).copied().unwrap_or(false))
+            }
             TrackedConfig::AllShards => Ok(true),
         }
     }
@@ -149,8 +207,8 @@ mod tests {
     };
     use near_store::test_utils::create_test_store;
 
-    use super::{account_id_to_shard_id, ShardTracker};
-    use near_primitives::shard_layout::ShardLayout;
+    use super::{account_id_to_shard_id, is_shard_tracked, ShardTracker};
+    use near_primitives::shard_layout::{ShardLayout, ShardUId};
 
     use crate::shard_tracker::TrackedConfig;
     #[cfg(feature = "protocol_feature_simple_nightshade")]
@@ -310,6 +368,96 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_track_shards() {
+        let num_shards = 4;
+        let epoch_manager = get_epoch_manager(PROTOCOL_VERSION, num_shards, None);
+        let tracked_shards =
+            vec![ShardUId { version: 0, shard_id: 1 }, ShardUId { version: 0, shard_id: 3 }];
+        let tracker = ShardTracker::new(
+            TrackedConfig::Shards(tracked_shards),
+            Arc::new(RwLock::new(epoch_manager)),
+        );
+        let total_tracked_shards: HashSet<_> = vec![1, 3].into_iter().collect();
+
+        assert_eq!(
+            get_all_shards_care_about(&tracker, num_shards, &CryptoHash::default()),
+            total_tracked_shards
+        );
+        assert_eq!(
+            get_all_shards_will_care_about(&tracker, num_shards, &CryptoHash::default()),
+            total_tracked_shards
+        );
+    }
+
+    #[test]
+    fn test_tracked_shards_after_resharding() {
+        let parent_layout = ShardLayout::v0(2, 0);
+        let shard_layout = ShardLayout::v1(
+            vec!["aurora".parse().unwrap()],
+            vec!["hhh", "ooo"].into_iter().map(|x| x.parse().unwrap()).collect(),
+            Some(vec![vec![0, 1], vec![2, 3]]),
+            1,
+        );
+        let shard_layouts = vec![&parent_layout, &shard_layout];
+        let tracked = |tracked_shards: &[ShardUId], shard_layout: &ShardLayout| {
+            (0..shard_layout.num_shards())
+                .filter(|shard_id| {
+                    is_shard_tracked(tracked_shards, *shard_id, shard_layout, &shard_layouts)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // The children of the tracked shard are tracked after resharding.
+        let tracked_shards = vec![ShardUId { version: 0, shard_id: 1 }];
+        assert_eq!(tracked(&tracked_shards, &parent_layout), vec![1]);
+        assert_eq!(tracked(&tracked_shards, &shard_layout), vec![2, 3]);
+
+        // Shards listed for the new layout take precedence.
+        let tracked_shards =
+            vec![ShardUId { version: 0, shard_id: 1 }, ShardUId { version: 1, shard_id: 0 }];
+        assert_eq!(tracked(&tracked_shards, &parent_layout), vec![1]);
+        assert_eq!(tracked(&tracked_shards, &shard_layout), vec![0]);
+    }
+
+    #[test]
+    fn test_tracked_shards_after_two_reshardings() {
+        let layout_v0 = ShardLayout::v0(2, 0);
+        let layout_v1 = ShardLayout::v1(
+            vec!["aurora".parse().unwrap()],
+            vec!["hhh", "ooo"].into_iter().map(|x| x.parse().unwrap()).collect(),
+            Some(vec![vec![0, 1], vec![2, 3]]),
+            1,
+        );
+        let layout_v2 = ShardLayout::v1(
+            vec!["aurora".parse().unwrap()],
+            vec!["ccc", "hhh", "ooo", "ttt"].into_iter().map(|x| x.parse().unwrap()).collect(),
+            Some(vec![vec![0], vec![1, 2], vec![3], vec![4, 5]]),
+            2,
+        );
+        let shard_layouts = vec![&layout_v0, &layout_v1, &layout_v2];
+        let tracked = |tracked_shards: &[ShardUId], shard_layout: &ShardLayout| {
+            (0..shard_layout.num_shards())
+                .filter(|shard_id| {
+                    is_shard_tracked(tracked_shards, *shard_id, shard_layout, &shard_layouts)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // The descendants of the tracked shard are tracked through both reshardings.
+        let tracked_shards = vec![ShardUId { version: 0, shard_id: 1 }];
+        assert_eq!(tracked(&tracked_shards, &layout_v0), vec![1]);
+        assert_eq!(tracked(&tracked_shards, &layout_v1), vec![2, 3]);
+        assert_eq!(tracked(&tracked_shards, &layout_v2), vec![3, 4, 5]);
+
+        // The closest listed ancestor layout decides.
+        let tracked_shards =
+            vec![ShardUId { version: 0, shard_id: 1 }, ShardUId { version: 1, shard_id: 1 }];
+        assert_eq!(tracked(&tracked_shards, &layout_v0), vec![1]);
+        assert_eq!(tracked(&tracked_shards, &layout_v1), vec![1]);
+        assert_eq!(tracked(&tracked_shards, &layout_v2), vec![1, 2]);
+    }
+
     #[test]
     #[cfg(feature = "protocol_feature_simple_nightshade")]
     fn test_track_shards_shard_layout_change() {
@@ -1,13 +1,33 @@
 use super::{DEFAULT_HOME, NEARD_VERSION, NEARD_VERSION_STRING, PROTOCOL_VERSION};
+use borsh::BorshDeserialize;
 use clap::{AppSettings, Clap};
 use futures::future::FutureExt;
-use near_primitives::types::{Gas, NumSeats, NumShards};
+use near_primitives::block::{Block, BlockHeader, Tip};
+use near_primitives::epoch_manager::block_info::BlockInfo;
+use near_primitives::epoch_manager::epoch_info::EpochInfo;
+use near_primitives::epoch_manager::AGGREGATOR_KEY;
+use near_primitives::hash::CryptoHash;
+use near_primitives::receipt::Receipt;
+use near_primitives::serialize::{from_base, to_base};
+use near_primitives::sharding::{PartialEncodedChunk, ShardChunk};
+use near_primitives::transaction::{ExecutionOutcomeWithIdAndProof, SignedTransaction};
+use near_primitives::types::{BlockHeight, ChunkExtra, Gas, NumSeats, NumShards};
+use near_primitives::validator_signer::SigningHistory;
+use near_primitives::views::LightClientBlockView;
+use near_store::migrations::get_store_version_from_store;
+use near_store::snapshot::{restore_snapshot, validate_snapshot, SnapshotInfo, SNAPSHOT_DATA_DIR};
+use near_store::stats::{all_column_stats, column_stats};
+use near_store::{
+    open_read_only_store, DBCol, Store, CHUNK_TAIL_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY,
+    HEADER_HEAD_KEY, HEAD_KEY, LARGEST_TARGET_HEIGHT_KEY, TAIL_KEY,
+};
+use nearcore::config::{Config, CONFIG_FILENAME};
 use nearcore::get_store_path;
+use std::fmt::Debug;
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::{env, fs, io};
 use tracing::debug;
-#[cfg(feature = "test_features")]
 use tracing::error;
 use tracing::info;
 use tracing::metadata::LevelFilter;
@@ -47,6 +67,9 @@ impl NeardCmd {
             NeardSubCommand::Init(cmd) => cmd.run(&home_dir),
             NeardSubCommand::Testnet(cmd) => cmd.run(&home_dir),
             NeardSubCommand::Run(cmd) => cmd.run(&home_dir),
+            NeardSubCommand::Db(cmd) => cmd.run(&home_dir),
+            NeardSubCommand::Restore(cmd) => cmd.run(&home_dir),
+            NeardSubCommand::SigningHistory(cmd) => cmd.run(&home_dir),
 
             NeardSubCommand::UnsafeResetData => {
                 let store_path = get_store_path(&home_dir);
@@ -97,6 +120,18 @@ pub(super) enum NeardSubCommand {
     /// config)
     #[clap(name = "unsafe_reset_data")]
     UnsafeResetData,
+    /// Inspects the node database without modifying it. Safe to use while the node is running
+    #[clap(name = "db")]
+    Db(DbCmd),
+    /// Restores the node data from a store snapshot after checking that it matches the genesis.
+    /// Requires the data directory to be empty, see `unsafe_reset_data`
+    #[clap(name = "restore")]
+    Restore(RestoreCmd),
+    /// Exports or imports the history of messages signed with the validator key, which protects
+    /// from double signing. Move it together with the key when moving the validator to another
+    /// node
+    #[clap(name = "signing_history")]
+    SigningHistory(SigningHistoryCmd),
 }
 
 #[derive(Clap)]
@@ -331,6 +366,260 @@ impl TestnetCmd {
     }
 }
 
+#[derive(Clap)]
+pub(super) struct DbCmd {
+    #[clap(subcommand)]
+    subcmd: DbSubCommand,
+}
+
+#[derive(Clap)]
+enum DbSubCommand {
+    /// Prints the database version and per-column key counts, key and value sizes and, for
+    /// refcounted columns such as `ColState`, the distribution of refcounts
+    #[clap(name = "stats")]
+    Stats(DbStatsCmd),
+    /// Prints records of a column, decoding values of known types
+    #[clap(name = "dump")]
+    Dump(DbDumpCmd),
+}
+
+#[derive(Clap)]
+struct DbStatsCmd {
+    /// Only collect statistics of the given column (e.g. `ColState` or `State`). Can be repeated.
+    #[clap(long)]
+    column: Vec<DBCol>,
+}
+
+#[derive(Clap)]
+struct DbDumpCmd {
+    /// Column to dump, e.g. `ColBlockHeader` or `BlockHeader`.
+    #[clap(long)]
+    column: DBCol,
+    /// Only dump the record with this base58-encoded key.
+    #[clap(long)]
+    key: Option<String>,
+    /// Only dump records with keys starting with this base58-encoded prefix.
+    #[clap(long, conflicts_with = "key")]
+    prefix: Option<String>,
+    /// Maximum number of records to dump.
+    #[clap(long, default_value = "10")]
+    limit: usize,
+}
+
+impl DbCmd {
+    pub(super) fn run(self, home_dir: &Path) {
+        let config = Config::from_file(&home_dir.join(CONFIG_FILENAME));
+        let store = open_read_only_store(&get_store_path(home_dir), &config.store)
+            .unwrap_or_else(|err| panic!("Failed to open the database: {}", err));
+        match self.subcmd {
+            DbSubCommand::Stats(cmd) => cmd.run(&store),
+            DbSubCommand::Dump(cmd) => cmd.run(&store),
+        }
+    }
+}
+
+impl DbStatsCmd {
+    fn run(self, store: &Store) {
+        match get_store_version_from_store(store) {
+            Some(version) => println!("DB version: {}", version),
+            None => println!("DB version: unknown"),
+        }
+        let stats = if self.column.is_empty() {
+            all_column_stats(store)
+        } else {
+            self.column.into_iter().map(|col| (col, column_stats(store, col))).collect()
+        };
+        println!(
+            "{:<32} {:>12} {:>16} {:>10} {:>16} {:>12} {:>12}",
+            "column", "keys", "key bytes", "avg key", "value bytes", "avg value", "max value"
+        );
+        for (col, stats) in stats.iter() {
+            println!(
+                "{:<32} {:>12} {:>16} {:>10.1} {:>16} {:>12.1} {:>12}",
+                format!("{:?}", col),
+                stats.num_keys,
+                stats.total_key_size,
+                stats.avg_key_size(),
+                stats.total_value_size,
+                stats.avg_value_size(),
+                stats.max_value_size,
+            );
+        }
+        for (col, stats) in stats.iter().filter(|(_, stats)| !stats.refcounts.is_empty()) {
+            println!("\nRefcount distribution of {:?}:", col);
+            for (bucket, count) in stats.refcounts.iter() {
+                if *bucket <= 0 {
+                    println!("{:>24} {:>12}", format!("{} (invalid)", bucket), count);
+                } else {
+                    println!("{:>24} {:>12}", format!("{}..{}", bucket, bucket * 2), count);
+                }
+            }
+        }
+    }
+}
+
+impl DbDumpCmd {
+    fn run(self, store: &Store) {
+        let decode_key = |key: &str| {
+            from_base(key).unwrap_or_else(|err| panic!("Failed to decode key `{}`: {}", key, err))
+        };
+        if let Some(key) = self.key.as_deref() {
+            let key = decode_key(key);
+            match store.get(self.column, &key).expect("Failed to read the database") {
+                Some(value) => print_record(self.column, &key, &value),
+                None => println!("{:?} has no record with key {}", self.column, to_base(&key)),
+            }
+            return;
+        }
+        let prefix = self.prefix.as_deref().map(decode_key).unwrap_or_default();
+        for (key, value) in store.iter_prefix(self.column, &prefix).take(self.limit) {
+            print_record(self.column, &key, &value);
+        }
+    }
+}
+
+fn print_record(col: DBCol, key: &[u8], value: &[u8]) {
+    println!(
+        "{:?} {} ({} bytes):\n{}\n",
+        col,
+        to_base(key),
+        value.len(),
+        format_value(col, key, value)
+    );
+}
+
+/// Keys of `ColBlockMisc` which store a `Tip`.
+const TIP_KEYS: [&[u8]; 3] = [HEAD_KEY, HEADER_HEAD_KEY, FINAL_HEAD_KEY];
+/// Keys of `ColBlockMisc` which store a `BlockHeight`.
+const HEIGHT_KEYS: [&[u8]; 4] =
+    [TAIL_KEY, CHUNK_TAIL_KEY, FORK_TAIL_KEY, LARGEST_TARGET_HEIGHT_KEY];
+
+/// Pretty prints values of the columns with known types, falls back to base58 otherwise.
+fn format_value(col: DBCol, key: &[u8], value: &[u8]) -> String {
+    fn decode<T: BorshDeserialize + Debug>(value: &[u8]) -> String {
+        match T::try_from_slice(value) {
+            Ok(value) => format!("{:#?}", value),
+            Err(err) => format!("failed to decode: {}, raw value: {}", err, to_base(value)),
+        }
+    }
+    match col {
+        DBCol::ColDbVersion => String::from_utf8_lossy(value).into_owned(),
+        DBCol::ColBlockMisc if TIP_KEYS.contains(&key) => decode::<Tip>(value),
+        DBCol::ColBlockMisc if HEIGHT_KEYS.contains(&key) => decode::<BlockHeight>(value),
+        DBCol::ColBlock => decode::<Block>(value),
+        DBCol::ColBlockHeader => decode::<BlockHeader>(value),
+        DBCol::ColBlockHeight | DBCol::ColNextBlockHashes | DBCol::ColBlockPerHeight
+            if value.len() == 32 =>
+        {
+            decode::<CryptoHash>(value)
+        }
+        DBCol::ColChunkExtra => decode::<ChunkExtra>(value),
+        DBCol::ColEpochInfo if key != AGGREGATOR_KEY => decode::<EpochInfo>(value),
+        DBCol::ColBlockInfo => decode::<BlockInfo>(value),
+        DBCol::ColChunks => decode::<ShardChunk>(value),
+        DBCol::ColPartialChunks => decode::<PartialEncodedChunk>(value),
+        DBCol::ColTransactions => decode::<SignedTransaction>(value),
+        DBCol::ColReceipts => decode::<Receipt>(value),
+        DBCol::ColTransactionResult => decode::<Vec<ExecutionOutcomeWithIdAndProof>>(value),
+        DBCol::ColOutcomeIds => decode::<Vec<CryptoHash>>(value),
+        DBCol::ColEpochLightClientBlocks => decode::<LightClientBlockView>(value),
+        _ => to_base(value),
+    }
+}
+
+#[derive(Clap)]
+pub(super) struct RestoreCmd {
+    /// Snapshot directory, e.g. `~/.near/snapshots/snapshot-1000`.
+    #[clap(long, parse(from_os_str))]
+    snapshot: PathBuf,
+}
+
+impl RestoreCmd {
+    pub(super) fn run(self, home_dir: &Path) {
+        let store_path = get_store_path(home_dir);
+        if store_path.exists() {
+            error!(target: "neard", "{} already exists, run `neard unsafe_reset_data` first", store_path.display());
+            std::process::exit(1);
+        }
+        // Restoring needs neither the validator key nor the signing history.
+        let (config, genesis) = nearcore::config::load_config_without_keys(home_dir);
+        let info = SnapshotInfo::read(&self.snapshot)
+            .unwrap_or_else(|err| panic!("Failed to read the snapshot: {}", err));
+        let store = open_read_only_store(&self.snapshot.join(SNAPSHOT_DATA_DIR), &config.store)
+            .unwrap_or_else(|err| panic!("Failed to open the snapshot: {}", err));
+        if let Err(err) = validate_snapshot(&store, &info, &genesis.json_hash()) {
+            error!(target: "neard", "Can't restore from {}: {}", self.snapshot.display(), err);
+            std::process::exit(1);
+        }
+        drop(store);
+        restore_snapshot(&self.snapshot, &store_path)
+            .unwrap_or_else(|err| panic!("Failed to restore the snapshot: {}", err));
+        info!(target: "neard", "Restored {} at #{} {}", store_path.display(), info.head_height, info.head_hash);
+    }
+}
+
+#[derive(Clap)]
+pub(super) struct SigningHistoryCmd {
+    #[clap(subcommand)]
+    subcmd: SigningHistorySubCommand,
+}
+
+#[derive(Clap)]
+enum SigningHistorySubCommand {
+    /// Writes the signing history of the node to a file
+    #[clap(name = "export")]
+    Export(SigningHistoryExportCmd),
+    /// Merges a signing history exported from another node into the history of this node. Only
+    /// run it while the node is stopped
+    #[clap(name = "import")]
+    Import(SigningHistoryImportCmd),
+}
+
+#[derive(Clap)]
+struct SigningHistoryExportCmd {
+    #[clap(long, parse(from_os_str))]
+    output: PathBuf,
+}
+
+#[derive(Clap)]
+struct SigningHistoryImportCmd {
+    #[clap(long, parse(from_os_str))]
+    input: PathBuf,
+}
+
+impl SigningHistoryCmd {
+    pub(super) fn run(self, home_dir: &Path) {
+        let config = Config::from_file(&home_dir.join(CONFIG_FILENAME));
+        let history_path = match &config.signing_history_file {
+            Some(signing_history_file) => home_dir.join(signing_history_file),
+            None => {
+                error!(target: "neard", "Double sign protection is disabled, `signing_history_file` is not set in the config");
+                std::process::exit(1);
+            }
+        };
+        let mut history = SigningHistory::from_file_or_default(&history_path)
+            .unwrap_or_else(|err| panic!("Failed to read {}: {}", history_path.display(), err));
+        match self.subcmd {
+            SigningHistorySubCommand::Export(cmd) => {
+                history.write_to_file(&cmd.output).unwrap_or_else(|err| {
+                    panic!("Failed to write {}: {}", cmd.output.display(), err)
+                });
+                info!(target: "neard", "Exported the signing history to {}", cmd.output.display());
+            }
+            SigningHistorySubCommand::Import(cmd) => {
+                let imported = SigningHistory::from_file(&cmd.input).unwrap_or_else(|err| {
+                    panic!("Failed to read {}: {}", cmd.input.display(), err)
+                });
+                history.merge(&imported);
+                history.write_to_file(&history_path).unwrap_or_else(|err| {
+                    panic!("Failed to write {}: {}", history_path.display(), err)
+                });
+                info!(target: "neard", "Imported the signing history from {}", cmd.input.display());
+            }
+        }
+    }
+}
+
 fn init_logging(verbose: Option<&str>) {
     let mut env_filter = EnvFilter::new(
         "tokio_reactor=info,near=info,stats=info,telemetry=info,delay_detector=info,\
@@ -392,6 +681,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn db_dump_args() {
+        let cmd = NeardCmd::parse_from(&["test", "db", "dump", "--column=State", "--limit=3"]);
+        if let NeardSubCommand::Db(DbCmd { subcmd: DbSubCommand::Dump(scmd) }) = cmd.subcmd {
+            assert_eq!(scmd.column, DBCol::ColState);
+            assert_eq!(scmd.limit, 3);
+        } else {
+            panic!("incorrect subcommand");
+        }
+        assert!(NeardCmd::try_parse_from(&["test", "db", "dump", "--column=NoSuchColumn"]).is_err());
+    }
+
     #[test]
     fn equal_no_value_syntax() {
         assert!(NeardCmd::try_parse_from(&[
@@ -7,7 +7,7 @@ use near_primitives::types::{
     AccountId, BlockHeight, EpochHeight, EpochId, EpochInfoProvider, MerkleHash,
 };
 use near_primitives::version::ProtocolVersion;
-use near_primitives::views::ViewStateResult;
+use near_primitives::views::{ViewStatePageResult, ViewStateResult};
 
 /// Adapter for querying runtime.
 pub trait ViewRuntimeAdapter {
@@ -65,4 +65,15 @@ pub trait ViewRuntimeAdapter {
         account_id: &AccountId,
         prefix: &[u8],
     ) -> Result<ViewStateResult, crate::state_viewer::errors::ViewStateError>;
+
+    fn view_state_paginated(
+        &self,
+        shard_uid: &ShardUId,
+        state_root: MerkleHash,
+        account_id: &AccountId,
+        prefix: &[u8],
+        start_key: Option<&[u8]>,
+        limit: u64,
+        include_proof: bool,
+    ) -> Result<ViewStatePageResult, crate::state_viewer::errors::ViewStateError>;
 }
@@ -1,5 +1,5 @@
 use std::cmp::max;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::rc::Rc;
 use std::sync::Arc;
 
@@ -28,6 +28,7 @@ use near_primitives::{
     receipt::{
         ActionReceipt, DataReceipt, DelayedReceiptIndices, Receipt, ReceiptEnum, ReceivedData,
     },
+    shard_layout::{account_id_to_shard_id, ShardLayout},
     state_record::StateRecord,
     transaction::{
         Action, ExecutionOutcome, ExecutionOutcomeWithId, ExecutionStatus, LogEntry,
@@ -125,6 +126,17 @@ pub struct ApplyResult {
     pub proof: Option<PartialStorage>,
 }
 
+/// Result of [`Runtime::simulate_transaction`].
+pub struct SimulationResult {
+    /// Outcomes of the transaction and of the executed receipts, in the execution order.
+    pub outcomes: Vec<ExecutionOutcomeWithId>,
+    /// Executed receipts in the execution order, including data receipts without outcomes.
+    pub receipts: Vec<Receipt>,
+    /// Receipts to the shards without state, which were not executed.
+    pub unprocessed_receipts: Vec<Receipt>,
+    pub state_changes: Vec<RawStateChangesWithTrieKey>,
+}
+
 #[derive(Debug)]
 pub struct ActionResult {
     pub gas_burnt: Gas,
@@ -1403,6 +1415,70 @@ impl Runtime {
         })
     }
 
+    /// Executes the transaction and then all the receipts it produces one by one, as if each of
+    /// them was included in the next block, until no receipts are left.
+    ///
+    /// `state_updates` hold the state of the shards of `shard_layout` to execute the receipts on,
+    /// it must include the shard of the transaction signer. Receipts to other shards are returned
+    /// unprocessed. The gas limit and the delayed receipts are ignored, and nothing is persisted.
+    pub fn simulate_transaction(
+        &self,
+        mut state_updates: BTreeMap<ShardId, TrieUpdate>,
+        shard_layout: &ShardLayout,
+        apply_state: &ApplyState,
+        signed_transaction: &SignedTransaction,
+        epoch_info_provider: &dyn EpochInfoProvider,
+    ) -> Result<SimulationResult, RuntimeError> {
+        let _span =
+            tracing::debug_span!(target: "runtime", "Runtime::simulate_transaction").entered();
+
+        let signer_shard_id =
+            account_id_to_shard_id(&signed_transaction.transaction.signer_id, shard_layout);
+        let state_update = state_updates
+            .get_mut(&signer_shard_id)
+            .expect("state of the signer shard must be provided");
+        let mut stats = ApplyStats::default();
+        let (receipt, outcome_with_id) =
+            self.process_transaction(state_update, apply_state, signed_transaction, &mut stats)?;
+
+        let mut outcomes = vec![outcome_with_id];
+        let mut receipts = vec![];
+        let mut unprocessed_receipts = vec![];
+        let mut validator_proposals = vec![];
+        let mut pending_receipts = VecDeque::from(vec![receipt]);
+        while let Some(receipt) = pending_receipts.pop_front() {
+            let shard_id = account_id_to_shard_id(&receipt.receiver_id, shard_layout);
+            let state_update = match state_updates.get_mut(&shard_id) {
+                Some(state_update) => state_update,
+                None => {
+                    unprocessed_receipts.push(receipt);
+                    continue;
+                }
+            };
+            let mut new_receipts = vec![];
+            if let Some(outcome_with_id) = self.process_receipt(
+                state_update,
+                apply_state,
+                &receipt,
+                &mut new_receipts,
+                &mut validator_proposals,
+                &mut stats,
+                epoch_info_provider,
+            )? {
+                outcomes.push(outcome_with_id);
+            }
+            receipts.push(receipt);
+            pending_receipts.extend(new_receipts);
+        }
+
+        let mut state_changes = vec![];
+        for (_, state_update) in state_updates {
+            let (_, shard_state_changes) = state_update.finalize()?;
+            state_changes.extend(shard_state_changes);
+        }
+        Ok(SimulationResult { outcomes, receipts, unprocessed_receipts, state_changes })
+    }
+
     // Adds the given receipt into the end of the delayed receipt queue in the state.
     pub fn delay_receipt(
         state_update: &mut TrieUpdate,
@@ -2417,4 +2493,75 @@ mod tests {
             .expect("Compiled contract should be cached")
             .expect("Compilation result should be non-empty");
     }
+
+    #[test]
+    fn test_simulate_transaction() {
+        let initial_balance = to_yocto(1_000_000);
+        let (runtime, tries, root, apply_state, signer, epoch_info_provider) =
+            setup_runtime(initial_balance, to_yocto(500_000), 10u64.pow(15));
+
+        let transaction = SignedTransaction::send_money(
+            1,
+            alice_account(),
+            alice_account(),
+            &*signer,
+            to_yocto(10_000),
+            CryptoHash::default(),
+        );
+        let mut state_updates = BTreeMap::new();
+        state_updates.insert(0, tries.new_trie_update(ShardUId::default(), root));
+        let result = runtime
+            .simulate_transaction(
+                state_updates,
+                &ShardLayout::v0(1, 0),
+                &apply_state,
+                &transaction,
+                &epoch_info_provider,
+            )
+            .unwrap();
+
+        assert_eq!(result.outcomes[0].id, transaction.get_hash());
+        assert_eq!(result.outcomes[1].id, result.receipts[0].receipt_id);
+        assert_eq!(result.outcomes.len(), result.receipts.len() + 1);
+        assert!(result.unprocessed_receipts.is_empty());
+        assert!(!result.state_changes.is_empty());
+
+        // Nothing is persisted.
+        let state_update = tries.new_trie_update(ShardUId::default(), root);
+        let account = get_account(&state_update, &alice_account()).unwrap().unwrap();
+        assert_eq!(account.amount(), initial_balance);
+    }
+
+    #[test]
+    fn test_simulate_transaction_to_other_shard() {
+        let (runtime, tries, root, apply_state, signer, epoch_info_provider) =
+            setup_runtime(to_yocto(1_000_000), to_yocto(500_000), 10u64.pow(15));
+
+        let transaction = SignedTransaction::send_money(
+            1,
+            alice_account(),
+            bob_account(),
+            &*signer,
+            to_yocto(10_000),
+            CryptoHash::default(),
+        );
+        // Alice is on shard 0 and bob is on shard 1.
+        let shard_layout = ShardLayout::v1(vec![], vec!["b".parse().unwrap()], None, 1);
+        let mut state_updates = BTreeMap::new();
+        state_updates.insert(0, tries.new_trie_update(ShardUId::default(), root));
+        let result = runtime
+            .simulate_transaction(
+                state_updates,
+                &shard_layout,
+                &apply_state,
+                &transaction,
+                &epoch_info_provider,
+            )
+            .unwrap();
+
+        assert_eq!(result.outcomes.len(), 1);
+        assert!(result.receipts.is_empty());
+        assert_eq!(result.unprocessed_receipts.len(), 1);
+        assert_eq!(result.unprocessed_receipts[0].receiver_id, bob_account());
+    }
 }
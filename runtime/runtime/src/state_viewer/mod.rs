@@ -18,7 +18,7 @@ use near_primitives::{
     transaction::FunctionCallAction,
     trie_key::trie_key_parsers,
     types::{AccountId, EpochInfoProvider, Gas},
-    views::{StateItem, ViewApplyState, ViewStateResult},
+    views::{StateItem, ViewApplyState, ViewStatePageResult, ViewStateResult},
 };
 use near_store::{get_access_key, get_account, get_code, TrieUpdate};
 use near_vm_logic::{ReturnData, ViewConfig};
@@ -26,6 +26,9 @@ use std::{str, sync::Arc, time::Instant};
 
 pub mod errors;
 
+/// Maximum number of items returned by [`TrieViewer::view_state_paginated`].
+pub const MAX_VIEW_STATE_PAGE_SIZE: u64 = 1000;
+
 pub struct TrieViewer {
     /// Upper bound of the byte size of contract state that is still viewable. None is no limit
     state_size_limit: Option<u64>,
@@ -158,6 +161,62 @@ impl TrieViewer {
         Ok(ViewStateResult { values, proof: vec![] })
     }
 
+    /// Returns up to `limit` items of the contract state with keys starting with `prefix`, from
+    /// `start_key` on. A `limit` of 0 is treated as 1. Unlike [`TrieViewer::view_state`], this is
+    /// not subject to the state size limit, since the size of the response is bounded by
+    /// [`MAX_VIEW_STATE_PAGE_SIZE`].
+    pub fn view_state_paginated(
+        &self,
+        state_update: &TrieUpdate,
+        account_id: &AccountId,
+        prefix: &[u8],
+        start_key: Option<&[u8]>,
+        limit: u64,
+        include_proof: bool,
+    ) -> Result<ViewStatePageResult, errors::ViewStateError> {
+        if get_account(state_update, account_id)?.is_none() {
+            return Err(errors::ViewStateError::AccountDoesNotExist {
+                requested_account_id: account_id.clone(),
+            });
+        }
+
+        // A page always has an item if there is one left, so that paging through the state ends.
+        let limit = limit.clamp(1, MAX_VIEW_STATE_PAGE_SIZE) as usize;
+        let query = trie_key_parsers::get_raw_prefix_for_contract_data(account_id, prefix);
+        let acc_sep_len = query.len() - prefix.len();
+        let root = state_update.get_root();
+        let mut iter = state_update.trie.iter(&root)?;
+        match start_key {
+            Some(start_key) if start_key > prefix => iter
+                .seek(trie_key_parsers::get_raw_prefix_for_contract_data(account_id, start_key))?,
+            _ => iter.seek(&query)?,
+        }
+        let mut values = vec![];
+        let mut next_key = None;
+        for item in iter {
+            let (key, value) = item?;
+            if !key.starts_with(&query) {
+                break;
+            }
+            if values.len() == limit {
+                next_key = Some(to_base64(&key[acc_sep_len..]));
+                break;
+            }
+            let proof = if include_proof {
+                let proof = state_update.trie.get_proof(&root, &key)?.unwrap_or_default();
+                proof.iter().map(to_base64).collect()
+            } else {
+                vec![]
+            };
+            values.push(StateItem {
+                key: to_base64(&key[acc_sep_len..]),
+                value: to_base64(&value),
+                proof,
+            });
+        }
+        Ok(ViewStatePageResult { values, next_key, state_root: root })
+    }
+
     pub fn call_function(
         &self,
         mut state_update: TrieUpdate,
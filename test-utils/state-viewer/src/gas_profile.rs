@@ -0,0 +1,329 @@
+//! Aggregation of the gas profiles recorded in the execution outcomes of a range of blocks, used
+//! to find the contracts and the methods that dominate the gas usage.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use serde_json::{json, Value};
+
+use near_chain::{ChainStore, ChainStoreAccess, Error};
+use near_primitives::hash::CryptoHash;
+use near_primitives::profile::{Cost, ProfileData};
+use near_primitives::receipt::{Receipt, ReceiptEnum};
+use near_primitives::transaction::{Action, ExecutionMetadata, ExecutionOutcome};
+use near_primitives::types::{AccountId, BlockHeight, Gas, ShardId};
+
+/// Method name of the receipts which are not in the store anymore.
+const UNKNOWN_METHOD: &str = "<unknown>";
+
+/// Gas used by the receipts to one account calling the same method.
+#[derive(Default)]
+pub struct MethodGasProfile {
+    pub num_receipts: u64,
+    pub gas_burnt: Gas,
+    /// Sum of the profiles of the receipts. Outcomes without a profile only add to `gas_burnt`.
+    pub profile: ProfileData,
+}
+
+/// One of the most expensive receipts.
+pub struct ReceiptGas {
+    pub receipt_id: CryptoHash,
+    pub block_height: BlockHeight,
+    pub receiver_id: AccountId,
+    pub method_name: String,
+    pub gas_burnt: Gas,
+}
+
+pub struct GasProfileReport {
+    /// Gas usage by receiver account and method name. Method name is empty for the receipts
+    /// without function calls, and the names are joined with commas if there are several.
+    pub methods: HashMap<(AccountId, String), MethodGasProfile>,
+    /// The most expensive receipts, from the most expensive one.
+    pub top_receipts: Vec<ReceiptGas>,
+    top_n: usize,
+}
+
+impl GasProfileReport {
+    pub fn new(top_n: usize) -> Self {
+        Self { methods: HashMap::new(), top_receipts: vec![], top_n }
+    }
+
+    pub fn add_outcome(
+        &mut self,
+        block_height: BlockHeight,
+        receipt_id: CryptoHash,
+        method_name: String,
+        outcome: &ExecutionOutcome,
+    ) {
+        let method =
+            self.methods.entry((outcome.executor_id.clone(), method_name.clone())).or_default();
+        method.num_receipts += 1;
+        method.gas_burnt += outcome.gas_burnt;
+        if let ExecutionMetadata::V2(profile) = &outcome.metadata {
+            method.profile.merge(profile);
+        }
+
+        let position =
+            self.top_receipts.partition_point(|receipt| receipt.gas_burnt >= outcome.gas_burnt);
+        if position < self.top_n {
+            self.top_receipts.insert(
+                position,
+                ReceiptGas {
+                    receipt_id,
+                    block_height,
+                    receiver_id: outcome.executor_id.clone(),
+                    method_name,
+                    gas_burnt: outcome.gas_burnt,
+                },
+            );
+            self.top_receipts.truncate(self.top_n);
+        }
+    }
+
+    /// Returns the gas burnt by the receipts to every account, from the largest.
+    pub fn accounts(&self) -> Vec<(&AccountId, Gas)> {
+        let mut accounts: HashMap<&AccountId, Gas> = HashMap::new();
+        for ((receiver_id, _), method) in &self.methods {
+            *accounts.entry(receiver_id).or_default() += method.gas_burnt;
+        }
+        let mut accounts: Vec<_> = accounts.into_iter().collect();
+        accounts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        accounts
+    }
+
+    /// Returns the methods from the one that burnt the most gas.
+    pub fn sorted_methods(&self) -> Vec<(&AccountId, &str, &MethodGasProfile)> {
+        let mut methods: Vec<_> = self
+            .methods
+            .iter()
+            .map(|((receiver_id, method_name), method)| (receiver_id, method_name.as_str(), method))
+            .collect();
+        methods.sort_by(|a, b| {
+            b.2.gas_burnt.cmp(&a.2.gas_burnt).then_with(|| (a.0, a.1).cmp(&(b.0, b.1)))
+        });
+        methods
+    }
+
+    /// Writes a row per method, with a column per cost.
+    pub fn write_methods_csv(&self, out: &mut dyn Write) -> io::Result<()> {
+        write!(out, "receiver_id,method_name,num_receipts,gas_burnt")?;
+        for cost in Cost::ALL {
+            write!(out, ",{}", cost_name(*cost))?;
+        }
+        writeln!(out)?;
+        for (receiver_id, method_name, method) in self.sorted_methods() {
+            write!(
+                out,
+                "{},{},{},{}",
+                receiver_id,
+                csv_field(method_name),
+                method.num_receipts,
+                method.gas_burnt
+            )?;
+            for cost in Cost::ALL {
+                write!(out, ",{}", method.profile[*cost])?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_receipts_csv(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "receipt_id,block_height,receiver_id,method_name,gas_burnt")?;
+        for receipt in &self.top_receipts {
+            writeln!(
+                out,
+                "{},{},{},{},{}",
+                receipt.receipt_id,
+                receipt.block_height,
+                receipt.receiver_id,
+                csv_field(&receipt.method_name),
+                receipt.gas_burnt
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns the report in the format of `gas_profile` of the RPC execution outcomes.
+    pub fn to_json(&self) -> Value {
+        let accounts: Vec<_> = self
+            .accounts()
+            .into_iter()
+            .map(|(receiver_id, gas_burnt)| {
+                json!({"receiver_id": receiver_id, "gas_burnt": gas_burnt.to_string()})
+            })
+            .collect();
+        let methods: Vec<_> = self
+            .sorted_methods()
+            .into_iter()
+            .map(|(receiver_id, method_name, method)| {
+                let gas_profile: Vec<_> = Cost::ALL
+                    .iter()
+                    .filter(|&cost| method.profile[*cost] > 0)
+                    .map(|&cost| {
+                        json!({
+                            "cost_category": cost_category(cost),
+                            "cost": cost_name(cost),
+                            "gas_used": method.profile[cost].to_string(),
+                        })
+                    })
+                    .collect();
+                json!({
+                    "receiver_id": receiver_id,
+                    "method_name": method_name,
+                    "num_receipts": method.num_receipts,
+                    "gas_burnt": method.gas_burnt.to_string(),
+                    "gas_profile": gas_profile,
+                })
+            })
+            .collect();
+        let top_receipts: Vec<_> = self
+            .top_receipts
+            .iter()
+            .map(|receipt| {
+                json!({
+                    "receipt_id": receipt.receipt_id,
+                    "block_height": receipt.block_height,
+                    "receiver_id": receipt.receiver_id,
+                    "method_name": receipt.method_name,
+                    "gas_burnt": receipt.gas_burnt.to_string(),
+                })
+            })
+            .collect();
+        json!({"accounts": accounts, "methods": methods, "top_receipts": top_receipts})
+    }
+}
+
+/// Collects the gas profiles of the receipts executed in the blocks `start_height..=end_height`.
+/// Outcomes are garbage collected on the nodes which are not archival, so only the recent blocks
+/// are accounted there.
+pub fn collect_gas_profiles(
+    chain_store: &mut ChainStore,
+    start_height: BlockHeight,
+    end_height: BlockHeight,
+    top_n: usize,
+) -> Result<GasProfileReport, Error> {
+    let mut report = GasProfileReport::new(top_n);
+    for height in start_height..=end_height {
+        let block_hash = match chain_store.get_block_hash_by_height(height) {
+            Ok(block_hash) => block_hash,
+            Err(_) => continue,
+        };
+        let num_shards = chain_store.get_block(&block_hash)?.chunks().len() as ShardId;
+        for shard_id in 0..num_shards {
+            let outcome_ids =
+                chain_store.get_outcomes_by_block_hash_and_shard_id(&block_hash, shard_id)?;
+            for outcome_id in outcome_ids {
+                // Converting transactions to receipts doesn't run any contract code.
+                if chain_store.get_transaction(&outcome_id)?.is_some() {
+                    continue;
+                }
+                let method_name = match chain_store.get_receipt(&outcome_id)? {
+                    Some(receipt) => receipt_method_name(receipt),
+                    None => UNKNOWN_METHOD.to_string(),
+                };
+                let outcome = chain_store
+                    .get_outcomes_by_id(&outcome_id)?
+                    .into_iter()
+                    .find(|outcome| outcome.block_hash == block_hash);
+                if let Some(outcome) = outcome {
+                    report.add_outcome(
+                        height,
+                        outcome_id,
+                        method_name,
+                        &outcome.outcome_with_id.outcome,
+                    );
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn receipt_method_name(receipt: &Receipt) -> String {
+    match &receipt.receipt {
+        ReceiptEnum::Action(action_receipt) => action_receipt
+            .actions
+            .iter()
+            .filter_map(|action| match action {
+                Action::FunctionCall(function_call) => Some(function_call.method_name.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(","),
+        ReceiptEnum::Data(_) => String::new(),
+    }
+}
+
+fn cost_category(cost: Cost) -> &'static str {
+    match cost {
+        Cost::ActionCost { .. } => "ACTION_COST",
+        Cost::ExtCost { .. } => "WASM_HOST_COST",
+    }
+}
+
+fn cost_name(cost: Cost) -> String {
+    match cost {
+        Cost::ActionCost { action_cost_kind } => format!("{:?}", action_cost_kind),
+        Cost::ExtCost { ext_cost_kind } => format!("{:?}", ext_cost_kind),
+    }
+    .to_ascii_uppercase()
+}
+
+/// Quotes the field if it contains characters special to CSV.
+fn csv_field(field: &str) -> String {
+    if field.contains(|c: char| c == ',' || c == '"' || c == '\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_primitives::config::ExtCosts;
+    use near_primitives::hash::hash;
+    use near_primitives::profile::ProfileData;
+    use near_primitives::transaction::{ExecutionMetadata, ExecutionOutcome};
+
+    use super::{csv_field, GasProfileReport};
+
+    fn outcome(receiver_id: &str, gas_burnt: u64, wasm_gas: u64) -> ExecutionOutcome {
+        let mut profile = ProfileData::new();
+        profile.add_ext_cost(ExtCosts::base, wasm_gas);
+        ExecutionOutcome {
+            gas_burnt,
+            executor_id: receiver_id.parse().unwrap(),
+            metadata: ExecutionMetadata::V2(profile),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_gas_profile_report() {
+        let mut report = GasProfileReport::new(2);
+        report.add_outcome(1, hash(&[1]), "a".to_string(), &outcome("alice", 10, 1));
+        report.add_outcome(1, hash(&[2]), "b".to_string(), &outcome("alice", 30, 2));
+        report.add_outcome(2, hash(&[3]), "a".to_string(), &outcome("alice", 20, 3));
+        report.add_outcome(2, hash(&[4]), "a".to_string(), &outcome("bob", 5, 4));
+
+        let method = &report.methods[&("alice".parse().unwrap(), "a".to_string())];
+        assert_eq!(method.num_receipts, 2);
+        assert_eq!(method.gas_burnt, 30);
+        assert_eq!(method.profile.get_ext_cost(ExtCosts::base), 4);
+
+        let accounts: Vec<_> =
+            report.accounts().into_iter().map(|(id, gas)| (id.to_string(), gas)).collect();
+        assert_eq!(accounts, vec![("alice".to_string(), 60), ("bob".to_string(), 5)]);
+
+        let top: Vec<_> = report.top_receipts.iter().map(|receipt| receipt.gas_burnt).collect();
+        assert_eq!(top, vec![30, 20]);
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("transfer"), "transfer");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("a\"b"), "\"a\"\"b\"");
+    }
+}
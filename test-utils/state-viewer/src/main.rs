@@ -7,14 +7,17 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use ansi_term::Color::Red;
-use clap::{App, AppSettings, Arg, SubCommand};
+use clap::{value_t_or_exit, App, AppSettings, Arg, SubCommand};
 use tracing::info;
 
 use borsh::BorshSerialize;
 use near_chain::chain::collect_receipts_from_response;
 use near_chain::migrations::check_if_block_is_first_with_chunk_of_version;
 use near_chain::types::{ApplyTransactionResult, BlockHeaderInfo};
-use near_chain::{ChainStore, ChainStoreAccess, ChainStoreUpdate, RuntimeAdapter};
+use near_chain::{
+    Chain, ChainGenesis, ChainStore, ChainStoreAccess, ChainStoreUpdate, DoomslugThresholdMode,
+    RuntimeAdapter,
+};
 use near_epoch_manager::EpochManager;
 use near_logger_utils::init_integration_logger;
 use near_network::peer_store::PeerStore;
@@ -33,6 +36,7 @@ use nearcore::{get_default_home, get_store_path, load_config, NearConfig, Nights
 use node_runtime::adapter::ViewRuntimeAdapter;
 use state_dump::state_dump;
 
+mod gas_profile;
 mod state_dump;
 
 #[allow(unused)]
@@ -644,6 +648,110 @@ fn check_block_chunk_existence(store: Arc<Store>, near_config: &NearConfig) {
     println!("Block check succeed");
 }
 
+fn report_gas_profile(
+    store: Arc<Store>,
+    near_config: &NearConfig,
+    start_height: Option<BlockHeight>,
+    end_height: Option<BlockHeight>,
+    top_n: usize,
+    format: &str,
+    output: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut chain_store = ChainStore::new(store, near_config.genesis.config.genesis_height);
+    let end_height = match end_height {
+        Some(end_height) => end_height,
+        None => chain_store.head()?.height,
+    };
+    let start_height = match start_height {
+        Some(start_height) => start_height,
+        None => chain_store.tail()?,
+    };
+    println!("Collecting gas profiles in the range {}..={}", start_height, end_height);
+    let report =
+        gas_profile::collect_gas_profiles(&mut chain_store, start_height, end_height, top_n)?;
+
+    println!("Gas burnt by account:");
+    for (receiver_id, gas_burnt) in report.accounts().into_iter().take(top_n) {
+        println!("{:>30} {}", gas_burnt, receiver_id);
+    }
+    println!("Gas burnt by method:");
+    for (receiver_id, method_name, method) in report.sorted_methods().into_iter().take(top_n) {
+        println!(
+            "{:>30} {} {} ({} receipts)",
+            method.gas_burnt, receiver_id, method_name, method.num_receipts
+        );
+    }
+    println!("Most expensive receipts:");
+    for receipt in &report.top_receipts {
+        println!(
+            "{:>30} {} #{} {} {}",
+            receipt.gas_burnt,
+            receipt.receipt_id,
+            receipt.block_height,
+            receipt.receiver_id,
+            receipt.method_name
+        );
+    }
+
+    match format {
+        "csv" => {
+            let methods_path = format!("{}.methods.csv", output);
+            report.write_methods_csv(&mut File::create(&methods_path)?)?;
+            let receipts_path = format!("{}.receipts.csv", output);
+            report.write_receipts_csv(&mut File::create(&receipts_path)?)?;
+            println!("Saved the report into {} and {}", methods_path, receipts_path);
+        }
+        "json" => {
+            let path = format!("{}.json", output);
+            serde_json::to_writer_pretty(File::create(&path)?, &report.to_json())?;
+            println!("Saved the report into {}", path);
+        }
+        _ => unreachable!("the format is validated by the argument parser"),
+    }
+    Ok(())
+}
+
+/// Dumps the state parts for state sync at `sync_hash`, by default the first block of the epoch of
+/// the head, so that other nodes can state sync from them with `state_sync_parts_dir`.
+fn dump_state_parts(
+    store: Arc<Store>,
+    home_dir: &Path,
+    near_config: &NearConfig,
+    sync_hash: Option<CryptoHash>,
+    shard_id: Option<ShardId>,
+    output: &Path,
+) {
+    let runtime = Arc::new(NightshadeRuntime::with_config(
+        &home_dir,
+        store,
+        &near_config,
+        None,
+        near_config.client_config.max_gas_burnt_view,
+    ));
+    let chain_genesis = ChainGenesis::from(&near_config.genesis);
+    let mut chain =
+        Chain::new(runtime.clone(), &chain_genesis, DoomslugThresholdMode::TwoThirds).unwrap();
+    let sync_hash = sync_hash.unwrap_or_else(|| {
+        let head = chain.head().unwrap();
+        let epoch_start_height = runtime.get_epoch_start_height(&head.last_block_hash).unwrap();
+        *chain.get_block_by_height(epoch_start_height).unwrap().hash()
+    });
+    let shard_ids = match shard_id {
+        Some(shard_id) => vec![shard_id],
+        None => (0..chain.get_block(&sync_hash).unwrap().chunks().len() as ShardId).collect(),
+    };
+    for shard_id in shard_ids {
+        let num_parts = chain.dump_state_parts(output, shard_id, sync_hash).unwrap();
+        println!(
+            "Dumped {} state parts of shard {} at {} into {}",
+            num_parts,
+            shard_id,
+            sync_hash,
+            near_chain::state_dump::shard_dump_dir(output, &sync_hash, shard_id).display()
+        );
+    }
+}
+
 fn dump_code(account: &str, contract_code: ContractCode, output: &str) {
     let mut file = File::create(output).unwrap();
     file.write_all(contract_code.code()).unwrap();
@@ -747,6 +855,69 @@ fn main() {
                 )
                 .help("apply blocks at a range of heights for a single shard"),
         )
+        .subcommand(
+            SubCommand::with_name("gas_profile")
+                .arg(
+                    Arg::with_name("start_index")
+                        .long("start_index")
+                        .required(false)
+                        .help("Start index of query")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("end_index")
+                        .long("end_index")
+                        .required(false)
+                        .help("End index of query")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("top")
+                        .long("top")
+                        .help("Number of the most expensive accounts, methods and receipts to report")
+                        .default_value("20")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .help("Format of the report")
+                        .possible_values(&["csv", "json"])
+                        .default_value("csv")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .help("Path of the report without the extension")
+                        .default_value("gas_profile")
+                        .takes_value(true),
+                )
+                .help("aggregate gas profiles of the receipts in a range of heights by account and method"),
+        )
+        .subcommand(
+            SubCommand::with_name("dump_state_parts")
+                .arg(
+                    Arg::with_name("sync_hash")
+                        .long("sync_hash")
+                        .help("Hash of the first block of an epoch, by default of the epoch of the head")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("shard_id")
+                        .long("shard_id")
+                        .help("Id of the shard to dump, by default all the shards")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .help("Directory to dump the state parts into")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .help("dump state sync parts to state sync other nodes from a directory"),
+        )
         .subcommand(
             SubCommand::with_name("apply")
                 .arg(
@@ -929,6 +1100,33 @@ fn main() {
                 progress,
             );
         }
+        ("gas_profile", Some(args)) => {
+            let start_index =
+                args.value_of("start_index").map(|_| value_t_or_exit!(args, "start_index", u64));
+            let end_index =
+                args.value_of("end_index").map(|_| value_t_or_exit!(args, "end_index", u64));
+            let top_n = value_t_or_exit!(args, "top", usize);
+            let format = args.value_of("format").unwrap();
+            let output = args.value_of("output").unwrap();
+            if let Err(err) = report_gas_profile(
+                store,
+                &near_config,
+                start_index,
+                end_index,
+                top_n,
+                format,
+                output,
+            ) {
+                eprintln!("Failed to report the gas profiles: {}", err);
+                std::process::exit(1);
+            }
+        }
+        ("dump_state_parts", Some(args)) => {
+            let sync_hash = args.value_of("sync_hash").map(|s| s.parse::<CryptoHash>().unwrap());
+            let shard_id = args.value_of("shard_id").map(|s| s.parse::<u64>().unwrap());
+            let output = args.value_of("output").map(|s| Path::new(s)).unwrap();
+            dump_state_parts(store, home_dir, &near_config, sync_hash, shard_id, output);
+        }
         ("view_chain", Some(args)) => {
             let height = args.value_of("height").map(|s| s.parse::<u64>().unwrap());
             let view_block = args.is_present("block");
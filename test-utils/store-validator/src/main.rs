@@ -40,6 +40,7 @@ fn main() {
         None,
         None,
         RuntimeConfigStore::new(None),
+        &near_config.store_config.trie_cache,
     ));
 
     let mut store_validator = StoreValidator::new(
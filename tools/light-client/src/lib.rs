@@ -0,0 +1,392 @@
+//! Light client which follows the chain through the light client blocks served by a node, as
+//! described in <https://nomicon.io/ChainSpec/LightClient.html>, and verifies execution outcomes
+//! against the blocks it trusts.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use near_primitives::hash::CryptoHash;
+use near_primitives::light_client::{validate_light_client_block, LightClientBlockError};
+use near_primitives::merkle::{
+    compute_root_from_path, compute_root_from_path_and_item, MerklePath,
+};
+use near_primitives::types::BlockHeight;
+use near_primitives::views::validator_stake_view::ValidatorStakeView;
+use near_primitives::views::{
+    ExecutionOutcomeWithIdView, LightClientBlockLiteView, LightClientBlockView,
+};
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum LightClientError {
+    #[error("Block at height {height} is not newer than the head at height {head_height}")]
+    NotNewer { height: BlockHeight, head_height: BlockHeight },
+    #[error(
+        "Block is in epoch {epoch_id} which is neither the epoch of the head nor the next one"
+    )]
+    UnexpectedEpoch { epoch_id: CryptoHash },
+    #[error("Invalid light client block: {0}")]
+    InvalidBlock(#[from] LightClientBlockError),
+    #[error(
+        "Outcome belongs to block {outcome_block_hash}, but the proof is for block {block_hash}"
+    )]
+    BlockMismatch { outcome_block_hash: CryptoHash, block_hash: CryptoHash },
+    #[error("Outcome has an invalid success value: {0}")]
+    InvalidOutcome(String),
+    #[error("Outcome proof doesn't lead to the outcome root of block {block_hash}")]
+    InvalidOutcomeProof { block_hash: CryptoHash },
+    #[error("Block {block_hash} is not in the block merkle tree of the head")]
+    InvalidBlockProof { block_hash: CryptoHash },
+}
+
+/// Everything the light client trusts. Persisted between runs.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LightClientState {
+    /// The last verified light client block. It always carries the block producers of the next
+    /// epoch.
+    pub head: LightClientBlockView,
+    /// Ordered block producers of the epoch of `head`.
+    pub epoch_block_producers: Vec<ValidatorStakeView>,
+}
+
+impl LightClientState {
+    /// Starts following the chain from `head`. Both `head` and `epoch_block_producers`, the
+    /// block producers of its epoch, must come from a trusted source.
+    pub fn new(
+        head: LightClientBlockView,
+        epoch_block_producers: Vec<ValidatorStakeView>,
+    ) -> Result<Self, LightClientError> {
+        validate_light_client_block(&head, &epoch_block_producers, true)?;
+        Ok(Self { head, epoch_block_producers })
+    }
+
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Writes the state through a temporary file, so that a crash never leaves a partially
+    /// written trusted head behind.
+    pub fn write_to_file(&self, path: &Path) -> io::Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp_path, path)
+    }
+
+    pub fn head_hash(&self) -> CryptoHash {
+        self.head.current_block_hash()
+    }
+
+    /// Verifies `block`, the next light client block served for the head, and makes it the new
+    /// head.
+    pub fn advance(&mut self, block: LightClientBlockView) -> Result<(), LightClientError> {
+        if block.inner_lite.height <= self.head.inner_lite.height {
+            return Err(LightClientError::NotNewer {
+                height: block.inner_lite.height,
+                head_height: self.head.inner_lite.height,
+            });
+        }
+        let epoch_block_producers = if block.inner_lite.epoch_id == self.head.inner_lite.epoch_id {
+            self.epoch_block_producers.clone()
+        } else if block.inner_lite.epoch_id == self.head.inner_lite.next_epoch_id {
+            self.head.next_bps.clone().expect("head always has the next block producers")
+        } else {
+            return Err(LightClientError::UnexpectedEpoch { epoch_id: block.inner_lite.epoch_id });
+        };
+        validate_light_client_block(&block, &epoch_block_producers, true)?;
+        self.head = block;
+        self.epoch_block_producers = epoch_block_producers;
+        Ok(())
+    }
+
+    /// Verifies that `outcome_proof` is included in the block of `block_header_lite` and that
+    /// the block is an ancestor of the head. The arguments are the parts of the
+    /// `light_client_proof` response for the head.
+    pub fn verify_outcome(
+        &self,
+        outcome_proof: &ExecutionOutcomeWithIdView,
+        outcome_root_proof: &MerklePath,
+        block_header_lite: &LightClientBlockLiteView,
+        block_proof: &MerklePath,
+    ) -> Result<(), LightClientError> {
+        let block_hash = block_header_lite.hash();
+        if outcome_proof.block_hash != block_hash {
+            return Err(LightClientError::BlockMismatch {
+                outcome_block_hash: outcome_proof.block_hash,
+                block_hash,
+            });
+        }
+
+        let outcome_hashes = outcome_proof
+            .to_hashes()
+            .map_err(|err| LightClientError::InvalidOutcome(err.to_string()))?;
+        let shard_outcome_root =
+            compute_root_from_path_and_item(&outcome_proof.proof, &outcome_hashes);
+        if compute_root_from_path_and_item(outcome_root_proof, &shard_outcome_root)
+            != block_header_lite.inner_lite.outcome_root
+        {
+            return Err(LightClientError::InvalidOutcomeProof { block_hash });
+        }
+
+        // The block merkle root of the head covers all the blocks before it.
+        if block_hash != self.head_hash()
+            && compute_root_from_path(block_proof, block_hash)
+                != self.head.inner_lite.block_merkle_root
+        {
+            return Err(LightClientError::InvalidBlockProof { block_hash });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use borsh::BorshSerialize;
+    use near_crypto::KeyType;
+    use near_primitives::block_header::ApprovalInner;
+    use near_primitives::hash::{hash, CryptoHash};
+    use near_primitives::light_client::LightClientBlockError;
+    use near_primitives::merkle::{combine_hash, merklize, Direction, MerklePathItem};
+    use near_primitives::types::validator_stake::ValidatorStake;
+    use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
+    use near_primitives::views::validator_stake_view::ValidatorStakeView;
+    use near_primitives::views::{
+        BlockHeaderInnerLiteView, ExecutionOutcomeView, ExecutionOutcomeWithIdView,
+        ExecutionStatusView, LightClientBlockLiteView, LightClientBlockView,
+    };
+
+    use super::{LightClientError, LightClientState};
+
+    fn inner_lite(height: u64, epoch_id: CryptoHash) -> BlockHeaderInnerLiteView {
+        BlockHeaderInnerLiteView {
+            height,
+            epoch_id,
+            next_epoch_id: hash(epoch_id.as_ref()),
+            prev_state_root: CryptoHash::default(),
+            outcome_root: CryptoHash::default(),
+            timestamp: 0,
+            timestamp_nanosec: 0,
+            next_bp_hash: CryptoHash::default(),
+            block_merkle_root: CryptoHash::default(),
+        }
+    }
+
+    fn light_client_block(inner_lite: BlockHeaderInnerLiteView) -> LightClientBlockView {
+        LightClientBlockView {
+            prev_block_hash: hash(&[1]),
+            next_block_inner_hash: hash(&[2]),
+            inner_lite,
+            inner_rest_hash: hash(&[3]),
+            next_bps: Some(vec![]),
+            approvals_after_next: vec![],
+        }
+    }
+
+    fn state(head: LightClientBlockView) -> LightClientState {
+        LightClientState { head, epoch_block_producers: vec![] }
+    }
+
+    fn signers(epoch: u8) -> Vec<InMemoryValidatorSigner> {
+        (0..3)
+            .map(|i| {
+                let account_id = format!("test{}_{}", epoch, i);
+                InMemoryValidatorSigner::from_seed(
+                    account_id.parse().unwrap(),
+                    KeyType::ED25519,
+                    &account_id,
+                )
+            })
+            .collect()
+    }
+
+    fn block_producers(signers: &[InMemoryValidatorSigner]) -> Vec<ValidatorStakeView> {
+        signers
+            .iter()
+            .map(|signer| {
+                ValidatorStake::new(signer.validator_id().clone(), signer.public_key(), 100).into()
+            })
+            .collect()
+    }
+
+    /// Returns a light client block at `height` in `epoch_id`, announcing `next_bps` and
+    /// approved by `signers`.
+    fn signed_light_client_block(
+        height: u64,
+        epoch_id: CryptoHash,
+        next_bps: Vec<ValidatorStakeView>,
+        signers: &[InMemoryValidatorSigner],
+    ) -> LightClientBlockView {
+        let next_bp_stakes: Vec<ValidatorStake> =
+            next_bps.iter().cloned().map(Into::into).collect();
+        let mut inner_lite = inner_lite(height, epoch_id);
+        inner_lite.next_bp_hash = hash(&next_bp_stakes.try_to_vec().unwrap());
+        let mut block =
+            LightClientBlockView { next_bps: Some(next_bps), ..light_client_block(inner_lite) };
+        let approval = ApprovalInner::Endorsement(block.next_block_hash());
+        block.approvals_after_next = signers
+            .iter()
+            .map(|signer| Some(signer.sign_approval(&approval, height + 2).unwrap()))
+            .collect();
+        block
+    }
+
+    #[test]
+    fn test_advance() {
+        let signers: Vec<_> = (0..3).map(signers).collect();
+        // The next epoch id of the test blocks is the hash of the epoch id.
+        let epoch_ids = vec![hash(&[0]), hash(hash(&[0]).as_ref())];
+        let head =
+            signed_light_client_block(10, epoch_ids[0], block_producers(&signers[1]), &signers[0]);
+        let mut state = LightClientState::new(head, block_producers(&signers[0])).unwrap();
+
+        // A block of the same epoch is approved by the same block producers.
+        let block =
+            signed_light_client_block(15, epoch_ids[0], block_producers(&signers[1]), &signers[0]);
+        assert_eq!(state.advance(block.clone()), Ok(()));
+        assert_eq!(state.head, block);
+
+        // A block of the next epoch must be approved by the block producers announced by the head.
+        let block =
+            signed_light_client_block(20, epoch_ids[1], block_producers(&signers[2]), &signers[0]);
+        assert_eq!(
+            state.advance(block),
+            Err(LightClientError::InvalidBlock(LightClientBlockError::InvalidSignature))
+        );
+        let block =
+            signed_light_client_block(20, epoch_ids[1], block_producers(&signers[2]), &signers[1]);
+        let mut truncated_approvals = block.clone();
+        truncated_approvals.approvals_after_next.truncate(2);
+        assert_eq!(
+            state.advance(truncated_approvals),
+            Err(LightClientError::InvalidBlock(LightClientBlockError::InvalidApprovalsCount {
+                approvals: 2,
+                block_producers: 3
+            }))
+        );
+        let mut tampered_block = block.clone();
+        tampered_block.inner_lite.outcome_root = hash(&[12]);
+        assert_eq!(
+            state.advance(tampered_block),
+            Err(LightClientError::InvalidBlock(LightClientBlockError::InvalidSignature))
+        );
+        assert_eq!(state.head.inner_lite.height, 15);
+
+        assert_eq!(state.advance(block.clone()), Ok(()));
+        assert_eq!(state.head, block);
+        assert_eq!(state.epoch_block_producers, block_producers(&signers[1]));
+    }
+
+    #[test]
+    fn test_advance_rejects_unexpected_blocks() {
+        let epoch_id = hash(&[10]);
+        let mut state = state(light_client_block(inner_lite(10, epoch_id)));
+
+        let old_block = light_client_block(inner_lite(10, epoch_id));
+        assert_eq!(
+            state.advance(old_block),
+            Err(LightClientError::NotNewer { height: 10, head_height: 10 })
+        );
+
+        let far_epoch_id = hash(&[20]);
+        let far_block = light_client_block(inner_lite(20, far_epoch_id));
+        assert_eq!(
+            state.advance(far_block),
+            Err(LightClientError::UnexpectedEpoch { epoch_id: far_epoch_id })
+        );
+    }
+
+    #[test]
+    fn test_verify_outcome() {
+        let outcome = ExecutionOutcomeWithIdView {
+            proof: vec![],
+            block_hash: CryptoHash::default(),
+            id: hash(&[4]),
+            outcome: ExecutionOutcomeView {
+                logs: vec!["log".to_string()],
+                receipt_ids: vec![hash(&[5])],
+                gas_burnt: 100,
+                tokens_burnt: 1000,
+                executor_id: "test".parse().unwrap(),
+                status: ExecutionStatusView::SuccessValue(String::new()),
+                metadata: Default::default(),
+            },
+        };
+        let (shard_outcome_root, outcome_paths) =
+            merklize(&[outcome.to_hashes().unwrap(), vec![hash(&[6])]]);
+        let (outcome_root, outcome_root_paths) = merklize(&[shard_outcome_root, hash(&[7])]);
+
+        let mut block_inner_lite = inner_lite(5, hash(&[10]));
+        block_inner_lite.outcome_root = outcome_root;
+        let block_header_lite = LightClientBlockLiteView {
+            prev_block_hash: hash(&[8]),
+            inner_rest_hash: hash(&[9]),
+            inner_lite: block_inner_lite,
+        };
+        let block_hash = block_header_lite.hash();
+        let outcome =
+            ExecutionOutcomeWithIdView { proof: outcome_paths[0].clone(), block_hash, ..outcome };
+
+        let sibling_hash = hash(&[11]);
+        let block_proof = vec![MerklePathItem { hash: sibling_hash, direction: Direction::Right }];
+        let mut head_inner_lite = inner_lite(10, hash(&[10]));
+        head_inner_lite.block_merkle_root = combine_hash(block_hash, sibling_hash);
+        let state = state(light_client_block(head_inner_lite));
+
+        assert_eq!(
+            state.verify_outcome(
+                &outcome,
+                &outcome_root_paths[0],
+                &block_header_lite,
+                &block_proof
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            state.verify_outcome(
+                &outcome,
+                &outcome_root_paths[1],
+                &block_header_lite,
+                &block_proof
+            ),
+            Err(LightClientError::InvalidOutcomeProof { block_hash })
+        );
+        assert_eq!(
+            state.verify_outcome(&outcome, &outcome_root_paths[0], &block_header_lite, &vec![]),
+            Err(LightClientError::InvalidBlockProof { block_hash })
+        );
+        let mut invalid_outcome = outcome.clone();
+        invalid_outcome.outcome.status = ExecutionStatusView::SuccessValue("%".to_string());
+        assert!(matches!(
+            state.verify_outcome(
+                &invalid_outcome,
+                &outcome_root_paths[0],
+                &block_header_lite,
+                &block_proof
+            ),
+            Err(LightClientError::InvalidOutcome(_))
+        ));
+        let mut tampered_outcome = outcome.clone();
+        tampered_outcome.outcome.gas_burnt += 1;
+        assert_eq!(
+            state.verify_outcome(
+                &tampered_outcome,
+                &outcome_root_paths[0],
+                &block_header_lite,
+                &block_proof
+            ),
+            Err(LightClientError::InvalidOutcomeProof { block_hash })
+        );
+    }
+
+    #[test]
+    fn test_state_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("light_client.json");
+        let state = state(light_client_block(inner_lite(10, hash(&[10]))));
+        state.write_to_file(&path).unwrap();
+        assert_eq!(LightClientState::from_file(&path).unwrap(), state);
+    }
+}
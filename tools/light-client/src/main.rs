@@ -0,0 +1,215 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use log::{error, info, LevelFilter};
+
+use near_jsonrpc_client::new_client;
+use near_jsonrpc_primitives::types::light_client::{
+    RpcLightClientExecutionProofRequest, RpcLightClientNextBlockRequest,
+};
+use near_jsonrpc_primitives::types::validator::RpcValidatorsOrderedRequest;
+use near_light_client::LightClientState;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{BlockId, TransactionOrReceiptId};
+
+const DEFAULT_RPC_URL: &str = "http://localhost:3030";
+const DEFAULT_STATE_FILE: &str = "light_client.json";
+const DEFAULT_POLL_PERIOD_SEC: &str = "10";
+
+fn parse_hash(matches: &ArgMatches, name: &str) -> CryptoHash {
+    matches
+        .value_of(name)
+        .map(|s| s.parse().unwrap_or_else(|_| panic!("{} must be a base58 hash", name)))
+        .unwrap()
+}
+
+/// Starts following the chain from the light client block served for `trusted_block_hash`.
+/// The node is trusted to return the right block producers for it.
+async fn init(rpc_url: &str, state_file: &Path, trusted_block_hash: CryptoHash) {
+    let client = new_client(rpc_url);
+    let head = client
+        .next_light_client_block(RpcLightClientNextBlockRequest {
+            last_block_hash: trusted_block_hash,
+        })
+        .await
+        .expect("Failed to get the light client block")
+        .light_client_block
+        .expect("Node has no light client block after the trusted block yet");
+    let epoch_block_producers = client
+        .EXPERIMENTAL_validators_ordered(RpcValidatorsOrderedRequest {
+            block_id: Some(BlockId::Hash(head.current_block_hash())),
+        })
+        .await
+        .expect("Failed to get the block producers");
+    let state = LightClientState::new(head, epoch_block_producers)
+        .expect("Node returned an invalid light client block");
+    state.write_to_file(state_file).expect("Failed to write the light client state");
+    info!("Initialized the light client at height {}", state.head.inner_lite.height);
+}
+
+/// Keeps moving the trusted head forward as the node produces new light client blocks.
+async fn follow(rpc_url: &str, state_file: &Path, poll_period: Duration) {
+    let client = new_client(rpc_url);
+    let mut state =
+        LightClientState::from_file(state_file).expect("Failed to read the light client state");
+    loop {
+        let response = client
+            .next_light_client_block(RpcLightClientNextBlockRequest {
+                last_block_hash: state.head_hash(),
+            })
+            .await;
+        match response.map(|response| response.light_client_block) {
+            Ok(Some(block)) => {
+                let height = block.inner_lite.height;
+                if let Err(err) = state.advance(block) {
+                    error!("Rejected light client block at height {}: {}", height, err);
+                } else {
+                    state
+                        .write_to_file(state_file)
+                        .expect("Failed to write the light client state");
+                    info!("Advanced the head to height {}", height);
+                    continue;
+                }
+            }
+            Ok(None) => {}
+            Err(err) => error!("Failed to get the next light client block: {}", err),
+        }
+        actix::clock::sleep(poll_period).await;
+    }
+}
+
+/// Fetches the outcome of a transaction or receipt with its proof for the trusted head and
+/// verifies it.
+async fn verify(rpc_url: &str, state_file: &Path, id: TransactionOrReceiptId) {
+    let client = new_client(rpc_url);
+    let state =
+        LightClientState::from_file(state_file).expect("Failed to read the light client state");
+    let response = client
+        .light_client_proof(RpcLightClientExecutionProofRequest {
+            id,
+            light_client_head: state.head_hash(),
+        })
+        .await
+        .expect("Failed to get the execution outcome proof");
+    match state.verify_outcome(
+        &response.outcome_proof,
+        &response.outcome_root_proof,
+        &response.block_header_lite,
+        &response.block_proof,
+    ) {
+        Ok(()) => println!(
+            "Verified outcome of {} in block {}:\n{:#?}",
+            response.outcome_proof.id,
+            response.outcome_proof.block_hash,
+            response.outcome_proof.outcome
+        ),
+        Err(err) => {
+            error!("Outcome verification failed: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    env_logger::Builder::new().filter(None, LevelFilter::Info).init();
+    let matches = App::new("light-client")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .about("Follows the chain through light client blocks and verifies execution outcomes")
+        .arg(
+            Arg::with_name("rpc-url")
+                .long("rpc-url")
+                .default_value(DEFAULT_RPC_URL)
+                .help("Url of RPC of the node to follow")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("state-file")
+                .long("state-file")
+                .default_value(DEFAULT_STATE_FILE)
+                .help("File with the trusted head of the light client")
+                .takes_value(true),
+        )
+        .subcommand(
+            SubCommand::with_name("init")
+                .about("Initializes the light client from a trusted block")
+                .arg(
+                    Arg::with_name("block-hash")
+                        .long("block-hash")
+                        .required(true)
+                        .help("Hash of a block known to be on the canonical chain")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("follow").about("Keeps the trusted head up to date").arg(
+                Arg::with_name("poll-period")
+                    .long("poll-period")
+                    .default_value(DEFAULT_POLL_PERIOD_SEC)
+                    .help("Period between requests for new light client blocks (in seconds)")
+                    .takes_value(true),
+            ),
+        )
+        .subcommand(
+            SubCommand::with_name("verify")
+                .about("Verifies the outcome of a transaction or a receipt against the head")
+                .arg(
+                    Arg::with_name("transaction-hash")
+                        .long("transaction-hash")
+                        .takes_value(true)
+                        .required_unless("receipt-id")
+                        .conflicts_with("receipt-id")
+                        .requires("account-id"),
+                )
+                .arg(
+                    Arg::with_name("receipt-id")
+                        .long("receipt-id")
+                        .takes_value(true)
+                        .requires("account-id"),
+                )
+                .arg(
+                    Arg::with_name("account-id")
+                        .long("account-id")
+                        .help("Signer of the transaction or receiver of the receipt")
+                        .takes_value(true),
+                ),
+        )
+        .get_matches();
+
+    let rpc_url = matches.value_of("rpc-url").unwrap().to_string();
+    let state_file = matches.value_of("state-file").map(PathBuf::from).unwrap();
+
+    let sys = actix::System::new();
+    match matches.subcommand() {
+        ("init", Some(args)) => {
+            let trusted_block_hash = parse_hash(args, "block-hash");
+            sys.block_on(init(&rpc_url, &state_file, trusted_block_hash));
+        }
+        ("follow", Some(args)) => {
+            let poll_period = args
+                .value_of("poll-period")
+                .map(|s| s.parse().expect("Poll period must be a number"))
+                .unwrap();
+            sys.block_on(follow(&rpc_url, &state_file, Duration::from_secs(poll_period)));
+        }
+        ("verify", Some(args)) => {
+            let account_id = args
+                .value_of("account-id")
+                .map(|s| s.parse().expect("Invalid account id"))
+                .unwrap();
+            let id = if args.is_present("transaction-hash") {
+                TransactionOrReceiptId::Transaction {
+                    transaction_hash: parse_hash(args, "transaction-hash"),
+                    sender_id: account_id,
+                }
+            } else {
+                TransactionOrReceiptId::Receipt {
+                    receipt_id: parse_hash(args, "receipt-id"),
+                    receiver_id: account_id,
+                }
+            };
+            sys.block_on(verify(&rpc_url, &state_file, id));
+        }
+        (_, _) => unreachable!(),
+    }
+}
@@ -0,0 +1,581 @@
+//! Keeps the validator key out of the node process. The node uses `RemoteValidatorSigner`, which
+//! forwards every signing request to a signer process over a Unix socket, and the signer process
+//! answers them with `serve`. Connections are not authenticated, so the signer only listens on a
+//! Unix socket accessible by its user.
+//!
+//! Every message is a little endian `u32` length followed by a borsh serialized `SignerRequest`
+//! or `SignerResponse`. A connection carries one request at a time.
+
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use log::{info, warn};
+
+use near_crypto::{PublicKey, Signature};
+use near_primitives::block::ApprovalInner;
+use near_primitives::challenge::ChallengeBody;
+use near_primitives::hash::CryptoHash;
+use near_primitives::network::PeerId;
+use near_primitives::sharding::ChunkHash;
+use near_primitives::telemetry::TelemetryInfo;
+use near_primitives::types::{AccountId, BlockHeight, EpochId, ShardId};
+use near_primitives::validator_signer::{SigningError, ValidatorSigner};
+
+/// Challenges carry partial state, so allow messages as large as the network does.
+const MAX_MESSAGE_SIZE: u32 = 512 * 1024 * 1024;
+/// How long the node waits for the signer before considering it unavailable. The node signs on
+/// the client actor, so this is kept well below the block production delay.
+const SIGNER_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub enum SignerRequest {
+    /// Asks for the account id and the public key of the validator.
+    Info,
+    /// Telemetry info serialized to JSON.
+    SignTelemetry(String),
+    SignBlockHeaderParts {
+        prev_hash: CryptoHash,
+        inner_lite: Vec<u8>,
+        inner_rest: Vec<u8>,
+    },
+    SignChunkHash {
+        chunk_hash: ChunkHash,
+        height_created: BlockHeight,
+        shard_id: ShardId,
+    },
+    SignApproval {
+        inner: ApprovalInner,
+        target_height: BlockHeight,
+    },
+    SignChallenge(ChallengeBody),
+    SignAccountAnnounce {
+        account_id: AccountId,
+        peer_id: PeerId,
+        epoch_id: EpochId,
+    },
+    ComputeVrfWithProof(Vec<u8>),
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub enum SignerResponse {
+    Info {
+        account_id: AccountId,
+        public_key: PublicKey,
+    },
+    /// Signed telemetry serialized to JSON.
+    Telemetry(String),
+    HashAndSignature(CryptoHash, Signature),
+    Signature(Signature),
+    VrfWithProof(near_crypto::vrf::Value, near_crypto::vrf::Proof),
+    /// The signer refused or failed to handle the request.
+    Error(String),
+}
+
+/// Address of a signer, `unix:<path>` for the Unix socket at `path`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignerAddr(pub PathBuf);
+
+impl FromStr for SignerAddr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("unix:") {
+            Some(path) if !path.is_empty() => Ok(SignerAddr(PathBuf::from(path))),
+            _ => Err(format!("{} is not a `unix:<path>` address", s)),
+        }
+    }
+}
+
+impl fmt::Display for SignerAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unix:{}", self.0.display())
+    }
+}
+
+pub struct SignerStream(UnixStream);
+
+impl SignerStream {
+    pub fn connect(addr: &SignerAddr) -> io::Result<Self> {
+        let stream = UnixStream::connect(&addr.0)?;
+        stream.set_read_timeout(Some(SIGNER_TIMEOUT))?;
+        stream.set_write_timeout(Some(SIGNER_TIMEOUT))?;
+        Ok(SignerStream(stream))
+    }
+}
+
+impl Read for SignerStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for SignerStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+pub struct SignerListener {
+    addr: SignerAddr,
+    listener: UnixListener,
+}
+
+impl SignerListener {
+    /// Listens on the Unix socket at `addr`, which is only accessible by the user running the
+    /// signer.
+    ///
+    /// A socket is accessible as the umask allows as soon as it is bound, so it is bound in a
+    /// directory only that user can enter and linked to its path once its permissions are set.
+    pub fn bind(addr: &SignerAddr) -> io::Result<Self> {
+        let path = &addr.0;
+        let file_name = path.file_name().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a file", addr))
+        })?;
+        let mut private_dir_name = OsString::from(".");
+        private_dir_name.push(file_name);
+        private_dir_name.push(format!(".{}", std::process::id()));
+        let private_dir = path.with_file_name(private_dir_name);
+        fs::DirBuilder::new().mode(0o700).create(&private_dir)?;
+        let private_path = private_dir.join("signer.sock");
+        let listener = UnixListener::bind(&private_path).and_then(|listener| {
+            fs::set_permissions(&private_path, fs::Permissions::from_mode(0o600))?;
+            fs::hard_link(&private_path, path)?;
+            Ok(listener)
+        });
+        let _ = fs::remove_dir_all(&private_dir);
+        Ok(Self { addr: addr.clone(), listener: listener? })
+    }
+
+    pub fn local_addr(&self) -> &SignerAddr {
+        &self.addr
+    }
+
+    pub fn accept(&self) -> io::Result<SignerStream> {
+        Ok(SignerStream(self.listener.accept()?.0))
+    }
+}
+
+pub fn write_message<T: BorshSerialize>(stream: &mut impl Write, message: &T) -> io::Result<()> {
+    let bytes = message.try_to_vec()?;
+    if bytes.len() > MAX_MESSAGE_SIZE as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Message of {} bytes is too large", bytes.len()),
+        ));
+    }
+    stream.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    stream.write_all(&bytes)?;
+    stream.flush()
+}
+
+pub fn read_message<T: BorshDeserialize>(stream: &mut impl Read) -> io::Result<T> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len);
+    if len > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Message of {} bytes is too large", len),
+        ));
+    }
+    let mut bytes = vec![0u8; len as usize];
+    stream.read_exact(&mut bytes)?;
+    T::try_from_slice(&bytes)
+}
+
+/// Signer which holds no key and asks the signer process at `addr` to sign instead.
+///
+/// If the signer can't be reached even after reconnecting, or refuses to sign, signing fails with
+/// `SigningError::Failed`, and the node skips what it was signing.
+pub struct RemoteValidatorSigner {
+    addr: SignerAddr,
+    account_id: AccountId,
+    public_key: PublicKey,
+    /// Connection to the signer, `None` after a failed request until the next one reconnects.
+    stream: Mutex<Option<SignerStream>>,
+}
+
+impl RemoteValidatorSigner {
+    /// Connects to the signer and asks which validator it signs for.
+    pub fn connect(addr: SignerAddr) -> io::Result<Self> {
+        let mut stream = SignerStream::connect(&addr)?;
+        match Self::roundtrip(&mut stream, &SignerRequest::Info)? {
+            SignerResponse::Info { account_id, public_key } => {
+                info!(target: "remote_signer", "Connected to the signer of {} at {}", account_id, addr);
+                Ok(Self { addr, account_id, public_key, stream: Mutex::new(Some(stream)) })
+            }
+            response => {
+                Err(io::Error::new(io::ErrorKind::InvalidData, unexpected_response(&response)))
+            }
+        }
+    }
+
+    fn roundtrip(stream: &mut SignerStream, request: &SignerRequest) -> io::Result<SignerResponse> {
+        write_message(stream, request)?;
+        read_message(stream)
+    }
+
+    fn request(&self, request: SignerRequest) -> Result<SignerResponse, SigningError> {
+        let mut stream = self.stream.lock().unwrap();
+        let response = match stream.as_mut() {
+            Some(connected) => Self::roundtrip(connected, &request),
+            None => Err(io::Error::new(io::ErrorKind::NotConnected, "not connected")),
+        }
+        .or_else(|err| {
+            warn!(target: "remote_signer", "Request to the signer at {} failed: {}, reconnecting", self.addr, err);
+            // A response to the failed request may still arrive, so the connection is not reused.
+            *stream = None;
+            let mut connected = SignerStream::connect(&self.addr)?;
+            let response = Self::roundtrip(&mut connected, &request)?;
+            *stream = Some(connected);
+            Ok(response)
+        });
+        drop(stream);
+        match response {
+            Ok(SignerResponse::Error(err)) => Err(SigningError::Failed(format!(
+                "Signer at {} refused to sign: {}",
+                self.addr, err
+            ))),
+            Ok(response) => Ok(response),
+            Err(err) => Err(SigningError::Failed(format!(
+                "Signer at {} is unavailable: {}",
+                self.addr, err
+            ))),
+        }
+    }
+
+    fn request_signature(&self, request: SignerRequest) -> Result<Signature, SigningError> {
+        match self.request(request)? {
+            SignerResponse::Signature(signature) => Ok(signature),
+            response => Err(unexpected_response(&response)),
+        }
+    }
+
+    fn request_hash_and_signature(
+        &self,
+        request: SignerRequest,
+    ) -> Result<(CryptoHash, Signature), SigningError> {
+        match self.request(request)? {
+            SignerResponse::HashAndSignature(hash, signature) => Ok((hash, signature)),
+            response => Err(unexpected_response(&response)),
+        }
+    }
+}
+
+fn unexpected_response(response: &SignerResponse) -> SigningError {
+    SigningError::Failed(format!("Unexpected response from the signer: {:?}", response))
+}
+
+impl ValidatorSigner for RemoteValidatorSigner {
+    fn validator_id(&self) -> &AccountId {
+        &self.account_id
+    }
+
+    fn public_key(&self) -> PublicKey {
+        self.public_key.clone()
+    }
+
+    fn sign_telemetry(&self, info: &TelemetryInfo) -> Result<serde_json::Value, SigningError> {
+        let info = serde_json::to_string(info).expect("Telemetry must serialize to JSON");
+        match self.request(SignerRequest::SignTelemetry(info))? {
+            SignerResponse::Telemetry(value) => serde_json::from_str(&value).map_err(|err| {
+                SigningError::Failed(format!("Signer returned invalid telemetry: {}", err))
+            }),
+            response => Err(unexpected_response(&response)),
+        }
+    }
+
+    fn sign_block_header_parts(
+        &self,
+        prev_hash: CryptoHash,
+        inner_lite: &[u8],
+        inner_rest: &[u8],
+    ) -> Result<(CryptoHash, Signature), SigningError> {
+        self.request_hash_and_signature(SignerRequest::SignBlockHeaderParts {
+            prev_hash,
+            inner_lite: inner_lite.to_vec(),
+            inner_rest: inner_rest.to_vec(),
+        })
+    }
+
+    fn sign_chunk_hash(
+        &self,
+        chunk_hash: &ChunkHash,
+        height_created: BlockHeight,
+        shard_id: ShardId,
+    ) -> Result<Signature, SigningError> {
+        self.request_signature(SignerRequest::SignChunkHash {
+            chunk_hash: chunk_hash.clone(),
+            height_created,
+            shard_id,
+        })
+    }
+
+    fn sign_approval(
+        &self,
+        inner: &ApprovalInner,
+        target_height: BlockHeight,
+    ) -> Result<Signature, SigningError> {
+        self.request_signature(SignerRequest::SignApproval { inner: inner.clone(), target_height })
+    }
+
+    fn sign_challenge(
+        &self,
+        challenge_body: &ChallengeBody,
+    ) -> Result<(CryptoHash, Signature), SigningError> {
+        self.request_hash_and_signature(SignerRequest::SignChallenge(challenge_body.clone()))
+    }
+
+    fn sign_account_announce(
+        &self,
+        account_id: &AccountId,
+        peer_id: &PeerId,
+        epoch_id: &EpochId,
+    ) -> Result<Signature, SigningError> {
+        self.request_signature(SignerRequest::SignAccountAnnounce {
+            account_id: account_id.clone(),
+            peer_id: peer_id.clone(),
+            epoch_id: epoch_id.clone(),
+        })
+    }
+
+    fn compute_vrf_with_proof(
+        &self,
+        data: &[u8],
+    ) -> Result<(near_crypto::vrf::Value, near_crypto::vrf::Proof), SigningError> {
+        match self.request(SignerRequest::ComputeVrfWithProof(data.to_vec()))? {
+            SignerResponse::VrfWithProof(value, proof) => Ok((value, proof)),
+            response => Err(unexpected_response(&response)),
+        }
+    }
+
+    fn write_to_file(&self, _path: &Path) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Other, "The validator key is held by the remote signer"))
+    }
+}
+
+fn signature_response(signature: Result<Signature, SigningError>) -> SignerResponse {
+    match signature {
+        Ok(signature) => SignerResponse::Signature(signature),
+        Err(err) => SignerResponse::Error(err.to_string()),
+    }
+}
+
+fn hash_and_signature_response(
+    hash_and_signature: Result<(CryptoHash, Signature), SigningError>,
+) -> SignerResponse {
+    match hash_and_signature {
+        Ok((hash, signature)) => SignerResponse::HashAndSignature(hash, signature),
+        Err(err) => SignerResponse::Error(err.to_string()),
+    }
+}
+
+fn handle_request(signer: &dyn ValidatorSigner, request: SignerRequest) -> SignerResponse {
+    match request {
+        SignerRequest::Info => SignerResponse::Info {
+            account_id: signer.validator_id().clone(),
+            public_key: signer.public_key(),
+        },
+        SignerRequest::SignTelemetry(info) => match serde_json::from_str::<TelemetryInfo>(&info) {
+            Ok(info) => match signer.sign_telemetry(&info) {
+                Ok(value) => SignerResponse::Telemetry(value.to_string()),
+                Err(err) => SignerResponse::Error(err.to_string()),
+            },
+            Err(err) => SignerResponse::Error(format!("Invalid telemetry: {}", err)),
+        },
+        SignerRequest::SignBlockHeaderParts { prev_hash, inner_lite, inner_rest } => {
+            hash_and_signature_response(signer.sign_block_header_parts(
+                prev_hash,
+                &inner_lite,
+                &inner_rest,
+            ))
+        }
+        SignerRequest::SignChunkHash { chunk_hash, height_created, shard_id } => {
+            signature_response(signer.sign_chunk_hash(&chunk_hash, height_created, shard_id))
+        }
+        SignerRequest::SignApproval { inner, target_height } => {
+            signature_response(signer.sign_approval(&inner, target_height))
+        }
+        SignerRequest::SignChallenge(challenge_body) => {
+            hash_and_signature_response(signer.sign_challenge(&challenge_body))
+        }
+        SignerRequest::SignAccountAnnounce { account_id, peer_id, epoch_id } => {
+            signature_response(signer.sign_account_announce(&account_id, &peer_id, &epoch_id))
+        }
+        SignerRequest::ComputeVrfWithProof(data) => match signer.compute_vrf_with_proof(&data) {
+            Ok((value, proof)) => SignerResponse::VrfWithProof(value, proof),
+            Err(err) => SignerResponse::Error(err.to_string()),
+        },
+    }
+}
+
+/// Answers requests coming over `stream` until the node disconnects. A signer that refuses to
+/// sign, like `DoubleSignProtectedValidatorSigner`, or panics results in an error response.
+pub fn handle_connection(
+    stream: &mut (impl Read + Write),
+    signer: &dyn ValidatorSigner,
+) -> io::Result<()> {
+    loop {
+        let request = match read_message(stream) {
+            Ok(request) => request,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        let response = panic::catch_unwind(AssertUnwindSafe(|| handle_request(signer, request)))
+            .unwrap_or_else(|err| {
+                let message = err
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or_else(|| err.downcast_ref::<&str>().map(|s| s.to_string()))
+                    .unwrap_or_else(|| "Signer panicked".to_string());
+                SignerResponse::Error(message)
+            });
+        write_message(stream, &response)?;
+    }
+}
+
+/// Serves every connection accepted by `listener` on its own thread.
+pub fn serve(listener: SignerListener, signer: Arc<dyn ValidatorSigner>) -> io::Result<()> {
+    loop {
+        let mut stream = listener.accept()?;
+        let signer = signer.clone();
+        thread::spawn(move || {
+            if let Err(err) = handle_connection(&mut stream, signer.as_ref()) {
+                warn!(target: "remote_signer", "Connection closed: {}", err);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::Arc;
+    use std::thread;
+
+    use near_crypto::key_conversion::convert_public_key;
+    use near_crypto::KeyType;
+    use near_primitives::block::ApprovalInner;
+    use near_primitives::hash::hash;
+    use near_primitives::sharding::ChunkHash;
+    use near_primitives::validator_signer::{
+        DoubleSignProtectedValidatorSigner, InMemoryValidatorSigner, SigningError, ValidatorSigner,
+    };
+
+    use super::{handle_connection, serve, RemoteValidatorSigner, SignerAddr, SignerListener};
+
+    fn start_signer(addr: &SignerAddr, signer: Arc<dyn ValidatorSigner>) -> SignerAddr {
+        let listener = SignerListener::bind(addr).unwrap();
+        let addr = listener.local_addr().clone();
+        thread::spawn(move || serve(listener, signer));
+        addr
+    }
+
+    #[test]
+    fn test_signer_addr() {
+        assert_eq!(
+            "unix:/tmp/signer.sock".parse::<SignerAddr>().unwrap(),
+            SignerAddr("/tmp/signer.sock".into())
+        );
+        assert!("127.0.0.1:2500".parse::<SignerAddr>().is_err());
+        assert!("unix:".parse::<SignerAddr>().is_err());
+    }
+
+    #[test]
+    fn test_remote_signer() {
+        let dir = tempfile::tempdir().unwrap();
+        let signer: Arc<dyn ValidatorSigner> = Arc::new(InMemoryValidatorSigner::from_seed(
+            "test".parse().unwrap(),
+            KeyType::ED25519,
+            "test",
+        ));
+        let addr = start_signer(&SignerAddr(dir.path().join("signer.sock")), signer.clone());
+        let remote = RemoteValidatorSigner::connect(addr).unwrap();
+        assert_eq!(remote.validator_id(), signer.validator_id());
+        assert_eq!(remote.public_key(), signer.public_key());
+
+        let inner = ApprovalInner::Endorsement(hash(&[1]));
+        assert_eq!(
+            remote.sign_approval(&inner, 10).unwrap(),
+            signer.sign_approval(&inner, 10).unwrap()
+        );
+        let chunk_hash = ChunkHash(hash(&[2]));
+        assert_eq!(
+            remote.sign_chunk_hash(&chunk_hash, 10, 0).unwrap(),
+            signer.sign_chunk_hash(&chunk_hash, 10, 0).unwrap()
+        );
+        let (value, proof) = remote.compute_vrf_with_proof(&[3]).unwrap();
+        let vrf_public_key = convert_public_key(signer.public_key().unwrap_as_ed25519()).unwrap();
+        assert!(vrf_public_key.is_vrf_valid(&[3], &value, &proof));
+    }
+
+    #[test]
+    fn test_remote_signer_refusal() {
+        let dir = tempfile::tempdir().unwrap();
+        let signer =
+            InMemoryValidatorSigner::from_seed("test".parse().unwrap(), KeyType::ED25519, "test");
+        let signer = DoubleSignProtectedValidatorSigner::new(
+            Arc::new(signer),
+            &dir.path().join("signing_history.json"),
+        )
+        .unwrap();
+        let addr = start_signer(&SignerAddr(dir.path().join("signer.sock")), Arc::new(signer));
+        let remote = RemoteValidatorSigner::connect(addr).unwrap();
+        remote.sign_approval(&ApprovalInner::Endorsement(hash(&[1])), 10).unwrap();
+        match remote.sign_approval(&ApprovalInner::Skip(8), 10) {
+            Err(SigningError::Failed(err)) => assert!(err.contains("refused to sign"), "{}", err),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert!(remote.write_to_file(&dir.path().join("validator_key.json")).is_err());
+    }
+
+    #[test]
+    fn test_remote_signer_unavailable() {
+        let dir = tempfile::tempdir().unwrap();
+        let signer =
+            InMemoryValidatorSigner::from_seed("test".parse().unwrap(), KeyType::ED25519, "test");
+        let path = dir.path().join("signer.sock");
+        let listener = SignerListener::bind(&SignerAddr(path.clone())).unwrap();
+        let addr = listener.local_addr().clone();
+        let server = thread::spawn(move || {
+            let mut stream = listener.accept().unwrap();
+            handle_connection(&mut stream, &signer).unwrap();
+        });
+        let remote = RemoteValidatorSigner::connect(addr).unwrap();
+        let inner = ApprovalInner::Endorsement(hash(&[1]));
+        remote.sign_approval(&inner, 10).unwrap();
+
+        // The signer process exits, so requests fail instead of panicking.
+        std::fs::remove_file(&path).unwrap();
+        drop(remote.stream.lock().unwrap().take());
+        server.join().unwrap();
+        assert!(matches!(remote.sign_approval(&inner, 11), Err(SigningError::Failed(_))));
+    }
+
+    #[test]
+    fn test_signer_listener_permissions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("signer.sock");
+        let _listener = SignerListener::bind(&SignerAddr(path.clone())).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // Only the socket is left, without the directory it was bound in.
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert!(SignerListener::bind(&SignerAddr(path)).is_err());
+    }
+}
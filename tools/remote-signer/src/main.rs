@@ -0,0 +1,56 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use clap::{App, Arg};
+use log::{info, LevelFilter};
+
+use near_primitives::validator_signer::{
+    DoubleSignProtectedValidatorSigner, InMemoryValidatorSigner, ValidatorSigner,
+};
+use near_remote_signer::{serve, SignerAddr, SignerListener};
+
+fn main() {
+    env_logger::Builder::new().filter(None, LevelFilter::Info).init();
+    let matches = App::new("remote-signer")
+        .about("Holds the validator key and signs on behalf of a node")
+        .arg(
+            Arg::with_name("key-file")
+                .long("key-file")
+                .required(true)
+                .help("Validator key file, e.g. `~/.near/validator_key.json`")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("listen")
+                .long("listen")
+                .required(true)
+                .help("Address to listen on, `unix:<path>`")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("signing-history")
+                .long("signing-history")
+                .help("File with the history of signed messages, used to never double sign")
+                .takes_value(true),
+        )
+        .get_matches();
+
+    let key_file = Path::new(matches.value_of("key-file").unwrap());
+    let signer = Arc::new(InMemoryValidatorSigner::from_file(key_file)) as Arc<dyn ValidatorSigner>;
+    let signer = match matches.value_of("signing-history") {
+        Some(signing_history) => {
+            let signer =
+                DoubleSignProtectedValidatorSigner::new(signer, Path::new(signing_history))
+                    .expect("Failed to read the signing history");
+            Arc::new(signer) as Arc<dyn ValidatorSigner>
+        }
+        None => signer,
+    };
+
+    let addr: SignerAddr =
+        matches.value_of("listen").unwrap().parse().expect("Failed to parse the listen address");
+    let listener = SignerListener::bind(&addr)
+        .unwrap_or_else(|err| panic!("Failed to listen on {}: {}", addr, err));
+    info!("Signing for {} on {}", signer.validator_id(), addr);
+    serve(listener, signer).expect("Failed to accept a connection");
+}
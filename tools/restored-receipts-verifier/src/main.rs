@@ -62,6 +62,7 @@ fn main() -> Result<()> {
         None,
         near_config.client_config.max_gas_burnt_view,
         RuntimeConfigStore::new(None),
+        &near_config.store_config.trie_cache,
     );
 
     let mut receipts_missing = Vec::<Receipt>::new();